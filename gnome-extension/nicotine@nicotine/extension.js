@@ -0,0 +1,149 @@
+// Window management for nicotine over D-Bus. Mutter doesn't let other
+// processes move or focus windows, so this runs inside GNOME Shell and
+// exports /org/nicotine/Windows on the Shell's session bus connection.
+import Gio from 'gi://Gio';
+import Meta from 'gi://Meta';
+import * as Main from 'resource:///org/gnome/shell/ui/main.js';
+import {Extension} from 'resource:///org/gnome/shell/extensions/extension.js';
+
+const IFACE = `
+<node>
+  <interface name="org.nicotine.Windows">
+    <method name="List"><arg type="s" direction="out"/></method>
+    <method name="Monitors"><arg type="s" direction="out"/></method>
+    <method name="Active"><arg type="t" direction="out"/></method>
+    <method name="Activate">
+      <arg type="t" direction="in"/><arg type="b" direction="out"/>
+    </method>
+    <method name="Place">
+      <arg type="t" direction="in"/>
+      <arg type="i" direction="in"/><arg type="i" direction="in"/>
+      <arg type="i" direction="in"/><arg type="i" direction="in"/>
+      <arg type="b" direction="out"/>
+    </method>
+    <method name="Minimize">
+      <arg type="t" direction="in"/><arg type="b" direction="out"/>
+    </method>
+    <method name="Restore">
+      <arg type="t" direction="in"/><arg type="b" direction="out"/>
+    </method>
+    <method name="Raise">
+      <arg type="t" direction="in"/><arg type="b" direction="out"/>
+    </method>
+    <method name="Close">
+      <arg type="t" direction="in"/><arg type="b" direction="out"/>
+    </method>
+  </interface>
+</node>`;
+
+function normalWindows() {
+    return global.get_window_actors()
+        .map(actor => actor.meta_window)
+        .filter(w => w.get_window_type() === Meta.WindowType.NORMAL);
+}
+
+function find(id) {
+    return normalWindows().find(w => w.get_id() === id) ?? null;
+}
+
+class Service {
+    List() {
+        return JSON.stringify(normalWindows().map(w => {
+            const rect = w.get_frame_rect();
+            return {
+                id: w.get_id(),
+                title: w.get_title() ?? '',
+                pid: w.get_pid(),
+                class: w.get_wm_class(),
+                x: rect.x,
+                y: rect.y,
+                width: rect.width,
+                height: rect.height,
+                monitor: w.get_monitor(),
+                minimized: w.minimized,
+            };
+        }));
+    }
+
+    Monitors() {
+        const display = global.display;
+        const monitors = [];
+        for (let i = 0; i < display.get_n_monitors(); i++) {
+            const rect = display.get_monitor_geometry(i);
+            monitors.push({
+                index: i,
+                x: rect.x,
+                y: rect.y,
+                width: rect.width,
+                height: rect.height,
+                focused: i === display.get_current_monitor(),
+            });
+        }
+        return JSON.stringify(monitors);
+    }
+
+    Active() {
+        return global.display.get_focus_window()?.get_id() ?? 0;
+    }
+
+    Activate(id) {
+        const w = find(id);
+        if (!w)
+            return false;
+        Main.activateWindow(w);
+        return true;
+    }
+
+    // A negative width or height keeps that dimension
+    Place(id, x, y, width, height) {
+        const w = find(id);
+        if (!w)
+            return false;
+        if (w.is_fullscreen())
+            w.unmake_fullscreen();
+        if (w.get_maximized())
+            w.unmaximize(Meta.MaximizeFlags.BOTH);
+        const rect = w.get_frame_rect();
+        w.move_resize_frame(true, x, y,
+            width < 0 ? rect.width : width,
+            height < 0 ? rect.height : height);
+        return true;
+    }
+
+    Minimize(id) {
+        const w = find(id);
+        w?.minimize();
+        return w !== null;
+    }
+
+    Restore(id) {
+        const w = find(id);
+        w?.unminimize();
+        return w !== null;
+    }
+
+    Raise(id) {
+        const w = find(id);
+        w?.raise();
+        return w !== null;
+    }
+
+    // Asks the client to close, like its close button
+    Close(id) {
+        const w = find(id);
+        w?.delete(global.get_current_time());
+        return w !== null;
+    }
+}
+
+export default class NicotineExtension extends Extension {
+    enable() {
+        this._dbus = Gio.DBusExportedObject.wrapJSObject(IFACE, new Service());
+        this._dbus.export(Gio.DBus.session, '/org/nicotine/Windows');
+    }
+
+    disable() {
+        this._dbus.unexport();
+        this._dbus = null;
+    }
+}
@@ -0,0 +1,116 @@
+use crate::config::Config;
+use crate::layout;
+use crate::window_manager::WindowManager;
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// min/median/max for one timed backend operation
+#[derive(Debug, Clone)]
+pub struct Timing {
+    pub name: &'static str,
+    pub min: Duration,
+    pub median: Duration,
+    pub max: Duration,
+}
+
+impl Timing {
+    fn from_samples(name: &'static str, mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        Self {
+            name,
+            min: samples.first().copied().unwrap_or_default(),
+            median: samples.get(samples.len() / 2).copied().unwrap_or_default(),
+            max: samples.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+fn time<F>(iterations: usize, mut op: F) -> Result<Vec<Duration>>
+where
+    F: FnMut() -> Result<()>,
+{
+    let mut samples = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        op()?;
+        samples.push(start.elapsed());
+    }
+    Ok(samples)
+}
+
+/// Time the read-only backend operations. Nothing is moved: activate only
+/// re-focuses the window that is already active, and plan_stack just computes
+/// placements.
+pub fn run(wm: &dyn WindowManager, config: &Config, iterations: usize) -> Result<Vec<Timing>> {
+    let iterations = iterations.max(1);
+    let mut timings = Vec::new();
+
+    let samples = time(iterations, || wm.get_eve_windows().map(|_| ()))?;
+    timings.push(Timing::from_samples("get_eve_windows", samples));
+
+    // Time the query itself, not the cache in front of it
+    let samples = time(iterations, || {
+        wm.invalidate_monitors();
+        wm.get_monitors().map(|_| ())
+    })?;
+    timings.push(Timing::from_samples("get_monitors", samples));
+
+    let active = wm.get_active_window().unwrap_or(0);
+    if active != 0 {
+        let samples = time(iterations, || wm.activate_window(active))?;
+        timings.push(Timing::from_samples("activate (no-op)", samples));
+    }
+
+    let windows = wm.get_eve_windows()?;
+    let monitors = wm.get_monitors()?;
+    let samples = time(iterations, || {
+        layout::plan_stack(&windows, &monitors, config);
+        Ok(())
+    })?;
+    timings.push(Timing::from_samples("plan_stack", samples));
+
+    Ok(timings)
+}
+
+pub fn print_report(timings: &[Timing], iterations: usize) {
+    println!("Backend timings over {} iterations:", iterations.max(1));
+    println!(
+        "  {:<18} {:>10} {:>10} {:>10}",
+        "operation", "min", "median", "max"
+    );
+    for t in timings {
+        println!(
+            "  {:<18} {:>10.2?} {:>10.2?} {:>10.2?}",
+            t.name, t.min, t.median, t.max
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_window_manager::{monitor, window, MockWindowManager};
+
+    #[test]
+    fn test_bench_reports_all_operations_without_moving() {
+        let wm = MockWindowManager::new(vec![window(1, "Pilot")])
+            .with_monitors(vec![monitor("DP-1", 0, 0, 1920, 1080)])
+            .with_active(1);
+        let timings = run(&wm, &Config::default(), 5).unwrap();
+
+        let names: Vec<&str> = timings.iter().map(|t| t.name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "get_eve_windows",
+                "get_monitors",
+                "activate (no-op)",
+                "plan_stack"
+            ]
+        );
+        for t in &timings {
+            assert!(t.min <= t.median && t.median <= t.max);
+        }
+        assert!(wm.placements().is_empty());
+    }
+}
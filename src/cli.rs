@@ -0,0 +1,475 @@
+use anyhow::Result;
+use clap::builder::PossibleValuesParser;
+use clap::{value_parser, Arg, ArgAction, Command};
+use clap_complete::Shell;
+use std::io::Write;
+
+/// Shells `nicotine completions` can generate scripts for
+pub const SHELLS: &[Shell] = &[Shell::Bash, Shell::Zsh, Shell::Fish];
+
+/// Names offered as completions. Snapshotted when the script is generated,
+/// so re-run `nicotine completions` after editing characters.txt or groups.
+#[derive(Debug, Default)]
+pub struct Hints {
+    pub characters: Vec<String>,
+    pub groups: Vec<String>,
+}
+
+/// Backends `--backend` can force instead of detecting one, or "auto" to
+/// detect even if config.toml names one
+pub const BACKENDS: &[&str] = &[
+    "auto", "x11", "kde", "sway", "hyprland", "gnome", "wlr", "windows", "macos",
+];
+
+/// `--json`, for commands whose output scripts may want to read
+fn json_flag() -> Arg {
+    Arg::new("json")
+        .long("json")
+        .action(ArgAction::SetTrue)
+        .help("Print as JSON")
+}
+
+/// For commands that move windows: print the plan instead
+fn dry_run_flag() -> Arg {
+    Arg::new("dry-run")
+        .long("dry-run")
+        .action(ArgAction::SetTrue)
+        .help("Print where each window would go without moving any")
+}
+
+/// Optional positional that completes to `values` when there are any
+fn hinted(name: &'static str, help: &'static str, values: &[String]) -> Arg {
+    let arg = Arg::new(name).help(help);
+    if values.is_empty() {
+        arg
+    } else {
+        arg.value_parser(PossibleValuesParser::new(values.to_vec()))
+    }
+}
+
+/// The nicotine command line. `main` parses with default hints, so names
+/// outside characters.txt and the groups are still accepted.
+pub fn command(hints: &Hints) -> Command {
+    let mut group_names = hints.groups.clone();
+    if !group_names.is_empty() {
+        group_names.push("clear".to_string());
+    }
+
+    Command::new("nicotine")
+        .about("EVE Online multiboxing tool")
+        .before_help(
+            "🚬 N I C O T I N E 🚬\n\n\
+             Questions or suggestions?\n\
+             Reach out to isomerc on Discord or open a Github issue",
+        )
+        .after_help("Quick start:\n  nicotine start         # Starts in background automatically")
+        // `nicotine N` is shorthand for `nicotine switch N`
+        .allow_external_subcommands(true)
+        .arg(
+            Arg::new("backend")
+                .long("backend")
+                .global(true)
+                .value_parser(BACKENDS.to_vec())
+                .help("Use this backend instead of detecting one"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .global(true)
+                .action(ArgAction::Count)
+                .help("Log more: -v for debug, -vv for trace"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .global(true)
+                .value_name("PATH")
+                .help("Read config.toml from PATH instead of ~/.config/nicotine"),
+        )
+        .subcommand(Command::new("start").about("Start everything (daemon + overlay)"))
+        .subcommand(Command::new("stop").about("Stop all Nicotine processes"))
+        .subcommand(
+            Command::new("stack")
+                .about("Stack all EVE windows")
+                .arg(dry_run_flag()),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List EVE clients")
+                .arg(json_flag()),
+        )
+        .subcommand(
+            Command::new("monitors")
+                .about("List monitors and their usable areas")
+                .arg(json_flag()),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Show the clients, the current one and the daemon's state")
+                .arg(json_flag()),
+        )
+        .subcommand(Command::new("preview").about("Draw where stack would put each window"))
+        .subcommand(
+            Command::new("heal")
+                .about("Re-place only windows that have drifted")
+                .arg(dry_run_flag()),
+        )
+        .subcommand(
+            Command::new("engage")
+                .about("Stack, focus the primary and warp the cursor onto it")
+                .arg(dry_run_flag()),
+        )
+        .subcommand(
+            Command::new("wait-for-windows")
+                .about("Stack clients as they log in")
+                .arg(
+                    Arg::new("count")
+                        .value_parser(value_parser!(usize))
+                        .help("Number of clients to wait for"),
+                )
+                .arg(
+                    Arg::new("secs")
+                        .value_parser(value_parser!(u64))
+                        .help("Timeout in seconds"),
+                )
+                .after_help("With -v, progress is printed at each poll"),
+        )
+        .subcommand(
+            Command::new("launch")
+                .about("Start accounts' launchers and stack each client as it logs in")
+                .arg(
+                    Arg::new("accounts")
+                        .num_args(0..)
+                        .help("Accounts in [launch] to start (default: all of them)"),
+                )
+                .after_help("With -v, login progress is printed at each poll"),
+        )
+        .subcommand(
+            Command::new("forward")
+                .visible_alias("f")
+                .alias("cycle-forward")
+                .alias("cycle-next")
+                .about("Cycle forward"),
+        )
+        .subcommand(
+            Command::new("backward")
+                .visible_alias("b")
+                .alias("cycle-backward")
+                .alias("cycle-prev")
+                .about("Cycle backward"),
+        )
+        .subcommand(
+            Command::new("cycle")
+                .about("Cycle to the next or previous client")
+                .arg(
+                    Arg::new("direction")
+                        .required(true)
+                        .value_parser(["next", "prev"]),
+                ),
+        )
+        .subcommand(
+            Command::new("switch")
+                .about("Switch to client N (targeted cycling); `nicotine N` for short")
+                .arg(
+                    Arg::new("n")
+                        .required(true)
+                        .value_parser(value_parser!(usize))
+                        .help("Position in characters.txt"),
+                ),
+        )
+        .subcommand(
+            Command::new("activate")
+                .about("Switch to a character's client")
+                .arg(
+                    hinted("character", "Character to switch to", &hints.characters).required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("focus-last")
+                .about("Switch back to the previously focused client (needs the daemon)"),
+        )
+        .subcommand(Command::new("refresh").about("Re-read the EVE window list"))
+        .subcommand(Command::new("reload").about("Make the daemon re-read config.toml"))
+        .subcommand(
+            Command::new("profile")
+                .about("Switch the daemon's keybinds and layout")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Profile in config.toml"),
+                ),
+        )
+        .subcommand(Command::new("init-config").about("Create default config.toml"))
+        .subcommand(
+            Command::new("config")
+                .about("Create or inspect the config")
+                .subcommand_required(true)
+                .subcommand(Command::new("init").about("Create default config.toml"))
+                .subcommand(Command::new("check").about("Check config.toml for mistakes"))
+                .subcommand(
+                    Command::new("show")
+                        .about("Print the effective config")
+                        .arg(
+                            Arg::new("json")
+                                .long("json")
+                                .action(ArgAction::SetTrue)
+                                .help("Print as JSON instead of TOML"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("dim")
+                .about("Dim all but the active client")
+                .arg(
+                    Arg::new("opacity")
+                        .value_parser(value_parser!(f64))
+                        .help("Opacity for inactive clients (default 0.6)"),
+                ),
+        )
+        .subcommand(
+            Command::new("rotate")
+                .about("Move every client into the next one's slot")
+                .arg(
+                    Arg::new("direction")
+                        .value_parser(["back"])
+                        .help("Rotate the other way"),
+                )
+                .arg(dry_run_flag()),
+        )
+        .subcommand(
+            Command::new("swap")
+                .about("Trade places between two clients, or one and the primary")
+                .arg(
+                    hinted(
+                        "characters",
+                        "Characters to swap (default: the active client and primary_character)",
+                        &hints.characters,
+                    )
+                    .num_args(0..=2),
+                )
+                .arg(dry_run_flag()),
+        )
+        .subcommand(
+            Command::new("pin")
+                .about("Keep a client above every other window")
+                .arg(hinted(
+                    "character",
+                    "Character to pin (default: the active client)",
+                    &hints.characters,
+                ))
+                .arg(dry_run_flag()),
+        )
+        .subcommand(
+            Command::new("unpin")
+                .about("Let a pinned client go back to the normal stacking order")
+                .arg(hinted(
+                    "character",
+                    "Character to unpin (default: the active client)",
+                    &hints.characters,
+                ))
+                .arg(dry_run_flag()),
+        )
+        .subcommand(
+            Command::new("esi")
+                .about("Character ids and corporations from ESI (needs [esi] in config.toml)")
+                .subcommand(Command::new("login").about("Log a character in through EVE SSO"))
+                .subcommand(
+                    Command::new("whois").about(
+                        "Show each client's character id, corporation and alliance (default)",
+                    ),
+                ),
+        )
+        .subcommand(
+            Command::new("timers")
+                .about("Skill queue, industry and planet timers of characters logged in to ESI")
+                .arg(json_flag()),
+        )
+        .subcommand(
+            Command::new("urgent")
+                .about("Make a background client blink in the taskbar without focusing it")
+                .arg(hinted("character", "Character to flag", &hints.characters).required(true))
+                .arg(dry_run_flag()),
+        )
+        .subcommand(
+            Command::new("quit-all")
+                .about("Ask every EVE client to close, as its close button would")
+                .arg(
+                    hinted(
+                        "group",
+                        "Only close the clients in this group",
+                        &hints.groups,
+                    )
+                    .long("group")
+                    .value_name("NAME"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .action(ArgAction::SetTrue)
+                        .help("Don't ask before closing them"),
+                )
+                .arg(dry_run_flag()),
+        )
+        .subcommand(
+            Command::new("layout")
+                .about("Save the clients' current arrangement, or restore a saved one")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("save")
+                        .about("Save where each character's window is now")
+                        .arg(Arg::new("name").required(true).help("Layout name")),
+                )
+                .subcommand(
+                    Command::new("restore")
+                        .about("Move each character back to where it was saved")
+                        .arg(Arg::new("name").required(true).help("Layout name"))
+                        .arg(dry_run_flag()),
+                ),
+        )
+        .subcommand(
+            Command::new("solo")
+                .about("Minimize the rest of that client's monitor")
+                .arg(hinted("character", "Character to solo", &hints.characters)),
+        )
+        .subcommand(
+            Command::new("minimize-all").about("Minimize every client (unsolo restores them)"),
+        )
+        .subcommand(Command::new("unsolo").about("Restore windows minimized by solo"))
+        .subcommand(Command::new("tidy").about("Hide launcher helper windows"))
+        .subcommand(Command::new("reset").about("Restore opacity, borders and helper windows"))
+        .subcommand(
+            Command::new("group")
+                .about("Cycle within a group, or list groups")
+                .arg(hinted("name", "Group name, or clear", &group_names))
+                .arg(
+                    Arg::new("direction")
+                        .value_parser(["forward", "backward", "f", "b", "set"])
+                        .help("Direction to cycle, or set to limit cycling to the group"),
+                ),
+        )
+        .subcommand(Command::new("daemon").about("Start daemon only"))
+        .subcommand(Command::new("overlay").about("Start overlay only"))
+        .subcommand(Command::new("thumbnails").about("Live client previews; click one to switch"))
+        .subcommand(
+            Command::new("bench").about("Time backend operations").arg(
+                Arg::new("iterations")
+                    .value_parser(value_parser!(usize))
+                    .help("Runs per operation (default 20)"),
+            ),
+        )
+        .subcommand(
+            Command::new("hyprland-rules")
+                .about("Add or remove Hyprland window rules for EVE")
+                .arg(
+                    Arg::new("action")
+                        .required(true)
+                        .value_parser(["install", "uninstall"]),
+                ),
+        )
+        .subcommand(
+            Command::new("gnome-extension")
+                .about("Install or remove the GNOME Shell extension nicotine needs on GNOME")
+                .arg(
+                    Arg::new("action")
+                        .required(true)
+                        .value_parser(["install", "uninstall"]),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_parser(["bash", "zsh", "fish"]),
+                ),
+        )
+}
+
+/// Write the completion script for `shell` (bash, zsh or fish)
+pub fn generate_completions(shell: &str, hints: &Hints, out: &mut dyn Write) -> Result<()> {
+    let shell = SHELLS
+        .iter()
+        .copied()
+        .find(|s| s.to_string() == shell)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported shell: {} (use bash, zsh or fish)", shell))?;
+
+    let mut cmd = command(hints);
+    clap_complete::generate(shell, &mut cmd, "nicotine", out);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions_generate_for_each_shell() {
+        let hints = Hints {
+            characters: vec!["Main".to_string(), "Scout".to_string()],
+            groups: vec!["miners".to_string()],
+        };
+
+        for shell in ["bash", "zsh", "fish"] {
+            let mut out = Vec::new();
+            generate_completions(shell, &hints, &mut out).unwrap();
+            let script = String::from_utf8(out).unwrap();
+
+            assert!(script.contains("nicotine"), "{} script", shell);
+            assert!(script.contains("wait-for-windows"), "{} script", shell);
+            // clap_complete's fish output doesn't complete positional values
+            if shell != "fish" {
+                assert!(
+                    script.contains("Scout"),
+                    "{} script lacks characters",
+                    shell
+                );
+                assert!(script.contains("miners"), "{} script lacks groups", shell);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_shell_is_an_error() {
+        let mut out = Vec::new();
+        assert!(generate_completions("tcsh", &Hints::default(), &mut out).is_err());
+    }
+
+    #[test]
+    fn test_command_definition_is_valid() {
+        command(&Hints::default()).debug_assert();
+    }
+
+    #[test]
+    fn test_parse_backend_override_and_shorthand() {
+        let parse = |args: &[&str]| command(&Hints::default()).try_get_matches_from(args);
+
+        let matches = parse(&["nicotine", "cycle", "next", "--backend", "sway"]).unwrap();
+        assert_eq!(
+            matches.get_one::<String>("backend").map(String::as_str),
+            Some("sway")
+        );
+        let (name, args) = matches.subcommand().unwrap();
+        assert_eq!(name, "cycle");
+        assert_eq!(
+            args.get_one::<String>("direction").map(String::as_str),
+            Some("next")
+        );
+
+        let matches = parse(&["nicotine", "stack", "--config", "/tmp/eve.toml"]).unwrap();
+        assert_eq!(
+            matches.get_one::<String>("config").map(String::as_str),
+            Some("/tmp/eve.toml")
+        );
+
+        let matches = parse(&["nicotine", "f"]).unwrap();
+        assert_eq!(matches.subcommand_name(), Some("forward"));
+        let matches = parse(&["nicotine", "3"]).unwrap();
+        assert_eq!(matches.subcommand_name(), Some("3"));
+
+        assert!(parse(&["nicotine", "--backend", "beos", "stack"]).is_err());
+        assert!(parse(&["nicotine", "switch", "two"]).is_err());
+    }
+}
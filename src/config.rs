@@ -1,10 +1,15 @@
+use crate::logging::LoggedOutput;
 use anyhow::{Context, Result};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
+/// Everything config.toml can set. Any key left out takes its value from
+/// `Config::default()`, or the field's own default where it has one.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
     pub display_width: u32,
     pub display_height: u32,
@@ -27,12 +32,41 @@ pub struct Config {
     pub backward_key: u16, // KEY_TAB (15) - Track SHIFT modifier internally
     #[serde(default = "default_show_overlay")]
     pub show_overlay: bool,
+    /// How the overlay keeps up with the clients: "poll" (redraw every frame,
+    /// re-read the window list twice a second) or "events" (redraw only on
+    /// focus, window and title changes). Falls back to polling on backends
+    /// without an event stream.
+    #[serde(default)]
+    pub overlay_sync: OverlaySync,
+    /// "full" or "compact"
+    #[serde(default)]
+    pub overlay_style: OverlayStyle,
+    /// Width of each live client thumbnail in `nicotine thumbnails`; the
+    /// height follows the client's aspect ratio
+    #[serde(default = "default_thumbnail_width")]
+    pub thumbnail_width: u32,
+    /// How often thumbnails are recaptured
+    #[serde(default = "default_thumbnail_interval_ms")]
+    pub thumbnail_interval_ms: u64,
+    /// Where the thumbnail strip opens
+    #[serde(default = "default_thumbnail_x")]
+    pub thumbnail_x: f32,
+    #[serde(default = "default_thumbnail_y")]
+    pub thumbnail_y: f32,
     #[serde(default = "default_mouse_device_name")]
     pub mouse_device_name: Option<String>,
     #[serde(default = "default_mouse_device_path")]
     pub mouse_device_path: Option<String>,
     #[serde(default = "default_minimize_inactive")]
     pub minimize_inactive: bool,
+    /// Characters minimize_inactive leaves showing, e.g. a scout whose
+    /// screen you keep an eye on
+    #[serde(default)]
+    pub minimize_exclude: Vec<String>,
+    /// Characters kept above every other window once stacked, e.g. a
+    /// scout watched over the main client. `nicotine unpin` lets one go.
+    #[serde(default)]
+    pub pinned: Vec<String>,
     #[serde(default = "default_keyboard_device_path")]
     pub keyboard_device_path: Option<String>,
     #[serde(default = "default_modifier_key")]
@@ -41,12 +75,816 @@ pub struct Config {
     pub primary_character: Option<String>,
     #[serde(default)]
     pub primary_monitor: Option<String>,
+    /// Treat whichever EVE window is focused at stack time as the primary
+    /// character, falling back to primary_character if focus isn't on EVE
+    #[serde(default)]
+    pub primary_follows_active: bool,
     #[serde(default)]
     pub fullscreen_stack: bool,
+    /// How stack places windows: "centered" (eve_width wide), "fullscreen",
+    /// "grid" (tile the clients, with the primary fullscreen on its own),
+    /// "cascade" (eve_width x eve_height, each offset from the last),
+    /// "monitor_only" (move to the right monitor, keep EVE's own size and
+    /// position memory), or "pip" (the active client fullscreen, the rest
+    /// pip_size along pip_edge, swapping places as you cycle).
+    /// fullscreen_stack = true is the same as "fullscreen".
+    #[serde(default)]
+    pub layout: LayoutMode,
+    /// Step between cascaded windows as [x, y] pixels; negative steps
+    /// cascade from the right or bottom edge
+    #[serde(default = "default_cascade_offset")]
+    pub cascade_offset: (i32, i32),
+    /// Part of each monitor's usable area the cascade stays inside, as
+    /// fractions of it. The cascade wraps back to the region's top left
+    /// rather than leaving it.
+    /// Example: cascade_region = { x = 0.5, y = 0.0, width = 0.5, height = 1.0 }
+    #[serde(default)]
+    pub cascade_region: Option<RectFrac>,
+    /// Size of each small window in the pip layout, as [width, height]
+    #[serde(default = "default_pip_size")]
+    pub pip_size: (u32, u32),
+    /// Screen edge the pip layout lines the small windows up along
+    #[serde(default)]
+    pub pip_edge: PipEdge,
+    /// Monitor the grid layout tiles every client but the primary onto, by
+    /// name or index. Unset tiles each monitor's clients where they are.
+    #[serde(default)]
+    pub grid_monitor: Option<MonitorRef>,
+    /// Pixels left between neighbouring grid cells
+    #[serde(default)]
+    pub grid_gap: u32,
+    /// Grid shape per monitor, keyed by monitor name; unset dimensions are
+    /// worked out from the number of clients
+    /// Example: [grids.DP-1] columns = 3, rows = 2, gap = 8
+    #[serde(default)]
+    pub grids: HashMap<String, GridSpec>,
+    /// Characters stack moves onto each monitor, keyed by monitor name.
+    /// character_layouts.<name>.monitor takes precedence.
+    /// Example: [monitor_assignments] "DP-1" = ["Main", "Scout"]
+    #[serde(default)]
+    pub monitor_assignments: HashMap<String, Vec<String>>,
+    /// Monitor, by name or index, for characters whose assigned monitor
+    /// isn't connected and for windows left off every monitor
+    #[serde(default)]
+    pub fallback_monitor: Option<MonitorRef>,
+    /// Have the daemon re-stack when monitors are plugged in, unplugged or
+    /// change resolution
+    #[serde(default = "default_restack_on_monitor_change")]
+    pub restack_on_monitor_change: bool,
+    /// Where monitor_only puts a window that has to change monitors
+    #[serde(default)]
+    pub monitor_only_anchor: MonitorAnchor,
+    /// What to do when the backend reports no monitors: "use_global_config"
+    /// (place using display_width/height), "abort", or "single_synthetic"
+    /// (treat display_width x display_height as one monitor at 0,0)
+    #[serde(default)]
+    pub no_monitors: NoMonitorPolicy,
+    /// Pixels a window may be off its planned position or size before
+    /// `nicotine heal` puts it back
+    #[serde(default = "default_heal_tolerance")]
+    pub heal_tolerance: u32,
+    /// Warp the pointer to the centre of a client after switching to it, so
+    /// the next click lands in that client
+    #[serde(default)]
+    pub warp_on_activate: bool,
+    /// Where in the client the pointer is warped to, as fractions of its
+    /// width and height. Also used by `nicotine engage`.
+    /// Example: warp_anchor = { x = 0.5, y = 0.1 } for just below the top
+    #[serde(default)]
+    pub warp_anchor: WarpAnchor,
+    /// Strip decorations from windows as they're stacked, leaving login and
+    /// launcher windows alone. `nicotine reset` puts them back.
+    #[serde(default)]
+    pub remove_decorations: bool,
+    /// Only move windows, never resize them
+    #[serde(default)]
+    pub move_only: bool,
+    /// Screen edge the panel sits on (panel_height is reserved on this edge)
+    #[serde(default)]
+    pub panel_edge: PanelEdge,
+    /// Measure panels instead of trusting panel_height: reserve the real
+    /// size and edge of any panel window whose class is in panel_classes.
+    /// Monitors where none is found still use panel_height.
+    #[serde(default)]
+    pub detect_panels: bool,
+    /// Window classes (Hyprland: layer namespaces) treated as panels
+    #[serde(default = "default_panel_classes")]
+    pub panel_classes: Vec<String>,
+    /// Per-monitor panel height overrides, keyed by monitor name
+    /// Example: { "DP-1" = 32, "HDMI-A-1" = 0 }
+    #[serde(default)]
+    pub monitor_panel_heights: HashMap<String, u32>,
+    /// Gap in pixels kept free around every edge of the usable area
+    #[serde(default)]
+    pub margin: u32,
+    /// Which Sway tree field holds the window title ("auto" tries name, then
+    /// window_properties.title for Xwayland clients)
+    #[serde(default)]
+    pub sway_title_field: SwayTitleField,
+    /// On X11, match on _NET_WM_VISIBLE_NAME (what the WM displays) before
+    /// _NET_WM_NAME and WM_NAME
+    #[serde(default)]
+    pub prefer_visible_name: bool,
+    /// How the X11 backend moves windows: "configure" (a plain
+    /// ConfigureWindow, the default) or "ewmh" (ask the window manager with
+    /// _NET_MOVERESIZE_WINDOW, allowing for its frame). Try "ewmh" if
+    /// windows land offset by their title bar or don't move at all.
+    #[serde(default)]
+    pub x11_move_method: X11MoveMethod,
+    /// Discover clients by a window property your launcher sets, as
+    /// [name, value], instead of by title. On X11 the name is a property
+    /// atom; on Sway and Hyprland it's a field of the window's JSON (app_id,
+    /// class, ...). Not supported on KDE.
+    /// Example: match_property = ["_NICOTINE_CLIENT", "eve"]
+    #[serde(default)]
+    pub match_property: Option<(String, String)>,
+    /// Which windows count as clients and where the character name is in
+    /// their title, for clients whose titles don't read "EVE - Name" or for
+    /// multiboxing other games
+    #[serde(default)]
+    pub window_match: WindowMatch,
+    /// Only discover EVE windows on these monitors (by name or 0-based index).
+    /// Unset means every monitor. Does not restrict where windows are placed.
+    /// Example: ["DP-1", 1]
+    #[serde(default)]
+    pub discover_monitors: Option<Vec<MonitorRef>>,
+    /// Preferred monitor order, e.g. left to right, for backends that report
+    /// them in another order. Index references elsewhere, and anything that
+    /// falls back to the first monitor, follow it. Example: ["DP-2", "DP-1"]
+    #[serde(default)]
+    pub monitor_order: Vec<MonitorRef>,
+    /// Don't move a client that's still at login/character select; stack
+    /// skips it and wait-for-windows retries it once a character is in game
+    #[serde(default)]
+    pub defer_login_windows: bool,
+    /// While the daemon runs, place each client as it logs in (appears, or
+    /// its title goes from "EVE" to a character) without restacking the rest
+    #[serde(default)]
+    pub auto_stack_on_login: bool,
+    /// How long wait-for-windows waits for clients (seconds)
+    #[serde(default = "default_wait_timeout")]
+    pub wait_timeout: u64,
+    /// Delay between wait-for-windows polls (milliseconds)
+    #[serde(default = "default_wait_poll_interval_ms")]
+    pub wait_poll_interval_ms: u64,
+    /// Double the poll delay after each poll, up to wait_max_poll_interval_ms,
+    /// so a slow Proton cold start isn't polled every second for minutes
+    #[serde(default)]
+    pub wait_backoff: bool,
+    #[serde(default = "default_wait_max_poll_interval_ms")]
+    pub wait_max_poll_interval_ms: u64,
+    /// Have `nicotine solo` minimize every window on the solo'd monitor, not
+    /// just the other EVE clients. `nicotine unsolo` restores them all.
+    #[serde(default)]
+    pub solo_minimize_all: bool,
+    /// Leave the active client up when `nicotine minimize-all` hides the rest
+    #[serde(default)]
+    pub minimize_all_keep_active: bool,
+    /// Activate windows brought back by `nicotine unsolo`, since not every
+    /// backend focuses a window it restores (KWin does, X11 doesn't). Set to
+    /// false to restore them without taking focus.
+    #[serde(default = "default_focus_on_restore")]
+    pub focus_on_restore: bool,
+    /// When no EVE clients are found, look for a Wine virtual desktop
+    /// they're hidden inside and say so, instead of just finding none
+    #[serde(default = "default_detect_wine_desktop")]
+    pub detect_wine_desktop: bool,
+    /// Which window manager backend to use: "auto" (detect it from the
+    /// session), "x11", "kde", "sway", "hyprland", "gnome" or "wlr".
+    /// `--backend` overrides it for one command.
+    #[serde(default)]
+    pub backend: Backend,
+    /// On Plasma Wayland, drive KWin through D-Bus scripting so native
+    /// Wayland EVE clients are found too. Falls back to wmctrl when scripting
+    /// isn't available; set to false to always use wmctrl.
+    #[serde(default = "default_kwin_scripting")]
+    pub kwin_scripting: bool,
+    /// Have the daemon save its windows, cycle position and applied effects
+    /// to /tmp every `snapshot_interval_secs` and on quit, and pick them back
+    /// up when it restarts
+    #[serde(default)]
+    pub persist_snapshot: bool,
+    #[serde(default = "default_snapshot_interval_secs")]
+    pub snapshot_interval_secs: u64,
+    /// Have the daemon own org.nicotine.Manager on the session bus, so
+    /// desktop tools can list, activate, stack and cycle clients
+    #[serde(default)]
+    pub dbus_service: bool,
+    /// Global shortcuts the daemon handles itself, e.g.
+    /// `"ctrl+f1" = "cycle_forward"` or `"ctrl+1" = "activate_character Main"`.
+    /// Actions: cycle_forward, cycle_backward, stack_windows,
+    /// activate_character <character name>, focus_last, swap_with_primary.
+    #[serde(default)]
+    pub hotkeys: HashMap<String, String>,
+    /// Shortcuts that switch straight to a character, e.g. `"f1" = "Main"`.
+    /// Short for activate_character in [hotkeys].
+    #[serde(default)]
+    pub character_hotkeys: HashMap<String, String>,
+    /// What `nicotine tidy` does with launcher helper windows (updater, EULA)
+    /// owned by an EVE process: "leave", "minimize", or "offscreen".
+    /// `nicotine reset` puts them back.
+    #[serde(default)]
+    pub helper_windows: HelperWindowAction,
+    /// Window class EVE clients run under (WM_CLASS / Hyprland class)
+    #[serde(default = "default_eve_window_class")]
+    pub eve_window_class: String,
+    /// On Hyprland, have `nicotine start` add window rules so EVE floats and
+    /// can't re-assert borderless fullscreen over the layout. Also available
+    /// by hand: `nicotine hyprland-rules install|uninstall`.
+    #[serde(default)]
+    pub hyprland_window_rules: bool,
+    /// Extra steps run, in order, before each activation for WMs that refuse
+    /// focus requests: "unminimize", "raise_first", "warp_pointer"
+    /// Example: focus_workarounds = ["unminimize", "raise_first"]
+    #[serde(default)]
+    pub focus_workarounds: Vec<FocusWorkaround>,
+    /// How each backend hides windows for minimize_inactive
+    /// Example: [minimize_strategy] sway = { workspace = "eve" }
+    #[serde(default)]
+    pub minimize_strategy: MinimizeStrategy,
+    /// Opacity for the focused client and for the rest, kept up to date by
+    /// the daemon as focus moves. Unset leaves opacity alone.
+    /// Example: [focus_opacity] active = 1.0, inactive = 0.7
+    #[serde(default)]
+    pub focus_opacity: Option<FocusOpacity>,
+    /// Have the daemon hand focus back to the active client when another
+    /// window takes it during a combat lock, which the combat_lock hotkey
+    /// action turns on and off
+    #[serde(default)]
+    pub focus_guard: bool,
+    /// Desktop notifications the daemon sends, and how urgent each kind is
+    /// Example: [notifications] login = "low", connection_lost = "critical"
+    #[serde(default)]
+    pub notifications: Notifications,
+    /// EVE SSO and ESI, for each client's character id, portrait,
+    /// corporation and alliance. Off unless [esi] is present.
+    /// Example: [esi] client_id = "0123abcd..."
+    #[serde(default)]
+    pub esi: Option<EsiConfig>,
+    /// Accounts `nicotine launch` starts, one client each, in order
+    /// Example: [[launch.accounts]] name = "main", command = "steam -applaunch 8500"
+    #[serde(default)]
+    pub launch: Launch,
+    /// Which steps `nicotine engage` runs
+    /// Example: [engage] warp_cursor = false
+    #[serde(default)]
+    pub engage: EngageSteps,
+    /// Shell command run after every stack, from the CLI, daemon, auto-stack
+    /// or engage. Gets just the windows that were stacked in
+    /// NICOTINE_WINDOW_COUNT / NICOTINE_WINDOW_IDS / NICOTINE_CHARACTERS and as
+    /// JSON on stdin. A failing hook is logged, never fails the stack.
+    #[serde(default)]
+    pub post_stack_command: Option<String>,
+    /// Prometheus textfile rewritten after every stack and daemon command,
+    /// for node_exporter's textfile collector
+    /// Example: metrics_textfile = "/var/lib/node_exporter/textfile/nicotine.prom"
+    #[serde(default)]
+    pub metrics_textfile: Option<PathBuf>,
+    /// Per-character overrides, keyed by character name
+    /// Example: [character_layouts.Main] z_priority = 10
+    /// Example: [character_layouts.Main] monitor = "DP-1", fullscreen = true
+    #[serde(default)]
+    pub character_layouts: HashMap<String, CharacterLayout>,
+    /// "fixed", "alphabetical" or "mru". Also the order `nicotine N` counts
+    /// in when there's no characters.txt.
+    #[serde(default)]
+    pub cycle_order: CycleOrder,
     /// Named groups of characters for selective cycling
     /// Example: { "scouts" = ["Scout1", "Scout2"], "combat" = ["DPS1", "DPS2", "Logi"] }
     #[serde(default)]
     pub groups: HashMap<String, Vec<String>>,
+    /// Also make a group per launcher instance (launcher-1, launcher-2, ...
+    /// in launch order), so separate account pools can be cycled apart
+    #[serde(default)]
+    pub group_by_launcher: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PanelEdge {
+    Top,
+    #[default]
+    Bottom,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    #[default]
+    Auto,
+    X11,
+    Kde,
+    Sway,
+    Hyprland,
+    Gnome,
+    /// Any compositor with wlr-foreign-toplevel-management
+    Wlr,
+    /// Win32, on Windows builds
+    Windows,
+    /// Accessibility API, on macOS builds
+    Macos,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        toml::Value::String(s.to_string())
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Unknown backend {:?}", s))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SwayTitleField {
+    #[default]
+    Auto,
+    Name,
+    WindowProperties,
+}
+
+/// The order forward/backward step through clients in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CycleOrder {
+    /// As the window manager lists them
+    #[default]
+    Fixed,
+    /// By character name
+    Alphabetical,
+    /// Most recently used first, like Alt-Tab (needs the daemon)
+    Mru,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutMode {
+    #[default]
+    Centered,
+    Fullscreen,
+    Grid,
+    Cascade,
+    MonitorOnly,
+    Pip,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PipEdge {
+    Top,
+    Bottom,
+    Left,
+    #[default]
+    Right,
+}
+
+/// Fixed columns and/or rows for the grid on one monitor. A grid too small
+/// for every client grows extra rows.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GridSpec {
+    #[serde(default)]
+    pub columns: Option<u32>,
+    #[serde(default)]
+    pub rows: Option<u32>,
+    /// Overrides grid_gap on this monitor
+    #[serde(default)]
+    pub gap: Option<u32>,
+}
+
+/// A point in a window as fractions (0.0-1.0) of its width and height
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct WarpAnchor {
+    #[serde(default = "default_warp_anchor")]
+    pub x: f64,
+    #[serde(default = "default_warp_anchor")]
+    pub y: f64,
+}
+
+impl Default for WarpAnchor {
+    fn default() -> Self {
+        Self { x: 0.5, y: 0.5 }
+    }
+}
+
+fn default_warp_anchor() -> f64 {
+    0.5
+}
+
+/// A rectangle as fractions (0.0-1.0) of another one
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct RectFrac {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HelperWindowAction {
+    #[default]
+    Leave,
+    Minimize,
+    Offscreen,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlaySync {
+    #[default]
+    Poll,
+    Events,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayStyle {
+    /// Logo, every client with the active one marked, and a restack button
+    #[default]
+    Full,
+    /// Just the active character and its cycle position, e.g. "Main 3/9"
+    Compact,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusWorkaround {
+    /// Raise the window before asking for focus
+    RaiseFirst,
+    /// Move the pointer to the window's centre
+    WarpPointer,
+    /// Restore the window in case it's minimized or hidden
+    Unminimize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MonitorAnchor {
+    #[default]
+    TopLeft,
+    Center,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NoMonitorPolicy {
+    #[default]
+    UseGlobalConfig,
+    Abort,
+    SingleSynthetic,
+}
+
+/// The steps of `nicotine engage`: stack, focus primary_character, then
+/// warp the cursor onto it. All on by default.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct EngageSteps {
+    #[serde(default = "default_engage_step")]
+    pub stack: bool,
+    #[serde(default = "default_engage_step")]
+    pub focus_primary: bool,
+    #[serde(default = "default_engage_step")]
+    pub warp_cursor: bool,
+}
+
+impl Default for EngageSteps {
+    fn default() -> Self {
+        Self {
+            stack: true,
+            focus_primary: true,
+            warp_cursor: true,
+        }
+    }
+}
+
+fn default_engage_step() -> bool {
+    true
+}
+
+/// Opacities (0.0-1.0) for the focused client and the others
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct FocusOpacity {
+    #[serde(default = "default_active_opacity")]
+    pub active: f64,
+    #[serde(default = "default_inactive_opacity")]
+    pub inactive: f64,
+}
+
+fn default_active_opacity() -> f64 {
+    1.0
+}
+
+fn default_inactive_opacity() -> f64 {
+    0.7
+}
+
+/// How urgent a desktop notification is, or "off" not to send it
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyUrgency {
+    #[default]
+    Off,
+    Low,
+    Normal,
+    Critical,
+}
+
+/// Which events are notified, all off unless set
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Notifications {
+    /// A client getting past character select
+    #[serde(default)]
+    pub login: NotifyUrgency,
+    /// The daemon losing its event connection to the X server or compositor
+    #[serde(default)]
+    pub connection_lost: NotifyUrgency,
+    /// Failures the daemon would otherwise only log, like a config.toml
+    /// edit that doesn't parse or an automatic stack going wrong
+    #[serde(default)]
+    pub error: NotifyUrgency,
+    /// A skill queue, industry job or planet's extractors finishing, for
+    /// characters logged in with `nicotine esi login`
+    #[serde(default)]
+    pub timers: NotifyUrgency,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EsiConfig {
+    /// Client ID of an application registered at
+    /// https://developers.eveonline.com, for `nicotine esi login`. Lookups
+    /// by name work without one.
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// Port of the application's callback URL,
+    /// http://localhost:<port>/callback
+    #[serde(default = "default_esi_callback_port")]
+    pub callback_port: u16,
+}
+
+fn default_esi_callback_port() -> u16 {
+    8635
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Launch {
+    /// Run through `sh -c` for accounts without a command of their own
+    #[serde(default = "default_launch_command")]
+    pub command: String,
+    #[serde(default)]
+    pub accounts: Vec<LaunchAccount>,
+}
+
+impl Default for Launch {
+    fn default() -> Self {
+        Self {
+            command: default_launch_command(),
+            accounts: Vec::new(),
+        }
+    }
+}
+
+fn default_launch_command() -> String {
+    "steam -applaunch 8500".to_string()
+}
+
+/// One account's launcher, e.g. a Wine prefix of its own
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LaunchAccount {
+    /// Name for `nicotine launch <name>`, also passed to the command in
+    /// NICOTINE_ACCOUNT
+    pub name: String,
+    /// Overrides [launch] command for this account
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct MinimizeStrategy {
+    #[serde(default)]
+    pub sway: SwayMinimize,
+    #[serde(default)]
+    pub hyprland: HyprlandMinimize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum X11MoveMethod {
+    #[default]
+    Configure,
+    Ewmh,
+}
+
+/// Sway has no real minimize. The scratchpad is shared by every workspace, so
+/// a named workspace can be used instead to keep clients out of it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SwayMinimize {
+    #[default]
+    Scratchpad,
+    Workspace(String),
+}
+
+/// Hyprland minimize: a special workspace ("" is the default unnamed one) or
+/// a regular workspace the window is moved to silently
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HyprlandMinimize {
+    Special(String),
+    Workspace(String),
+}
+
+impl Default for HyprlandMinimize {
+    fn default() -> Self {
+        HyprlandMinimize::Special(String::new())
+    }
+}
+
+/// The [window_match] table. Patterns use `*` for any run of characters;
+/// in title_pattern, `{name}` marks the character name. title_regex is a
+/// full regular expression for titles the wildcards can't describe.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct WindowMatch {
+    /// Titles starting with this are clients, the rest being the name
+    #[serde(default = "default_title_prefix")]
+    pub title_prefix: String,
+    /// Used instead of title_prefix when set, e.g. "星战前夜 - {name}"
+    #[serde(default)]
+    pub title_pattern: Option<String>,
+    /// Regex used instead of title_prefix and title_pattern, with the
+    /// character name in a group named `name`,
+    /// e.g. "^(?:EVE|星战前夜) - (?P<name>.+)$"
+    #[serde(default)]
+    pub title_regex: Option<TitleRegex>,
+    /// Detect clients by this X11 / XWayland WM_CLASS (Hyprland class),
+    /// e.g. "steam_app_8500", using the title only for the character name.
+    /// Backends that can't report a window's class fall back to its title.
+    #[serde(default)]
+    pub wm_class: Option<String>,
+    /// Detect native Wayland clients by this app_id. With wm_class also
+    /// set, a window matching either is a client.
+    #[serde(default)]
+    pub app_id: Option<String>,
+    /// Titles matching any of these are never clients
+    #[serde(default = "default_title_exclude")]
+    pub exclude: Vec<String>,
+}
+
+impl Default for WindowMatch {
+    fn default() -> Self {
+        Self {
+            title_prefix: default_title_prefix(),
+            title_pattern: None,
+            title_regex: None,
+            wm_class: None,
+            app_id: None,
+            exclude: default_title_exclude(),
+        }
+    }
+}
+
+/// A title_regex, compiled once when config.toml is read. A regex that
+/// doesn't compile or has no `name` group fails the config.
+#[derive(Debug, Clone)]
+pub struct TitleRegex(pub Regex);
+
+impl TitleRegex {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).context("Invalid title_regex")?;
+        if !regex.capture_names().any(|name| name == Some("name")) {
+            anyhow::bail!("title_regex needs a group named name, e.g. (?P<name>.+)");
+        }
+        Ok(Self(regex))
+    }
+}
+
+impl PartialEq for TitleRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for TitleRegex {}
+
+impl Serialize for TitleRegex {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for TitleRegex {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        TitleRegex::new(&pattern).map_err(|e| serde::de::Error::custom(format!("{:#}", e)))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct CharacterLayout {
+    /// Stacking order after placement: positive values are raised above
+    /// other clients (highest on top), negative values lowered beneath them
+    #[serde(default)]
+    pub z_priority: Option<i32>,
+    /// Monitor stack puts this character on, by name or index, instead of
+    /// primary_monitor or the one it's already on
+    #[serde(default)]
+    pub monitor: Option<MonitorRef>,
+    /// Fill the monitor's usable area, whatever the layout mode
+    #[serde(default)]
+    pub fullscreen: bool,
+    /// Explicit geometry, relative to the monitor's usable area. Any of these
+    /// set takes the window out of the layout mode; unset ones default to
+    /// the area's corner and eve_width by the area's height.
+    #[serde(default)]
+    pub x: Option<i32>,
+    #[serde(default)]
+    pub y: Option<i32>,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+}
+
+impl CharacterLayout {
+    /// Whether this layout places the window itself rather than leaving it
+    /// to the layout mode
+    pub fn has_geometry(&self) -> bool {
+        self.fullscreen
+            || self.x.is_some()
+            || self.y.is_some()
+            || self.width.is_some()
+            || self.height.is_some()
+    }
+}
+
+/// A monitor named by its output name or by its position in the backend's
+/// monitor list
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum MonitorRef {
+    Index(usize),
+    Name(String),
+}
+
+fn default_panel_classes() -> Vec<String> {
+    [
+        "waybar",
+        "polybar",
+        "plasmashell",
+        "xfce4-panel",
+        "tint2",
+        "lxqt-panel",
+    ]
+    .iter()
+    .map(|c| c.to_string())
+    .collect()
+}
+
+fn default_thumbnail_width() -> u32 {
+    240
+}
+
+fn default_thumbnail_interval_ms() -> u64 {
+    250
+}
+
+fn default_thumbnail_x() -> f32 {
+    240.0
+}
+
+fn default_thumbnail_y() -> f32 {
+    10.0
+}
+
+fn default_restack_on_monitor_change() -> bool {
+    true
+}
+
+fn default_heal_tolerance() -> u32 {
+    10
+}
+
+fn default_focus_on_restore() -> bool {
+    true
+}
+
+fn default_detect_wine_desktop() -> bool {
+    true
+}
+
+fn default_kwin_scripting() -> bool {
+    true
+}
+
+fn default_snapshot_interval_secs() -> u64 {
+    30
+}
+
+fn default_cascade_offset() -> (i32, i32) {
+    (30, 30)
+}
+
+fn default_pip_size() -> (u32, u32) {
+    (480, 270)
 }
 
 fn default_enable_mouse() -> bool {
@@ -97,17 +935,161 @@ fn default_modifier_key() -> Option<u16> {
     None // No modifier for backward shifting by default
 }
 
+fn default_title_prefix() -> String {
+    "EVE - ".to_string()
+}
+
+fn default_title_exclude() -> Vec<String> {
+    vec!["*Launcher*".to_string()]
+}
+
+fn default_eve_window_class() -> String {
+    "steam_app_8500".to_string()
+}
+
+fn default_wait_timeout() -> u64 {
+    120
+}
+
+fn default_wait_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_wait_max_poll_interval_ms() -> u64 {
+    10_000
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            display_width: 1920,
+            display_height: 1080,
+            panel_height: 0,
+            eve_width: 1036,
+            eve_height: 1080,
+            overlay_x: 10.0,
+            overlay_y: 10.0,
+            enable_mouse_buttons: default_enable_mouse(),
+            forward_button: default_forward_button(),
+            backward_button: default_backward_button(),
+            enable_keyboard_buttons: default_enable_keyboard(),
+            forward_key: default_forward_key(),
+            backward_key: default_backward_key(),
+            show_overlay: default_show_overlay(),
+            overlay_sync: OverlaySync::default(),
+            overlay_style: OverlayStyle::default(),
+            thumbnail_width: default_thumbnail_width(),
+            thumbnail_interval_ms: default_thumbnail_interval_ms(),
+            thumbnail_x: default_thumbnail_x(),
+            thumbnail_y: default_thumbnail_y(),
+            mouse_device_name: default_mouse_device_name(),
+            mouse_device_path: default_mouse_device_path(),
+            minimize_inactive: default_minimize_inactive(),
+            minimize_exclude: Vec::new(),
+            pinned: Vec::new(),
+            keyboard_device_path: default_keyboard_device_path(),
+            modifier_key: default_modifier_key(),
+            primary_character: None,
+            primary_monitor: None,
+            primary_follows_active: false,
+            fullscreen_stack: false,
+            layout: LayoutMode::default(),
+            cascade_offset: default_cascade_offset(),
+            cascade_region: None,
+            pip_size: default_pip_size(),
+            pip_edge: PipEdge::default(),
+            grid_monitor: None,
+            grid_gap: 0,
+            grids: HashMap::new(),
+            monitor_assignments: HashMap::new(),
+            fallback_monitor: None,
+            restack_on_monitor_change: default_restack_on_monitor_change(),
+            monitor_only_anchor: MonitorAnchor::default(),
+            no_monitors: NoMonitorPolicy::default(),
+            heal_tolerance: default_heal_tolerance(),
+            warp_on_activate: false,
+            warp_anchor: WarpAnchor::default(),
+            remove_decorations: false,
+            move_only: false,
+            panel_edge: PanelEdge::default(),
+            detect_panels: false,
+            panel_classes: default_panel_classes(),
+            monitor_panel_heights: HashMap::new(),
+            margin: 0,
+            sway_title_field: SwayTitleField::default(),
+            prefer_visible_name: false,
+            x11_move_method: X11MoveMethod::default(),
+            match_property: None,
+            window_match: WindowMatch::default(),
+            discover_monitors: None,
+            monitor_order: Vec::new(),
+            defer_login_windows: false,
+            auto_stack_on_login: false,
+            wait_timeout: default_wait_timeout(),
+            wait_poll_interval_ms: default_wait_poll_interval_ms(),
+            wait_backoff: false,
+            wait_max_poll_interval_ms: default_wait_max_poll_interval_ms(),
+            solo_minimize_all: false,
+            minimize_all_keep_active: false,
+            focus_on_restore: default_focus_on_restore(),
+            detect_wine_desktop: default_detect_wine_desktop(),
+            backend: Backend::default(),
+            kwin_scripting: default_kwin_scripting(),
+            persist_snapshot: false,
+            snapshot_interval_secs: default_snapshot_interval_secs(),
+            dbus_service: false,
+            hotkeys: HashMap::new(),
+            character_hotkeys: HashMap::new(),
+            helper_windows: HelperWindowAction::default(),
+            eve_window_class: default_eve_window_class(),
+            hyprland_window_rules: false,
+            focus_workarounds: Vec::new(),
+            minimize_strategy: MinimizeStrategy::default(),
+            focus_opacity: None,
+            focus_guard: false,
+            notifications: Notifications::default(),
+            esi: None,
+            launch: Launch::default(),
+            engage: EngageSteps::default(),
+            post_stack_command: None,
+            metrics_textfile: None,
+            character_layouts: HashMap::new(),
+            cycle_order: CycleOrder::default(),
+            groups: HashMap::new(),
+            group_by_launcher: false,
+        }
+    }
+}
+
 impl Config {
+    /// $XDG_CONFIG_HOME/nicotine, else ~/.config/nicotine
     fn config_dir() -> PathBuf {
-        let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+        let mut path = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(dirs::config_dir)
+            .unwrap_or_else(|| PathBuf::from("."));
         path.push("nicotine");
         path
     }
 
-    fn config_path() -> PathBuf {
-        let mut path = Self::config_dir();
-        path.push("config.toml");
-        path
+    /// Where a new config.toml goes: NICOTINE_CONFIG (set by `--config`) if
+    /// given, else the XDG location
+    fn default_config_path() -> PathBuf {
+        match std::env::var_os("NICOTINE_CONFIG").filter(|path| !path.is_empty()) {
+            Some(path) => PathBuf::from(path),
+            None => Self::config_dir().join("config.toml"),
+        }
+    }
+
+    /// The config.toml to read
+    pub fn config_path() -> PathBuf {
+        let explicit = std::env::var_os("NICOTINE_CONFIG").filter(|path| !path.is_empty());
+        resolve_config_path(
+            explicit.map(PathBuf::from),
+            Self::config_dir().join("config.toml"),
+            PathBuf::from("config.toml"),
+        )
     }
 
     /// Load character order from characters.txt
@@ -156,9 +1138,9 @@ impl Config {
         }
 
         // Fallback to common resolution
-        eprintln!("Warning: Could not detect display size, using default 1920x1080");
-        eprintln!(
-            "Edit ~/.config/nicotine/config.toml to set correct display_width and display_height"
+        tracing::warn!(
+            "Could not detect display size, using default 1920x1080\n\
+             Edit ~/.config/nicotine/config.toml to set correct display_width and display_height"
         );
         (1920, 1080)
     }
@@ -166,7 +1148,7 @@ impl Config {
     fn detect_via_xrandr() -> Option<(u32, u32)> {
         let output = std::process::Command::new("xrandr")
             .args(["--current"])
-            .output()
+            .logged_output()
             .ok()?;
 
         if !output.status.success() {
@@ -192,7 +1174,7 @@ impl Config {
     fn detect_via_swaymsg() -> Option<(u32, u32)> {
         let output = std::process::Command::new("swaymsg")
             .args(["-t", "get_outputs"])
-            .output()
+            .logged_output()
             .ok()?;
 
         if !output.status.success() {
@@ -224,7 +1206,7 @@ impl Config {
     fn detect_via_hyprctl() -> Option<(u32, u32)> {
         let output = std::process::Command::new("hyprctl")
             .args(["monitors", "-j"])
-            .output()
+            .logged_output()
             .ok()?;
 
         if !output.status.success() {
@@ -258,7 +1240,9 @@ impl Config {
     }
 
     fn detect_via_wlr_randr() -> Option<(u32, u32)> {
-        let output = std::process::Command::new("wlr-randr").output().ok()?;
+        let output = std::process::Command::new("wlr-randr")
+            .logged_output()
+            .ok()?;
 
         if !output.status.success() {
             return None;
@@ -291,39 +1275,17 @@ impl Config {
 
         // Try to load existing config
         if let Ok(contents) = fs::read_to_string(&config_path) {
-            return toml::from_str(&contents).context("Failed to parse config.toml");
+            let profile = std::env::var("NICOTINE_PROFILE").ok();
+            return Self::from_toml_str(&contents, profile.as_deref());
         }
 
         // Auto-generate config based on detected display
+        let config_path = Self::default_config_path();
         println!("Generating config based on your display...");
         let (display_width, display_height) = Self::detect_display_size();
         println!("Detected display: {}x{}", display_width, display_height);
 
-        let config = Self {
-            display_width,
-            display_height,
-            panel_height: 0, // Assume no panel by default
-            eve_width: (display_width as f32 * 0.54) as u32, // ~54% of width
-            eve_height: display_height,
-            overlay_x: 10.0,
-            overlay_y: 10.0,
-            enable_mouse_buttons: true,
-            forward_button: 276,  // BTN_SIDE (button 9)
-            backward_button: 275, // BTN_EXTRA (button 8)
-            enable_keyboard_buttons: false,
-            forward_key: 15,  // KEY_TAB
-            backward_key: 15, // KEY_TAB (with Shift)
-            show_overlay: true,
-            mouse_device_name: None,
-            mouse_device_path: None,
-            minimize_inactive: false,
-            keyboard_device_path: None,
-            modifier_key: None,
-            primary_character: None,
-            primary_monitor: None,
-            fullscreen_stack: false,
-            groups: HashMap::new(),
-        };
+        let config = Self::generated(display_width, display_height);
 
         // Save the generated config
         if let Some(parent) = config_path.parent() {
@@ -337,35 +1299,27 @@ impl Config {
         Ok(config)
     }
 
-    pub fn save_default() -> Result<()> {
+    /// The existing config.toml, without generating one or printing anything
+    pub fn read_existing() -> Option<Self> {
+        let contents = fs::read_to_string(Self::config_path()).ok()?;
+        let profile = std::env::var("NICOTINE_PROFILE").ok();
+        Self::from_toml_str(&contents, profile.as_deref()).ok()
+    }
+
+    /// config.toml with `profile` applied (or the file's own `profile` key
+    /// with None), for switching profile or reloading at runtime
+    pub fn load_profile(profile: Option<&str>) -> Result<Self> {
         let config_path = Self::config_path();
+        let contents = fs::read_to_string(&config_path)
+            .with_context(|| format!("Failed to read {}", config_path.display()))?;
+        Self::from_toml_str(&contents, profile)
+    }
+
+    pub fn save_default() -> Result<()> {
+        let config_path = Self::default_config_path();
         let (display_width, display_height) = Self::detect_display_size();
 
-        let config = Self {
-            display_width,
-            display_height,
-            panel_height: 0,
-            eve_width: (display_width as f32 * 0.54) as u32,
-            eve_height: display_height,
-            overlay_x: 10.0,
-            overlay_y: 10.0,
-            enable_mouse_buttons: true,
-            forward_button: 276,
-            backward_button: 275,
-            enable_keyboard_buttons: false,
-            forward_key: 15,
-            backward_key: 15,
-            show_overlay: true,
-            mouse_device_name: None,
-            mouse_device_path: None,
-            minimize_inactive: false,
-            keyboard_device_path: None,
-            modifier_key: None,
-            primary_character: None,
-            primary_monitor: None,
-            fullscreen_stack: false,
-            groups: HashMap::new(),
-        };
+        let config = Self::generated(display_width, display_height);
 
         if let Some(parent) = config_path.parent() {
             fs::create_dir_all(parent)?;
@@ -376,9 +1330,252 @@ impl Config {
         Ok(())
     }
 
+    /// Build a config sized for the detected display
+    fn generated(display_width: u32, display_height: u32) -> Self {
+        Self {
+            display_width,
+            display_height,
+            panel_height: 0, // Assume no panel by default
+            eve_width: (display_width as f32 * 0.54) as u32, // ~54% of width
+            eve_height: display_height,
+            ..Self::default()
+        }
+    }
+
     pub fn eve_height_adjusted(&self) -> u32 {
         self.display_height - self.panel_height
     }
+
+    /// Parse config.toml contents, applying the selected profile.
+    ///
+    /// Profiles live under [profiles.<name>] and hold any subset of the
+    /// top-level settings, plus an optional `inherits = "<other profile>"`.
+    /// The profile is `profile_override` (NICOTINE_PROFILE) if given, else the
+    /// top-level `profile` key, else none.
+    pub fn from_toml_str(contents: &str, profile_override: Option<&str>) -> Result<Self> {
+        let mut root: toml::Table =
+            toml::from_str(contents).context("Failed to parse config.toml")?;
+        let profiles = match root.remove("profiles") {
+            Some(toml::Value::Table(profiles)) => profiles,
+            Some(_) => anyhow::bail!("[profiles] in config.toml must be a table"),
+            None => toml::Table::new(),
+        };
+        let selected = match (profile_override, root.remove("profile")) {
+            (Some(name), _) => Some(name.to_string()),
+            (None, Some(toml::Value::String(name))) => Some(name),
+            (None, Some(_)) => anyhow::bail!("profile in config.toml must be a string"),
+            (None, None) => None,
+        };
+
+        if let Some(name) = selected {
+            // Walk the inherits chain, then apply it base-most first
+            let mut chain: Vec<toml::Table> = Vec::new();
+            let mut next = Some(name);
+            let mut seen: Vec<String> = Vec::new();
+            while let Some(name) = next {
+                if seen.contains(&name) {
+                    anyhow::bail!(
+                        "Profile inheritance loop: {} -> {}",
+                        seen.join(" -> "),
+                        name
+                    );
+                }
+                let mut table = match profiles.get(&name) {
+                    Some(toml::Value::Table(table)) => table.clone(),
+                    _ => anyhow::bail!("Unknown profile: {}", name),
+                };
+                next = match table.remove("inherits") {
+                    Some(toml::Value::String(parent)) => Some(parent),
+                    Some(_) => anyhow::bail!("inherits in profile {} must be a string", name),
+                    None => None,
+                };
+                seen.push(name);
+                chain.push(table);
+            }
+            for table in chain.into_iter().rev() {
+                merge_tables(&mut root, table);
+            }
+        }
+
+        reject_unknown_keys(&root)?;
+        let config: Self = toml::Value::Table(root)
+            .try_into()
+            .context("Failed to parse config.toml")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check settings that parse but can't work together, reporting every
+    /// problem at once
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        for (name, value) in [
+            ("display_width", self.display_width as u64),
+            ("display_height", self.display_height as u64),
+            ("eve_width", self.eve_width as u64),
+            ("eve_height", self.eve_height as u64),
+            ("thumbnail_width", self.thumbnail_width as u64),
+            ("thumbnail_interval_ms", self.thumbnail_interval_ms),
+            ("wait_poll_interval_ms", self.wait_poll_interval_ms),
+            ("snapshot_interval_secs", self.snapshot_interval_secs),
+        ] {
+            if value == 0 {
+                problems.push(format!("{} must be greater than 0", name));
+            }
+        }
+        if self.panel_height >= self.display_height {
+            problems.push(format!(
+                "panel_height ({}) must be less than display_height ({})",
+                self.panel_height, self.display_height
+            ));
+        }
+        if self.wait_backoff && self.wait_max_poll_interval_ms < self.wait_poll_interval_ms {
+            problems.push(format!(
+                "wait_max_poll_interval_ms ({}) must be at least wait_poll_interval_ms ({})",
+                self.wait_max_poll_interval_ms, self.wait_poll_interval_ms
+            ));
+        }
+        if let Some(region) = &self.cascade_region {
+            let fits = [region.x, region.y, region.width, region.height]
+                .iter()
+                .all(|v| (0.0..=1.0).contains(v))
+                && region.x + region.width <= 1.0
+                && region.y + region.height <= 1.0;
+            if !fits || region.width == 0.0 || region.height == 0.0 {
+                problems.push(
+                    "cascade_region must be a non-empty area inside 0.0..1.0 on both axes"
+                        .to_string(),
+                );
+            }
+        }
+        let rules = &self.window_match;
+        if rules.title_regex.is_some() && rules.title_pattern.is_some() {
+            problems.push("window_match: set title_regex or title_pattern, not both".to_string());
+        }
+        let anchor = self.warp_anchor;
+        if !(0.0..=1.0).contains(&anchor.x) || !(0.0..=1.0).contains(&anchor.y) {
+            problems.push("warp_anchor must be inside 0.0..1.0 on both axes".to_string());
+        }
+        if let Some(opacity) = &self.focus_opacity {
+            for (name, value) in [("active", opacity.active), ("inactive", opacity.inactive)] {
+                if !(0.0..=1.0).contains(&value) {
+                    problems.push(format!(
+                        "focus_opacity.{} ({}) must be between 0.0 and 1.0",
+                        name, value
+                    ));
+                }
+            }
+        }
+        if self
+            .primary_character
+            .as_deref()
+            .is_some_and(|c| c.trim().is_empty())
+        {
+            problems.push("primary_character is set but empty".to_string());
+        }
+        let mut assigned: HashMap<&str, &str> = HashMap::new();
+        let mut monitors: Vec<_> = self.monitor_assignments.iter().collect();
+        monitors.sort();
+        for (monitor, characters) in monitors {
+            for character in characters {
+                if let Some(first) = assigned.insert(character, monitor) {
+                    problems.push(format!(
+                        "monitor_assignments puts {} on both {} and {}",
+                        character, first, monitor
+                    ));
+                }
+            }
+        }
+        #[cfg(target_os = "linux")]
+        if let Err(e) = crate::hotkeys::Hotkeys::from_config(self) {
+            problems.push(format!("{:#}", e));
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        anyhow::bail!("Invalid config.toml:\n  - {}", problems.join("\n  - "))
+    }
+
+    /// Render the effective config for `config show`
+    pub fn render(&self, json: bool) -> Result<String> {
+        if json {
+            Ok(serde_json::to_string_pretty(self)?)
+        } else {
+            Ok(toml::to_string_pretty(self)?)
+        }
+    }
+
+    /// Layout to stack with, honouring the older fullscreen_stack flag
+    pub fn layout_mode(&self) -> LayoutMode {
+        if self.fullscreen_stack && self.layout == LayoutMode::Centered {
+            LayoutMode::Fullscreen
+        } else {
+            self.layout
+        }
+    }
+}
+
+/// Overlay `overrides` onto `base`, merging nested tables key by key
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(value)) => {
+                merge_tables(existing, value)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Fail on top-level keys Config doesn't have, rather than silently ignoring
+/// a misspelt setting
+fn reject_unknown_keys(root: &toml::Table) -> Result<()> {
+    let serde_json::Value::Object(known) = serde_json::to_value(Config::default())? else {
+        unreachable!("Config serializes to a map");
+    };
+    let unknown: Vec<String> = root
+        .keys()
+        .filter(|key| !known.contains_key(*key))
+        .map(
+            |key| match known.keys().find(|name| edit_distance(key, name) <= 2) {
+                Some(name) => format!("{} (did you mean {}?)", key, name),
+                None => key.clone(),
+            },
+        )
+        .collect();
+    if !unknown.is_empty() {
+        anyhow::bail!("Unknown setting in config.toml: {}", unknown.join(", "));
+    }
+    Ok(())
+}
+
+/// Levenshtein distance, for suggesting the setting a typo was meant to be
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(previous + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// `explicit` if given, else the XDG config if it exists, else a
+/// config.toml in the current directory, else the XDG path to create
+fn resolve_config_path(explicit: Option<PathBuf>, xdg: PathBuf, local: PathBuf) -> PathBuf {
+    match explicit {
+        Some(path) => path,
+        None if !xdg.exists() && local.exists() => local,
+        None => xdg,
+    }
 }
 
 #[cfg(test)]
@@ -393,24 +1590,7 @@ mod tests {
             panel_height: 40,
             eve_width: 1000,
             eve_height: 1080,
-            overlay_x: 10.0,
-            overlay_y: 10.0,
-            enable_mouse_buttons: true,
-            forward_button: 276,
-            backward_button: 275,
-            enable_keyboard_buttons: false,
-            forward_key: 15,
-            backward_key: 15,
-            show_overlay: true,
-            mouse_device_name: None,
-            mouse_device_path: None,
-            minimize_inactive: false,
-            keyboard_device_path: None,
-            modifier_key: None,
-            primary_character: None,
-            primary_monitor: None,
-            fullscreen_stack: false,
-            groups: HashMap::new(),
+            ..Config::default()
         };
 
         // Height should be: 1080 - 40 = 1040
@@ -425,24 +1605,7 @@ mod tests {
             panel_height: 0,
             eve_width: 1000,
             eve_height: 1080,
-            overlay_x: 10.0,
-            overlay_y: 10.0,
-            enable_mouse_buttons: true,
-            forward_button: 276,
-            backward_button: 275,
-            enable_keyboard_buttons: false,
-            forward_key: 15,
-            backward_key: 15,
-            show_overlay: true,
-            mouse_device_name: None,
-            mouse_device_path: None,
-            minimize_inactive: false,
-            keyboard_device_path: None,
-            modifier_key: None,
-            primary_character: None,
-            primary_monitor: None,
-            fullscreen_stack: false,
-            groups: HashMap::new(),
+            ..Config::default()
         };
 
         assert_eq!(config.eve_height_adjusted(), 1080);
@@ -456,24 +1619,7 @@ mod tests {
             panel_height: 0,
             eve_width: 4147,
             eve_height: 2160,
-            overlay_x: 10.0,
-            overlay_y: 10.0,
-            enable_mouse_buttons: true,
-            forward_button: 276,
-            backward_button: 275,
-            enable_keyboard_buttons: false,
-            forward_key: 15,
-            backward_key: 15,
-            show_overlay: true,
-            mouse_device_name: None,
-            mouse_device_path: None,
-            minimize_inactive: false,
-            keyboard_device_path: None,
-            modifier_key: None,
-            primary_character: None,
-            primary_monitor: None,
-            fullscreen_stack: false,
-            groups: HashMap::new(),
+            ..Config::default()
         };
 
         let toml_str = toml::to_string(&config).unwrap();
@@ -496,24 +1642,8 @@ mod tests {
             panel_height: 0,
             eve_width: 1000,
             eve_height: 1080,
-            overlay_x: 10.0,
-            overlay_y: 10.0,
-            enable_mouse_buttons: true,
-            forward_button: 276,
-            backward_button: 275,
-            enable_keyboard_buttons: false,
-            forward_key: 15,
-            backward_key: 15,
-            show_overlay: true,
-            mouse_device_name: None,
-            mouse_device_path: None,
-            minimize_inactive: false,
-            keyboard_device_path: None,
-            modifier_key: None,
-            primary_character: None,
-            primary_monitor: None,
-            fullscreen_stack: false,
             groups,
+            ..Config::default()
         };
 
         let toml_str = toml::to_string(&config).unwrap();
@@ -523,4 +1653,146 @@ mod tests {
         assert_eq!(deserialized.groups.get("scouts").unwrap().len(), 2);
         assert_eq!(deserialized.groups.get("combat").unwrap().len(), 2);
     }
+
+    #[test]
+    fn test_minimize_strategy_from_toml() {
+        let config: Config = toml::from_str(
+            r#"
+            display_width = 1920
+            display_height = 1080
+            panel_height = 0
+            eve_width = 1036
+            eve_height = 1080
+            overlay_x = 10.0
+            overlay_y = 10.0
+
+            [minimize_strategy]
+            sway = { workspace = "eve" }
+            hyprland = { special = "minimized" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.minimize_strategy.sway,
+            SwayMinimize::Workspace("eve".to_string())
+        );
+        assert_eq!(
+            config.minimize_strategy.hyprland,
+            HyprlandMinimize::Special("minimized".to_string())
+        );
+        assert_eq!(
+            Config::default().minimize_strategy.sway,
+            SwayMinimize::Scratchpad
+        );
+    }
+
+    #[test]
+    fn test_config_show_reflects_profile_override() {
+        let contents = r#"
+display_width = 2560
+display_height = 1440
+panel_height = 0
+eve_width = 1400
+eve_height = 1440
+overlay_x = 10.0
+overlay_y = 10.0
+profile = "mining"
+
+[groups]
+fleet = ["Main"]
+
+[profiles.base_alt]
+minimize_inactive = true
+
+[profiles.mining]
+inherits = "base_alt"
+eve_width = 1000
+
+[profiles.mining.groups]
+miners = ["Orca", "Hulk"]
+"#;
+
+        let config = Config::from_toml_str(contents, None).unwrap();
+        let shown: toml::Table = toml::from_str(&config.render(false).unwrap()).unwrap();
+        assert_eq!(shown["eve_width"].as_integer(), Some(1000));
+        assert_eq!(shown["display_width"].as_integer(), Some(2560));
+        assert_eq!(shown["minimize_inactive"].as_bool(), Some(true));
+        assert!(shown["groups"].get("fleet").is_some());
+        assert!(shown["groups"].get("miners").is_some());
+
+        let json: serde_json::Value = serde_json::from_str(&config.render(true).unwrap()).unwrap();
+        assert_eq!(json["eve_width"], 1000);
+
+        // NICOTINE_PROFILE wins over the file's profile key
+        let config = Config::from_toml_str(contents, Some("base_alt")).unwrap();
+        assert_eq!(config.eve_width, 1400);
+        assert!(config.minimize_inactive);
+
+        assert!(Config::from_toml_str(contents, Some("missing")).is_err());
+    }
+
+    #[test]
+    fn test_unknown_keys_and_invalid_settings_are_reported() {
+        // Anything left out is defaulted, so a near-empty config loads
+        let config = Config::from_toml_str("eve_width = 1200\n", None).unwrap();
+        assert_eq!(config.eve_width, 1200);
+        assert_eq!(config.display_height, Config::default().display_height);
+
+        let err = Config::from_toml_str("eve_widht = 1200\nbogus = 1\n", None).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(
+            message.contains("eve_widht (did you mean eve_width?)"),
+            "{}",
+            message
+        );
+        assert!(message.contains("bogus"), "{}", message);
+
+        // Typos inside a profile are caught once it's applied
+        let profiled = "[profiles.mining]\nminimise_inactive = true\n";
+        assert!(Config::from_toml_str(profiled, Some("mining")).is_err());
+
+        let err = Config::from_toml_str(
+            "display_height = 1080\npanel_height = 1080\nthumbnail_width = 0\n",
+            None,
+        )
+        .unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("panel_height (1080)"), "{}", message);
+        assert!(message.contains("thumbnail_width must be"), "{}", message);
+
+        let dimmed = Config::from_toml_str("[focus_opacity]\ninactive = 0.5\n", None).unwrap();
+        assert_eq!(dimmed.focus_opacity.unwrap().active, 1.0);
+        let err = Config::from_toml_str("[focus_opacity]\ninactive = 70\n", None).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(
+            message.contains("focus_opacity.inactive (70)"),
+            "{}",
+            message
+        );
+
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_config_path_prefers_explicit_then_xdg_then_local() {
+        let dir = std::env::temp_dir().join(format!("nicotine-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let xdg = dir.join("xdg.toml");
+        let local = dir.join("local.toml");
+        let explicit = Some(dir.join("explicit.toml"));
+
+        // Nothing exists yet: a new config is created in the XDG location
+        assert_eq!(resolve_config_path(None, xdg.clone(), local.clone()), xdg);
+        fs::write(&local, "").unwrap();
+        assert_eq!(resolve_config_path(None, xdg.clone(), local.clone()), local);
+        fs::write(&xdg, "").unwrap();
+        assert_eq!(resolve_config_path(None, xdg.clone(), local.clone()), xdg);
+        assert_eq!(
+            resolve_config_path(explicit.clone(), xdg.clone(), local.clone()),
+            dir.join("explicit.toml")
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use nix::errno::Errno;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+use std::path::Path;
+use std::time::Duration;
+
+/// Edits arriving within this long of each other are one change, since an
+/// editor's save is often several writes and renames
+const SETTLE: Duration = Duration::from_millis(250);
+
+/// Call `on_change` on a background thread whenever `path` is edited.
+///
+/// The directory is watched rather than the file: editors commonly save by
+/// writing a new file and renaming it over the old one, which a watch on the
+/// file itself would lose track of.
+pub fn spawn(path: &Path, mut on_change: impl FnMut() + Send + 'static) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => ".".into(),
+    };
+    let name = path
+        .file_name()
+        .with_context(|| format!("{} is not a file", path.display()))?
+        .to_os_string();
+
+    let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+        .context("Failed to start inotify")?;
+    inotify
+        .add_watch(
+            &dir,
+            AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_TO | AddWatchFlags::IN_CREATE,
+        )
+        .with_context(|| format!("Failed to watch {}", dir.display()))?;
+
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SETTLE);
+        match inotify.read_events() {
+            Ok(events) => {
+                if events.iter().any(|e| e.name.as_ref() == Some(&name)) {
+                    on_change();
+                }
+            }
+            Err(Errno::EAGAIN) => {}
+            Err(e) => {
+                tracing::warn!("Config watcher stopped: {}", e);
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_edits_to_the_file_are_noticed_once_settled() {
+        let dir = std::env::temp_dir().join(format!("nicotine-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "eve_width = 1000\n").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        spawn(&path, move || tx.send(()).unwrap()).unwrap();
+
+        // Another file in the same directory doesn't count
+        std::fs::write(dir.join("characters.txt"), "Main\n").unwrap();
+        assert!(rx.recv_timeout(SETTLE * 3).is_err());
+
+        // A save by rename, as many editors do it
+        std::fs::write(dir.join("config.toml.tmp"), "eve_width = 1200\n").unwrap();
+        std::fs::rename(dir.join("config.toml.tmp"), &path).unwrap();
+        assert!(rx.recv_timeout(Duration::from_secs(2)).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,25 +1,162 @@
-use crate::window_manager::{EveWindow, WindowManager};
+use crate::config::{Config, CycleOrder, LayoutMode};
+use crate::window_manager::{EveWindow, WindowManager, WindowNotFound};
 use anyhow::Result;
 use std::fs;
 use std::path::Path;
+use std::time::{Duration, Instant};
 
 const INDEX_FILE: &str = "/tmp/nicotine-index";
+const CURRENT_KEY_FILE: &str = "/tmp/nicotine-current-key";
+
+/// Cycle presses closer together than this walk one MRU order, like holding
+/// Alt while tapping Tab; after a pause the order is re-sorted by recency
+const MRU_BURST: Duration = Duration::from_secs(1);
+
+/// How many clients the focus history remembers
+const FOCUS_HISTORY: usize = 32;
 
 pub struct CycleState {
     current_index: usize,
     windows: Vec<EveWindow>,
+    /// Group that plain forward/backward cycling is limited to, and its members
+    active_group: Option<(String, Vec<String>)>,
+    order: CycleOrder,
+    /// Stable keys of clients by last use, most recent first
+    recent: Vec<String>,
+    /// When the last plain cycle step was, to tell an MRU burst from a new one
+    last_step: Option<Instant>,
+    /// Stable keys of clients in the order they had focus, most recent
+    /// first, however the focus got there. Unlike `recent` this isn't held
+    /// still during an MRU burst.
+    history: Vec<String>,
+    /// Characters minimize_inactive doesn't minimize
+    minimize_exclude: Vec<String>,
+    /// Config to re-stack with after each switch, for the pip layout, whose
+    /// big slot follows the active client
+    follow_layout: Option<Config>,
+    /// Set by the combat_lock hotkey: while on, focus_guard hands focus
+    /// taken by other windows back to the current client
+    combat_lock: bool,
 }
 
 impl CycleState {
     pub fn new() -> Self {
+        Self::with_order(CycleOrder::default())
+    }
+
+    pub fn with_order(order: CycleOrder) -> Self {
         Self {
             current_index: 0,
             windows: Vec::new(),
+            active_group: None,
+            order,
+            recent: Vec::new(),
+            last_step: None,
+            history: Vec::new(),
+            minimize_exclude: Vec::new(),
+            follow_layout: None,
+            combat_lock: false,
         }
     }
 
+    pub fn from_config(config: &Config) -> Self {
+        let mut state = Self::with_order(config.cycle_order);
+        state.set_minimize_exclude(config.minimize_exclude.clone());
+        state.set_layout(config);
+        state
+    }
+
+    pub fn set_minimize_exclude(&mut self, characters: Vec<String>) {
+        self.minimize_exclude = characters;
+    }
+
+    /// Follow switches with a re-stack if `config` uses the pip layout
+    pub fn set_layout(&mut self, config: &Config) {
+        self.follow_layout = (config.layout_mode() == LayoutMode::Pip).then(|| config.clone());
+    }
+
+    pub fn set_order(&mut self, order: CycleOrder) {
+        self.order = order;
+        self.apply_order();
+    }
+
+    /// Sort windows by the cycle order, keeping the selection on the same client
+    fn apply_order(&mut self) {
+        let current_key = self.windows.get(self.current_index).map(|w| w.stable_key());
+        match self.order {
+            CycleOrder::Fixed => return,
+            CycleOrder::Alphabetical => self
+                .windows
+                .sort_by_key(|w| (w.title.to_lowercase(), w.title.clone())),
+            CycleOrder::Mru => {
+                let recent = &self.recent;
+                self.windows.sort_by_key(|w| {
+                    let key = w.stable_key();
+                    recent
+                        .iter()
+                        .position(|k| *k == key)
+                        .unwrap_or(recent.len())
+                });
+            }
+        }
+        if let Some(key) = current_key {
+            self.select_by_key(&key);
+        }
+    }
+
+    /// Mark the client at `index` as the most recently used
+    fn touch(&mut self, index: usize) {
+        let Some(key) = self.windows.get(index).map(|w| w.stable_key()) else {
+            return;
+        };
+        self.recent.retain(|k| *k != key);
+        self.recent.insert(0, key);
+    }
+
+    /// In MRU mode, start a new burst if the last press was a while ago: the
+    /// current client becomes the most recent and the others follow by use
+    fn start_mru_step(&mut self) {
+        if self.order != CycleOrder::Mru {
+            return;
+        }
+        let burst_over = self.last_step.is_none_or(|t| t.elapsed() > MRU_BURST);
+        if burst_over {
+            self.touch(self.current_index);
+            self.apply_order();
+        }
+        self.last_step = Some(Instant::now());
+    }
+
+    /// Limit cycle_forward/cycle_backward to `members` until cleared
+    pub fn set_active_group(&mut self, name: &str, members: Vec<String>) {
+        self.active_group = Some((name.to_string(), members));
+    }
+
+    pub fn clear_active_group(&mut self) {
+        self.active_group = None;
+    }
+
+    pub fn active_group(&self) -> Option<&str> {
+        self.active_group.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    pub fn set_combat_lock(&mut self, on: bool) {
+        self.combat_lock = on;
+    }
+
+    pub fn combat_lock(&self) -> bool {
+        self.combat_lock
+    }
+
     pub fn update_windows(&mut self, windows: Vec<EveWindow>) {
+        // Keep the selection on the same client even if it was reordered or
+        // came back under a new id
+        let current_key = self.windows.get(self.current_index).map(|w| w.stable_key());
         self.windows = windows;
+        if let Some(key) = current_key {
+            self.select_by_key(&key);
+        }
+        self.apply_order();
         // Clamp current index
         if self.current_index >= self.windows.len() && !self.windows.is_empty() {
             self.current_index = 0;
@@ -32,9 +169,14 @@ impl CycleState {
         minimize_inactive: bool,
         skip_character: Option<&str>,
     ) -> Result<()> {
+        // An active group replaces the skip: its members are the whole rotation
+        if let Some((_, members)) = self.active_group.clone() {
+            return self.cycle_group_forward(wm, minimize_inactive, &members);
+        }
         if self.windows.is_empty() {
             return Ok(());
         }
+        self.start_mru_step();
 
         let previous_index = self.current_index;
 
@@ -53,19 +195,12 @@ impl CycleState {
         self.current_index = next;
         self.write_index();
 
-        let new_window_id = self.windows[self.current_index].id;
+        let previous = self.windows[previous_index].clone();
+        self.focus_current(wm, minimize_inactive)?;
 
         if minimize_inactive {
-            // Restore new window first (in case it was minimized)
-            let _ = wm.restore_window(new_window_id);
-        }
-
-        wm.activate_window(new_window_id)?;
-
-        if minimize_inactive && previous_index != self.current_index {
             // Minimize the previous window after activating the new one
-            let previous_window_id = self.windows[previous_index].id;
-            let _ = wm.minimize_window(previous_window_id);
+            self.minimize_previous(wm, &previous);
         }
 
         Ok(())
@@ -77,9 +212,14 @@ impl CycleState {
         minimize_inactive: bool,
         skip_character: Option<&str>,
     ) -> Result<()> {
+        // An active group replaces the skip: its members are the whole rotation
+        if let Some((_, members)) = self.active_group.clone() {
+            return self.cycle_group_backward(wm, minimize_inactive, &members);
+        }
         if self.windows.is_empty() {
             return Ok(());
         }
+        self.start_mru_step();
 
         let previous_index = self.current_index;
 
@@ -106,26 +246,140 @@ impl CycleState {
         self.current_index = prev;
         self.write_index();
 
-        let new_window_id = self.windows[self.current_index].id;
+        let previous = self.windows[previous_index].clone();
+        self.focus_current(wm, minimize_inactive)?;
 
         if minimize_inactive {
-            // Restore new window first (in case it was minimized)
-            let _ = wm.restore_window(new_window_id);
+            // Minimize the previous window after activating the new one
+            self.minimize_previous(wm, &previous);
         }
 
-        wm.activate_window(new_window_id)?;
+        Ok(())
+    }
 
-        if minimize_inactive && previous_index != self.current_index {
-            // Minimize the previous window after activating the new one
-            let previous_window_id = self.windows[previous_index].id;
-            let _ = wm.minimize_window(previous_window_id);
+    /// Re-read the live window list, e.g. after a client was closed
+    pub fn refresh(&mut self, wm: &dyn WindowManager) -> Result<()> {
+        let windows = wm.get_eve_windows()?;
+        self.update_windows(windows);
+        Ok(())
+    }
+
+    /// Minimize the client cycled away from, unless focus is still on it or
+    /// it's in minimize_exclude
+    fn minimize_previous(&self, wm: &dyn WindowManager, previous: &EveWindow) {
+        if self.current_window_id() == Some(previous.id)
+            || self.minimize_exclude.contains(&previous.title)
+        {
+            return;
         }
+        let _ = wm.minimize_window(previous.id);
+    }
 
+    fn current_window_id(&self) -> Option<u64> {
+        self.windows.get(self.current_index).map(|w| w.id)
+    }
+
+    /// Restore (if minimizing) and activate the window at current_index,
+    /// then re-stack if the layout follows focus
+    fn focus_current(&mut self, wm: &dyn WindowManager, minimize_inactive: bool) -> Result<()> {
+        self.activate_current(wm, minimize_inactive)?;
+        self.note_focus();
+        self.relayout(wm);
         Ok(())
     }
 
+    /// Put the client at current_index at the top of the focus history
+    fn note_focus(&mut self) {
+        let Some(key) = self.windows.get(self.current_index).map(|w| w.stable_key()) else {
+            return;
+        };
+        self.history.retain(|k| *k != key);
+        self.history.insert(0, key);
+        self.history.truncate(FOCUS_HISTORY);
+    }
+
+    /// Switch back to the client that had focus before the current one,
+    /// skipping any that have closed since. Repeating it flips between the
+    /// two, whatever the cycle order.
+    pub fn focus_last(&mut self, wm: &dyn WindowManager, minimize_inactive: bool) -> Result<()> {
+        let current = self.windows.get(self.current_index).map(|w| w.stable_key());
+        let last = self
+            .history
+            .iter()
+            .filter(|key| Some(*key) != current.as_ref())
+            .find_map(|key| self.windows.iter().position(|w| w.stable_key() == *key));
+        match last {
+            Some(index) => self.focus_index(index, wm, minimize_inactive),
+            None => Ok(()),
+        }
+    }
+
+    /// Under the pip layout, put the client just switched to in the big slot
+    fn relayout(&self, wm: &dyn WindowManager) {
+        let (Some(config), Some(current)) =
+            (&self.follow_layout, self.windows.get(self.current_index))
+        else {
+            return;
+        };
+        // Fresh geometry tells which slot each small window is in; the
+        // switch just made is what says which one is big
+        let windows = wm
+            .get_eve_windows()
+            .unwrap_or_else(|_| self.windows.clone());
+        let config = Config {
+            primary_character: Some(current.title.clone()),
+            ..config.clone()
+        };
+        match wm.place_windows(&windows, &config) {
+            Ok(report) if !report.failed.is_empty() => report.log("Re-stacked"),
+            Ok(_) => {}
+            Err(e) => tracing::error!("Re-stack failed: {}", e),
+        }
+    }
+
+    /// Restore (if minimizing) and activate the window at current_index. If the
+    /// backend reports it's gone, refresh and retry once, following the same
+    /// character if it reappeared under a new id.
+    fn activate_current(&mut self, wm: &dyn WindowManager, minimize_inactive: bool) -> Result<()> {
+        let window = self.windows[self.current_index].clone();
+
+        if minimize_inactive {
+            // Restore new window first (in case it was minimized)
+            let _ = wm.restore_window(window.id);
+        }
+
+        match wm.activate_window(window.id) {
+            Err(e) if e.is::<WindowNotFound>() => {
+                self.refresh(wm)?;
+                if let Some(index) = self.windows.iter().position(|w| w.title == window.title) {
+                    self.current_index = index;
+                }
+                self.write_index();
+
+                let Some(retry_id) = self.current_window_id() else {
+                    return Ok(()); // Nothing left to activate
+                };
+                if minimize_inactive {
+                    let _ = wm.restore_window(retry_id);
+                }
+                wm.activate_window(retry_id)
+            }
+            result => result,
+        }
+    }
+
     fn write_index(&self) {
         let _ = fs::write(INDEX_FILE, self.current_index.to_string());
+        if let Some(window) = self.windows.get(self.current_index) {
+            let _ = fs::write(CURRENT_KEY_FILE, window.stable_key());
+        }
+    }
+
+    /// Stable key of the window that was current when the index was last written
+    pub fn read_current_key_from_file() -> Option<String> {
+        fs::read_to_string(CURRENT_KEY_FILE)
+            .ok()
+            .map(|s| s.trim().to_string())
     }
 
     pub fn read_index_from_file() -> Option<usize> {
@@ -152,12 +406,28 @@ impl CycleState {
         }
     }
 
+    /// Point current_index at the window with this stable key, if present
+    pub fn select_by_key(&mut self, key: &str) -> bool {
+        match self.windows.iter().position(|w| w.stable_key() == key) {
+            Some(index) => {
+                self.current_index = index;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn sync_with_active(&mut self, active_window: u64) {
         // Find which window is active and update current_index
-        for (i, window) in self.windows.iter().enumerate() {
-            if window.id == active_window {
-                self.current_index = i;
-                break;
+        if let Some(i) = self.windows.iter().position(|w| w.id == active_window) {
+            // Focus moved outside of cycling, which counts as a use
+            if i != self.current_index {
+                self.touch(i);
+            }
+            let moved = i != self.current_index || self.history.is_empty();
+            self.current_index = i;
+            if moved {
+                self.note_focus();
             }
         }
     }
@@ -200,17 +470,12 @@ impl CycleState {
         self.current_index = group_indices[next_group_pos];
         self.write_index();
 
-        let new_window_id = self.windows[self.current_index].id;
+        let previous = self.windows[previous_index].clone();
+        self.focus_current(wm, minimize_inactive)?;
 
         if minimize_inactive {
-            let _ = wm.restore_window(new_window_id);
-        }
-
-        wm.activate_window(new_window_id)?;
-
-        if minimize_inactive && previous_index != self.current_index {
-            let previous_window_id = self.windows[previous_index].id;
-            let _ = wm.minimize_window(previous_window_id);
+            // Minimize the previous window after activating the new one
+            self.minimize_previous(wm, &previous);
         }
 
         Ok(())
@@ -258,17 +523,12 @@ impl CycleState {
         self.current_index = group_indices[prev_group_pos];
         self.write_index();
 
-        let new_window_id = self.windows[self.current_index].id;
+        let previous = self.windows[previous_index].clone();
+        self.focus_current(wm, minimize_inactive)?;
 
         if minimize_inactive {
-            let _ = wm.restore_window(new_window_id);
-        }
-
-        wm.activate_window(new_window_id)?;
-
-        if minimize_inactive && previous_index != self.current_index {
-            let previous_window_id = self.windows[previous_index].id;
-            let _ = wm.minimize_window(previous_window_id);
+            // Minimize the previous window after activating the new one
+            self.minimize_previous(wm, &previous);
         }
 
         Ok(())
@@ -321,26 +581,77 @@ impl CycleState {
             target_idx
         };
 
+        self.focus_index(target_index, wm, minimize_inactive)
+    }
+
+    /// Switch to a character's window by name
+    pub fn activate(
+        &mut self,
+        character: &str,
+        wm: &dyn WindowManager,
+        minimize_inactive: bool,
+    ) -> Result<()> {
+        let index = match self.find_character(character) {
+            Some(index) => index,
+            None => {
+                // It may have logged in, or relogged into a new window, since
+                // the list was last read
+                self.refresh(wm)?;
+                self.find_character(character).ok_or_else(|| {
+                    anyhow::anyhow!("Character '{}' not found in active windows", character)
+                })?
+            }
+        };
+        self.focus_index(index, wm, minimize_inactive)
+    }
+
+    /// The client for `name`: an exact title, else one differing only in
+    /// case, else the only title it's the start of
+    fn find_character(&self, name: &str) -> Option<usize> {
+        let name_lower = name.to_lowercase();
+        self.windows
+            .iter()
+            .position(|w| w.title == name)
+            .or_else(|| {
+                self.windows
+                    .iter()
+                    .position(|w| w.title.to_lowercase() == name_lower)
+            })
+            .or_else(|| {
+                let mut starting = self
+                    .windows
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, w)| w.title.to_lowercase().starts_with(&name_lower));
+                match (starting.next(), starting.next()) {
+                    (Some((index, _)), None) => Some(index),
+                    _ => None,
+                }
+            })
+    }
+
+    fn focus_index(
+        &mut self,
+        index: usize,
+        wm: &dyn WindowManager,
+        minimize_inactive: bool,
+    ) -> Result<()> {
         // Don't do anything if already on target
-        if target_index == self.current_index {
+        if index == self.current_index {
             return Ok(());
         }
 
         let previous_index = self.current_index;
-        self.current_index = target_index;
+        self.current_index = index;
+        self.touch(index);
         self.write_index();
 
-        let new_window_id = self.windows[self.current_index].id;
+        let previous = self.windows[previous_index].clone();
+        self.focus_current(wm, minimize_inactive)?;
 
         if minimize_inactive {
-            let _ = wm.restore_window(new_window_id);
-        }
-
-        wm.activate_window(new_window_id)?;
-
-        if minimize_inactive {
-            let previous_window_id = self.windows[previous_index].id;
-            let _ = wm.minimize_window(previous_window_id);
+            // Minimize the previous window after activating the new one
+            self.minimize_previous(wm, &previous);
         }
 
         Ok(())
@@ -350,12 +661,16 @@ impl CycleState {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock_window_manager::{monitor, MockWindowManager};
 
     fn create_test_window(id: u64, title: &str) -> EveWindow {
         EveWindow {
             id,
             title: title.to_string(),
             monitor: None,
+            geometry: None,
+            pid: None,
+            class: None,
         }
     }
 
@@ -508,56 +823,160 @@ mod tests {
         assert_eq!(state.get_current_index(), 2);
     }
 
-    // Mock WindowManager for testing switch_to
-    struct MockWindowManager {
-        activated_windows: std::sync::Mutex<Vec<u64>>,
+    fn rotation() -> Vec<EveWindow> {
+        vec![
+            create_test_window(100, "Main"),
+            create_test_window(200, "Alt1"),
+            create_test_window(300, "Alt2"),
+        ]
     }
 
-    impl MockWindowManager {
-        fn new() -> Self {
-            Self {
-                activated_windows: std::sync::Mutex::new(Vec::new()),
-            }
-        }
+    /// A backend with the same clients `state` has
+    fn mock(state: &CycleState) -> MockWindowManager {
+        MockWindowManager::new(state.get_windows().to_vec())
+    }
 
-        fn get_activated(&self) -> Vec<u64> {
-            self.activated_windows.lock().unwrap().clone()
+    /// The same rotation every backend sees: cycling only ever goes through
+    /// the trait, so X11, KWin, Sway and Hyprland get identical calls
+    fn cycle_through(wm: &dyn WindowManager, minimize_inactive: bool) -> CycleState {
+        let mut state = CycleState::new();
+        state.update_windows(rotation());
+        for _ in 0..3 {
+            let skip = Some("Main");
+            state.cycle_forward(wm, minimize_inactive, skip).unwrap();
         }
+        state.cycle_backward(wm, minimize_inactive, None).unwrap();
+        state
     }
 
-    impl WindowManager for MockWindowManager {
-        fn get_eve_windows(&self) -> anyhow::Result<Vec<EveWindow>> {
-            Ok(vec![])
-        }
+    #[test]
+    fn test_cycle_forward_and_backward_wrap_and_skip() {
+        let wm = MockWindowManager::new(rotation());
+        let state = cycle_through(&wm, false);
 
-        fn activate_window(&self, window_id: u64) -> anyhow::Result<()> {
-            self.activated_windows.lock().unwrap().push(window_id);
-            Ok(())
-        }
+        // Main is skipped going forward, so Alt2 wraps round to Alt1
+        assert_eq!(wm.activated(), vec![200, 300, 200, 100]);
+        assert_eq!(state.get_current_index(), 0);
+        assert!(wm.calls().iter().all(|c| c.starts_with("activate")));
+    }
 
-        fn stack_windows(
-            &self,
-            _windows: &[EveWindow],
-            _config: &crate::config::Config,
-        ) -> anyhow::Result<()> {
-            Ok(())
-        }
+    #[test]
+    fn test_cycle_with_minimize_inactive_restores_then_minimizes() {
+        let wm = MockWindowManager::new(rotation());
+        cycle_through(&wm, true);
+
+        assert_eq!(
+            wm.calls()[..6],
+            [
+                "restore 200",
+                "activate 200",
+                "minimize 100",
+                "restore 300",
+                "activate 300",
+                "minimize 200"
+            ]
+        );
+        assert_eq!(wm.calls().len(), 12);
+    }
 
-        fn get_active_window(&self) -> anyhow::Result<u64> {
-            Ok(0)
-        }
+    #[test]
+    fn test_minimize_exclude_keeps_character_showing() {
+        let wm = MockWindowManager::new(rotation());
+        let mut state = CycleState::from_config(&Config {
+            minimize_exclude: vec!["Main".to_string()],
+            ..Config::default()
+        });
+        state.update_windows(rotation());
+        state.cycle_forward(&wm, true, None).unwrap();
+        state.cycle_forward(&wm, true, None).unwrap();
+
+        assert_eq!(
+            wm.calls(),
+            [
+                "restore 200",
+                "activate 200",
+                "restore 300",
+                "activate 300",
+                "minimize 200"
+            ]
+        );
+    }
 
-        fn find_window_by_title(&self, _title: &str) -> anyhow::Result<Option<u64>> {
-            Ok(None)
-        }
+    #[test]
+    fn test_alphabetical_order_sorts_by_name() {
+        let mut state = CycleState::with_order(CycleOrder::Alphabetical);
+        state.update_windows(vec![
+            create_test_window(100, "gamma"),
+            create_test_window(200, "Alpha"),
+            create_test_window(300, "beta"),
+        ]);
 
-        fn minimize_window(&self, _window_id: u64) -> anyhow::Result<()> {
-            Ok(())
-        }
+        let titles: Vec<_> = state
+            .get_windows()
+            .iter()
+            .map(|w| w.title.as_str())
+            .collect();
+        assert_eq!(titles, ["Alpha", "beta", "gamma"]);
+    }
 
-        fn restore_window(&self, _window_id: u64) -> anyhow::Result<()> {
-            Ok(())
-        }
+    #[test]
+    fn test_mru_order_bounces_between_recent_clients() {
+        let mut state = CycleState::with_order(CycleOrder::Mru);
+        state.update_windows(vec![
+            create_test_window(100, "Alpha"),
+            create_test_window(200, "Beta"),
+            create_test_window(300, "Gamma"),
+            create_test_window(400, "Delta"),
+        ]);
+        let wm = mock(&state);
+
+        // Gamma then Alpha are used from outside cycling
+        state.sync_with_active(300);
+        state.sync_with_active(100);
+
+        // After a pause each press goes back to the previous client
+        state.cycle_forward(&wm, false, None).unwrap();
+        state.last_step = None;
+        state.cycle_forward(&wm, false, None).unwrap();
+        assert_eq!(wm.activated(), vec![300, 100]);
+
+        // Quick presses walk further back: Alpha -> Gamma -> Beta
+        state.last_step = None;
+        state.cycle_forward(&wm, false, None).unwrap();
+        state.cycle_forward(&wm, false, None).unwrap();
+        assert_eq!(wm.activated()[2..], [300, 200]);
+    }
+
+    #[test]
+    fn test_focus_last_flips_between_the_two_latest_clients() {
+        let mut state = CycleState::new();
+        state.update_windows(vec![
+            create_test_window(100, "Alpha"),
+            create_test_window(200, "Beta"),
+            create_test_window(300, "Gamma"),
+        ]);
+        let wm = mock(&state);
+
+        // Alpha has focus, Gamma is clicked, then cycling lands on Alpha
+        state.sync_with_active(100);
+        state.sync_with_active(300);
+        state.cycle_forward(&wm, false, None).unwrap();
+        assert_eq!(wm.activated(), vec![100]);
+
+        // Back and forth between Alpha and Gamma, never Beta
+        state.focus_last(&wm, false).unwrap();
+        state.focus_last(&wm, false).unwrap();
+        state.focus_last(&wm, false).unwrap();
+        assert_eq!(wm.activated()[1..], [300, 100, 300]);
+
+        // Gamma logs out, so the one before it is next
+        state.activate("Beta", &wm, false).unwrap();
+        state.update_windows(vec![
+            create_test_window(100, "Alpha"),
+            create_test_window(200, "Beta"),
+        ]);
+        state.focus_last(&wm, false).unwrap();
+        assert_eq!(wm.activated()[4..], [200, 100]);
     }
 
     #[test]
@@ -570,12 +989,12 @@ mod tests {
         ];
         state.update_windows(windows);
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Switch to target 2 (0-indexed: 1)
         state.switch_to(2, &wm, false, None).unwrap();
         assert_eq!(state.get_current_index(), 1);
-        assert_eq!(wm.get_activated(), vec![200]);
+        assert_eq!(wm.activated(), vec![200]);
     }
 
     #[test]
@@ -589,7 +1008,7 @@ mod tests {
         ];
         state.update_windows(windows);
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Character order defines: 1=Alpha, 2=Beta, 3=Gamma
         let char_order = vec!["Alpha".to_string(), "Beta".to_string(), "Gamma".to_string()];
@@ -597,7 +1016,7 @@ mod tests {
         // Switch to target 1 (Alpha) - should find window 200
         state.switch_to(1, &wm, false, Some(&char_order)).unwrap();
         assert_eq!(state.get_current_index(), 1); // Index of Alpha in windows
-        assert_eq!(wm.get_activated(), vec![200]);
+        assert_eq!(wm.activated(), vec![200]);
     }
 
     #[test]
@@ -610,13 +1029,13 @@ mod tests {
         state.update_windows(windows);
         state.current_index = 0;
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Switch to target 1 when already on index 0
         state.switch_to(1, &wm, false, None).unwrap();
 
         // Should not have activated anything
-        assert!(wm.get_activated().is_empty());
+        assert!(wm.activated().is_empty());
     }
 
     #[test]
@@ -628,7 +1047,7 @@ mod tests {
         ];
         state.update_windows(windows);
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Switch to target 5 when only 2 windows exist
         let result = state.switch_to(5, &wm, false, None);
@@ -644,7 +1063,7 @@ mod tests {
         ];
         state.update_windows(windows);
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Character order includes a character not in windows
         let char_order = vec!["Alpha".to_string(), "Beta".to_string(), "Gamma".to_string()];
@@ -654,28 +1073,43 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_activate_by_character_name() {
+        let mut state = CycleState::new();
+        state.update_windows(vec![
+            create_test_window(100, "Alpha"),
+            create_test_window(200, "Beta"),
+        ]);
+
+        let wm = mock(&state);
+        state.activate("Beta", &wm, false).unwrap();
+        assert_eq!(wm.activated(), vec![200]);
+        assert_eq!(state.get_current_index(), 1);
+        assert!(state.activate("Gamma", &wm, false).is_err());
+    }
+
     #[test]
     fn test_switch_to_zero_does_nothing() {
         let mut state = CycleState::new();
         let windows = vec![create_test_window(100, "Alpha")];
         state.update_windows(windows);
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Switch to target 0 should do nothing
         state.switch_to(0, &wm, false, None).unwrap();
-        assert!(wm.get_activated().is_empty());
+        assert!(wm.activated().is_empty());
     }
 
     #[test]
     fn test_switch_to_empty_windows_does_nothing() {
         let mut state = CycleState::new();
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Switch with no windows
         state.switch_to(1, &wm, false, None).unwrap();
-        assert!(wm.get_activated().is_empty());
+        assert!(wm.activated().is_empty());
     }
 
     #[test]
@@ -692,7 +1126,7 @@ mod tests {
         state.update_windows(windows);
         state.current_index = 0; // Start at Alpha
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Group only contains: Alpha, Gamma, Epsilon (indices 0, 2, 4)
         let group = vec!["Alpha".to_string(), "Gamma".to_string(), "Epsilon".to_string()];
@@ -700,7 +1134,31 @@ mod tests {
         // Cycle forward from Alpha -> should go to Gamma (next in group)
         state.cycle_group_forward(&wm, false, &group).unwrap();
         assert_eq!(state.get_current_index(), 2); // Gamma
-        assert_eq!(wm.get_activated(), vec![300]);
+        assert_eq!(wm.activated(), vec![300]);
+    }
+
+    #[test]
+    fn test_active_group_limits_plain_cycling() {
+        let mut state = CycleState::new();
+        state.update_windows(vec![
+            create_test_window(100, "Alpha"),
+            create_test_window(200, "Beta"),
+            create_test_window(300, "Gamma"),
+            create_test_window(400, "Delta"),
+        ]);
+        let wm = mock(&state);
+
+        state.set_active_group("pvp", vec!["Alpha".to_string(), "Delta".to_string()]);
+        assert_eq!(state.active_group(), Some("pvp"));
+        state.cycle_forward(&wm, false, None).unwrap();
+        state.cycle_forward(&wm, false, None).unwrap();
+        state.cycle_backward(&wm, false, None).unwrap();
+        assert_eq!(wm.activated(), vec![400, 100, 400]);
+
+        state.clear_active_group();
+        state.cycle_forward(&wm, false, None).unwrap();
+        assert_eq!(state.get_current_index(), 0);
+        assert_eq!(wm.activated().last(), Some(&100));
     }
 
     #[test]
@@ -714,7 +1172,7 @@ mod tests {
         state.update_windows(windows);
         state.current_index = 2; // Start at Gamma
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Group: Alpha, Gamma (indices 0, 2)
         let group = vec!["Alpha".to_string(), "Gamma".to_string()];
@@ -722,7 +1180,7 @@ mod tests {
         // Cycle forward from Gamma -> should wrap to Alpha
         state.cycle_group_forward(&wm, false, &group).unwrap();
         assert_eq!(state.get_current_index(), 0); // Alpha
-        assert_eq!(wm.get_activated(), vec![100]);
+        assert_eq!(wm.activated(), vec![100]);
     }
 
     #[test]
@@ -737,7 +1195,7 @@ mod tests {
         state.update_windows(windows);
         state.current_index = 2; // Start at Gamma
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Group: Alpha, Gamma, Delta (indices 0, 2, 3)
         let group = vec!["Alpha".to_string(), "Gamma".to_string(), "Delta".to_string()];
@@ -745,7 +1203,7 @@ mod tests {
         // Cycle backward from Gamma -> should go to Alpha (previous in group)
         state.cycle_group_backward(&wm, false, &group).unwrap();
         assert_eq!(state.get_current_index(), 0); // Alpha
-        assert_eq!(wm.get_activated(), vec![100]);
+        assert_eq!(wm.activated(), vec![100]);
     }
 
     #[test]
@@ -759,7 +1217,7 @@ mod tests {
         state.update_windows(windows);
         state.current_index = 0; // Start at Alpha
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Group: Alpha, Gamma (indices 0, 2)
         let group = vec!["Alpha".to_string(), "Gamma".to_string()];
@@ -767,7 +1225,7 @@ mod tests {
         // Cycle backward from Alpha -> should wrap to Gamma
         state.cycle_group_backward(&wm, false, &group).unwrap();
         assert_eq!(state.get_current_index(), 2); // Gamma
-        assert_eq!(wm.get_activated(), vec![300]);
+        assert_eq!(wm.activated(), vec![300]);
     }
 
     #[test]
@@ -781,7 +1239,7 @@ mod tests {
         state.update_windows(windows);
         state.current_index = 1; // Start at Beta (not in group)
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Group: Alpha, Gamma (indices 0, 2)
         let group = vec!["Alpha".to_string(), "Gamma".to_string()];
@@ -790,7 +1248,7 @@ mod tests {
         state.cycle_group_forward(&wm, false, &group).unwrap();
         // Since Beta is not in group, it starts from "last" position and cycles to first
         assert_eq!(state.get_current_index(), 0); // Alpha
-        assert_eq!(wm.get_activated(), vec![100]);
+        assert_eq!(wm.activated(), vec![100]);
     }
 
     #[test]
@@ -799,13 +1257,13 @@ mod tests {
         let windows = vec![create_test_window(100, "Alpha")];
         state.update_windows(windows);
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         let empty_group: Vec<String> = vec![];
 
         // Cycling with empty group should do nothing
         state.cycle_group_forward(&wm, false, &empty_group).unwrap();
-        assert!(wm.get_activated().is_empty());
+        assert!(wm.activated().is_empty());
     }
 
     #[test]
@@ -817,13 +1275,132 @@ mod tests {
         ];
         state.update_windows(windows);
 
-        let wm = MockWindowManager::new();
+        let wm = mock(&state);
 
         // Group with characters that don't exist
         let group = vec!["Omega".to_string(), "Zeta".to_string()];
 
         // Should do nothing since no windows match
         state.cycle_group_forward(&wm, false, &group).unwrap();
-        assert!(wm.get_activated().is_empty());
+        assert!(wm.activated().is_empty());
+    }
+
+    #[test]
+    fn test_stale_id_refreshes_and_retries() {
+        let mut state = CycleState::new();
+        state.update_windows(vec![
+            create_test_window(1, "Alpha"),
+            create_test_window(2, "Beta"),
+            create_test_window(3, "Gamma"),
+        ]);
+
+        // Beta's client was restarted and now has a new id
+        let wm = MockWindowManager::new(vec![
+            create_test_window(1, "Alpha"),
+            create_test_window(20, "Beta"),
+            create_test_window(3, "Gamma"),
+        ]);
+
+        state.cycle_forward(&wm, false, None).unwrap();
+
+        assert_eq!(wm.activated(), vec![2, 20]);
+        assert_eq!(state.get_current_index(), 1);
+        assert_eq!(state.get_windows()[1].id, 20);
+    }
+
+    #[test]
+    fn test_stale_id_for_closed_character_activates_next() {
+        let mut state = CycleState::new();
+        state.update_windows(vec![
+            create_test_window(1, "Alpha"),
+            create_test_window(2, "Beta"),
+            create_test_window(3, "Gamma"),
+        ]);
+
+        // Beta was closed outright
+        let wm = MockWindowManager::new(vec![
+            create_test_window(1, "Alpha"),
+            create_test_window(3, "Gamma"),
+        ]);
+
+        state.cycle_forward(&wm, false, None).unwrap();
+
+        assert_eq!(wm.activated(), vec![2, 3]);
+        assert_eq!(state.get_windows().len(), 2);
+    }
+
+    #[test]
+    fn test_activate_finds_characters_that_logged_in_since_and_loose_names() {
+        let mut state = CycleState::new();
+        state.update_windows(vec![
+            create_test_window(1, "Main Char"),
+            create_test_window(2, "Alt 2"),
+        ]);
+
+        // Alt 2 relogged into a new window and Scout logged in, unseen so far
+        let wm = MockWindowManager::new(vec![
+            create_test_window(1, "Main Char"),
+            create_test_window(20, "Alt 2"),
+            create_test_window(3, "Scout"),
+        ]);
+
+        state.activate("Scout", &wm, false).unwrap();
+        state.activate("alt 2", &wm, false).unwrap();
+        state.activate("main", &wm, false).unwrap();
+        assert_eq!(wm.activated(), vec![3, 20, 1]);
+
+        assert!(state.activate("Nobody", &wm, false).is_err());
+    }
+
+    #[test]
+    fn test_update_windows_follows_reconnected_client() {
+        let window = |id: u64, pid: u32, title: &str| EveWindow {
+            id,
+            title: title.to_string(),
+            pid: Some(pid),
+            class: Some("steam_app_8500".to_string()),
+            ..Default::default()
+        };
+
+        let mut state = CycleState::new();
+        state.update_windows(vec![window(0x10, 100, "Main"), window(0x20, 200, "Alt")]);
+        state.set_current_index(1);
+
+        // Alt reconnected with a new address and now lists first
+        state.update_windows(vec![window(0x99, 200, "Alt"), window(0x10, 100, "Main")]);
+
+        assert_eq!(state.get_current_index(), 0);
+        assert_eq!(state.get_windows()[0].id, 0x99);
+    }
+
+    #[test]
+    fn test_pip_swaps_the_client_switched_to_into_the_big_slot() {
+        let config = Config {
+            layout: LayoutMode::Pip,
+            panel_height: 0,
+            ..Config::default()
+        };
+        let windows = vec![
+            create_test_window(1, "Alpha"),
+            create_test_window(2, "Beta"),
+            create_test_window(3, "Gamma"),
+        ];
+        let wm = MockWindowManager::new(windows.clone())
+            .with_monitors(vec![monitor("DP-1", 0, 0, 1920, 1080)]);
+        let mut state = CycleState::from_config(&config);
+        state.update_windows(windows);
+        let geometry = |id: u64| wm.window(id).and_then(|w| w.geometry);
+
+        // Beta goes big; the others line up down the right edge in order
+        state.cycle_forward(&wm, false, None).unwrap();
+        assert_eq!(geometry(2), Some((0, 0, 1920, 1080)));
+        assert_eq!(geometry(1), Some((1440, 0, 480, 270)));
+        assert_eq!(geometry(3), Some((1440, 270, 480, 270)));
+
+        // Gamma and Beta trade places; Alpha isn't moved
+        state.cycle_forward(&wm, false, None).unwrap();
+        assert_eq!(geometry(3), Some((0, 0, 1920, 1080)));
+        assert_eq!(geometry(1), Some((1440, 0, 480, 270)));
+        assert_eq!(geometry(2), Some((1440, 270, 480, 270)));
     }
 }
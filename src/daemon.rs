@@ -1,35 +1,107 @@
-use crate::config::Config;
+use crate::config::{Config, NotifyUrgency};
+#[cfg(target_os = "linux")]
+use crate::config_watch;
 use crate::cycle_state::CycleState;
+#[cfg(target_os = "linux")]
+use crate::dbus_service;
+use crate::effects::FocusDimmer;
+use crate::esi;
+use crate::events;
+use crate::focus_guard::FocusGuard;
+#[cfg(target_os = "linux")]
+use crate::hotkeys::{Hotkeys, RunningHotkeys};
+use crate::hotplug;
+use crate::keybinds::{self, Keybinds, SharedKeybinds};
+#[cfg(target_os = "linux")]
 use crate::keyboard_listener::KeyboardListener;
+use crate::launcher;
+use crate::layout;
+use crate::metrics;
+#[cfg(target_os = "linux")]
 use crate::mouse_listener::MouseListener;
-use crate::window_manager::WindowManager;
-use anyhow::Result;
-use std::fs;
+use crate::notifications::{self, Event};
+use crate::snapshot::Snapshot;
+use crate::timers::{self, Timer};
+use crate::wait;
+use crate::window_manager::{self, EveWindow, WindowManager};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Write};
-use std::os::unix::net::{UnixListener, UnixStream};
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+#[cfg(not(unix))]
+use std::net::{TcpListener as Listener, TcpStream as Stream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener as Listener, UnixStream as Stream};
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::{debug, error, info, warn};
 
+#[cfg(unix)]
 const SOCKET_PATH: &str = "/tmp/nicotine.sock";
+/// Without unix sockets the daemon listens on a loopback port instead
+#[cfg(not(unix))]
+const SOCKET_PATH: &str = "127.0.0.1:47147";
 
-#[derive(Debug)]
+/// How often ESI timers are read again. ESI itself caches industry jobs for
+/// five minutes and planets for ten.
+const TIMER_REFRESH: std::time::Duration = std::time::Duration::from_secs(600);
+
+/// How often timers are checked for having finished
+const TIMER_CHECK: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How often groups are resolved again, for launcher groups and ESI
+/// affiliations to pick up clients that logged in since
+const GROUP_REFRESH: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Groups with launcher and ESI members filled in, by name
+type SharedGroups = Arc<RwLock<HashMap<String, Vec<String>>>>;
+
+/// A request to the daemon. On the socket each is one line of JSON, e.g.
+/// `{"command":"forward"}` or `{"command":"activate","arg":"Main"}`. The
+/// older plain text lines ("forward", "switch:2") are still accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", content = "arg", rename_all = "kebab-case")]
 pub enum Command {
     Forward,
     Backward,
     Switch(usize),
+    Activate(String),
+    /// Back to the client that had focus before the current one
+    FocusLast,
     GroupForward(String),
     GroupBackward(String),
+    /// Limit forward/backward to a group, or cycle everything again with None
+    SetGroup(Option<String>),
+    Rotate(isize),
+    /// Trade the places of two characters, of one and primary_character,
+    /// or of the active client and primary_character
+    Swap(Vec<String>),
+    /// Stack every client with the current layout
+    Stack,
+    Profile(String),
     Refresh,
+    /// Re-read config.toml, as the daemon does itself when it changes
+    Reload,
+    /// Answered with a Status in the reply
+    Status,
     Quit,
 }
 
 impl Command {
     pub fn from_str(s: &str) -> Option<Self> {
         let s = s.trim();
+        if s.starts_with('{') {
+            return serde_json::from_str(s).ok();
+        }
         match s {
             "forward" => Some(Command::Forward),
             "backward" => Some(Command::Backward),
+            "focus-last" => Some(Command::FocusLast),
+            "stack" => Some(Command::Stack),
             "refresh" => Some(Command::Refresh),
+            "reload" => Some(Command::Reload),
+            "status" => Some(Command::Status),
             "quit" => Some(Command::Quit),
             _ => {
                 // Check for switch:N format
@@ -38,6 +110,9 @@ impl Command {
                         return Some(Command::Switch(num));
                     }
                 }
+                if let Some(name) = s.strip_prefix("activate:") {
+                    return Some(Command::Activate(name.to_string()));
+                }
                 // Check for group-forward:name format
                 if let Some(group_name) = s.strip_prefix("group-forward:") {
                     return Some(Command::GroupForward(group_name.to_string()));
@@ -46,131 +121,644 @@ impl Command {
                 if let Some(group_name) = s.strip_prefix("group-backward:") {
                     return Some(Command::GroupBackward(group_name.to_string()));
                 }
+                if let Some(name) = s.strip_prefix("group-set:") {
+                    return Some(Command::SetGroup(Some(name.to_string())));
+                }
+                if s == "group-clear" {
+                    return Some(Command::SetGroup(None));
+                }
+                // Check for profile:name format
+                if let Some(name) = s.strip_prefix("profile:") {
+                    return Some(Command::Profile(name.to_string()));
+                }
+                // Check for rotate / rotate:N format
+                if s == "rotate" {
+                    return Some(Command::Rotate(1));
+                }
+                if let Some(steps) = s.strip_prefix("rotate:") {
+                    return steps.parse::<isize>().ok().map(Command::Rotate);
+                }
+                // Check for swap / swap:A / swap:A,B format
+                if s == "swap" {
+                    return Some(Command::Swap(Vec::new()));
+                }
+                if let Some(names) = s.strip_prefix("swap:") {
+                    return Some(Command::Swap(
+                        names.split(',').map(|n| n.trim().to_string()).collect(),
+                    ));
+                }
                 None
             }
         }
     }
 }
 
+/// The daemon's answer to each command, one line of JSON
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Reply {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+/// What `nicotine status` reports. Its JSON form is meant for scripts and
+/// status bars, so fields are only ever added to it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    /// Whether this came from a running daemon
+    pub daemon: bool,
+    pub clients: Vec<EveWindow>,
+    /// Index into clients of the current one, None without clients
+    pub current_index: Option<usize>,
+    pub current_character: Option<String>,
+    pub active_group: Option<String>,
+    pub profile: Option<String>,
+    /// Whether the combat_lock hotkey has turned the combat lock on
+    #[serde(default)]
+    pub combat_lock: bool,
+    /// Skill queue, industry and planet timers of the characters logged in
+    /// through ESI, soonest first
+    #[serde(default)]
+    pub timers: Vec<Timer>,
+}
+
+impl Status {
+    pub fn of(state: &CycleState, daemon: bool, profile: Option<String>) -> Self {
+        let clients = state.get_windows().to_vec();
+        let current_index = Some(state.get_current_index()).filter(|&i| i < clients.len());
+        Self {
+            daemon,
+            current_character: current_index.map(|i| clients[i].title.clone()),
+            clients,
+            current_index,
+            active_group: state.active_group().map(str::to_string),
+            profile,
+            combat_lock: state.combat_lock(),
+            timers: Vec::new(),
+        }
+    }
+}
+
 pub struct Daemon {
     wm: Arc<dyn WindowManager>,
     state: Arc<Mutex<CycleState>>,
     config: Config,
+    /// `config` as the window tracking thread sees it, kept in step on reload
+    shared_config: Arc<RwLock<Config>>,
     character_order: Option<Vec<String>>,
+    /// How many slots the windows have been rotated from where they started
+    rotation: Arc<AtomicIsize>,
+    /// Profile last switched to over IPC, or the one selected at startup
+    active_profile: Option<String>,
+    keybinds: SharedKeybinds,
+    /// Last read by the timers thread, if ESI is set up
+    timers: Arc<Mutex<Vec<Timer>>>,
+    /// Resolved off the command loop, since that can mean reading /proc
+    /// and asking ESI; group commands only read it
+    groups: SharedGroups,
+    /// Wakes the group refresh thread once it's running
+    group_refresh: Option<Sender<()>>,
+    #[cfg(target_os = "linux")]
+    hotkeys: Option<RunningHotkeys>,
+    #[cfg(target_os = "linux")]
+    mouse_listening: bool,
+    #[cfg(target_os = "linux")]
+    keyboard_listening: bool,
 }
 
 impl Daemon {
     pub fn new(wm: Arc<dyn WindowManager>, config: Config) -> Self {
-        let state = Arc::new(Mutex::new(CycleState::new()));
+        let state = Arc::new(Mutex::new(CycleState::from_config(&config)));
+        let mut rotation = 0;
 
         // Initialize windows
         if let Ok(windows) = wm.get_eve_windows() {
-            state.lock().unwrap().update_windows(windows);
+            if let Err(e) = window_manager::check_wine_desktop(&*wm, &windows, &config) {
+                warn!("{}", e);
+            }
+            let mut state = state.lock().unwrap();
+            let snapshot = Snapshot::load().filter(|_| config.persist_snapshot);
+
+            if let Some(snapshot) = snapshot {
+                let reconciled = snapshot.reconcile(windows);
+                state.update_windows(reconciled.windows);
+                if let Some(key) = &reconciled.current_key {
+                    state.select_by_key(key);
+                }
+                rotation = reconciled.rotation;
+                if let Err(e) = reconciled.effects.save() {
+                    warn!("Could not restore effects ledger: {}", e);
+                }
+                info!(
+                    "Restored snapshot: {} windows, effects on {}",
+                    state.get_windows().len(),
+                    reconciled.effects.touched().len()
+                );
+                for title in &reconciled.gone {
+                    info!("  {} is no longer running", title);
+                }
+            } else {
+                state.update_windows(windows);
+
+                // Pick up where the last daemon left off, even if that client
+                // has since been given a new window id
+                if let Some(key) = CycleState::read_current_key_from_file() {
+                    state.select_by_key(&key);
+                }
+            }
         }
 
         // Load character order for targeted cycling
         let character_order = Config::load_characters();
         if character_order.is_some() {
-            println!("Loaded character order from characters.txt");
+            info!("Loaded character order from characters.txt");
         }
 
         Self {
             wm,
             state,
+            keybinds: keybinds::shared(&config),
+            shared_config: Arc::new(RwLock::new(config.clone())),
+            groups: Arc::new(RwLock::new(config.groups.clone())),
             config,
             character_order,
+            rotation: Arc::new(AtomicIsize::new(rotation)),
+            active_profile: std::env::var("NICOTINE_PROFILE").ok(),
+            timers: Arc::default(),
+            group_refresh: None,
+            #[cfg(target_os = "linux")]
+            hotkeys: None,
+            #[cfg(target_os = "linux")]
+            mouse_listening: false,
+            #[cfg(target_os = "linux")]
+            keyboard_listening: false,
         }
     }
 
     pub fn run(&mut self) -> Result<()> {
         // Remove old socket if it exists
-        let _ = fs::remove_file(SOCKET_PATH);
+        remove_socket();
+
+        let listener = Listener::bind(SOCKET_PATH)?;
+        info!("EVE Multibox daemon listening on {}", SOCKET_PATH);
+
+        #[cfg(target_os = "linux")]
+        self.start_listeners();
+
+        self.start_window_tracking();
+
+        self.start_monitor_watch();
+
+        #[cfg(target_os = "linux")]
+        self.start_hotkeys();
+
+        self.start_timers();
+
+        self.group_refresh = Some(spawn_group_refresh(
+            Arc::clone(&self.wm),
+            Arc::clone(&self.shared_config),
+            Arc::clone(&self.groups),
+        ));
+
+        // Edits to config.toml are picked up through the socket, so they're
+        // applied between commands like any other request
+        #[cfg(target_os = "linux")]
+        let watched = config_watch::spawn(&Config::config_path(), || {
+            if let Err(e) = send_command(&Command::Reload) {
+                error!("Config reload failed: {}", e);
+            }
+        });
+        #[cfg(target_os = "linux")]
+        if let Err(e) = watched {
+            warn!("config.toml changes need a restart: {:#}", e);
+        }
 
-        let listener = UnixListener::bind(SOCKET_PATH)?;
-        println!("EVE Multibox daemon listening on {}", SOCKET_PATH);
+        #[cfg(target_os = "linux")]
+        if self.config.dbus_service {
+            match dbus_service::spawn() {
+                Ok(()) => info!("✓ Serving {} on the session bus", dbus_service::NAME),
+                Err(e) => warn!("D-Bus service not started: {:#}", e),
+            }
+        }
 
-        // Start mouse event listener if enabled
-        if self.config.enable_mouse_buttons {
+        if self.config.persist_snapshot {
+            let state = Arc::clone(&self.state);
+            let rotation = Arc::clone(&self.rotation);
+            let every = std::time::Duration::from_secs(self.config.snapshot_interval_secs);
+            std::thread::spawn(move || loop {
+                std::thread::sleep(every);
+                let state = state.lock().unwrap();
+                let _ = Snapshot::capture(&state, rotation.load(Ordering::Relaxed)).save();
+            });
+        }
+
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = self.handle_client(stream) {
+                        error!("Error handling client: {}", e);
+                    }
+                }
+                Err(e) => {
+                    error!("Connection error: {}", e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keep the cycle order current: from the backend's window events where
+    /// it has them, so a client that logs in is in the order straight away,
+    /// and by polling the window list otherwise. With auto_stack_on_login,
+    /// clients that log in are placed as they're noticed.
+    fn start_window_tracking(&self) {
+        let wm = Arc::clone(&self.wm);
+        let state = Arc::clone(&self.state);
+
+        let events = match self.wm.subscribe_events() {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Window event subscription failed, polling instead: {}", e);
+                None
+            }
+        };
+
+        let config = Arc::clone(&self.shared_config);
+
+        std::thread::spawn(move || {
+            let mut logins = wait::LoginWatcher::default();
+            let mut dimmer = FocusDimmer::default();
+            let mut on_change = || {
+                let config = config.read().unwrap().clone();
+                let (windows, current) = {
+                    let state = state.lock().unwrap();
+                    let windows = state.get_windows().to_vec();
+                    let current = windows.get(state.get_current_index()).map(|w| w.id);
+                    (windows, current)
+                };
+                let rules = config.focus_opacity.as_ref();
+                if let Err(e) = dimmer.update(&*wm, &windows, current, rules) {
+                    debug!("Could not set client opacity: {}", e);
+                }
+                let notify_logins = config.notifications.login != NotifyUrgency::Off;
+                if !config.auto_stack_on_login && !notify_logins {
+                    return;
+                }
+                let logged_in = logins.poll(&*wm, &windows, &config);
+                for window in &logged_in.new {
+                    let body = format!("{} is in game", window.title);
+                    notifications::send(&config.notifications, Event::Login, body);
+                }
+                if !config.auto_stack_on_login {
+                    return;
+                }
+                match logged_in.stack(&*wm, &config) {
+                    Ok(report) if report.total() > 0 => report.log("Stacked on login"),
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Auto-stack failed: {}", e);
+                        let body = format!("Auto-stack failed: {}", e);
+                        notifications::send(&config.notifications, Event::Error, body);
+                    }
+                }
+            };
+
+            if let Some(events) = events {
+                let guarded = (Arc::clone(&wm), Arc::clone(&state), Arc::clone(&config));
+                let mut guard = FocusGuard::default();
+                let events = events::tap(events, move |event| {
+                    let (wm, state, config) = &guarded;
+                    let events::WindowEvent::Focused(focused) = event else {
+                        return;
+                    };
+                    if !config.read().unwrap().focus_guard {
+                        return;
+                    }
+                    if let Err(e) = guard.enforce(&**wm, state, focused) {
+                        warn!("Could not take focus back: {}", e);
+                    }
+                });
+                events::sync_state(&*wm, &state, events, &mut on_change);
+                warn!("Window events stopped, polling instead");
+                notifications::send(
+                    &config.read().unwrap().notifications,
+                    Event::ConnectionLost,
+                    "Window events stopped; the daemon is polling for changes instead",
+                );
+            }
+            let mut guard = FocusGuard::default();
+            loop {
+                std::thread::sleep(std::time::Duration::from_millis(500));
+                if let Ok(windows) = wm.get_eve_windows() {
+                    let mut locked = state.lock().unwrap();
+                    locked.update_windows(windows);
+                    // Without focus events, this is how focus moved by
+                    // clicking reaches the history focus-last goes by
+                    let active = wm.get_active_window().ok();
+                    if let Some(active) = active {
+                        locked.sync_with_active(active);
+                    }
+                    drop(locked);
+                    if let Some(active) = active.filter(|_| config.read().unwrap().focus_guard) {
+                        if let Err(e) = guard.enforce(&*wm, &state, active) {
+                            warn!("Could not take focus back: {}", e);
+                        }
+                    }
+                    on_change();
+                }
+            }
+        });
+    }
+
+    /// Re-stack when monitors come and go, so clients on an unplugged one
+    /// aren't left off-screen. Goes through the socket like a config reload.
+    fn start_monitor_watch(&self) {
+        let config = Arc::clone(&self.shared_config);
+        hotplug::spawn(Arc::clone(&self.wm), move |change| {
+            info!("Monitors changed: {}", change.describe());
+            if !config.read().unwrap().restack_on_monitor_change {
+                return;
+            }
+            if let Err(e) = send_command(&Command::Stack) {
+                error!("Re-stack after monitor change failed: {}", e);
+                let body = format!("Re-stack after monitor change failed: {}", e);
+                notifications::send(&config.read().unwrap().notifications, Event::Error, body);
+            }
+        });
+    }
+
+    /// Keep the ESI timers of logged-in characters current, notifying as
+    /// each one finishes
+    fn start_timers(&self) {
+        let Some(esi_config) = self.config.esi.clone() else {
+            return;
+        };
+        if esi_config.client_id.is_none() || esi::Tokens::load().characters().is_empty() {
+            return;
+        }
+        let timers = Arc::clone(&self.timers);
+        let config = Arc::clone(&self.shared_config);
+        std::thread::spawn(move || {
+            let mut fetched: Option<std::time::Instant> = None;
+            let mut checked = esi::unix_now();
+            loop {
+                if fetched.is_none_or(|at| at.elapsed() >= TIMER_REFRESH) {
+                    match timers::fetch(&esi_config) {
+                        Ok(found) => *timers.lock().unwrap() = found,
+                        Err(e) => warn!("Could not read timers: {:#}", e),
+                    }
+                    fetched = Some(std::time::Instant::now());
+                }
+                let now = esi::unix_now();
+                let notifications = config.read().unwrap().notifications;
+                for timer in timers::finished_between(&timers.lock().unwrap(), checked, now) {
+                    info!("{}: {} done", timer.character, timer.describe());
+                    let body = format!("{}: {} done", timer.character, timer.describe());
+                    notifications::send(&notifications, Event::Timer, body);
+                }
+                checked = now;
+                std::thread::sleep(TIMER_CHECK);
+            }
+        });
+    }
+
+    /// Register the [hotkeys] from config.toml, if there are any, or swap
+    /// in the current ones if they're already registered
+    #[cfg(target_os = "linux")]
+    fn start_hotkeys(&mut self) {
+        let result = match &self.hotkeys {
+            Some(running) => running.reload(&self.config),
+            None if self.config.hotkeys.is_empty() && self.config.character_hotkeys.is_empty() => {
+                return
+            }
+            None => Hotkeys::from_config(&self.config)
+                .and_then(|hotkeys| {
+                    hotkeys.spawn(
+                        Arc::clone(&self.wm),
+                        Arc::clone(&self.state),
+                        self.config.clone(),
+                    )
+                })
+                .map(|running| self.hotkeys = Some(running)),
+        };
+        if let Err(e) = result {
+            warn!("Could not register hotkeys: {:#}", e);
+        }
+    }
+
+    /// Start the mouse and keyboard listeners the config enables, unless
+    /// they're already running. Running listeners pick up new keybinds on
+    /// their own; one a profile disables just ignores its events.
+    #[cfg(target_os = "linux")]
+    fn start_listeners(&mut self) {
+        if self.config.enable_mouse_buttons && !self.mouse_listening {
             let mouse_listener = MouseListener::new(self.config.clone());
             let wm_clone = Arc::clone(&self.wm);
             let state_clone = Arc::clone(&self.state);
 
-            match mouse_listener.spawn(wm_clone, state_clone) {
-                Ok(_) => println!("Mouse button listener started"),
+            match mouse_listener.spawn(wm_clone, state_clone, Arc::clone(&self.keybinds)) {
+                Ok(_) => {
+                    info!("Mouse button listener started");
+                    self.mouse_listening = true;
+                }
                 Err(e) => {
-                    eprintln!("Warning: Could not start mouse listener: {}", e);
-                    eprintln!(
-                        "Mouse buttons will not work. You can disable this warning by setting"
+                    warn!(
+                        "Could not start mouse listener: {}\n\
+                         Mouse buttons will not work. You can disable this warning by setting\n\
+                         'enable_mouse_buttons = false' in ~/.config/nicotine/config.toml",
+                        e
                     );
-                    eprintln!("'enable_mouse_buttons = false' in ~/.config/nicotine/config.toml");
                 }
             }
         }
 
-        if self.config.enable_keyboard_buttons {
+        if self.config.enable_keyboard_buttons && !self.keyboard_listening {
             let keyboard_listener = KeyboardListener::new(self.config.clone());
             let wm_clone = Arc::clone(&self.wm);
             let state_clone = Arc::clone(&self.state);
 
-            match keyboard_listener.spawn(wm_clone, state_clone) {
-                Ok(_) => println!("Keyboard key listener started"),
+            match keyboard_listener.spawn(wm_clone, state_clone, Arc::clone(&self.keybinds)) {
+                Ok(_) => {
+                    info!("Keyboard key listener started");
+                    self.keyboard_listening = true;
+                }
                 Err(e) => {
-                    eprintln!("Warning: Could not start keyboard listener: {}", e);
-                    eprintln!(
-                        "Keyboard keys will not work.  You can disable this warning by setting"
-                    );
-                    eprintln!(
-                        "'enable_keyboard_buttons = false' in ~/.config/nicotine/config.toml"
+                    warn!(
+                        "Could not start keyboard listener: {}\n\
+                         Keyboard keys will not work. You can disable this warning by setting\n\
+                         'enable_keyboard_buttons = false' in ~/.config/nicotine/config.toml",
+                        e
                     );
                 }
             }
         }
+    }
+
+    /// Apply a profile from config.toml: swap in its keybinds and re-stack
+    /// with its layout
+    fn switch_profile(&mut self, name: &str) -> Result<()> {
+        let config = Config::load_profile(Some(name))?;
+        self.apply_config(config);
+
+        let windows = self.wm.get_eve_windows()?;
+        let report = wait::stack_in_game(&*self.wm, &windows, &self.config)?;
+        self.rotation.store(0, Ordering::Relaxed);
 
-        // Refresh window list periodically in background
-        let wm_clone = Arc::clone(&self.wm);
-        let state_clone = Arc::clone(&self.state);
-        std::thread::spawn(move || loop {
-            std::thread::sleep(std::time::Duration::from_millis(500));
-            if let Ok(windows) = wm_clone.get_eve_windows() {
-                state_clone.lock().unwrap().update_windows(windows);
+        info!(
+            "Switched profile {} -> {}",
+            self.active_profile.as_deref().unwrap_or("(none)"),
+            name
+        );
+        report.log("Stacked");
+        self.active_profile = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Re-read config.toml after an edit, keeping the active profile and
+    /// where each client is in the cycle. Clients are only re-stacked if the
+    /// edit moves where a stack would put them.
+    fn reload(&mut self) -> Result<()> {
+        let config = Config::load_profile(self.active_profile.as_deref())?;
+        let windows = self.wm.get_eve_windows()?;
+        let plan = |config: &Config| -> Result<Vec<layout::Placement>> {
+            let monitors = self.wm.get_layout_monitors(config)?;
+            Ok(layout::plan_stack(&windows, &monitors, config))
+        };
+        let relayout = plan(&self.config)? != plan(&config)?;
+
+        self.apply_config(config);
+        info!("✓ Reloaded {}", Config::config_path().display());
+
+        if relayout {
+            wait::stack_in_game(&*self.wm, &windows, &self.config)?.log("Stacked");
+            self.rotation.store(0, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Make `config` the one in effect: keybinds, hotkeys, cycle settings
+    /// and any listeners it newly enables
+    fn apply_config(&mut self, config: Config) {
+        *self.keybinds.write().unwrap() = Keybinds::from_config(&config);
+        let mut state = self.state.lock().unwrap();
+        state.set_order(config.cycle_order);
+        state.set_minimize_exclude(config.minimize_exclude.clone());
+        state.set_layout(&config);
+        drop(state);
+        *self.shared_config.write().unwrap() = config.clone();
+        self.config = config;
+        if let Some(refresh) = &self.group_refresh {
+            let _ = refresh.send(());
+        }
+        #[cfg(target_os = "linux")]
+        {
+            self.start_listeners();
+            self.start_hotkeys();
+        }
+    }
+
+    fn handle_client(&mut self, mut stream: Stream) -> Result<()> {
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+
+        let mut status = None;
+        let result = match Command::from_str(&line) {
+            Some(Command::Status) => {
+                status = Some(self.status());
+                Ok(())
             }
-        });
+            Some(command) => self.execute(command),
+            None => Err(anyhow::anyhow!("Unknown command: {}", line.trim())),
+        };
+        let reply = Reply {
+            ok: result.is_ok(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            status,
+        };
+        // A plain text client may already have hung up
+        let _ = writeln!(stream, "{}", serde_json::to_string(&reply)?);
+        result
+    }
 
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    if let Err(e) = self.handle_client(stream) {
-                        eprintln!("Error handling client: {}", e);
-                    }
+    fn status(&self) -> Status {
+        let mut state = self.state.lock().unwrap();
+        if let Ok(active) = self.wm.get_active_window() {
+            state.sync_with_active(active);
+        }
+        let mut status = Status::of(&state, true, self.active_profile.clone());
+        status.timers = self.timers.lock().unwrap().clone();
+        timers::attach_windows(&mut status.timers, &status.clients);
+        status
+    }
+
+    fn execute(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Forward => {
+                let mut state = self.state.lock().unwrap();
+
+                // Sync with active window first
+                if let Ok(active) = self.wm.get_active_window() {
+                    state.sync_with_active(active);
                 }
-                Err(e) => {
-                    eprintln!("Connection error: {}", e);
+
+                let skip = self.config.primary_character.as_deref();
+                state.cycle_forward(&*self.wm, self.config.minimize_inactive, skip)?;
+            }
+            Command::Backward => {
+                let mut state = self.state.lock().unwrap();
+
+                // Sync with active window first
+                if let Ok(active) = self.wm.get_active_window() {
+                    state.sync_with_active(active);
                 }
+
+                let skip = self.config.primary_character.as_deref();
+                state.cycle_backward(&*self.wm, self.config.minimize_inactive, skip)?;
             }
-        }
+            Command::Switch(target) => {
+                let mut state = self.state.lock().unwrap();
 
-        Ok(())
-    }
+                // Sync with active window first
+                if let Ok(active) = self.wm.get_active_window() {
+                    state.sync_with_active(active);
+                }
 
-    fn handle_client(&mut self, stream: UnixStream) -> Result<()> {
-        let mut reader = BufReader::new(&stream);
-        let mut line = String::new();
-        reader.read_line(&mut line)?;
+                state.switch_to(
+                    target,
+                    &*self.wm,
+                    self.config.minimize_inactive,
+                    self.character_order.as_deref(),
+                )?;
+            }
+            Command::Activate(character) => {
+                let mut state = self.state.lock().unwrap();
 
-        if let Some(command) = Command::from_str(&line) {
-            match command {
-                Command::Forward => {
-                    let mut state = self.state.lock().unwrap();
+                // Sync with active window first
+                if let Ok(active) = self.wm.get_active_window() {
+                    state.sync_with_active(active);
+                }
 
-                    // Sync with active window first
-                    if let Ok(active) = self.wm.get_active_window() {
-                        state.sync_with_active(active);
-                    }
+                state.activate(&character, &*self.wm, self.config.minimize_inactive)?;
+            }
+            Command::FocusLast => {
+                let mut state = self.state.lock().unwrap();
 
-                    let skip = self.config.primary_character.as_deref();
-                    state.cycle_forward(&*self.wm, self.config.minimize_inactive, skip)?;
+                // Sync with active window first
+                if let Ok(active) = self.wm.get_active_window() {
+                    state.sync_with_active(active);
                 }
-                Command::Backward => {
+
+                state.focus_last(&*self.wm, self.config.minimize_inactive)?;
+            }
+            Command::GroupForward(group_name) => {
+                let groups = self.groups.read().unwrap();
+                if let Some(group_members) = groups.get(&group_name) {
                     let mut state = self.state.lock().unwrap();
 
                     // Sync with active window first
@@ -178,10 +766,18 @@ impl Daemon {
                         state.sync_with_active(active);
                     }
 
-                    let skip = self.config.primary_character.as_deref();
-                    state.cycle_backward(&*self.wm, self.config.minimize_inactive, skip)?;
+                    state.cycle_group_forward(
+                        &*self.wm,
+                        self.config.minimize_inactive,
+                        group_members,
+                    )?;
+                } else {
+                    anyhow::bail!("Unknown group: {}", group_name);
                 }
-                Command::Switch(target) => {
+            }
+            Command::GroupBackward(group_name) => {
+                let groups = self.groups.read().unwrap();
+                if let Some(group_members) = groups.get(&group_name) {
                     let mut state = self.state.lock().unwrap();
 
                     // Sync with active window first
@@ -189,70 +785,257 @@ impl Daemon {
                         state.sync_with_active(active);
                     }
 
-                    state.switch_to(
-                        target,
+                    state.cycle_group_backward(
                         &*self.wm,
                         self.config.minimize_inactive,
-                        self.character_order.as_deref(),
+                        group_members,
                     )?;
+                } else {
+                    anyhow::bail!("Unknown group: {}", group_name);
                 }
-                Command::GroupForward(group_name) => {
-                    if let Some(group_members) = self.config.groups.get(&group_name) {
-                        let mut state = self.state.lock().unwrap();
-
-                        // Sync with active window first
-                        if let Ok(active) = self.wm.get_active_window() {
-                            state.sync_with_active(active);
-                        }
-
-                        state.cycle_group_forward(
-                            &*self.wm,
-                            self.config.minimize_inactive,
-                            group_members,
-                        )?;
-                    } else {
-                        eprintln!("Unknown group: {}", group_name);
-                    }
+            }
+            Command::SetGroup(Some(group_name)) => {
+                let groups = self.groups.read().unwrap();
+                let Some(members) = groups.get(&group_name) else {
+                    anyhow::bail!("Unknown group: {}", group_name);
+                };
+                let mut state = self.state.lock().unwrap();
+                state.set_active_group(&group_name, members.clone());
+                info!("Cycling limited to group {}", group_name);
+            }
+            Command::SetGroup(None) => {
+                let mut state = self.state.lock().unwrap();
+                if let Some(group_name) = state.active_group() {
+                    info!("No longer limited to group {}", group_name);
                 }
-                Command::GroupBackward(group_name) => {
-                    if let Some(group_members) = self.config.groups.get(&group_name) {
-                        let mut state = self.state.lock().unwrap();
-
-                        // Sync with active window first
-                        if let Ok(active) = self.wm.get_active_window() {
-                            state.sync_with_active(active);
-                        }
-
-                        state.cycle_group_backward(
-                            &*self.wm,
-                            self.config.minimize_inactive,
-                            group_members,
-                        )?;
-                    } else {
-                        eprintln!("Unknown group: {}", group_name);
-                    }
+                state.clear_active_group();
+            }
+            Command::Swap(names) => {
+                self.wm.swap_windows(&names, &self.config)?;
+            }
+            Command::Stack => {
+                let windows = self.wm.get_eve_windows()?;
+                wait::stack_in_game(&*self.wm, &windows, &self.config)?.log("Stacked");
+                self.rotation.store(0, Ordering::Relaxed);
+            }
+            Command::Rotate(steps) => {
+                let windows = self.wm.get_eve_windows()?;
+                let placements = layout::plan_rotation(&windows, steps);
+                for placement in &placements {
+                    self.wm.apply_placement(placement)?;
+                }
+                if !placements.is_empty() {
+                    let rotation = (self.rotation.load(Ordering::Relaxed) + steps)
+                        .rem_euclid(placements.len() as isize);
+                    self.rotation.store(rotation, Ordering::Relaxed);
+                    info!("Rotated windows, now {} slots from the start", rotation);
                 }
-                Command::Refresh => {
-                    let windows = self.wm.get_eve_windows()?;
-                    self.state.lock().unwrap().update_windows(windows);
+            }
+            Command::Profile(name) => {
+                self.switch_profile(&name)?;
+            }
+            Command::Refresh => {
+                self.state.lock().unwrap().refresh(&*self.wm)?;
+            }
+            Command::Reload => {
+                if let Err(e) = self.reload() {
+                    let body = format!("config.toml not reloaded: {:#}", e);
+                    notifications::send(&self.config.notifications, Event::Error, body);
+                    return Err(e);
                 }
-                Command::Quit => {
-                    std::process::exit(0);
+            }
+            Command::Status => {}
+            Command::Quit => {
+                if self.config.persist_snapshot {
+                    let state = self.state.lock().unwrap();
+                    let rotation = self.rotation.load(Ordering::Relaxed);
+                    if let Err(e) = Snapshot::capture(&state, rotation).save() {
+                        warn!("Could not save snapshot: {}", e);
+                    }
                 }
+                std::process::exit(0);
             }
         }
 
+        let state = self.state.lock().unwrap();
+        let active = state
+            .get_windows()
+            .get(state.get_current_index())
+            .map(|w| w.id);
+        metrics::export(&self.config, state.get_windows(), active, None);
         Ok(())
     }
 }
 
-pub fn send_command(command: &str) -> Result<()> {
-    if !Path::new(SOCKET_PATH).exists() {
-        anyhow::bail!("Daemon not running. Start with: eve-multibox daemon");
-    }
+/// Resolve groups on a background thread, again whenever nudged through the
+/// returned sender and every GROUP_REFRESH besides
+fn spawn_group_refresh(
+    wm: Arc<dyn WindowManager>,
+    config: Arc<RwLock<Config>>,
+    groups: SharedGroups,
+) -> Sender<()> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || loop {
+        let config = config.read().unwrap().clone();
+        let resolved = launcher::resolve_groups(&config, &*wm);
+        *groups.write().unwrap() = resolved;
+        match rx.recv_timeout(GROUP_REFRESH) {
+            Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    });
+    tx
+}
+
+/// Remove the socket file a daemon left behind, where there is one
+pub fn remove_socket() {
+    #[cfg(unix)]
+    let _ = std::fs::remove_file(SOCKET_PATH);
+}
+
+/// A connection to the running daemon, or None if it isn't running
+pub fn connect() -> Option<Stream> {
+    Stream::connect(SOCKET_PATH).ok()
+}
 
-    let mut stream = UnixStream::connect(SOCKET_PATH)?;
-    writeln!(stream, "{}", command)?;
+/// Send `command` over `stream` and wait for the daemon to carry it out
+pub fn request(stream: Stream, command: &Command) -> Result<()> {
+    exchange(stream, command).map(|_| ())
+}
+
+/// The running daemon's status, or None if it isn't running
+pub fn query_status() -> Result<Option<Status>> {
+    let Some(stream) = connect() else {
+        return Ok(None);
+    };
+    Ok(exchange(stream, &Command::Status)?.and_then(|reply| reply.status))
+}
+
+/// Send `command` and read the reply, failing if the daemon reports an error
+fn exchange(mut stream: Stream, command: &Command) -> Result<Option<Reply>> {
+    writeln!(stream, "{}", serde_json::to_string(command)?)?;
     stream.flush()?;
-    Ok(())
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    // A daemon told to quit exits without answering
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    let reply: Reply = serde_json::from_str(&line).context("Bad reply from daemon")?;
+    match reply.error {
+        Some(error) if !reply.ok => anyhow::bail!("{}", error),
+        _ => Ok(Some(reply)),
+    }
+}
+
+pub fn send_command(command: &Command) -> Result<()> {
+    let stream = connect().context("Daemon not running. Start with: nicotine daemon")?;
+    request(stream, command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands_parse_from_json_and_plain_text() {
+        let activate = Command::Activate("Main Alt".to_string());
+        let json = serde_json::to_string(&activate).unwrap();
+        assert_eq!(json, r#"{"command":"activate","arg":"Main Alt"}"#);
+        assert_eq!(Command::from_str(&json), Some(activate.clone()));
+        assert_eq!(Command::from_str("activate:Main Alt"), Some(activate));
+
+        assert_eq!(
+            Command::from_str(r#"{"command":"forward"}"#),
+            Some(Command::Forward)
+        );
+        assert_eq!(
+            Command::from_str(r#"{"command":"switch","arg":3}"#),
+            Some(Command::Switch(3))
+        );
+        assert_eq!(Command::from_str("rotate:-1"), Some(Command::Rotate(-1)));
+        assert_eq!(Command::from_str("reload"), Some(Command::Reload));
+        assert_eq!(
+            Command::from_str("swap:Main,Scout"),
+            Some(Command::Swap(vec!["Main".to_string(), "Scout".to_string()]))
+        );
+        assert_eq!(Command::from_str("swap"), Some(Command::Swap(Vec::new())));
+        assert_eq!(Command::from_str("focus-last"), Some(Command::FocusLast));
+        assert_eq!(Command::from_str("stack"), Some(Command::Stack));
+        assert_eq!(
+            Command::from_str(r#"{"command":"set-group","arg":null}"#),
+            Some(Command::SetGroup(None))
+        );
+        assert_eq!(
+            Command::from_str("group-set:pvp"),
+            Some(Command::SetGroup(Some("pvp".to_string())))
+        );
+        assert_eq!(Command::from_str(r#"{"command":"dance"}"#), None);
+    }
+
+    #[test]
+    fn test_status_reports_current_client_and_survives_the_socket() {
+        let mut state = CycleState::new();
+        state.update_windows(
+            [(1, "Main"), (2, "Scout")]
+                .into_iter()
+                .map(|(id, title)| EveWindow {
+                    id,
+                    title: title.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        state.sync_with_active(2);
+
+        let status = Status::of(&state, true, Some("pvp".to_string()));
+        assert_eq!(status.current_index, Some(1));
+        assert_eq!(status.current_character.as_deref(), Some("Scout"));
+
+        let reply = Reply {
+            ok: true,
+            error: None,
+            status: Some(status.clone()),
+        };
+        let line = serde_json::to_string(&reply).unwrap();
+        let parsed: Reply = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed.status, Some(status));
+
+        assert_eq!(
+            Status::of(&CycleState::new(), false, None).current_index,
+            None
+        );
+    }
+
+    #[test]
+    fn test_groups_are_resolved_in_the_background_and_on_reload() {
+        use crate::mock_window_manager::{window, MockWindowManager};
+
+        let wm: Arc<dyn WindowManager> = Arc::new(MockWindowManager::new(vec![window(1, "Main")]));
+        let config = Config::from_toml_str("[groups]\npvp = [\"Main\"]\n", None).unwrap();
+        let config = Arc::new(RwLock::new(config));
+        let groups = SharedGroups::default();
+        let has = |name: &str| {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+            while std::time::Instant::now() < deadline {
+                if groups.read().unwrap().contains_key(name) {
+                    return true;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            false
+        };
+
+        let refresh = spawn_group_refresh(wm, Arc::clone(&config), Arc::clone(&groups));
+        assert!(has("pvp"));
+
+        config
+            .write()
+            .unwrap()
+            .groups
+            .insert("logi".to_string(), vec!["Main".to_string()]);
+        refresh.send(()).unwrap();
+        assert!(has("logi"));
+    }
 }
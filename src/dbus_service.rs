@@ -0,0 +1,215 @@
+use crate::daemon::{self, Command, Status};
+use anyhow::{Context, Result};
+use std::time::Duration;
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+use zbus::object_server::SignalEmitter;
+
+/// Bus name, object path and interface the daemon is reachable under
+pub const NAME: &str = "org.nicotine.Manager";
+const PATH: &str = "/org/nicotine/Manager";
+
+/// How often the daemon's state is checked for changes to signal
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Carries out a call: a Status query answers with the daemon's state
+type Run = Box<dyn Fn(Command) -> Result<Option<Status>> + Send + Sync>;
+
+/// Errors the service answers a call with
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.nicotine.Manager.Error")]
+enum Error {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    /// The daemon couldn't carry out the call
+    Failed(String),
+}
+
+/// The org.nicotine.Manager object. Calls are passed to the daemon over its
+/// socket like any other client's.
+struct Manager {
+    run: Run,
+}
+
+impl Manager {
+    fn call(&self, command: Command) -> Result<Option<Status>, Error> {
+        (self.run)(command).map_err(|e| Error::Failed(format!("{:#}", e)))
+    }
+}
+
+#[zbus::interface(name = "org.nicotine.Manager")]
+impl Manager {
+    /// The character names in cycle order
+    #[zbus(out_args("characters"))]
+    fn list_windows(&self) -> Result<Vec<String>, Error> {
+        let status = self
+            .call(Command::Status)?
+            .ok_or_else(|| Error::Failed("Daemon not running".to_string()))?;
+        Ok(characters(&status))
+    }
+
+    fn activate(&self, character: String) -> Result<(), Error> {
+        self.call(Command::Activate(character)).map(|_| ())
+    }
+
+    fn stack(&self) -> Result<(), Error> {
+        self.call(Command::Stack).map(|_| ())
+    }
+
+    fn cycle_next(&self) -> Result<(), Error> {
+        self.call(Command::Forward).map(|_| ())
+    }
+
+    fn cycle_prev(&self) -> Result<(), Error> {
+        self.call(Command::Backward).map(|_| ())
+    }
+
+    #[zbus(signal)]
+    async fn active_changed(emitter: &SignalEmitter<'_>, character: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn windows_changed(
+        emitter: &SignalEmitter<'_>,
+        characters: Vec<String>,
+    ) -> zbus::Result<()>;
+}
+
+fn characters(status: &Status) -> Vec<String> {
+    status.clients.iter().map(|c| c.title.clone()).collect()
+}
+
+/// Own org.nicotine.Manager on the session bus and serve it on zbus's
+/// threads, broadcasting changes in the daemon's state as signals from a
+/// background thread
+pub fn spawn() -> Result<()> {
+    let manager = Manager {
+        run: Box::new(|command| match command {
+            Command::Status => daemon::query_status()?
+                .map(Some)
+                .context("Daemon not running"),
+            command => daemon::send_command(&command).map(|_| None),
+        }),
+    };
+    let connection = Builder::session()?
+        .name(NAME)?
+        .serve_at(PATH, manager)?
+        .build()
+        .with_context(|| format!("Could not own {} on the session bus", NAME))?;
+
+    std::thread::spawn(move || {
+        let mut last: Option<Status> = None;
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let Ok(Some(status)) = daemon::query_status() else {
+                continue;
+            };
+            if let Err(e) = emit_changes(&connection, last.as_ref(), &status) {
+                tracing::warn!("D-Bus service stopped: {}", e);
+                return;
+            }
+            last = Some(status);
+        }
+    });
+    Ok(())
+}
+
+/// What changed between two looks at the daemon's state
+#[derive(Debug, PartialEq, Eq)]
+enum Change {
+    Windows(Vec<String>),
+    Active(String),
+}
+
+fn changes(before: Option<&Status>, now: &Status) -> Vec<Change> {
+    let mut changes = Vec::new();
+    if before.map(characters) != Some(characters(now)) {
+        changes.push(Change::Windows(characters(now)));
+    }
+    if before.map(|s| &s.current_character) != Some(&now.current_character) {
+        changes.push(Change::Active(
+            now.current_character.clone().unwrap_or_default(),
+        ));
+    }
+    changes
+}
+
+fn emit_changes(connection: &Connection, before: Option<&Status>, now: &Status) -> Result<()> {
+    for change in changes(before, now) {
+        match change {
+            Change::Windows(characters) => {
+                connection.emit_signal(None::<()>, PATH, NAME, "WindowsChanged", &(characters,))?
+            }
+            Change::Active(character) => {
+                connection.emit_signal(None::<()>, PATH, NAME, "ActiveChanged", &(character,))?
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_window_manager::window;
+    use std::sync::{Arc, Mutex};
+
+    fn status(titles: &[&str], current: Option<&str>) -> Status {
+        Status {
+            clients: titles
+                .iter()
+                .enumerate()
+                .map(|(i, title)| window(i as u64 + 1, title))
+                .collect(),
+            current_character: current.map(String::from),
+            ..Status::default()
+        }
+    }
+
+    #[test]
+    fn test_calls_become_daemon_commands() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&sent);
+        let manager = Manager {
+            run: Box::new(move |command| {
+                recorded.lock().unwrap().push(command.clone());
+                match command {
+                    Command::Status => Ok(Some(status(&["Main", "Scout"], None))),
+                    Command::Stack => anyhow::bail!("No EVE windows"),
+                    _ => Ok(None),
+                }
+            }),
+        };
+
+        manager.cycle_next().unwrap();
+        manager.activate("Scout".to_string()).unwrap();
+        assert_eq!(manager.list_windows().unwrap(), ["Main", "Scout"]);
+        assert!(matches!(manager.stack(), Err(Error::Failed(e)) if e == "No EVE windows"));
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                Command::Forward,
+                Command::Activate("Scout".to_string()),
+                Command::Status,
+                Command::Stack,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_only_changes_are_signalled() {
+        let before = status(&["Main", "Scout"], Some("Main"));
+        assert_eq!(
+            changes(None, &before),
+            vec![
+                Change::Windows(vec!["Main".to_string(), "Scout".to_string()]),
+                Change::Active("Main".to_string()),
+            ]
+        );
+        assert!(changes(Some(&before), &before).is_empty());
+        assert_eq!(
+            changes(Some(&before), &status(&["Main", "Scout"], Some("Scout"))),
+            vec![Change::Active("Scout".to_string())]
+        );
+    }
+}
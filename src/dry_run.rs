@@ -0,0 +1,232 @@
+use crate::config::Config;
+use crate::events::WindowEvent;
+use crate::layout::Placement;
+use crate::window_manager::{EveWindow, Monitor, WindowManager};
+use anyhow::Result;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+
+/// A backend that reads from the real one but changes nothing, printing
+/// where each window would go instead. Layouts are computed by the trait's
+/// own stack and swap methods, so the plan is the same on every backend.
+pub struct DryRun {
+    inner: Arc<dyn WindowManager>,
+}
+
+impl DryRun {
+    pub fn wrap(inner: Arc<dyn WindowManager>) -> Arc<dyn WindowManager> {
+        Arc::new(Self { inner })
+    }
+}
+
+/// Turn off what a stack does outside the window manager: the decorations
+/// recorded in the effects ledger as if they'd been removed, the metrics
+/// textfile and post_stack_command
+pub fn disable_side_effects(config: &mut Config) {
+    config.remove_decorations = false;
+    config.metrics_textfile = None;
+    config.post_stack_command = None;
+}
+
+/// e.g. "Main on DP-1 at 0,0 2560x1440"
+pub fn describe(placement: &Placement) -> String {
+    let size = match placement.size {
+        Some((width, height)) => format!("{}x{}", width, height),
+        None => "(size unchanged)".to_string(),
+    };
+    format!(
+        "{} on {} at {},{} {}",
+        placement.title,
+        placement.monitor.as_deref().unwrap_or("(any monitor)"),
+        placement.x,
+        placement.y,
+        size
+    )
+}
+
+impl WindowManager for DryRun {
+    fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
+        self.inner.get_eve_windows()
+    }
+
+    fn get_process_windows(&self) -> Result<Vec<EveWindow>> {
+        self.inner.get_process_windows()
+    }
+
+    fn get_panel_windows(&self) -> Result<Vec<EveWindow>> {
+        self.inner.get_panel_windows()
+    }
+
+    fn get_active_monitor(&self) -> Result<Option<Monitor>> {
+        self.inner.get_active_monitor()
+    }
+
+    fn get_title(&self, window_id: u64) -> Result<Option<String>> {
+        self.inner.get_title(window_id)
+    }
+
+    fn get_active_window(&self) -> Result<u64> {
+        self.inner.get_active_window()
+    }
+
+    fn find_window_by_title(&self, title: &str) -> Result<Option<u64>> {
+        self.inner.find_window_by_title(title)
+    }
+
+    fn is_minimized(&self, window_id: u64) -> Result<bool> {
+        self.inner.is_minimized(window_id)
+    }
+
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        self.inner.get_monitors()
+    }
+
+    fn invalidate_monitors(&self) {
+        self.inner.invalidate_monitors()
+    }
+
+    fn subscribe_events(&self) -> Result<Option<Receiver<WindowEvent>>> {
+        self.inner.subscribe_events()
+    }
+
+    fn get_opacity(&self, window_id: u64) -> Result<Option<f64>> {
+        self.inner.get_opacity(window_id)
+    }
+
+    fn is_pinned(&self, window_id: u64) -> Result<bool> {
+        self.inner.is_pinned(window_id)
+    }
+
+    fn apply_placement(&self, placement: &Placement) -> Result<()> {
+        println!("Would place {}", describe(placement));
+        Ok(())
+    }
+
+    fn activate_window(&self, _window_id: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn move_window(&self, _window_id: u64, _x: i32, _y: i32) -> Result<()> {
+        Ok(())
+    }
+
+    fn minimize_window(&self, _window_id: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn restore_window(&self, _window_id: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn raise_window(&self, _window_id: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn lower_window(&self, _window_id: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn warp_pointer(&self, _window_id: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_decorations(&self, _window_id: u64, _decorated: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_opacity(&self, _window_id: u64, _opacity: Option<f64>) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_pinned(&self, _window_id: u64, _pinned: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn mark_urgent(&self, _window_id: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn close_window(&self, _window_id: u64) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LayoutMode;
+    use crate::mock_window_manager::{monitor, window, MockWindowManager};
+    use crate::wait;
+
+    /// Two clients on one monitor; anything that reaches it shows in calls()
+    fn backend() -> Arc<MockWindowManager> {
+        let mut main = window(1, "Main");
+        main.geometry = Some((0, 0, 2560, 1440));
+        let mut scout = window(2, "Scout");
+        scout.geometry = Some((100, 100, 1280, 720));
+        let wm = MockWindowManager::new(vec![main, scout])
+            .with_monitors(vec![monitor("DP-1", 0, 0, 2560, 1440)])
+            .with_active(1);
+        Arc::new(wm)
+    }
+
+    #[test]
+    fn test_dry_run_plans_without_touching_windows() {
+        let inner = backend();
+        let wm = DryRun::wrap(inner.clone());
+        let config = Config {
+            layout: LayoutMode::Fullscreen,
+            primary_character: Some("Main".to_string()),
+            ..Config::default()
+        };
+        let windows = wm.get_eve_windows().unwrap();
+
+        let report = wm.stack_windows(&windows, &config).unwrap();
+        assert_eq!(report.succeeded.len(), 2);
+        assert!(report.failed.is_empty());
+        wm.swap_windows(&["Scout".to_string()], &config).unwrap();
+        wm.activate_window(2).unwrap();
+        assert!(inner.calls().is_empty());
+    }
+
+    #[test]
+    fn test_dry_run_stack_skips_the_post_stack_hook() {
+        let out =
+            std::env::temp_dir().join(format!("nicotine-dry-run-hook-{}", std::process::id()));
+        let inner = backend();
+        let wm = DryRun::wrap(inner.clone());
+        let mut config = Config {
+            post_stack_command: Some(format!("touch {}", out.display())),
+            ..Config::default()
+        };
+        disable_side_effects(&mut config);
+
+        let windows = wm.get_eve_windows().unwrap();
+        let report = wait::stack_in_game(&*wm, &windows, &config).unwrap();
+        assert_eq!(report.succeeded.len(), 2);
+        assert!(!out.exists());
+        assert!(inner.calls().is_empty());
+    }
+
+    #[test]
+    fn test_placements_describe_character_monitor_and_geometry() {
+        let placement = Placement {
+            window_id: 1,
+            title: "Main".to_string(),
+            monitor: Some("DP-1".to_string()),
+            x: -1920,
+            y: 0,
+            size: Some((1920, 1080)),
+        };
+        assert_eq!(describe(&placement), "Main on DP-1 at -1920,0 1920x1080");
+        let moved = Placement {
+            monitor: None,
+            size: None,
+            ..placement
+        };
+        assert_eq!(
+            describe(&moved),
+            "Main on (any monitor) at -1920,0 (size unchanged)"
+        );
+    }
+}
@@ -0,0 +1,477 @@
+use crate::config::{FocusOpacity, HelperWindowAction};
+use crate::layout::Placement;
+use crate::window_manager::{bounding_box, EveWindow, Monitor, WindowManager};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+
+const LEDGER_FILE: &str = "/tmp/nicotine-effects.json";
+const HELPER_LEDGER_FILE: &str = "/tmp/nicotine-helpers.json";
+
+/// Records what nicotine changed on each window so it can be put back
+/// exactly: the opacity from before it first touched it, and which windows
+/// it stripped decorations from. Compositors have no "reset to default", and
+/// a window that never had an explicit opacity must get the property removed
+/// rather than set to 1.0.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EffectsLedger {
+    original: HashMap<u64, Option<f64>>,
+    #[serde(default)]
+    undecorated: BTreeSet<u64>,
+}
+
+impl EffectsLedger {
+    /// Load the ledger left behind by an earlier invocation (empty if none)
+    pub fn load() -> Self {
+        fs::read_to_string(LEDGER_FILE)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if self.original.is_empty() && self.undecorated.is_empty() {
+            let _ = fs::remove_file(LEDGER_FILE);
+        } else {
+            fs::write(LEDGER_FILE, serde_json::to_string(self)?)?;
+        }
+        Ok(())
+    }
+
+    /// Set a window's opacity, remembering its original value the first time
+    pub fn apply(&mut self, wm: &dyn WindowManager, window_id: u64, opacity: f64) -> Result<()> {
+        if let Entry::Vacant(entry) = self.original.entry(window_id) {
+            entry.insert(wm.get_opacity(window_id)?);
+        }
+        wm.set_opacity(window_id, Some(opacity))
+    }
+
+    /// Put one window back to its original opacity
+    pub fn restore(&mut self, wm: &dyn WindowManager, window_id: u64) -> Result<()> {
+        if let Some(previous) = self.original.remove(&window_id) {
+            wm.set_opacity(window_id, previous)?;
+        }
+        Ok(())
+    }
+
+    /// Strip a stacked window's decorations, remembering to give them back
+    pub fn undecorate(&mut self, wm: &(impl WindowManager + ?Sized), window_id: u64) -> Result<()> {
+        wm.set_decorations(window_id, false)?;
+        self.undecorated.insert(window_id);
+        Ok(())
+    }
+
+    /// Every window the ledger will put something back on
+    pub fn touched(&self) -> BTreeSet<u64> {
+        self.original
+            .keys()
+            .chain(&self.undecorated)
+            .copied()
+            .collect()
+    }
+
+    /// Follow windows that came back under new ids. Ids missing from the map
+    /// are kept; restoring a closed window is harmless.
+    pub fn remap(&mut self, ids: &HashMap<u64, u64>) {
+        let moved = |id: u64| ids.get(&id).copied().unwrap_or(id);
+        self.original = self
+            .original
+            .drain()
+            .map(|(id, v)| (moved(id), v))
+            .collect();
+        self.undecorated = std::mem::take(&mut self.undecorated)
+            .into_iter()
+            .map(moved)
+            .collect();
+    }
+
+    /// Put every touched window back. Windows that have since closed are
+    /// dropped from the ledger without failing the rest. Returns how many
+    /// windows were restored.
+    pub fn restore_all(&mut self, wm: &dyn WindowManager) -> usize {
+        let mut restored = BTreeSet::new();
+        for (window_id, previous) in self.original.drain() {
+            if wm.set_opacity(window_id, previous).is_ok() {
+                restored.insert(window_id);
+            }
+        }
+        for window_id in std::mem::take(&mut self.undecorated) {
+            if wm.set_decorations(window_id, true).is_ok() {
+                restored.insert(window_id);
+            }
+        }
+        restored.len()
+    }
+}
+
+/// Keeps the focused client at focus_opacity.active and the others at
+/// .inactive as focus moves, going through the EffectsLedger so `nicotine
+/// reset` still puts the original opacities back
+#[derive(Debug, Default)]
+pub struct FocusDimmer {
+    applied: HashMap<u64, f64>,
+}
+
+impl FocusDimmer {
+    /// Give each client its opacity now that `active` has focus, only
+    /// touching those whose opacity changes. Without rules, whatever was
+    /// applied is undone.
+    pub fn update(
+        &mut self,
+        wm: &dyn WindowManager,
+        windows: &[EveWindow],
+        active: Option<u64>,
+        rules: Option<&FocusOpacity>,
+    ) -> Result<()> {
+        self.applied
+            .retain(|id, _| windows.iter().any(|w| w.id == *id));
+        let changes = self.plan(windows, active, rules);
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let mut ledger = EffectsLedger::load();
+        let result = self.apply(&mut ledger, wm, &changes);
+        ledger.save()?;
+        result
+    }
+
+    /// Each window whose opacity has to change, with the opacity it gets,
+    /// or None to put back its original
+    fn plan(
+        &self,
+        windows: &[EveWindow],
+        active: Option<u64>,
+        rules: Option<&FocusOpacity>,
+    ) -> Vec<(u64, Option<f64>)> {
+        let Some(rules) = rules else {
+            return self.applied.keys().map(|&id| (id, None)).collect();
+        };
+        windows
+            .iter()
+            .map(|w| {
+                let opacity = if Some(w.id) == active {
+                    rules.active
+                } else {
+                    rules.inactive
+                };
+                (w.id, opacity)
+            })
+            .filter(|(id, opacity)| self.applied.get(id) != Some(opacity))
+            .map(|(id, opacity)| (id, Some(opacity)))
+            .collect()
+    }
+
+    /// Carry out `changes`, going on past windows that fail
+    fn apply(
+        &mut self,
+        ledger: &mut EffectsLedger,
+        wm: &dyn WindowManager,
+        changes: &[(u64, Option<f64>)],
+    ) -> Result<()> {
+        let mut first_error = None;
+        for &(window_id, opacity) in changes {
+            let result = match opacity {
+                Some(opacity) => ledger.apply(wm, window_id, opacity),
+                None => ledger.restore(wm, window_id),
+            };
+            match (result, opacity) {
+                (Ok(()), Some(opacity)) => {
+                    self.applied.insert(window_id, opacity);
+                }
+                (Ok(()), None) => {
+                    self.applied.remove(&window_id);
+                }
+                (Err(e), _) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+/// Windows owned by the same process as one of the character windows that
+/// aren't character windows themselves (updater, EULA, crash reporter...)
+pub fn helper_windows(all: &[EveWindow], characters: &[EveWindow]) -> Vec<EveWindow> {
+    let pids: HashSet<u32> = characters.iter().filter_map(|w| w.pid).collect();
+    let ids: HashSet<u64> = characters.iter().map(|w| w.id).collect();
+
+    all.iter()
+        .filter(|w| !ids.contains(&w.id) && w.pid.is_some_and(|pid| pids.contains(&pid)))
+        .cloned()
+        .collect()
+}
+
+/// A spot just right of every monitor, where moved windows can't be seen
+pub fn offscreen_position(monitors: &[Monitor]) -> (i32, i32) {
+    match bounding_box(monitors) {
+        Some((x, y, width, _)) => (x + width as i32 + 100, y),
+        None => (10_000, 0),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Tidied {
+    Minimized,
+    Moved { x: i32, y: i32 },
+}
+
+/// Helper windows tidied away by `nicotine tidy`, and how to bring them back
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HelperLedger {
+    tidied: HashMap<u64, Tidied>,
+}
+
+impl HelperLedger {
+    pub fn load() -> Self {
+        fs::read_to_string(HELPER_LEDGER_FILE)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if self.tidied.is_empty() {
+            let _ = fs::remove_file(HELPER_LEDGER_FILE);
+        } else {
+            fs::write(HELPER_LEDGER_FILE, serde_json::to_string(self)?)?;
+        }
+        Ok(())
+    }
+
+    /// Hide one helper window. Already-tidied windows are left alone, and
+    /// offscreen needs the window's current position to undo it later.
+    pub fn tidy(
+        &mut self,
+        wm: &dyn WindowManager,
+        window: &EveWindow,
+        action: HelperWindowAction,
+        offscreen: (i32, i32),
+    ) -> Result<bool> {
+        if self.tidied.contains_key(&window.id) {
+            return Ok(false);
+        }
+
+        let tidied = match (action, window.geometry) {
+            (HelperWindowAction::Leave, _) => return Ok(false),
+            (HelperWindowAction::Minimize, _) => {
+                wm.minimize_window(window.id)?;
+                Tidied::Minimized
+            }
+            (HelperWindowAction::Offscreen, Some((x, y, _, _))) => {
+                wm.apply_placement(&Placement {
+                    window_id: window.id,
+                    title: window.title.clone(),
+                    monitor: None,
+                    x: offscreen.0,
+                    y: offscreen.1,
+                    size: None,
+                })?;
+                Tidied::Moved { x, y }
+            }
+            (HelperWindowAction::Offscreen, None) => return Ok(false),
+        };
+
+        self.tidied.insert(window.id, tidied);
+        Ok(true)
+    }
+
+    /// Bring every tidied window back. Windows that have since closed are
+    /// dropped without failing the rest.
+    pub fn restore_all(&mut self, wm: &dyn WindowManager) -> usize {
+        let mut restored = 0;
+        for (window_id, tidied) in self.tidied.drain() {
+            let result = match tidied {
+                // Launcher windows come back without taking focus from EVE
+                Tidied::Minimized => wm.restore_window(window_id),
+                Tidied::Moved { x, y } => wm.apply_placement(&Placement {
+                    window_id,
+                    title: String::new(),
+                    monitor: None,
+                    x,
+                    y,
+                    size: None,
+                }),
+            };
+            if result.is_ok() {
+                restored += 1;
+            }
+        }
+        restored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_window_manager::{window, MockWindowManager};
+
+    #[test]
+    fn test_apply_then_restore_returns_original_opacity() {
+        let wm = MockWindowManager::new(vec![window(1, "Main"), window(2, "Alt")])
+            .with_opacity(1, Some(0.9))
+            .with_opacity(2, None);
+
+        let mut ledger = EffectsLedger::default();
+        ledger.apply(&wm, 1, 0.5).unwrap();
+        ledger.apply(&wm, 2, 0.5).unwrap();
+        // Dimming again must not overwrite the recorded original
+        ledger.apply(&wm, 1, 0.3).unwrap();
+        assert_eq!(wm.get_opacity(1).unwrap(), Some(0.3));
+
+        assert_eq!(ledger.restore_all(&wm), 2);
+        assert_eq!(wm.opacities(), HashMap::from([(1, Some(0.9)), (2, None)]));
+        assert!(ledger.original.is_empty());
+    }
+
+    #[test]
+    fn test_decorations_stripped_per_window_and_restored() {
+        let wm = MockWindowManager::new(vec![window(1, "Main"), window(2, "Alt")]);
+        let mut ledger = EffectsLedger::default();
+        ledger.undecorate(&wm, 1).unwrap();
+        ledger.undecorate(&wm, 2).unwrap();
+        ledger.apply(&wm, 2, 0.5).unwrap();
+
+        // Window 2 was dimmed and undecorated but is only one window
+        assert_eq!(ledger.restore_all(&wm), 2);
+        assert_eq!(
+            wm.calls(),
+            [
+                "undecorate 1",
+                "undecorate 2",
+                "opacity 2",
+                "opacity 2",
+                "decorate 1",
+                "decorate 2"
+            ]
+        );
+        assert!(ledger.undecorated.is_empty());
+    }
+
+    #[test]
+    fn test_ledger_round_trips_through_json() {
+        let mut ledger = EffectsLedger::default();
+        ledger.original.insert(42, None);
+        ledger.original.insert(7, Some(0.75));
+        ledger.undecorated.insert(42);
+
+        let json = serde_json::to_string(&ledger).unwrap();
+        let loaded: EffectsLedger = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded.original, ledger.original);
+        assert_eq!(loaded.undecorated, ledger.undecorated);
+
+        // Ledgers written before decorations were tracked still load
+        let old: EffectsLedger = serde_json::from_str(r#"{"original":{}}"#).unwrap();
+        assert!(old.undecorated.is_empty());
+    }
+
+    #[test]
+    fn test_focus_opacity_follows_the_active_client() {
+        let windows = vec![process_window(1, "Main", 1), process_window(2, "Alt", 2)];
+        let wm = MockWindowManager::new(windows.clone()).with_opacity(2, Some(0.9));
+        let rules = FocusOpacity {
+            active: 1.0,
+            inactive: 0.7,
+        };
+        let mut ledger = EffectsLedger::default();
+        let mut dimmer = FocusDimmer::default();
+        let mut step = |dimmer: &mut FocusDimmer, active, rules| {
+            let changes = dimmer.plan(&windows, active, rules);
+            dimmer.apply(&mut ledger, &wm, &changes).unwrap();
+            changes
+        };
+
+        assert_eq!(
+            step(&mut dimmer, Some(1), Some(&rules)),
+            [(1, Some(1.0)), (2, Some(0.7))]
+        );
+        // Nothing moved, so nothing is sent again
+        assert!(step(&mut dimmer, Some(1), Some(&rules)).is_empty());
+
+        let mut changes = step(&mut dimmer, Some(2), Some(&rules));
+        changes.sort_by_key(|&(id, _)| id);
+        assert_eq!(changes, [(1, Some(0.7)), (2, Some(1.0))]);
+
+        // Turning it off puts back what each window had
+        step(&mut dimmer, Some(2), None);
+        assert_eq!(wm.get_opacity(1).unwrap(), None);
+        assert_eq!(wm.get_opacity(2).unwrap(), Some(0.9));
+    }
+
+    fn process_window(id: u64, title: &str, pid: u32) -> EveWindow {
+        EveWindow {
+            id,
+            title: title.to_string(),
+            geometry: Some((100, 200, 640, 480)),
+            pid: Some(pid),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_helper_windows_found_by_shared_pid_and_tidied() {
+        let characters = vec![
+            process_window(1, "Main", 500),
+            process_window(2, "Alt", 600),
+        ];
+        let all = vec![
+            process_window(1, "EVE - Main", 500),
+            process_window(2, "EVE - Alt", 600),
+            process_window(3, "EVE Launcher - Updating", 500),
+            process_window(4, "EULA", 600),
+            // Unrelated process
+            process_window(5, "Firefox", 700),
+        ];
+
+        let helpers = helper_windows(&all, &characters);
+        let ids: Vec<u64> = helpers.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![3, 4]);
+
+        let wm = MockWindowManager::new(all.clone());
+        let mut ledger = HelperLedger::default();
+        assert!(ledger
+            .tidy(&wm, &helpers[0], HelperWindowAction::Minimize, (0, 0))
+            .unwrap());
+        assert!(ledger
+            .tidy(&wm, &helpers[1], HelperWindowAction::Offscreen, (5000, 0))
+            .unwrap());
+        // Tidying twice must not lose the original position
+        assert!(!ledger
+            .tidy(&wm, &helpers[1], HelperWindowAction::Offscreen, (6000, 0))
+            .unwrap());
+        let moves = |wm: &MockWindowManager| -> Vec<(u64, i32, i32)> {
+            let placements = wm.placements();
+            placements.iter().map(|p| (p.window_id, p.x, p.y)).collect()
+        };
+        assert_eq!(wm.calls(), ["minimize 3", "place 4"]);
+        assert_eq!(moves(&wm), [(4, 5000, 0)]);
+
+        wm.clear_calls();
+        assert_eq!(ledger.restore_all(&wm), 2);
+        let mut calls = wm.calls();
+        calls.sort();
+        assert_eq!(calls, ["place 4", "restore 3"]);
+        assert_eq!(moves(&wm), [(4, 100, 200)]);
+    }
+
+    #[test]
+    fn test_offscreen_position_is_past_every_monitor() {
+        let monitors = vec![
+            Monitor {
+                x: -1920,
+                width: 1920,
+                height: 1080,
+                ..Default::default()
+            },
+            Monitor {
+                width: 2560,
+                height: 1440,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(offscreen_position(&monitors), (2660, 0));
+    }
+}
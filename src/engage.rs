@@ -0,0 +1,176 @@
+use crate::config::Config;
+use crate::wait;
+use crate::window_manager::{EveWindow, WindowManager};
+use anyhow::Result;
+
+/// The steps of `nicotine engage`, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Stack,
+    FocusPrimary,
+    WarpCursor,
+}
+
+impl Step {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Step::Stack => "stack",
+            Step::FocusPrimary => "focus primary",
+            Step::WarpCursor => "warp cursor",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Done,
+    /// Turned off in config, or nothing for it to act on
+    Skipped(String),
+    Failed(anyhow::Error),
+}
+
+#[derive(Debug, Default)]
+pub struct EngageReport {
+    pub steps: Vec<(Step, Outcome)>,
+}
+
+impl EngageReport {
+    pub fn failures(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|(_, outcome)| matches!(outcome, Outcome::Failed(_)))
+            .count()
+    }
+}
+
+fn outcome(result: Result<()>) -> Outcome {
+    match result {
+        Ok(()) => Outcome::Done,
+        Err(e) => Outcome::Failed(e),
+    }
+}
+
+fn stack(wm: &dyn WindowManager, windows: &[EveWindow], config: &Config) -> Result<()> {
+    let report = wait::stack_in_game(wm, windows, config)?;
+    for title in &report.skipped {
+        println!("Skipping {} (still at login)", title);
+    }
+    match report.failed.first() {
+        Some((title, error)) => anyhow::bail!(
+            "{} of {} windows failed, first {}: {}",
+            report.failed.len(),
+            report.total(),
+            title,
+            error
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Stack, focus the primary character, then warp the cursor onto it, with
+/// each step switchable in [engage]. A step that fails or has nothing to do
+/// doesn't stop the ones after it.
+pub fn engage(wm: &dyn WindowManager, config: &Config) -> EngageReport {
+    let steps = &config.engage;
+    let mut report = EngageReport::default();
+
+    let windows = match wm.get_eve_windows() {
+        Ok(windows) => windows,
+        Err(e) => {
+            report.steps.push((Step::Stack, Outcome::Failed(e)));
+            return report;
+        }
+    };
+
+    let stacked = if !steps.stack {
+        Outcome::Skipped("disabled".to_string())
+    } else {
+        outcome(stack(wm, &windows, config))
+    };
+    report.steps.push((Step::Stack, stacked));
+
+    let primary = config
+        .primary_character
+        .as_deref()
+        .and_then(|name| windows.iter().find(|w| w.title == name));
+    let mut focused = None;
+    let focus = if !steps.focus_primary {
+        Outcome::Skipped("disabled".to_string())
+    } else if let Some(window) = primary {
+        let result = wm.activate_window(window.id);
+        if result.is_ok() {
+            focused = Some(window.id);
+        }
+        outcome(result)
+    } else {
+        match &config.primary_character {
+            Some(name) => Outcome::Skipped(format!("{} isn't logged in", name)),
+            None => Outcome::Skipped("no primary_character set".to_string()),
+        }
+    };
+    report.steps.push((Step::FocusPrimary, focus));
+
+    // Without a focused primary, fall back to whichever client is active
+    let target = focused.or_else(|| {
+        wm.get_active_window()
+            .ok()
+            .filter(|active| windows.iter().any(|w| w.id == *active))
+    });
+    let warp = if !steps.warp_cursor {
+        Outcome::Skipped("disabled".to_string())
+    } else if let Some(window_id) = target {
+        outcome(wm.warp_pointer(window_id))
+    } else {
+        Outcome::Skipped("no EVE client focused".to_string())
+    };
+    report.steps.push((Step::WarpCursor, warp));
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_window_manager::{window, MockWindowManager};
+
+    fn wm() -> MockWindowManager {
+        // Focus is on something that isn't an EVE client
+        MockWindowManager::new(vec![window(1, "Main"), window(2, "Alt")]).with_active(99)
+    }
+
+    #[test]
+    fn test_engage_runs_steps_in_order() {
+        let wm = wm();
+        let config = Config {
+            primary_character: Some("Alt".to_string()),
+            ..Config::default()
+        };
+
+        let report = engage(&wm, &config);
+
+        let steps: Vec<Step> = report.steps.iter().map(|(step, _)| *step).collect();
+        assert_eq!(
+            steps,
+            vec![Step::Stack, Step::FocusPrimary, Step::WarpCursor]
+        );
+        assert_eq!(report.failures(), 0);
+        assert_eq!(wm.calls(), ["place 1", "place 2", "activate 2", "warp 2"]);
+    }
+
+    #[test]
+    fn test_engage_tolerates_missing_primary() {
+        let wm = wm();
+        let config = Config {
+            primary_character: Some("Scout".to_string()),
+            ..Config::default()
+        };
+
+        let report = engage(&wm, &config);
+
+        assert_eq!(report.failures(), 0);
+        assert!(matches!(report.steps[0].1, Outcome::Done));
+        assert!(matches!(&report.steps[1].1, Outcome::Skipped(why) if why.contains("Scout")));
+        assert!(matches!(report.steps[2].1, Outcome::Skipped(_)));
+        assert_eq!(wm.calls(), ["place 1", "place 2"]);
+    }
+}
@@ -0,0 +1,625 @@
+use crate::config::EsiConfig;
+use crate::window_manager::EveWindow;
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const ESI_URL: &str = "https://esi.evetech.net/latest";
+const SSO_AUTHORIZE_URL: &str = "https://login.eveonline.com/v2/oauth/authorize";
+const SSO_TOKEN_URL: &str = "https://login.eveonline.com/v2/oauth/token";
+const TIMEOUT_SECS: u64 = 10;
+
+/// What `nicotine esi login` asks for: the skill queue, industry jobs and
+/// planets, for timers
+const SCOPES: &[&str] = &[
+    "esi-skills.read_skillqueue.v1",
+    "esi-industry.read_character_jobs.v1",
+    "esi-planets.manage_planets.v1",
+];
+
+/// Access tokens this close to running out are refreshed before use
+const REFRESH_MARGIN_SECS: u64 = 60;
+
+/// Identities are looked up again after this long, to notice corp changes
+const IDENTITY_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Who a character is, as ESI reports it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Identity {
+    pub character_id: u64,
+    pub corporation_id: u64,
+    pub corporation: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alliance_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alliance: Option<String>,
+    /// Unix time ESI was asked
+    pub fetched: u64,
+}
+
+impl Identity {
+    /// The character's portrait on the image server, `size` pixels square
+    /// (a power of two from 32 to 1024)
+    pub fn portrait_url(&self, size: u32) -> String {
+        format!(
+            "https://images.evetech.net/characters/{}/portrait?size={}",
+            self.character_id, size
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Named {
+    id: u64,
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Ids {
+    #[serde(default)]
+    characters: Vec<Named>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Affiliation {
+    character_id: u64,
+    corporation_id: u64,
+    alliance_id: Option<u64>,
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn client() -> Result<reqwest::blocking::Client> {
+    reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(TIMEOUT_SECS))
+        .user_agent(concat!("nicotine/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("Failed to build HTTP client")
+}
+
+fn post_json<T: DeserializeOwned>(
+    client: &reqwest::blocking::Client,
+    path: &str,
+    body: &impl Serialize,
+) -> Result<T> {
+    client
+        .post(format!("{}{}", ESI_URL, path))
+        .json(body)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("ESI request {} failed", path))?
+        .json()
+        .with_context(|| format!("Unreadable ESI response from {}", path))
+}
+
+/// Look characters up by exact name, then their corporations and alliances.
+/// Names ESI doesn't know are left out.
+fn fetch(names: &[String], now: u64) -> Result<Vec<(String, Identity)>> {
+    let client = client()?;
+    let ids: Ids = post_json(&client, "/universe/ids/", &names)?;
+    if ids.characters.is_empty() {
+        return Ok(Vec::new());
+    }
+    let character_ids: Vec<u64> = ids.characters.iter().map(|c| c.id).collect();
+    let affiliations: Vec<Affiliation> =
+        post_json(&client, "/characters/affiliation/", &character_ids)?;
+    let mut org_ids: Vec<u64> = affiliations
+        .iter()
+        .flat_map(|a| std::iter::once(a.corporation_id).chain(a.alliance_id))
+        .collect();
+    org_ids.sort();
+    org_ids.dedup();
+    let orgs: Vec<Named> = post_json(&client, "/universe/names/", &org_ids)?;
+    Ok(identities(&ids.characters, &affiliations, &orgs, now))
+}
+
+/// Join ESI's three answers into an identity per character name
+fn identities(
+    characters: &[Named],
+    affiliations: &[Affiliation],
+    orgs: &[Named],
+    now: u64,
+) -> Vec<(String, Identity)> {
+    let org_name = |id: u64| orgs.iter().find(|o| o.id == id).map(|o| o.name.clone());
+    characters
+        .iter()
+        .filter_map(|character| {
+            let affiliation = affiliations
+                .iter()
+                .find(|a| a.character_id == character.id)?;
+            let identity = Identity {
+                character_id: character.id,
+                corporation_id: affiliation.corporation_id,
+                corporation: org_name(affiliation.corporation_id).unwrap_or_default(),
+                alliance_id: affiliation.alliance_id,
+                alliance: affiliation.alliance_id.and_then(org_name),
+                fetched: now,
+            };
+            Some((character.name.clone(), identity))
+        })
+        .collect()
+}
+
+/// $XDG_DATA_HOME/nicotine/<name>
+fn data_path(name: &str) -> Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .context("Could not determine data directory")?
+        .join("nicotine")
+        .join(name))
+}
+
+/// Identities already looked up, by character name, kept in
+/// $XDG_DATA_HOME/nicotine/esi.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Directory {
+    #[serde(default)]
+    identities: BTreeMap<String, Identity>,
+    /// Names ESI had no character for, and when it was asked
+    #[serde(default)]
+    unknown: BTreeMap<String, u64>,
+}
+
+impl Directory {
+    pub fn load() -> Self {
+        data_path("esi.json")
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = data_path("esi.json")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Names that haven't been asked about in the last day
+    fn stale(&self, names: &[String], now: u64) -> Vec<String> {
+        let fresh = |fetched: u64| now.saturating_sub(fetched) < IDENTITY_TTL_SECS;
+        names
+            .iter()
+            .filter(|name| {
+                let known = self.identities.get(*name).map(|i| i.fetched);
+                !known
+                    .or(self.unknown.get(*name).copied())
+                    .is_some_and(fresh)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// The identities of `names`, asking ESI about any it hasn't been asked
+    /// about in the last day
+    pub fn resolve(&mut self, names: &[String]) -> Result<HashMap<String, Identity>> {
+        let now = unix_now();
+        let stale = self.stale(names, now);
+        if !stale.is_empty() {
+            let found = fetch(&stale, now)?;
+            for name in &stale {
+                if !found.iter().any(|(found, _)| found == name) {
+                    self.unknown.insert(name.clone(), now);
+                }
+            }
+            for (name, identity) in found {
+                self.unknown.remove(&name);
+                self.identities.insert(name, identity);
+            }
+            self.save()?;
+        }
+        Ok(names
+            .iter()
+            .filter_map(|name| Some((name.clone(), self.identities.get(name)?.clone())))
+            .collect())
+    }
+}
+
+/// Whether a group member names a corporation ("corp:Name") or an alliance
+/// ("alliance:Name") rather than a character
+pub fn is_affiliation(member: &str) -> bool {
+    member.starts_with("corp:") || member.starts_with("alliance:")
+}
+
+/// Whether `identity` belongs to the corporation or alliance `member` names
+fn belongs(member: &str, identity: &Identity) -> bool {
+    if let Some(corp) = member.strip_prefix("corp:") {
+        return identity.corporation.eq_ignore_ascii_case(corp.trim());
+    }
+    if let Some(alliance) = member.strip_prefix("alliance:") {
+        return identity
+            .alliance
+            .as_deref()
+            .is_some_and(|a| a.eq_ignore_ascii_case(alliance.trim()));
+    }
+    false
+}
+
+/// Replace the corp: and alliance: members of `groups` with the running
+/// characters that belong, in window order
+pub fn expand_groups(
+    groups: &mut HashMap<String, Vec<String>>,
+    windows: &[EveWindow],
+    identities: &HashMap<String, Identity>,
+) {
+    for members in groups.values_mut() {
+        let mut expanded: Vec<String> = Vec::new();
+        for member in members.drain(..) {
+            if !is_affiliation(&member) {
+                if !expanded.contains(&member) {
+                    expanded.push(member);
+                }
+                continue;
+            }
+            for window in windows {
+                let belongs = identities
+                    .get(&window.title)
+                    .is_some_and(|identity| belongs(&member, identity));
+                if belongs && !expanded.contains(&window.title) {
+                    expanded.push(window.title.clone());
+                }
+            }
+        }
+        *members = expanded;
+    }
+}
+
+/// A character's SSO tokens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Token {
+    pub character_id: u64,
+    pub character: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Unix time the access token runs out
+    pub expires_at: u64,
+}
+
+/// SSO tokens by character name, in $XDG_DATA_HOME/nicotine/esi_tokens.json,
+/// which only the user can read
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Tokens {
+    #[serde(default)]
+    tokens: BTreeMap<String, Token>,
+}
+
+impl Tokens {
+    pub fn load() -> Self {
+        data_path("esi_tokens.json")
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = data_path("esi_tokens.json")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string_pretty(self)?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // Only the owner may read the tokens
+        #[cfg(unix)]
+        options.mode(0o600);
+        options
+            .open(&path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn insert(&mut self, token: Token) {
+        self.tokens.insert(token.character.clone(), token);
+    }
+
+    pub fn contains(&self, character: &str) -> bool {
+        self.tokens.contains_key(character)
+    }
+
+    pub fn characters(&self) -> Vec<String> {
+        self.tokens.keys().cloned().collect()
+    }
+
+    /// The character's token, refreshed first if it has run out or is
+    /// about to. Save afterwards, as SSO hands out a new refresh token.
+    pub fn access_token(&mut self, character: &str, client_id: &str) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(character)
+            .with_context(|| format!("{} isn't logged in (nicotine esi login)", character))?;
+        if token.expires_at > unix_now() + REFRESH_MARGIN_SECS {
+            return Ok(token.clone());
+        }
+        let refreshed = request_token(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", token.refresh_token.as_str()),
+            ("client_id", client_id),
+        ])
+        .with_context(|| format!("Could not refresh {}'s login", character))?;
+        self.insert(refreshed.clone());
+        Ok(refreshed)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+    refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    /// "CHARACTER:EVE:<id>"
+    sub: String,
+    name: String,
+}
+
+/// The character an access token is for, from its JWT payload. The token
+/// came straight from SSO over TLS, so its signature isn't checked.
+fn token_from(response: TokenResponse, now: u64) -> Result<Token> {
+    let payload = response
+        .access_token
+        .split('.')
+        .nth(1)
+        .context("SSO access token isn't a JWT")?;
+    let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload)?)
+        .context("Unreadable SSO access token")?;
+    let character_id = claims
+        .sub
+        .rsplit(':')
+        .next()
+        .and_then(|id| id.parse().ok())
+        .with_context(|| format!("Unexpected SSO token subject {:?}", claims.sub))?;
+    Ok(Token {
+        character_id,
+        character: claims.name,
+        access_token: response.access_token,
+        refresh_token: response.refresh_token,
+        expires_at: now + response.expires_in,
+    })
+}
+
+fn random_token(len: usize) -> Result<String> {
+    let mut bytes = vec![0; len];
+    fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// The S256 PKCE challenge for `verifier`
+fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// The authorization code from the callback SSO sends the browser to, e.g.
+/// "/callback?code=...&state=...", if `state` is the one this login sent
+fn code_from(path: &str, state: &str) -> Result<String> {
+    let url = reqwest::Url::parse("http://localhost")?.join(path)?;
+    let params: HashMap<_, _> = url.query_pairs().collect();
+    if let Some(error) = params.get("error") {
+        anyhow::bail!("EVE SSO refused the login: {}", error);
+    }
+    if params.get("state").map(|s| s.as_ref()) != Some(state) {
+        anyhow::bail!("EVE SSO callback isn't for this login");
+    }
+    params
+        .get("code")
+        .map(|code| code.to_string())
+        .context("EVE SSO callback has no code")
+}
+
+/// Answer requests on `listener` until the SSO callback arrives, and return
+/// its code
+fn wait_for_code(listener: &TcpListener, state: &str) -> Result<String> {
+    loop {
+        let (mut stream, _) = listener.accept()?;
+        let mut request = String::new();
+        BufReader::new(&stream).read_line(&mut request)?;
+        // Browsers ask for /favicon.ico as well
+        let Some(path) = request
+            .split_whitespace()
+            .nth(1)
+            .filter(|p| p.starts_with("/callback"))
+        else {
+            let _ = write!(
+                stream,
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"
+            );
+            continue;
+        };
+        let code = code_from(path, state);
+        let body = match &code {
+            Ok(_) => "Logged in to nicotine. You can close this tab.",
+            Err(_) => "Login failed; see the terminal nicotine is running in.",
+        };
+        let _ = write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        return code;
+    }
+}
+
+/// Log a character in through EVE SSO in the browser, with PKCE so no
+/// application secret is needed
+pub fn login(config: &EsiConfig) -> Result<Token> {
+    let client_id = config.client_id.as_deref().context(
+        "Set [esi] client_id to the Client ID of an application registered at \
+         https://developers.eveonline.com",
+    )?;
+    let redirect = format!("http://localhost:{}/callback", config.callback_port);
+    let listener = TcpListener::bind(("127.0.0.1", config.callback_port))
+        .with_context(|| format!("Could not listen on port {}", config.callback_port))?;
+
+    let verifier = random_token(32)?;
+    let state = random_token(16)?;
+    let url = reqwest::Url::parse_with_params(
+        SSO_AUTHORIZE_URL,
+        &[
+            ("response_type", "code"),
+            ("redirect_uri", redirect.as_str()),
+            ("client_id", client_id),
+            ("code_challenge", pkce_challenge(&verifier).as_str()),
+            ("code_challenge_method", "S256"),
+            ("scope", SCOPES.join(" ").as_str()),
+            ("state", state.as_str()),
+        ],
+    )?;
+    println!("Log in through EVE SSO in your browser:\n{}", url);
+    let _ = std::process::Command::new("xdg-open")
+        .arg(url.as_str())
+        .spawn();
+
+    let code = wait_for_code(&listener, &state)?;
+    request_token(&[
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("client_id", client_id),
+        ("code_verifier", verifier.as_str()),
+    ])
+}
+
+fn request_token(form: &[(&str, &str)]) -> Result<Token> {
+    let response: TokenResponse = client()?
+        .post(SSO_TOKEN_URL)
+        .form(form)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .context("EVE SSO token request failed")?
+        .json()
+        .context("Unreadable EVE SSO token response")?;
+    token_from(response, unix_now())
+}
+
+/// GET an ESI endpoint, as `token`'s character for authenticated ones
+pub fn get_json<T: DeserializeOwned>(token: Option<&Token>, path: &str) -> Result<T> {
+    let mut request = client()?.get(format!("{}{}", ESI_URL, path));
+    if let Some(token) = token {
+        request = request.bearer_auth(&token.access_token);
+    }
+    request
+        .send()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("ESI request {} failed", path))?
+        .json()
+        .with_context(|| format!("Unreadable ESI response from {}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(corporation: &str, alliance: Option<&str>) -> Identity {
+        Identity {
+            character_id: 1,
+            corporation_id: 2,
+            corporation: corporation.to_string(),
+            alliance_id: alliance.map(|_| 3),
+            alliance: alliance.map(str::to_string),
+            fetched: 0,
+        }
+    }
+
+    #[test]
+    fn test_identities_join_names_affiliations_and_orgs() {
+        let characters: Ids =
+            serde_json::from_str(r#"{"characters":[{"id":90,"name":"Main"}]}"#).unwrap();
+        let affiliations: Vec<Affiliation> =
+            serde_json::from_str(r#"[{"character_id":90,"corporation_id":98,"alliance_id":99}]"#)
+                .unwrap();
+        let orgs: Vec<Named> = serde_json::from_str(
+            r#"[{"category":"corporation","id":98,"name":"Corp"},
+                {"category":"alliance","id":99,"name":"Alliance"}]"#,
+        )
+        .unwrap();
+
+        let found = identities(&characters.characters, &affiliations, &orgs, 7);
+        assert_eq!(found.len(), 1);
+        let (name, identity) = &found[0];
+        assert_eq!(name, "Main");
+        assert_eq!(identity.corporation, "Corp");
+        assert_eq!(identity.alliance.as_deref(), Some("Alliance"));
+        assert_eq!(
+            identity.portrait_url(64),
+            "https://images.evetech.net/characters/90/portrait?size=64"
+        );
+    }
+
+    #[test]
+    fn test_corp_and_alliance_members_expand_to_running_characters() {
+        let windows: Vec<EveWindow> = ["Main", "Scout", "Hauler"]
+            .iter()
+            .map(|title| EveWindow {
+                title: title.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let identities = HashMap::from([
+            ("Main".to_string(), identity("Corp", Some("Alliance"))),
+            (
+                "Scout".to_string(),
+                identity("Other Corp", Some("Alliance")),
+            ),
+            ("Hauler".to_string(), identity("corp", None)),
+        ]);
+        let mut groups = HashMap::from([
+            ("mine".to_string(), vec!["corp:Corp".to_string()]),
+            (
+                "fleet".to_string(),
+                vec!["Hauler".to_string(), "alliance:Alliance".to_string()],
+            ),
+        ]);
+
+        expand_groups(&mut groups, &windows, &identities);
+        assert_eq!(groups["mine"], ["Main", "Hauler"]);
+        assert_eq!(groups["fleet"], ["Hauler", "Main", "Scout"]);
+    }
+
+    #[test]
+    fn test_sso_pieces() {
+        // RFC 7636, appendix B
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+
+        assert_eq!(
+            code_from("/callback?code=abc%2B1&state=xyz", "xyz").unwrap(),
+            "abc+1"
+        );
+        assert!(code_from("/callback?code=abc&state=other", "xyz").is_err());
+        assert!(code_from("/callback?error=access_denied&state=xyz", "xyz").is_err());
+
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"sub":"CHARACTER:EVE:90","name":"Main"}"#);
+        let response = TokenResponse {
+            access_token: format!("header.{}.signature", payload),
+            expires_in: 1199,
+            refresh_token: "refresh".to_string(),
+        };
+        let token = token_from(response, 1000).unwrap();
+        assert_eq!((token.character_id, token.character.as_str()), (90, "Main"));
+        assert_eq!(token.expires_at, 2199);
+    }
+}
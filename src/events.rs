@@ -0,0 +1,271 @@
+use crate::cycle_state::CycleState;
+use crate::window_manager::{EveWindow, WindowManager};
+use serde_json::Value;
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+
+/// A change reported by a backend's event stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowEvent {
+    /// Focus moved to this window (which may not be an EVE client)
+    Focused(u64),
+    Opened,
+    Closed,
+    TitleChanged,
+}
+
+impl WindowEvent {
+    /// Whether the window list has to be re-read to handle this event
+    pub fn changes_windows(&self) -> bool {
+        !matches!(self, WindowEvent::Focused(_))
+    }
+}
+
+/// One line from Hyprland's socket2, e.g. "activewindowv2>>55ade765da10"
+pub fn parse_hyprland_event(line: &str) -> Option<WindowEvent> {
+    let (name, data) = line.split_once(">>")?;
+    match name {
+        "activewindowv2" => u64::from_str_radix(data.trim_start_matches("0x"), 16)
+            .ok()
+            .map(WindowEvent::Focused),
+        "openwindow" => Some(WindowEvent::Opened),
+        "closewindow" => Some(WindowEvent::Closed),
+        "windowtitlev2" => Some(WindowEvent::TitleChanged),
+        _ => None,
+    }
+}
+
+/// One window event payload from sway's IPC, as JSON
+pub fn parse_sway_event(line: &str) -> Option<WindowEvent> {
+    let event: Value = serde_json::from_str(line).ok()?;
+    match event.get("change")?.as_str()? {
+        "focus" => event
+            .get("container")?
+            .get("id")?
+            .as_u64()
+            .map(WindowEvent::Focused),
+        "new" => Some(WindowEvent::Opened),
+        "close" => Some(WindowEvent::Closed),
+        "title" => Some(WindowEvent::TitleChanged),
+        _ => None,
+    }
+}
+
+/// Parse `reader` line by line on a background thread. The thread exits when
+/// the stream ends or the receiver is dropped.
+pub fn spawn_reader<R>(reader: R, parse: fn(&str) -> Option<WindowEvent>) -> Receiver<WindowEvent>
+where
+    R: BufRead + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in reader.lines() {
+            let Ok(line) = line else { break };
+            if let Some(event) = parse(&line) {
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    rx
+}
+
+/// Pass `events` on, letting `inspect` see each one first, for a second
+/// consumer of a stream only one thread can read
+pub fn tap(
+    events: Receiver<WindowEvent>,
+    mut inspect: impl FnMut(WindowEvent) + Send + 'static,
+) -> Receiver<WindowEvent> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for event in events {
+            inspect(event);
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Apply an event to the overlay's state, with the freshly read window list
+/// for events that change it. Returns whether the overlay needs a redraw:
+/// focus landing on a non-EVE window or the already active row doesn't.
+pub fn apply_event(
+    state: &mut CycleState,
+    event: WindowEvent,
+    windows: Option<Vec<EveWindow>>,
+) -> bool {
+    match event {
+        WindowEvent::Focused(window_id) => {
+            let before = state.get_current_index();
+            if !state.get_windows().iter().any(|w| w.id == window_id) {
+                return false;
+            }
+            state.sync_with_active(window_id);
+            state.get_current_index() != before
+        }
+        _ => {
+            let Some(windows) = windows else {
+                return false;
+            };
+            state.update_windows(windows);
+            if state.get_windows().is_empty() {
+                state.set_current_index(0);
+            }
+            true
+        }
+    }
+}
+
+/// Keep `state` in step with the backend's event stream until it ends,
+/// calling `on_change` whenever the cycle order or active window moved.
+/// Blocks, so run it on its own thread.
+pub fn sync_state(
+    wm: &dyn WindowManager,
+    state: &Mutex<CycleState>,
+    events: Receiver<WindowEvent>,
+    mut on_change: impl FnMut(),
+) {
+    // Events only report changes, so start from the current picture
+    if let Ok(windows) = wm.get_eve_windows() {
+        let mut state = state.lock().unwrap();
+        apply_event(&mut state, WindowEvent::Opened, Some(windows));
+        if let Ok(active) = wm.get_active_window() {
+            apply_event(&mut state, WindowEvent::Focused(active), None);
+        }
+    }
+    on_change();
+
+    for event in events {
+        let windows = if event.changes_windows() {
+            wm.get_eve_windows().ok()
+        } else {
+            None
+        };
+        if apply_event(&mut state.lock().unwrap(), event, windows) {
+            on_change();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_window_manager::{window, MockWindowManager};
+
+    fn state() -> CycleState {
+        let mut state = CycleState::new();
+        state.update_windows(vec![
+            window(0x10, "Main"),
+            window(0x20, "Alt"),
+            window(0x30, "Scout"),
+        ]);
+        state
+    }
+
+    #[test]
+    fn test_focus_event_marks_overlay_row_active() {
+        let mut state = state();
+
+        let event = parse_hyprland_event("activewindowv2>>20").unwrap();
+        assert!(apply_event(&mut state, event, None));
+        assert_eq!(state.get_current_index(), 1);
+
+        // Focusing the row that's already active needs no redraw
+        assert!(!apply_event(&mut state, event, None));
+    }
+
+    #[test]
+    fn test_focus_on_other_window_keeps_highlight() {
+        let mut state = state();
+        state.set_current_index(2);
+
+        assert!(!apply_event(&mut state, WindowEvent::Focused(0x99), None));
+        assert_eq!(state.get_current_index(), 2);
+    }
+
+    #[test]
+    fn test_last_client_closing_empties_overlay() {
+        let mut state = state();
+        state.set_current_index(2);
+
+        assert!(apply_event(
+            &mut state,
+            WindowEvent::Closed,
+            Some(Vec::new())
+        ));
+        assert!(state.get_windows().is_empty());
+        assert_eq!(state.get_current_index(), 0);
+        // A stray focus event afterwards is harmless
+        assert!(!apply_event(&mut state, WindowEvent::Focused(0x10), None));
+    }
+
+    #[test]
+    fn test_parse_hyprland_events() {
+        assert_eq!(
+            parse_hyprland_event("activewindowv2>>55ade765da10"),
+            Some(WindowEvent::Focused(0x55ade765da10))
+        );
+        assert_eq!(
+            parse_hyprland_event("openwindow>>55ade765da10,1,steam_app_8500,EVE"),
+            Some(WindowEvent::Opened)
+        );
+        assert_eq!(
+            parse_hyprland_event("windowtitlev2>>55ade765da10,EVE - Main"),
+            Some(WindowEvent::TitleChanged)
+        );
+        assert_eq!(parse_hyprland_event("workspace>>2"), None);
+        assert_eq!(parse_hyprland_event("activewindowv2>>,"), None);
+    }
+
+    #[test]
+    fn test_parse_sway_events() {
+        assert_eq!(
+            parse_sway_event(r#"{"change":"focus","container":{"id":42}}"#),
+            Some(WindowEvent::Focused(42))
+        );
+        assert_eq!(
+            parse_sway_event(r#"{"change":"close","container":{"id":42}}"#),
+            Some(WindowEvent::Closed)
+        );
+        assert_eq!(
+            parse_sway_event(r#"{"change":"move","container":{"id":42}}"#),
+            None
+        );
+        assert_eq!(parse_sway_event("not json"), None);
+    }
+
+    #[test]
+    fn test_sync_state_picks_up_login_from_event() {
+        let (tx, rx) = mpsc::channel();
+        let wm = MockWindowManager::new(vec![window(0x10, "Main"), window(0x20, "Alt")])
+            .with_active(0x20)
+            .with_events(rx);
+        let state = Mutex::new(CycleState::new());
+
+        // A client logs in; its event is waiting when the sync starts
+        wm.open(window(0x30, "Scout"));
+        tx.send(WindowEvent::Opened).unwrap();
+        tx.send(WindowEvent::Focused(0x99)).unwrap();
+        drop(tx);
+
+        let mut changes = 0;
+        let events = wm.subscribe_events().unwrap().unwrap();
+        sync_state(&wm, &state, events, || changes += 1);
+
+        let state = state.lock().unwrap();
+        let titles: Vec<&str> = state
+            .get_windows()
+            .iter()
+            .map(|w| w.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["Main", "Alt", "Scout"]);
+        assert_eq!(state.get_current_index(), 1);
+        // Initial sync and the login event; focus on a non-EVE window
+        // changes nothing
+        assert_eq!(changes, 2);
+    }
+}
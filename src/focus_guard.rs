@@ -0,0 +1,138 @@
+use crate::cycle_state::CycleState;
+use crate::window_manager::WindowManager;
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Hand-backs within this long of each other count towards MAX_RECLAIMS
+const RECLAIM_WINDOW: Duration = Duration::from_secs(5);
+
+/// A window that takes focus back this many times in RECLAIM_WINDOW is
+/// left with it, rather than the two fighting over focus
+const MAX_RECLAIMS: usize = 3;
+
+/// Hands focus back to the EVE client that had it when another window takes
+/// it during a combat lock
+#[derive(Debug, Default)]
+pub struct FocusGuard {
+    /// The client focus last landed on, which may be ahead of the cycle
+    /// state if its event hasn't been applied yet
+    last_client: Option<u64>,
+    reclaims: VecDeque<Instant>,
+    gave_up: bool,
+}
+
+impl FocusGuard {
+    /// The client to re-activate now that `focused` has focus, if any
+    pub fn check(&mut self, state: &CycleState, focused: u64, now: Instant) -> Option<u64> {
+        let windows = state.get_windows();
+        if windows.iter().any(|w| w.id == focused) {
+            self.last_client = Some(focused);
+            return None;
+        }
+        if !state.combat_lock() {
+            self.reclaims.clear();
+            self.gave_up = false;
+            return None;
+        }
+        // Nothing has focus: a client closing, or the root window clicked
+        if focused == 0 {
+            return None;
+        }
+        let client = self
+            .last_client
+            .filter(|id| windows.iter().any(|w| w.id == *id))
+            .or_else(|| windows.get(state.get_current_index()).map(|w| w.id))?;
+
+        while self
+            .reclaims
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > RECLAIM_WINDOW)
+        {
+            self.reclaims.pop_front();
+        }
+        if self.reclaims.len() >= MAX_RECLAIMS {
+            if !self.gave_up {
+                warn!("Window {:#x} keeps taking focus, leaving it there", focused);
+                self.gave_up = true;
+            }
+            return None;
+        }
+        self.gave_up = false;
+        self.reclaims.push_back(now);
+        Some(client)
+    }
+
+    /// Re-activate the client if `focused` took focus from it
+    pub fn enforce(
+        &mut self,
+        wm: &dyn WindowManager,
+        state: &Mutex<CycleState>,
+        focused: u64,
+    ) -> Result<()> {
+        let Some(client) = self.check(&state.lock().unwrap(), focused, Instant::now()) else {
+            return Ok(());
+        };
+        info!(
+            "Window {:#x} took focus during combat lock, handing it back",
+            focused
+        );
+        wm.activate_window(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::window_manager::EveWindow;
+
+    fn state() -> CycleState {
+        let mut state = CycleState::new();
+        state.update_windows(
+            [(1, "EVE - Main"), (2, "EVE - Scout")]
+                .into_iter()
+                .map(|(id, title)| EveWindow {
+                    id,
+                    title: title.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        state
+    }
+
+    #[test]
+    fn test_focus_goes_back_to_the_last_client_only_while_locked() {
+        let mut state = state();
+        let mut guard = FocusGuard::default();
+        let now = Instant::now();
+
+        assert_eq!(guard.check(&state, 2, now), None);
+        assert_eq!(guard.check(&state, 99, now), None);
+
+        state.set_combat_lock(true);
+        assert_eq!(guard.check(&state, 99, now), Some(2));
+        assert_eq!(guard.check(&state, 1, now), None);
+        assert_eq!(guard.check(&state, 99, now), Some(1));
+        assert_eq!(guard.check(&state, 0, now), None);
+    }
+
+    #[test]
+    fn test_a_window_that_keeps_taking_focus_is_left_alone() {
+        let mut state = state();
+        state.set_combat_lock(true);
+        let mut guard = FocusGuard::default();
+        let start = Instant::now();
+
+        for _ in 0..MAX_RECLAIMS {
+            assert_eq!(guard.check(&state, 99, start), Some(1));
+            assert_eq!(guard.check(&state, 1, start), None);
+        }
+        assert_eq!(guard.check(&state, 99, start), None);
+
+        let later = start + RECLAIM_WINDOW + Duration::from_secs(1);
+        assert_eq!(guard.check(&state, 99, later), Some(1));
+    }
+}
@@ -0,0 +1,258 @@
+use crate::config::Config;
+use crate::layout::{self, Placement};
+use crate::report::ActionReport;
+use crate::window_manager::{monitor_at, EveWindow, Monitor, WindowManager};
+use anyhow::Result;
+
+/// How a managed window has wandered from its layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drift {
+    /// Its centre isn't on any monitor
+    OffMonitor,
+    /// Further than the tolerance from its planned position or size
+    Misplaced,
+    /// Minimized although nothing should have minimized it
+    Minimized,
+}
+
+/// A window that needs repair and what to do about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair {
+    pub window_id: u64,
+    pub title: String,
+    pub drift: Vec<Drift>,
+    pub placement: Option<Placement>,
+}
+
+#[derive(Debug, Default)]
+pub struct HealReport {
+    pub healed: Vec<Repair>,
+    /// Healed, failed, and skipped (already in place) windows by character
+    pub actions: ActionReport,
+}
+
+fn within(a: i64, b: i64, tolerance: u32) -> bool {
+    (a - b).unsigned_abs() <= tolerance as u64
+}
+
+/// Whether `window` sits where `placement` would put it, give or take
+/// `tolerance` pixels on each coordinate
+fn matches_placement(window: &EveWindow, placement: &Placement, tolerance: u32) -> bool {
+    let Some((x, y, width, height)) = window.geometry else {
+        // Nothing to compare against, so don't guess
+        return true;
+    };
+
+    let position_ok = within(x as i64, placement.x as i64, tolerance)
+        && within(y as i64, placement.y as i64, tolerance);
+    let size_ok = placement.size.is_none_or(|(w, h)| {
+        within(width as i64, w as i64, tolerance) && within(height as i64, h as i64, tolerance)
+    });
+    position_ok && size_ok
+}
+
+fn is_off_monitors(window: &EveWindow, monitors: &[Monitor]) -> bool {
+    let Some((x, y, width, height)) = window.geometry else {
+        return false;
+    };
+    let centre_x = x as i64 + width as i64 / 2;
+    let centre_y = y as i64 + height as i64 / 2;
+    !monitors.is_empty() && monitor_at(monitors, centre_x as i32, centre_y as i32).is_none()
+}
+
+/// Compare every window against the stack plan. `minimized` lists the
+/// windows that are minimized but shouldn't be. Windows that are fine are
+/// left out of the result.
+pub fn diff(
+    windows: &[EveWindow],
+    monitors: &[Monitor],
+    config: &Config,
+    minimized: &[u64],
+) -> Vec<Repair> {
+    let plan = layout::plan_stack(windows, monitors, config);
+
+    windows
+        .iter()
+        .filter_map(|window| {
+            let planned = plan.iter().find(|p| p.window_id == window.id);
+            let mut drift = Vec::new();
+
+            if is_off_monitors(window, monitors) {
+                drift.push(Drift::OffMonitor);
+            }
+            if planned.is_some_and(|p| !matches_placement(window, p, config.heal_tolerance)) {
+                drift.push(Drift::Misplaced);
+            }
+            if minimized.contains(&window.id) {
+                drift.push(Drift::Minimized);
+            }
+            if drift.is_empty() {
+                return None;
+            }
+
+            // monitor_only leaves windows it considers placed out of the plan;
+            // one that's off every monitor still goes back to the first
+            let moved = drift.iter().any(|d| *d != Drift::Minimized);
+            let placement = planned.cloned().filter(|_| moved).or_else(|| {
+                let mon = monitors
+                    .first()
+                    .filter(|_| drift.contains(&Drift::OffMonitor))?;
+                let (x, y, _, _) = mon.usable_rect(config);
+                Some(Placement {
+                    window_id: window.id,
+                    title: window.title.clone(),
+                    monitor: Some(mon.name.clone()),
+                    x,
+                    y,
+                    size: None,
+                })
+            });
+
+            Some(Repair {
+                window_id: window.id,
+                title: window.title.clone(),
+                drift,
+                placement,
+            })
+        })
+        .collect()
+}
+
+fn repair(wm: &dyn WindowManager, repair: &Repair) -> Result<()> {
+    if repair.drift.contains(&Drift::Minimized) {
+        wm.restore_window(repair.window_id)?;
+    }
+    if let Some(placement) = &repair.placement {
+        wm.apply_placement(placement)?;
+    }
+    Ok(())
+}
+
+/// Re-apply the layout to just the windows that have drifted. A failure on
+/// one window is recorded and the rest are still repaired.
+pub fn heal(wm: &dyn WindowManager, windows: &[EveWindow], config: &Config) -> Result<HealReport> {
+    let monitors = wm.get_layout_monitors(config)?;
+
+    // With minimize_inactive only the active client is meant to be showing
+    let active = wm.get_active_window().ok();
+    let minimized: Vec<u64> = windows
+        .iter()
+        .filter(|w| !config.minimize_inactive || Some(w.id) == active)
+        .filter(|w| wm.is_minimized(w.id).unwrap_or(false))
+        .map(|w| w.id)
+        .collect();
+
+    let mut report = HealReport::default();
+    let repairs = diff(windows, &monitors, config, &minimized);
+    for window in windows {
+        if !repairs.iter().any(|r| r.window_id == window.id) {
+            report.actions.skip(&window.title);
+        }
+    }
+    for needed in repairs {
+        match repair(wm, &needed) {
+            Ok(()) => {
+                report.actions.succeed(&needed.title);
+                report.healed.push(needed);
+            }
+            Err(e) => report.actions.fail(&needed.title, &e),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_window_manager::MockWindowManager;
+
+    fn config() -> Config {
+        Config {
+            panel_height: 0,
+            eve_width: 1000,
+            ..Config::default()
+        }
+    }
+
+    fn monitor() -> Monitor {
+        Monitor {
+            name: "DP-1".to_string(),
+            width: 2000,
+            height: 1000,
+            ..Default::default()
+        }
+    }
+
+    /// Where the centered layout puts every window on `monitor()`
+    fn stacked(id: u64, title: &str) -> EveWindow {
+        EveWindow {
+            id,
+            title: title.to_string(),
+            monitor: Some("DP-1".to_string()),
+            geometry: Some((500, 0, 1000, 1000)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_heal_repairs_only_the_drifted_window() {
+        let mut drifted = stacked(2, "Alt");
+        // Nudged within tolerance: left alone
+        let nudged = EveWindow {
+            geometry: Some((504, 3, 1000, 1000)),
+            ..stacked(3, "Scout")
+        };
+        drifted.geometry = Some((620, 40, 1000, 1000));
+        let windows = vec![stacked(1, "Main"), drifted, nudged];
+        let wm = MockWindowManager::new(windows.clone()).with_monitors(vec![monitor()]);
+
+        let report = heal(&wm, &windows, &config()).unwrap();
+
+        assert_eq!(report.healed.len(), 1);
+        assert_eq!(report.healed[0].title, "Alt");
+        assert_eq!(report.healed[0].drift, vec![Drift::Misplaced]);
+        assert!(report.actions.failed.is_empty());
+        assert_eq!(report.actions.skipped, vec!["Main", "Scout"]);
+        assert_eq!(wm.calls(), ["place 2"]);
+        assert_eq!(wm.window(2).unwrap().geometry, Some((500, 0, 1000, 1000)));
+    }
+
+    #[test]
+    fn test_heal_restores_minimized_and_collects_errors() {
+        let lost = EveWindow {
+            geometry: Some((5000, 5000, 1000, 1000)),
+            ..stacked(3, "Scout")
+        };
+        let windows = vec![stacked(1, "Main"), stacked(2, "Alt"), lost];
+        let wm = MockWindowManager::new(windows.clone())
+            .with_monitors(vec![monitor()])
+            .with_minimized(1)
+            .with_failing(3);
+
+        let report = heal(&wm, &windows, &config()).unwrap();
+
+        assert_eq!(report.healed.len(), 1);
+        assert_eq!(report.healed[0].drift, vec![Drift::Minimized]);
+        assert_eq!(report.actions.failed.len(), 1);
+        assert_eq!(report.actions.failed[0].0, "Scout");
+        assert_eq!(wm.calls(), ["restore 1", "place 3"]);
+        assert!(wm.placements().is_empty());
+    }
+
+    #[test]
+    fn test_minimize_inactive_only_expects_active_window_showing() {
+        let config = Config {
+            minimize_inactive: true,
+            ..config()
+        };
+        let windows = vec![stacked(1, "Main"), stacked(2, "Alt"), stacked(3, "Scout")];
+        let wm = MockWindowManager::new(windows.clone())
+            .with_monitors(vec![monitor()])
+            .with_active(1)
+            .with_minimized(2)
+            .with_minimized(3);
+
+        let report = heal(&wm, &windows, &config).unwrap();
+        assert!(report.healed.is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::config::Config;
+use crate::window_manager::EveWindow;
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Run the user's post_stack_command through `sh -c`.
+///
+/// The stacked windows are passed two ways:
+/// - env: NICOTINE_WINDOW_COUNT, NICOTINE_WINDOW_IDS and NICOTINE_CHARACTERS
+///   (comma separated, in stack order)
+/// - stdin: a JSON array of {"id", "character", "monitor"} objects
+pub fn run_post_stack(command: &str, windows: &[EveWindow]) -> Result<ExitStatus> {
+    let ids: Vec<String> = windows.iter().map(|w| w.id.to_string()).collect();
+    let characters: Vec<&str> = windows.iter().map(|w| w.title.as_str()).collect();
+    let payload: Vec<serde_json::Value> = windows
+        .iter()
+        .map(|w| {
+            serde_json::json!({
+                "id": w.id,
+                "character": w.title,
+                "monitor": w.monitor,
+            })
+        })
+        .collect();
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("NICOTINE_WINDOW_COUNT", windows.len().to_string())
+        .env("NICOTINE_WINDOW_IDS", ids.join(","))
+        .env("NICOTINE_CHARACTERS", characters.join(","))
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run post_stack_command: {}", command))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The hook may not read stdin at all, so a broken pipe is fine
+        let _ = stdin.write_all(serde_json::to_string(&payload)?.as_bytes());
+    }
+
+    Ok(child.wait()?)
+}
+
+/// Run post_stack_command, if there is one, for the windows a stack placed.
+/// A hook that can't start or exits non-zero is only warned about.
+pub fn after_stack(config: &Config, stacked: &[EveWindow]) {
+    let Some(command) = &config.post_stack_command else {
+        return;
+    };
+    if stacked.is_empty() {
+        return;
+    }
+    match run_post_stack(command, stacked) {
+        Ok(status) if !status.success() => {
+            tracing::warn!("post_stack_command exited with {}", status);
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("{:#}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_post_stack_hook_receives_window_metadata() {
+        let dir = std::env::temp_dir().join(format!("nicotine-hook-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let env_out = dir.join("env");
+        let stdin_out = dir.join("stdin");
+
+        let windows = vec![
+            EveWindow {
+                id: 101,
+                title: "Main".to_string(),
+                monitor: Some("DP-1".to_string()),
+                ..Default::default()
+            },
+            EveWindow {
+                id: 202,
+                title: "Alt".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let command = format!(
+            "echo \"$NICOTINE_WINDOW_COUNT|$NICOTINE_WINDOW_IDS|$NICOTINE_CHARACTERS\" > {}; cat > {}",
+            env_out.display(),
+            stdin_out.display()
+        );
+        let status = run_post_stack(&command, &windows).unwrap();
+        assert!(status.success());
+
+        assert_eq!(
+            fs::read_to_string(&env_out).unwrap().trim(),
+            "2|101,202|Main,Alt"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&stdin_out).unwrap()).unwrap();
+        assert_eq!(json[0]["character"], "Main");
+        assert_eq!(json[0]["monitor"], "DP-1");
+        assert_eq!(json[1]["id"], 202);
+        assert!(json[1]["monitor"].is_null());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_post_stack_hook_failure_is_reported_not_raised() {
+        let status = run_post_stack("exit 3", &[]).unwrap();
+        assert_eq!(status.code(), Some(3));
+    }
+}
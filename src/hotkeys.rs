@@ -0,0 +1,541 @@
+use crate::config::Config;
+use crate::cycle_state::CycleState;
+use crate::keyboard_listener::KeyboardListener;
+use crate::wait;
+use crate::window_manager::WindowManager;
+use anyhow::{Context, Result};
+use evdev::{Device, InputEventKind, Key};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt as _, GrabMode, ModMask, Window};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    Super,
+}
+
+impl Modifier {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "ctrl" | "control" => Some(Modifier::Ctrl),
+            "shift" => Some(Modifier::Shift),
+            "alt" => Some(Modifier::Alt),
+            "super" | "meta" | "win" => Some(Modifier::Super),
+            _ => None,
+        }
+    }
+
+    /// Either side's key counts
+    fn of_key(key: Key) -> Option<Self> {
+        match key {
+            Key::KEY_LEFTCTRL | Key::KEY_RIGHTCTRL => Some(Modifier::Ctrl),
+            Key::KEY_LEFTSHIFT | Key::KEY_RIGHTSHIFT => Some(Modifier::Shift),
+            Key::KEY_LEFTALT | Key::KEY_RIGHTALT => Some(Modifier::Alt),
+            Key::KEY_LEFTMETA | Key::KEY_RIGHTMETA => Some(Modifier::Super),
+            _ => None,
+        }
+    }
+}
+
+/// A shortcut such as "ctrl+shift+f1": modifiers that must be held, then a
+/// key by its evdev name without the KEY_ prefix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Combo {
+    pub modifiers: BTreeSet<Modifier>,
+    pub key: u16,
+}
+
+impl FromStr for Combo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let lower = s.trim().to_lowercase();
+        let mut parts: Vec<&str> = lower.split('+').map(str::trim).collect();
+        let key_name = parts.pop().filter(|k| !k.is_empty()).context("No key")?;
+
+        let modifiers = parts
+            .iter()
+            .map(|m| Modifier::parse(m).with_context(|| format!("Unknown modifier {:?}", m)))
+            .collect::<Result<_>>()?;
+        let key = Key::from_str(&format!("KEY_{}", key_name.to_uppercase()))
+            .map_err(|_| anyhow::anyhow!("Unknown key {:?}", key_name))?
+            .code();
+        Ok(Self { modifiers, key })
+    }
+}
+
+/// What a hotkey does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    CycleForward,
+    CycleBackward,
+    StackWindows,
+    ActivateCharacter(String),
+    FocusLast,
+    /// Trade places with primary_character: the named client, or the
+    /// active one
+    SwapWithPrimary(Option<String>),
+    /// Keep the named client, or the active one, above every other window
+    Pin(Option<String>),
+    Unpin(Option<String>),
+    /// Turn the combat lock on or off, see focus_guard
+    CombatLock,
+}
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s {
+            "cycle_forward" => Ok(Action::CycleForward),
+            "cycle_backward" => Ok(Action::CycleBackward),
+            "stack_windows" => Ok(Action::StackWindows),
+            "focus_last" => Ok(Action::FocusLast),
+            "swap_with_primary" => Ok(Action::SwapWithPrimary(None)),
+            "pin" => Ok(Action::Pin(None)),
+            "unpin" => Ok(Action::Unpin(None)),
+            "combat_lock" => Ok(Action::CombatLock),
+            _ => match s.strip_prefix("activate_character ") {
+                Some(name) if !name.trim().is_empty() => {
+                    Ok(Action::ActivateCharacter(name.trim().to_string()))
+                }
+                _ => match s.split_once(' ') {
+                    Some(("swap_with_primary", name)) if !name.trim().is_empty() => {
+                        Ok(Action::SwapWithPrimary(Some(name.trim().to_string())))
+                    }
+                    Some(("pin", name)) if !name.trim().is_empty() => {
+                        Ok(Action::Pin(Some(name.trim().to_string())))
+                    }
+                    Some(("unpin", name)) if !name.trim().is_empty() => {
+                        Ok(Action::Unpin(Some(name.trim().to_string())))
+                    }
+                    _ => anyhow::bail!(
+                        "Unknown hotkey action {:?} (use cycle_forward, cycle_backward, \
+                         stack_windows, activate_character <name>, focus_last, \
+                         swap_with_primary, pin, unpin or combat_lock)",
+                        s
+                    ),
+                },
+            },
+        }
+    }
+}
+
+/// The [hotkeys] table, parsed
+#[derive(Debug, Clone)]
+pub struct Hotkeys {
+    bindings: Vec<(Combo, Action)>,
+}
+
+impl Hotkeys {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let bindings = config
+            .hotkeys
+            .iter()
+            .map(|(combo, action)| {
+                let parsed = combo
+                    .parse::<Combo>()
+                    .and_then(|c| Ok((c, action.parse::<Action>()?)));
+                parsed.with_context(|| format!("Invalid hotkey {} = {:?}", combo, action))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut hotkeys = Self { bindings };
+        for (combo, character) in &config.character_hotkeys {
+            let parsed = combo
+                .parse::<Combo>()
+                .with_context(|| format!("Invalid character hotkey {} = {:?}", combo, character))?;
+            if character.trim().is_empty() {
+                anyhow::bail!("Character hotkey {} has no character", combo);
+            }
+            if hotkeys.bindings.iter().any(|(c, _)| *c == parsed) {
+                anyhow::bail!("Hotkey {} is bound twice", combo);
+            }
+            hotkeys.bindings.push((
+                parsed,
+                Action::ActivateCharacter(character.trim().to_string()),
+            ));
+        }
+        Ok(hotkeys)
+    }
+
+    /// The action for `key` going down while exactly `held` are held
+    pub fn action_for(&self, key: u16, held: &BTreeSet<Modifier>) -> Option<&Action> {
+        self.bindings
+            .iter()
+            .find(|(combo, _)| combo.key == key && combo.modifiers == *held)
+            .map(|(_, action)| action)
+    }
+
+    /// Run actions as their shortcuts are pressed, on a background thread.
+    /// Under X11 the keys are grabbed so the game doesn't also see them;
+    /// Wayland compositors don't allow that, so there the keyboard is read
+    /// passively through evdev.
+    pub fn spawn(
+        self,
+        wm: Arc<dyn WindowManager>,
+        state: Arc<Mutex<CycleState>>,
+        config: Config,
+    ) -> Result<RunningHotkeys> {
+        let device_path = config.keyboard_device_path.clone();
+        let on_x11 =
+            std::env::var_os("WAYLAND_DISPLAY").is_none() && std::env::var_os("DISPLAY").is_some();
+        let config = Arc::new(RwLock::new(config));
+        let mut on_press = {
+            let config = Arc::clone(&config);
+            move |action: &Action| {
+                let config = config.read().unwrap().clone();
+                if let Err(e) = run(action, &*wm, &state, &config) {
+                    tracing::error!("Hotkey {:?} failed: {}", action, e);
+                }
+            }
+        };
+
+        let x11 = if on_x11 {
+            let (conn, screen_num) = x11rb::connect(None).context("Failed to connect to X11")?;
+            let root = conn.setup().roots[screen_num].root;
+            self.grab_x11(&conn, root)?;
+            Some((Arc::new(conn), root))
+        } else {
+            None
+        };
+        let hotkeys = Arc::new(RwLock::new(self));
+
+        let listening = Arc::clone(&hotkeys);
+        match &x11 {
+            Some((conn, _)) => {
+                let conn = Arc::clone(conn);
+                std::thread::spawn(move || listen_x11(&listening, &conn, &mut on_press));
+            }
+            None => {
+                let device = KeyboardListener::find_keyboard_device(device_path.as_deref())
+                    .context("Failed to find keyboard device for hotkeys")?;
+                std::thread::spawn(move || listen_evdev(&listening, device, &mut on_press));
+            }
+        }
+        Ok(RunningHotkeys {
+            hotkeys,
+            config,
+            x11,
+        })
+    }
+
+    /// XGrabKey every combo on the root window. X keycodes are evdev codes
+    /// offset by 8. Each is also grabbed with Caps Lock and Num Lock on.
+    fn grab_x11(&self, conn: &RustConnection, root: Window) -> Result<()> {
+        for (combo, _) in &self.bindings {
+            let keycode = u8::try_from(combo.key + 8).context("Key has no X keycode")?;
+            for lock in locks() {
+                conn.grab_key(
+                    true,
+                    root,
+                    x11_mask(&combo.modifiers) | lock,
+                    keycode,
+                    GrabMode::ASYNC,
+                    GrabMode::ASYNC,
+                )?
+                .check()
+                .with_context(|| {
+                    format!(
+                        "Key code {} is already grabbed by another program",
+                        combo.key
+                    )
+                })?;
+            }
+        }
+        conn.flush()?;
+        tracing::info!("Grabbed {} hotkeys", self.bindings.len());
+        Ok(())
+    }
+
+    fn ungrab_x11(&self, conn: &RustConnection, root: Window) -> Result<()> {
+        for (combo, _) in &self.bindings {
+            let Ok(keycode) = u8::try_from(combo.key + 8) else {
+                continue;
+            };
+            for lock in locks() {
+                conn.ungrab_key(keycode, root, x11_mask(&combo.modifiers) | lock)?;
+            }
+        }
+        conn.flush()?;
+        Ok(())
+    }
+}
+
+/// Hotkeys that are being listened for, which a config reload can change
+pub struct RunningHotkeys {
+    hotkeys: Arc<RwLock<Hotkeys>>,
+    config: Arc<RwLock<Config>>,
+    x11: Option<(Arc<RustConnection>, Window)>,
+}
+
+impl RunningHotkeys {
+    /// Switch to the [hotkeys] and settings in `config`. Under X11 the old
+    /// shortcuts are released and the new ones grabbed.
+    pub fn reload(&self, config: &Config) -> Result<()> {
+        let new = Hotkeys::from_config(config)?;
+        let mut hotkeys = self.hotkeys.write().unwrap();
+        if let Some((conn, root)) = &self.x11 {
+            if hotkeys.bindings != new.bindings {
+                hotkeys.ungrab_x11(conn, *root)?;
+                new.grab_x11(conn, *root)?;
+            }
+        }
+        *hotkeys = new;
+        *self.config.write().unwrap() = config.clone();
+        Ok(())
+    }
+}
+
+fn listen_x11(
+    hotkeys: &RwLock<Hotkeys>,
+    conn: &RustConnection,
+    on_press: &mut impl FnMut(&Action),
+) {
+    loop {
+        let event = match conn.wait_for_event() {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::error!("Hotkey listener error: {}", e);
+                return;
+            }
+        };
+        let Event::KeyPress(press) = event else {
+            continue;
+        };
+        let held = [
+            (ModMask::CONTROL, Modifier::Ctrl),
+            (ModMask::SHIFT, Modifier::Shift),
+            (ModMask::M1, Modifier::Alt),
+            (ModMask::M4, Modifier::Super),
+        ]
+        .into_iter()
+        .filter(|(mask, _)| u16::from(press.state) & u16::from(*mask) != 0)
+        .map(|(_, modifier)| modifier)
+        .collect();
+        let key = u16::from(press.detail).saturating_sub(8);
+        let action = hotkeys.read().unwrap().action_for(key, &held).cloned();
+        if let Some(action) = action {
+            on_press(&action);
+        }
+    }
+}
+
+fn listen_evdev(hotkeys: &RwLock<Hotkeys>, mut device: Device, on_press: &mut impl FnMut(&Action)) {
+    tracing::info!(
+        "Listening for {} hotkeys",
+        hotkeys.read().unwrap().bindings.len()
+    );
+    let mut held = BTreeSet::new();
+    loop {
+        let events = match device.fetch_events() {
+            Ok(events) => events.collect::<Vec<_>>(),
+            Err(e) => {
+                tracing::error!("Hotkey listener error: {}", e);
+                return;
+            }
+        };
+        for event in events {
+            let InputEventKind::Key(key) = event.kind() else {
+                continue;
+            };
+            if let Some(modifier) = Modifier::of_key(key) {
+                if event.value() == 0 {
+                    held.remove(&modifier);
+                } else {
+                    held.insert(modifier);
+                }
+                continue;
+            }
+            // 1 is the press; 2 is autorepeat, which shouldn't re-fire
+            if event.value() != 1 {
+                continue;
+            }
+            let action = hotkeys
+                .read()
+                .unwrap()
+                .action_for(key.code(), &held)
+                .cloned();
+            if let Some(action) = action {
+                on_press(&action);
+            }
+        }
+    }
+}
+
+/// Caps Lock and Num Lock in every combination, since X counts those as
+/// held modifiers when matching a grab
+fn locks() -> [ModMask; 4] {
+    [
+        ModMask::from(0u16),
+        ModMask::LOCK,
+        ModMask::M2,
+        ModMask::LOCK | ModMask::M2,
+    ]
+}
+
+fn x11_mask(modifiers: &BTreeSet<Modifier>) -> ModMask {
+    modifiers
+        .iter()
+        .fold(ModMask::from(0u16), |mask, modifier| {
+            mask | match modifier {
+                Modifier::Ctrl => ModMask::CONTROL,
+                Modifier::Shift => ModMask::SHIFT,
+                Modifier::Alt => ModMask::M1,
+                Modifier::Super => ModMask::M4,
+            }
+        })
+}
+
+fn run(
+    action: &Action,
+    wm: &dyn WindowManager,
+    state: &Mutex<CycleState>,
+    config: &Config,
+) -> Result<()> {
+    let skip = config.primary_character.as_deref();
+    match action {
+        Action::CycleForward | Action::CycleBackward => {
+            let mut state = state.lock().unwrap();
+            if let Ok(active) = wm.get_active_window() {
+                state.sync_with_active(active);
+            }
+            if *action == Action::CycleForward {
+                state.cycle_forward(wm, config.minimize_inactive, skip)
+            } else {
+                state.cycle_backward(wm, config.minimize_inactive, skip)
+            }
+        }
+        Action::StackWindows => {
+            let windows = wm.get_eve_windows()?;
+            wait::stack_in_game(wm, &windows, config)?.log("Stacked");
+            Ok(())
+        }
+        Action::ActivateCharacter(name) => {
+            let mut state = state.lock().unwrap();
+            state.activate(name, wm, config.minimize_inactive)
+        }
+        Action::FocusLast => {
+            let mut state = state.lock().unwrap();
+            if let Ok(active) = wm.get_active_window() {
+                state.sync_with_active(active);
+            }
+            state.focus_last(wm, config.minimize_inactive)
+        }
+        Action::SwapWithPrimary(name) => {
+            let names: Vec<String> = name.iter().cloned().collect();
+            wm.swap_windows(&names, config)
+        }
+        Action::Pin(name) => wm.pin_character(name.as_deref(), true).map(drop),
+        Action::Unpin(name) => wm.pin_character(name.as_deref(), false).map(drop),
+        Action::CombatLock => {
+            let mut state = state.lock().unwrap();
+            let on = !state.combat_lock();
+            state.set_combat_lock(on);
+            match (on, config.focus_guard) {
+                (true, true) => tracing::info!("Combat lock on"),
+                (true, false) => {
+                    tracing::warn!("Combat lock on, but focus_guard is off in config.toml")
+                }
+                (false, _) => tracing::info!("Combat lock off"),
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hotkeys_parse_and_match_exact_modifiers() {
+        let config = Config {
+            hotkeys: [
+                ("ctrl+f1", "cycle_forward"),
+                ("ctrl+shift+F1", "cycle_backward"),
+                ("super+s", "stack_windows"),
+                ("alt+2", "activate_character Scout Alt"),
+                ("alt+s", "swap_with_primary"),
+                ("alt+grave", "focus_last"),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+            ..Config::default()
+        };
+        let hotkeys = Hotkeys::from_config(&config).unwrap();
+
+        let ctrl = BTreeSet::from([Modifier::Ctrl]);
+        let ctrl_shift = BTreeSet::from([Modifier::Ctrl, Modifier::Shift]);
+        let f1 = Key::KEY_F1.code();
+        assert_eq!(hotkeys.action_for(f1, &ctrl), Some(&Action::CycleForward));
+        assert_eq!(
+            hotkeys.action_for(f1, &ctrl_shift),
+            Some(&Action::CycleBackward)
+        );
+        assert_eq!(hotkeys.action_for(f1, &BTreeSet::new()), None);
+        assert_eq!(
+            hotkeys.action_for(Key::KEY_2.code(), &BTreeSet::from([Modifier::Alt])),
+            Some(&Action::ActivateCharacter("Scout Alt".to_string()))
+        );
+        assert_eq!(
+            hotkeys.action_for(Key::KEY_S.code(), &BTreeSet::from([Modifier::Alt])),
+            Some(&Action::SwapWithPrimary(None))
+        );
+        assert_eq!(
+            hotkeys.action_for(Key::KEY_GRAVE.code(), &BTreeSet::from([Modifier::Alt])),
+            Some(&Action::FocusLast)
+        );
+        assert_eq!(
+            "swap_with_primary Scout".parse::<Action>().unwrap(),
+            Action::SwapWithPrimary(Some("Scout".to_string()))
+        );
+        assert_eq!("pin".parse::<Action>().unwrap(), Action::Pin(None));
+        assert_eq!("combat_lock".parse::<Action>().unwrap(), Action::CombatLock);
+        assert_eq!(
+            "unpin Scout".parse::<Action>().unwrap(),
+            Action::Unpin(Some("Scout".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_character_hotkeys_activate_characters() {
+        let mut config = Config {
+            hotkeys: [("ctrl+f1".to_string(), "cycle_forward".to_string())].into(),
+            character_hotkeys: [("F1".to_string(), "Main Char".to_string())].into(),
+            ..Config::default()
+        };
+        let hotkeys = Hotkeys::from_config(&config).unwrap();
+        assert_eq!(
+            hotkeys.action_for(Key::KEY_F1.code(), &BTreeSet::new()),
+            Some(&Action::ActivateCharacter("Main Char".to_string()))
+        );
+
+        config
+            .character_hotkeys
+            .insert("Ctrl+F1".to_string(), "Alt 2".to_string());
+        assert!(Hotkeys::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_bad_hotkeys_are_rejected() {
+        assert!("hyper+f1".parse::<Combo>().is_err());
+        assert!("ctrl+nosuchkey".parse::<Combo>().is_err());
+        assert!("ctrl+".parse::<Combo>().is_err());
+        assert!("dance".parse::<Action>().is_err());
+        assert!("activate_character ".parse::<Action>().is_err());
+        assert_eq!(
+            "ctrl+tab".parse::<Combo>().unwrap().key,
+            Key::KEY_TAB.code()
+        );
+    }
+}
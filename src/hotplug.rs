@@ -0,0 +1,161 @@
+use crate::window_manager::{Monitor, WindowManager};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often monitors are re-read on backends that can't report changes
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Changes arriving within this long of each other are one change: a
+/// hotplug is several RandR events, and compositors move outputs around for
+/// a moment after one
+const SETTLE: Duration = Duration::from_secs(1);
+
+/// How the monitors differ from the last time they were read
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MonitorChange {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl MonitorChange {
+    /// e.g. "DP-2 removed", or "monitors reconfigured" for a mode change
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.added.is_empty() {
+            parts.push(format!("{} added", self.added.join(", ")));
+        }
+        if !self.removed.is_empty() {
+            parts.push(format!("{} removed", self.removed.join(", ")));
+        }
+        if parts.is_empty() {
+            return "monitors reconfigured".to_string();
+        }
+        parts.join("; ")
+    }
+}
+
+/// What a layout depends on: each output's name and area. Focus and struts
+/// change without anything being plugged in.
+fn outputs(monitors: &[Monitor]) -> Vec<(&str, i32, i32, u32, u32)> {
+    let mut outputs: Vec<_> = monitors
+        .iter()
+        .map(|m| (m.name.as_str(), m.x, m.y, m.width, m.height))
+        .collect();
+    outputs.sort();
+    outputs
+}
+
+/// The change from `before` to `after`, or None if the layout-relevant
+/// parts are the same
+pub fn diff(before: &[Monitor], after: &[Monitor]) -> Option<MonitorChange> {
+    if outputs(before) == outputs(after) {
+        return None;
+    }
+    let names =
+        |monitors: &[Monitor]| -> Vec<String> { monitors.iter().map(|m| m.name.clone()).collect() };
+    let (before, after) = (names(before), names(after));
+    Some(MonitorChange {
+        added: after
+            .iter()
+            .filter(|n| !before.contains(n))
+            .cloned()
+            .collect(),
+        removed: before
+            .iter()
+            .filter(|n| !after.contains(n))
+            .cloned()
+            .collect(),
+    })
+}
+
+/// Call `on_change` on a background thread whenever the monitors change,
+/// from the backend's events where it has them and by polling otherwise
+pub fn spawn(
+    wm: Arc<dyn WindowManager>,
+    mut on_change: impl FnMut(&MonitorChange) + Send + 'static,
+) {
+    let events = match wm.subscribe_monitor_events() {
+        Ok(events) => events,
+        Err(e) => {
+            tracing::warn!("Monitor event subscription failed, polling instead: {}", e);
+            None
+        }
+    };
+
+    std::thread::spawn(move || {
+        let mut last = wm.get_monitors().unwrap_or_default();
+        loop {
+            if !wait(events.as_ref()) {
+                tracing::warn!("Monitor events stopped");
+                return;
+            }
+            wm.invalidate_monitors();
+            // Nothing to place windows on while every output is gone, and a
+            // failed read isn't a change
+            let monitors = match wm.get_monitors() {
+                Ok(monitors) if !monitors.is_empty() => monitors,
+                _ => continue,
+            };
+            if let Some(change) = diff(&last, &monitors) {
+                on_change(&change);
+            }
+            last = monitors;
+        }
+    });
+}
+
+/// Block until the monitors may have changed and have settled. False if
+/// the event stream ended.
+fn wait(events: Option<&Receiver<()>>) -> bool {
+    let Some(events) = events else {
+        std::thread::sleep(POLL_INTERVAL);
+        return true;
+    };
+    if events.recv().is_err() {
+        return false;
+    }
+    loop {
+        match events.recv_timeout(SETTLE) {
+            Ok(()) => {}
+            Err(RecvTimeoutError::Timeout) => return true,
+            Err(RecvTimeoutError::Disconnected) => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(name: &str, x: i32, width: u32) -> Monitor {
+        Monitor {
+            name: name.to_string(),
+            x,
+            width,
+            height: 1080,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_only_outputs_and_their_areas_count_as_changes() {
+        let docked = vec![monitor("eDP-1", 0, 1920), monitor("DP-1", 1920, 2560)];
+
+        let mut focused = docked.clone();
+        focused[1].focused = true;
+        assert_eq!(diff(&docked, &focused), None);
+
+        let undocked = vec![monitor("eDP-1", 0, 1920)];
+        let change = diff(&docked, &undocked).unwrap();
+        assert_eq!(change.removed, vec!["DP-1".to_string()]);
+        assert_eq!(change.describe(), "DP-1 removed");
+        assert_eq!(diff(&undocked, &docked).unwrap().describe(), "DP-1 added");
+
+        let resized = vec![monitor("eDP-1", 0, 1280), monitor("DP-1", 1280, 2560)];
+        assert_eq!(
+            diff(&docked, &resized).unwrap().describe(),
+            "monitors reconfigured"
+        );
+    }
+}
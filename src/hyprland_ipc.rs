@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+/// Hyprland's sockets for this session. Requests go to .socket.sock, which
+/// answers one request per connection; events stream from .socket2.sock.
+#[derive(Debug, Clone)]
+pub struct HyprlandIpc {
+    dir: PathBuf,
+}
+
+impl HyprlandIpc {
+    /// The sockets live under $XDG_RUNTIME_DIR/hypr since Hyprland 0.40,
+    /// and under /tmp/hypr before that
+    pub fn locate() -> Result<Self> {
+        let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE")
+            .context("HYPRLAND_INSTANCE_SIGNATURE is not set. Make sure you're running Hyprland")?;
+
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR").map(PathBuf::from);
+        let candidates: Vec<PathBuf> = runtime_dir
+            .into_iter()
+            .chain([PathBuf::from("/tmp")])
+            .map(|dir| dir.join("hypr").join(&signature))
+            .collect();
+
+        let dir = candidates
+            .iter()
+            .find(|dir| dir.join(".socket.sock").exists())
+            .or(candidates.last())
+            .cloned()
+            .context("No Hyprland socket directory")?;
+        Ok(Self { dir })
+    }
+
+    /// Send one request (what you'd pass to hyprctl, e.g. "dispatch
+    /// focuswindow address:0x1") and return Hyprland's reply
+    pub fn request(&self, request: &str) -> Result<String> {
+        let mut stream = UnixStream::connect(self.dir.join(".socket.sock"))
+            .context("Failed to connect to Hyprland")?;
+        stream.write_all(request.as_bytes())?;
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        tracing::debug!("hyprland {:?} -> {} bytes", request, reply.len());
+        tracing::trace!("hyprland reply: {}", reply.trim_end());
+        Ok(reply)
+    }
+
+    /// A query with JSON output, e.g. "clients"
+    pub fn json(&self, query: &str) -> Result<Value> {
+        let reply = self.request(&format!("j/{}", query))?;
+        serde_json::from_str(&reply)
+            .with_context(|| format!("Failed to parse Hyprland {} reply", query))
+    }
+
+    pub fn dispatch(&self, dispatcher: &str, args: &str) -> Result<String> {
+        self.request(&format!("dispatch {} {}", dispatcher, args))
+    }
+
+    /// Several requests in one round trip, as `hyprctl --batch` sends them.
+    /// The replies come back run together.
+    pub fn batch(&self, requests: &[String]) -> Result<String> {
+        self.request(&format!("[[BATCH]]{}", requests.join(";")))
+    }
+
+    /// The event socket (openwindow, closewindow, activewindowv2, ...)
+    pub fn events(&self) -> Result<UnixStream> {
+        UnixStream::connect(self.dir.join(".socket2.sock"))
+            .context("Failed to connect to Hyprland event socket")
+    }
+}
+
+/// Whether every dispatch in a batch reply answered "ok"
+pub fn all_ok(reply: &str) -> bool {
+    !reply.trim().is_empty() && reply.replace("ok", "").trim().is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn test_request_reads_reply_until_close() {
+        let dir = std::env::temp_dir().join(format!("nicotine-hypr-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let listener = UnixListener::bind(dir.join(".socket.sock")).unwrap();
+
+        let hyprland = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 64];
+            let n = stream.read(&mut request).unwrap();
+            assert_eq!(&request[..n], b"j/clients");
+            stream.write_all(br#"[{"address":"0x1"}]"#).unwrap();
+        });
+
+        let ipc = HyprlandIpc { dir: dir.clone() };
+        let clients = ipc.json("clients").unwrap();
+        hyprland.join().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(clients[0]["address"], "0x1");
+    }
+
+    #[test]
+    fn test_only_ok_replies_count_as_success() {
+        assert!(all_ok("okokok"));
+        assert!(all_ok("ok\n\nok\n\n"));
+        assert!(!all_ok("okWindow is fullscreenok"));
+        assert!(!all_ok(""));
+    }
+}
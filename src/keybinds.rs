@@ -0,0 +1,91 @@
+use crate::config::Config;
+use std::sync::{Arc, RwLock};
+
+/// What the mouse and keyboard listeners react to, and how a press cycles.
+/// Swapped as a whole when the daemon switches profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keybinds {
+    pub mouse_enabled: bool,
+    pub forward_button: u16,
+    pub backward_button: u16,
+    pub keyboard_enabled: bool,
+    pub forward_key: u16,
+    pub backward_key: u16,
+    pub modifier_key: Option<u16>,
+    pub minimize_inactive: bool,
+    pub primary_character: Option<String>,
+}
+
+impl Keybinds {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            mouse_enabled: config.enable_mouse_buttons,
+            forward_button: config.forward_button,
+            backward_button: config.backward_button,
+            keyboard_enabled: config.enable_keyboard_buttons,
+            forward_key: config.forward_key,
+            backward_key: config.backward_key,
+            modifier_key: config.modifier_key,
+            minimize_inactive: config.minimize_inactive,
+            primary_character: config.primary_character.clone(),
+        }
+    }
+}
+
+/// Keybinds shared between the daemon and its listener threads. Listeners
+/// read them on every event, so a switch applies without reopening devices.
+pub type SharedKeybinds = Arc<RwLock<Keybinds>>;
+
+pub fn shared(config: &Config) -> SharedKeybinds {
+    Arc::new(RwLock::new(Keybinds::from_config(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+display_width = 2560
+display_height = 1440
+panel_height = 0
+eve_width = 1382
+eve_height = 1440
+overlay_x = 10.0
+overlay_y = 10.0
+forward_key = 15
+backward_key = 15
+modifier_key = 42
+
+[profiles.solo]
+enable_mouse_buttons = false
+minimize_inactive = true
+
+[profiles.fleet]
+forward_key = 59
+backward_key = 60
+modifier_key = 29
+"#;
+
+    #[test]
+    fn test_switching_profile_swaps_active_keybinds() {
+        let solo = Config::from_toml_str(CONFIG, Some("solo")).unwrap();
+        let fleet = Config::from_toml_str(CONFIG, Some("fleet")).unwrap();
+
+        let keybinds = shared(&solo);
+        // What a listener thread holds
+        let listener = Arc::clone(&keybinds);
+        assert_eq!(listener.read().unwrap().forward_key, 15);
+        assert!(!listener.read().unwrap().mouse_enabled);
+
+        *keybinds.write().unwrap() = Keybinds::from_config(&fleet);
+
+        let active = listener.read().unwrap().clone();
+        assert_eq!(active, Keybinds::from_config(&fleet));
+        assert_eq!(
+            (active.forward_key, active.backward_key, active.modifier_key),
+            (59, 60, Some(29))
+        );
+        assert!(active.mouse_enabled);
+        assert!(!active.minimize_inactive);
+    }
+}
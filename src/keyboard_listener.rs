@@ -1,5 +1,6 @@
 use crate::config::Config;
 use crate::cycle_state::CycleState;
+use crate::keybinds::SharedKeybinds;
 use crate::window_manager::WindowManager;
 use anyhow::{Context, Result};
 use evdev::{Device, InputEventKind, Key};
@@ -16,12 +17,12 @@ impl KeyboardListener {
     }
 
     /// Find keyboard device by looking for devices with standard keyboard keys
-    fn find_keyboard_device(configured_path: Option<&str>) -> Result<Device> {
+    pub(crate) fn find_keyboard_device(configured_path: Option<&str>) -> Result<Device> {
         if let Some(path_str) = configured_path {
             let path = Path::new(path_str);
             match Device::open(path) {
                 Ok(device) => {
-                    println!(
+                    tracing::info!(
                         "Using configured keyboard device {} ({})",
                         device.name().unwrap_or("Unknown"),
                         path.display()
@@ -29,11 +30,12 @@ impl KeyboardListener {
                     return Ok(device);
                 }
                 Err(e) => {
-                    eprintln!(
-                        "Warning: Failed to open configured keyboard device '{}': {}",
-                        path_str, e
+                    tracing::warn!(
+                        "Failed to open configured keyboard device '{}': {}",
+                        path_str,
+                        e
                     );
-                    eprintln!("Falling back to automatic device detection...");
+                    tracing::info!("Falling back to automatic device detection...");
                 }
             }
         }
@@ -52,7 +54,7 @@ impl KeyboardListener {
                                     || keys.contains(Key::KEY_LEFTSHIFT)
                                     || keys.contains(Key::KEY_Z)
                             }) {
-                                println!(
+                                tracing::info!(
                                     "Found keyboard device: {} ({})",
                                     device.name().unwrap_or("Unknown"),
                                     path.display()
@@ -68,52 +70,35 @@ impl KeyboardListener {
         anyhow::bail!("No keyboard device found in /dev/input")
     }
 
-    /// Run the keyboard event listener in a background thread
+    /// Run the keyboard event listener in a background thread. Keys are read
+    /// from `keybinds` on every event, so a profile switch applies at once.
     pub fn spawn(
         &self,
         wm: Arc<dyn WindowManager>,
         state: Arc<Mutex<CycleState>>,
+        keybinds: SharedKeybinds,
     ) -> Result<std::thread::JoinHandle<()>> {
         if !self.config.enable_keyboard_buttons {
             anyhow::bail!("Keyboard buttons are disabled in config");
         }
 
-        let forward_key = self.config.forward_key;
-        let backward_key = self.config.backward_key;
-        let modifier_key = self.config.modifier_key;
         let keyboard_device_path = self.config.keyboard_device_path.clone();
-        let minimize_inactive = self.config.minimize_inactive;
-        let primary_character = self.config.primary_character.clone();
 
         let handle = std::thread::spawn(move || {
-            match Self::run_listener(
-                wm,
-                state,
-                forward_key,
-                backward_key,
-                modifier_key,
-                keyboard_device_path,
-                minimize_inactive,
-                primary_character,
-            ) {
-                Ok(_) => println!("Keyboard listener stopped"),
-                Err(e) => println!("Keyboard listener error: {}", e),
+            match Self::run_listener(wm, state, keybinds, keyboard_device_path) {
+                Ok(_) => tracing::info!("Keyboard listener stopped"),
+                Err(e) => tracing::error!("Keyboard listener error: {}", e),
             }
         });
 
         Ok(handle)
     }
 
-    #[allow(clippy::too_many_arguments)]
     fn run_listener(
         wm: Arc<dyn WindowManager>,
         state: Arc<Mutex<CycleState>>,
-        forward_key: u16,
-        backward_key: u16,
-        modifier_key: Option<u16>,
+        keybinds: SharedKeybinds,
         keyboard_device_path: Option<String>,
-        minimize_inactive: bool,
-        primary_character: Option<String>,
     ) -> Result<()> {
         let mut device = Self::find_keyboard_device(keyboard_device_path.as_deref()).context(
             "Failed to find keyboard device. Make sure you have permission to read /dev/input/event*",
@@ -122,40 +107,55 @@ impl KeyboardListener {
         // DON'T grab the device - we only want to passively listen to events
         // Grabbing would prevent normal keyboard usage!
 
-        println!(
-            "Listening for keyboard keys: forward={} backward={}",
-            forward_key, backward_key
-        );
+        {
+            let binds = keybinds.read().unwrap();
+            tracing::info!(
+                "Listening for keyboard keys: forward={} backward={}",
+                binds.forward_key,
+                binds.backward_key
+            );
+        }
         let mut modifier_pressed = false;
 
         loop {
             for event in device.fetch_events()? {
                 if let InputEventKind::Key(key) = event.kind() {
+                    let binds = keybinds.read().unwrap().clone();
+                    if !binds.keyboard_enabled {
+                        continue;
+                    }
                     let code = key.code();
                     //let mut modifier_pressed = false;
-                    if let Some(mod_key) = modifier_key {
+                    if let Some(mod_key) = binds.modifier_key {
                         if code == mod_key {
-                            println!("Modifier Pressed");
+                            tracing::debug!("Modifier Pressed");
                             modifier_pressed = event.value() != 0;
                         }
                     }
                     //print(code);
                     if event.value() != 0 {
+                        let skip = binds.primary_character.as_deref();
                         // Have to check modifier + backwards first, otherwise if backward == forward it ignores the modifier flag
-                        if code == backward_key && modifier_pressed {
-                            println!("Backward + Modifier button pressed");
-                            if let Err(e) = Self::cycle_backward(&wm, &state, minimize_inactive, primary_character.as_deref()) {
-                                eprintln!("Failed to cycle backward: {}", e);
+                        if code == binds.backward_key && modifier_pressed {
+                            tracing::debug!("Backward + Modifier button pressed");
+                            if let Err(e) =
+                                Self::cycle_backward(&wm, &state, binds.minimize_inactive, skip)
+                            {
+                                tracing::error!("Failed to cycle backward: {}", e);
                             }
-                        } else if code == forward_key {
-                            println!("Forward button pressed");
-                            if let Err(e) = Self::cycle_forward(&wm, &state, minimize_inactive, primary_character.as_deref()) {
-                                eprintln!("Failed to cycle forward: {}", e);
+                        } else if code == binds.forward_key {
+                            tracing::debug!("Forward button pressed");
+                            if let Err(e) =
+                                Self::cycle_forward(&wm, &state, binds.minimize_inactive, skip)
+                            {
+                                tracing::error!("Failed to cycle forward: {}", e);
                             }
-                        } else if code == backward_key {
-                            println!("Backward button pressed");
-                            if let Err(e) = Self::cycle_backward(&wm, &state, minimize_inactive, primary_character.as_deref()) {
-                                eprintln!("Failed to cycle backward: {}", e);
+                        } else if code == binds.backward_key {
+                            tracing::debug!("Backward button pressed");
+                            if let Err(e) =
+                                Self::cycle_backward(&wm, &state, binds.minimize_inactive, skip)
+                            {
+                                tracing::error!("Failed to cycle backward: {}", e);
                             }
                         }
                     }
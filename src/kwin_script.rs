@@ -0,0 +1,222 @@
+use crate::logging::LoggedOutput;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+
+/// Helpers every script gets, papering over the KWin 5 / KWin 6 API split
+const PRELUDE: &str = r#"
+function nicotineWindows() {
+    return workspace.windowList ? workspace.windowList() : workspace.clientList();
+}
+function nicotineFind(id) {
+    var list = nicotineWindows();
+    for (var i = 0; i < list.length; i++) {
+        if (String(list[i].internalId) === id) return list[i];
+    }
+    return null;
+}
+function nicotineActive() {
+    return "activeWindow" in workspace ? workspace.activeWindow : workspace.activeClient;
+}
+"#;
+
+/// How long to wait for a query's script to call back
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where query scripts send their results, on this process's own
+/// connection to the session bus
+const REPLY_PATH: &str = "/org/nicotine/KWinReply";
+const REPLY_INTERFACE: &str = "org.nicotine.KWinReply";
+
+static SCRIPT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Queries waiting on their script, by token
+static PENDING: Mutex<Option<HashMap<String, Sender<String>>>> = Mutex::new(None);
+
+fn gdbus(object_path: &str, method: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest",
+            "org.kde.KWin",
+            "--object-path",
+            object_path,
+            "--method",
+            method,
+        ])
+        .args(args)
+        .logged_output()
+        .context("Failed to execute gdbus")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "KWin D-Bus call {} failed: {}",
+            method,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Script id from a loadScript reply such as "(3,)" or "(int32 3,)"
+fn parse_script_id(reply: &str) -> Option<i32> {
+    reply
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .trim_end_matches(',')
+        .trim_start_matches("int32")
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Load `source` as a KWin script, run it once and unload it again
+pub fn run(source: &str) -> Result<()> {
+    let n = SCRIPT_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let name = format!("nicotine-{}-{}", std::process::id(), n);
+    let path = std::env::temp_dir().join(format!("{}.js", name));
+    std::fs::write(&path, format!("{}\n{}", PRELUDE, source))?;
+
+    let result = (|| {
+        let reply = gdbus(
+            "/Scripting",
+            "org.kde.kwin.Scripting.loadScript",
+            &[&path.to_string_lossy(), &name],
+        )?;
+        let id = parse_script_id(&reply)
+            .filter(|id| *id >= 0)
+            .with_context(|| format!("KWin refused to load script: {}", reply.trim()))?;
+
+        // KWin 6 exports scripts under /Scripting, KWin 5 at the root
+        gdbus(
+            &format!("/Scripting/Script{}", id),
+            "org.kde.kwin.Script.run",
+            &[],
+        )
+        .or_else(|_| gdbus(&format!("/{}", id), "org.kde.kwin.Script.run", &[]))
+        .map(|_| ())
+    })();
+
+    let _ = gdbus(
+        "/Scripting",
+        "org.kde.kwin.Scripting.unloadScript",
+        &[&name],
+    );
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Wait for the reply sent under `token`
+fn expect(token: &str) -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    PENDING
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(token.to_string(), tx);
+    rx
+}
+
+/// Hand a reply to the query waiting for it; unknown tokens are dropped
+fn deliver(token: &str, json: String) {
+    let waiting = PENDING
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|p| p.remove(token));
+    if let Some(tx) = waiting {
+        let _ = tx.send(json);
+    }
+}
+
+/// Answers the callDBus at the end of each query script
+struct ReplyReceiver;
+
+#[zbus::interface(name = "org.nicotine.KWinReply")]
+impl ReplyReceiver {
+    fn reply(&self, token: String, json: String) {
+        deliver(&token, json);
+    }
+}
+
+/// This process's session bus connection serving ReplyReceiver, set up by
+/// the first query
+fn reply_bus() -> Result<&'static Connection> {
+    static CONNECTION: OnceLock<Connection> = OnceLock::new();
+    if let Some(connection) = CONNECTION.get() {
+        return Ok(connection);
+    }
+    let connection = Builder::session()?
+        .serve_at(REPLY_PATH, ReplyReceiver)?
+        .build()
+        .context("Could not connect to the session bus")?;
+    Ok(CONNECTION.get_or_init(|| connection))
+}
+
+/// Run `body` (a function body ending in `return <value>;`) and read back
+/// the value, which the script sends to this process's bus connection with
+/// callDBus
+pub fn query(body: &str) -> Result<Value> {
+    let bus = reply_bus()?;
+    let destination = bus
+        .unique_name()
+        .context("No unique name on the session bus")?
+        .to_string();
+    let token = format!(
+        "nicotine-reply-{}",
+        SCRIPT_COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    let reply = expect(&token);
+
+    let result = run(&format!(
+        "callDBus({:?}, {:?}, {:?}, \"Reply\", {:?}, \
+         JSON.stringify((function() {{ {} }})()));",
+        destination, REPLY_PATH, REPLY_INTERFACE, token, body
+    ))
+    .and_then(|_| {
+        reply
+            .recv_timeout(REPLY_TIMEOUT)
+            .map_err(|_| anyhow::anyhow!("No reply from KWin script"))
+    });
+    if let Some(pending) = PENDING.lock().unwrap().as_mut() {
+        pending.remove(&token);
+    }
+    serde_json::from_str(&result?).context("Bad reply from KWin script")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_load_script_reply() {
+        assert_eq!(parse_script_id("(3,)\n"), Some(3));
+        assert_eq!(parse_script_id("(int32 12,)"), Some(12));
+        assert_eq!(parse_script_id("(-1,)"), Some(-1));
+        assert_eq!(parse_script_id("garbage"), None);
+    }
+
+    #[test]
+    fn test_replies_reach_the_query_waiting_for_them() {
+        let main = expect("test-reply-1");
+        let other = expect("test-reply-2");
+
+        deliver("test-reply-9", "[]".to_string());
+        deliver("test-reply-1", r#"[{"caption":"EVE - Main"}]"#.to_string());
+        assert_eq!(main.try_recv().unwrap(), r#"[{"caption":"EVE - Main"}]"#);
+        assert!(other.try_recv().is_err());
+
+        // Each token is answered once
+        deliver("test-reply-1", "[]".to_string());
+        assert!(main.try_recv().is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::config::{Launch, LaunchAccount};
+use crate::events::WindowEvent;
+use crate::report::ActionReport;
+use crate::window_manager::{EveWindow, WindowManager};
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// The accounts named in `names`, in the order given, or every configured
+/// account without any
+pub fn select<'a>(launch: &'a Launch, names: &[String]) -> Result<Vec<&'a LaunchAccount>> {
+    if launch.accounts.is_empty() {
+        anyhow::bail!("No accounts to launch; add [[launch.accounts]] to config.toml");
+    }
+    if names.is_empty() {
+        return Ok(launch.accounts.iter().collect());
+    }
+    names
+        .iter()
+        .map(|name| {
+            launch
+                .accounts
+                .iter()
+                .find(|account| account.name == *name)
+                .with_context(|| format!("No account {:?} in [launch]", name))
+        })
+        .collect()
+}
+
+/// Start an account's launcher through `sh -c`, leaving it running
+pub fn spawn(launch: &Launch, account: &LaunchAccount) -> Result<()> {
+    let command = account.command.as_deref().unwrap_or(&launch.command);
+    Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("NICOTINE_ACCOUNT", &account.name)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to launch {}: {}", account.name, command))?;
+    Ok(())
+}
+
+/// Wait up to `timeout` for an EVE client that isn't in `known` to open.
+/// Woken by `events` when the backend has a stream, else polled every
+/// `interval`.
+pub fn wait_for_new_window(
+    wm: &dyn WindowManager,
+    known: &HashSet<u64>,
+    mut events: Option<&Receiver<WindowEvent>>,
+    timeout: Duration,
+    interval: Duration,
+) -> Result<Option<EveWindow>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(window) = wm
+            .get_eve_windows()?
+            .into_iter()
+            .find(|w| !known.contains(&w.id))
+        {
+            return Ok(Some(window));
+        }
+
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(None);
+        }
+        match events {
+            // Focus changes don't open windows; wait for the next event
+            Some(rx) => match rx.recv_timeout(left) {
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                // The stream died, so fall back to polling
+                Err(RecvTimeoutError::Disconnected) => events = None,
+            },
+            None => std::thread::sleep(interval.min(left)),
+        }
+    }
+}
+
+/// The clients quit-all closes: every one, or only the members of `group`
+/// out of the resolved `groups`
+pub fn quit_targets(
+    wm: &dyn WindowManager,
+    groups: &HashMap<String, Vec<String>>,
+    group: Option<&str>,
+) -> Result<Vec<EveWindow>> {
+    let mut windows = wm.get_eve_windows()?;
+    if let Some(name) = group {
+        let members = groups
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown group: {}", name))?;
+        windows.retain(|w| members.contains(&w.title));
+    }
+    Ok(windows)
+}
+
+/// Ask each client to close, carrying on past any that fail
+pub fn close_clients(wm: &dyn WindowManager, windows: &[EveWindow]) -> ActionReport {
+    let mut report = ActionReport::default();
+    for window in windows {
+        report.record(&window.title, wm.close_window(window.id));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::mock_window_manager::{window, MockWindowManager};
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_accounts_are_picked_by_name_in_the_order_given() {
+        let config = Config::from_toml_str(
+            "[[launch.accounts]]\nname = \"main\"\n\
+             [[launch.accounts]]\nname = \"alts\"\ncommand = \"wine launcher.exe\"\n",
+            None,
+        )
+        .unwrap();
+        let launch = &config.launch;
+        assert_eq!(launch.command, "steam -applaunch 8500");
+
+        let names = |accounts: Vec<&LaunchAccount>| -> Vec<String> {
+            accounts.iter().map(|a| a.name.clone()).collect()
+        };
+        assert_eq!(names(select(launch, &[]).unwrap()), ["main", "alts"]);
+        assert_eq!(
+            names(select(launch, &["alts".to_string(), "main".to_string()]).unwrap()),
+            ["alts", "main"]
+        );
+        assert!(select(launch, &["nope".to_string()]).is_err());
+        assert!(select(&Launch::default(), &[]).is_err());
+    }
+
+    #[test]
+    fn test_a_new_client_is_noticed_and_old_ones_are_not() {
+        let wm = MockWindowManager::new(vec![window(1, "Main")]);
+        let known: HashSet<u64> = [1].into_iter().collect();
+        let short = Duration::from_millis(20);
+
+        assert!(
+            wait_for_new_window(&wm, &known, None, short, Duration::from_millis(5))
+                .unwrap()
+                .is_none()
+        );
+
+        let (tx, rx) = mpsc::channel();
+        wm.open(window(2, "Alt"));
+        tx.send(WindowEvent::Opened).unwrap();
+        let found = wait_for_new_window(&wm, &known, Some(&rx), short, short).unwrap();
+        assert_eq!(found.map(|w| w.id), Some(2));
+    }
+
+    #[test]
+    fn test_quit_all_closes_the_group_and_reports_failures() {
+        let wm = MockWindowManager::new(vec![
+            window(1, "Main"),
+            window(2, "Alt"),
+            window(3, "Scout"),
+        ])
+        .with_failing(2);
+        let groups: HashMap<String, Vec<String>> = [(
+            "fleet".to_string(),
+            vec!["Alt".to_string(), "Scout".to_string()],
+        )]
+        .into();
+
+        assert!(quit_targets(&wm, &groups, Some("nope")).is_err());
+        let fleet = quit_targets(&wm, &groups, Some("fleet")).unwrap();
+        let report = close_clients(&wm, &fleet);
+
+        assert_eq!(wm.calls(), ["close 2", "close 3"]);
+        assert_eq!(report.succeeded, ["Scout"]);
+        assert_eq!(report.failed.len(), 1);
+        let left: Vec<u64> = wm.get_eve_windows().unwrap().iter().map(|w| w.id).collect();
+        assert_eq!(left, [1, 2]);
+
+        assert_eq!(quit_targets(&wm, &groups, None).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::config::Config;
+use crate::esi;
+use crate::window_manager::{EveWindow, WindowManager};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+/// Parents are followed at most this far looking for the launcher
+const MAX_DEPTH: usize = 32;
+
+/// The parts of /proc/<pid>/stat needed to find a client's launcher
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub name: String,
+    pub ppid: u32,
+    pub pgid: u32,
+}
+
+/// Read a process from /proc, or None if it has exited
+pub fn read_process(pid: u32) -> Option<ProcessInfo> {
+    parse_stat(&fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+}
+
+/// Parse "pid (comm) state ppid pgrp ...". comm may itself contain spaces
+/// and parentheses, so split at the last ')'.
+fn parse_stat(stat: &str) -> Option<ProcessInfo> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let name = stat.get(open + 1..close)?.to_string();
+    let mut fields = stat.get(close + 1..)?.split_whitespace().skip(1);
+
+    Some(ProcessInfo {
+        name,
+        ppid: fields.next()?.parse().ok()?,
+        pgid: fields.next()?.parse().ok()?,
+    })
+}
+
+fn is_launcher(name: &str) -> bool {
+    name.to_lowercase().contains("evelauncher")
+}
+
+/// Identify the launcher instance a client belongs to: the pid of the
+/// nearest evelauncher ancestor, or the client's process group if the
+/// launcher has exited and the client was reparented.
+pub fn launcher_of(pid: u32, lookup: impl Fn(u32) -> Option<ProcessInfo>) -> Option<u32> {
+    let client = lookup(pid)?;
+    let mut current = client.clone();
+    for _ in 0..MAX_DEPTH {
+        if current.ppid <= 1 {
+            break;
+        }
+        let parent_pid = current.ppid;
+        current = match lookup(parent_pid) {
+            Some(parent) => parent,
+            None => break,
+        };
+        if is_launcher(&current.name) {
+            return Some(parent_pid);
+        }
+    }
+    Some(client.pgid)
+}
+
+/// One group per launcher instance, named launcher-1, launcher-2, ... in
+/// launcher start (pid) order. Windows with no known pid are left out.
+pub fn launcher_groups(
+    windows: &[EveWindow],
+    lookup: impl Fn(u32) -> Option<ProcessInfo>,
+) -> Vec<(String, Vec<String>)> {
+    let mut by_launcher: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for window in windows {
+        if let Some(launcher) = window.pid.and_then(|pid| launcher_of(pid, &lookup)) {
+            by_launcher
+                .entry(launcher)
+                .or_default()
+                .push(window.title.clone());
+        }
+    }
+
+    by_launcher
+        .into_values()
+        .enumerate()
+        .map(|(i, members)| (format!("launcher-{}", i + 1), members))
+        .collect()
+}
+
+/// Configured groups, plus a group per launcher when group_by_launcher is
+/// set. A configured group wins over a launcher group of the same name.
+pub fn resolve_groups(config: &Config, wm: &dyn WindowManager) -> HashMap<String, Vec<String>> {
+    let mut groups = config.groups.clone();
+    if config.group_by_launcher {
+        let windows = wm.get_eve_windows().unwrap_or_default();
+        for (name, members) in launcher_groups(&windows, read_process) {
+            groups.entry(name).or_insert(members);
+        }
+    }
+    if config.esi.is_some() && groups.values().flatten().any(|m| esi::is_affiliation(m)) {
+        let windows = wm.get_eve_windows().unwrap_or_default();
+        let names: Vec<String> = windows.iter().map(|w| w.title.clone()).collect();
+        match esi::Directory::load().resolve(&names) {
+            Ok(identities) => esi::expand_groups(&mut groups, &windows, &identities),
+            Err(e) => tracing::warn!("Could not look up corporations: {:#}", e),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(name: &str, ppid: u32, pgid: u32) -> ProcessInfo {
+        ProcessInfo {
+            name: name.to_string(),
+            ppid,
+            pgid,
+        }
+    }
+
+    /// Two launchers (100, 200) each starting clients through wine, plus a
+    /// client whose launcher has exited (reparented to init)
+    fn table(pid: u32) -> Option<ProcessInfo> {
+        match pid {
+            100 => Some(process("evelauncher.sh", 1, 100)),
+            101 => Some(process("wineserver", 100, 100)),
+            110 => Some(process("exefile.exe", 101, 100)),
+            111 => Some(process("exefile.exe", 101, 100)),
+            200 => Some(process("evelauncher.sh", 1, 200)),
+            210 => Some(process("exefile.exe", 200, 200)),
+            310 => Some(process("exefile.exe", 1, 300)),
+            _ => None,
+        }
+    }
+
+    fn window(title: &str, pid: Option<u32>) -> EveWindow {
+        EveWindow {
+            title: title.to_string(),
+            pid,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_windows_map_to_launcher_groups() {
+        let windows = [
+            window("Alt", Some(210)),
+            window("Main", Some(110)),
+            window("Orphan", Some(310)),
+            window("Hauler", Some(111)),
+            window("NoPid", None),
+        ];
+
+        let groups = launcher_groups(&windows, table);
+        assert_eq!(
+            groups,
+            vec![
+                (
+                    "launcher-1".to_string(),
+                    vec!["Main".to_string(), "Hauler".to_string()]
+                ),
+                ("launcher-2".to_string(), vec!["Alt".to_string()]),
+                ("launcher-3".to_string(), vec!["Orphan".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_launcher_of_falls_back_to_process_group() {
+        assert_eq!(launcher_of(110, table), Some(100));
+        assert_eq!(launcher_of(310, table), Some(300));
+        assert_eq!(launcher_of(999, table), None);
+    }
+
+    #[test]
+    fn test_parse_stat_handles_parens_in_name() {
+        assert_eq!(
+            parse_stat("4242 (exe (file).exe) S 4100 4000 4000 0 -1 4194560"),
+            Some(process("exe (file).exe", 4100, 4000))
+        );
+        assert_eq!(parse_stat("4242 (exefile.exe)"), None);
+    }
+}
@@ -0,0 +1,1428 @@
+use crate::config::{
+    CharacterLayout, Config, GridSpec, LayoutMode, MonitorAnchor, NoMonitorPolicy, PipEdge,
+    RectFrac,
+};
+use crate::window_manager::{bounding_box, monitor_at, EveWindow, Monitor};
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Target geometry for a single window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    pub window_id: u64,
+    pub title: String,
+    pub monitor: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    /// New (width, height), or None to move without resizing
+    pub size: Option<(u32, u32)>,
+}
+
+impl Placement {
+    /// Where the client area goes when this placement is the outer frame of
+    /// a window decorated by `extents` ([left, right, top, bottom]), so a
+    /// titlebar doesn't spill into the next window's slot
+    pub fn inside_frame(&self, extents: [u32; 4]) -> Placement {
+        let [left, right, top, bottom] = extents;
+        Placement {
+            x: self.x + left as i32,
+            y: self.y + top as i32,
+            size: self.size.map(|(width, height)| {
+                (
+                    width.saturating_sub(left + right),
+                    height.saturating_sub(top + bottom),
+                )
+            }),
+            ..self.clone()
+        }
+    }
+}
+
+/// Apply the no_monitors policy when the backend couldn't list any monitors
+pub fn resolve_monitors(monitors: Vec<Monitor>, config: &Config) -> Result<Vec<Monitor>> {
+    if !monitors.is_empty() {
+        return Ok(monitors);
+    }
+
+    match config.no_monitors {
+        NoMonitorPolicy::UseGlobalConfig => {
+            tracing::warn!(
+                "No monitors detected, placing windows using display_width/display_height ({}x{}) from config",
+                config.display_width, config.display_height
+            );
+            Ok(monitors)
+        }
+        NoMonitorPolicy::Abort => anyhow::bail!(
+            "No monitors detected (xrandr, sway or Hyprland returned nothing). \
+             Not moving any windows; set no_monitors in config.toml to place them anyway"
+        ),
+        NoMonitorPolicy::SingleSynthetic => {
+            tracing::warn!(
+                "No monitors detected, assuming one {}x{} monitor at 0,0",
+                config.display_width,
+                config.display_height
+            );
+            Ok(vec![Monitor {
+                name: "synthetic".to_string(),
+                width: config.display_width,
+                height: config.display_height,
+                ..Default::default()
+            }])
+        }
+    }
+}
+
+/// Primary character for this stack: the focused EVE window when
+/// primary_follows_active is set, otherwise (or if focus isn't on one of
+/// `windows`) the configured primary_character
+pub fn resolve_primary(
+    windows: &[EveWindow],
+    active: Option<u64>,
+    config: &Config,
+) -> Option<String> {
+    if config.primary_follows_active {
+        if let Some(window) = active.and_then(|id| windows.iter().find(|w| w.id == id)) {
+            return Some(window.title.clone());
+        }
+    }
+    config.primary_character.clone()
+}
+
+/// Compute where every window should go without touching any of them
+pub fn plan_stack(windows: &[EveWindow], monitors: &[Monitor], config: &Config) -> Vec<Placement> {
+    let mode = config.layout_mode();
+
+    let big = match mode {
+        LayoutMode::Pip => pip_big(windows, config),
+        _ => None,
+    };
+
+    // Grid and cascade need to know how many windows share a monitor and
+    // each one's slot
+    let mut targets: Vec<Option<&Monitor>> = windows
+        .iter()
+        .map(|window| target_monitor(window, monitors, config))
+        .collect();
+    let explicit: Vec<Option<&CharacterLayout>> = windows
+        .iter()
+        .map(|window| {
+            config
+                .character_layouts
+                .get(&window.title)
+                .filter(|l| l.has_geometry())
+        })
+        .collect();
+    // Pip keeps every client on the big one's monitor
+    if let Some(big) = big {
+        for i in 0..windows.len() {
+            if explicit[i].is_none() {
+                targets[i] = targets[big];
+            }
+        }
+    }
+    // The grid leaves the primary out, and pip the active client: either
+    // fills its monitor
+    let fills_monitor = |i: usize| match mode {
+        LayoutMode::Grid => is_primary(&windows[i], config),
+        _ => Some(i) == big,
+    };
+    // Windows with their own geometry don't take a grid or cascade slot
+    let mut per_monitor: HashMap<&str, u32> = HashMap::new();
+    let mut slots: Vec<u32> = targets
+        .iter()
+        .zip(&explicit)
+        .enumerate()
+        .map(|(i, (target, explicit))| {
+            if explicit.is_some() || fills_monitor(i) {
+                return 0;
+            }
+            let count = per_monitor
+                .entry(target.map(|m| m.name.as_str()).unwrap_or(""))
+                .or_insert(0);
+            *count += 1;
+            *count - 1
+        })
+        .collect();
+    if let Some(big) = big {
+        let area = match targets[big] {
+            Some(mon) => mon.usable_rect(config),
+            None => fallback_area(monitors, config),
+        };
+        let small: Vec<usize> = (0..windows.len())
+            .filter(|&i| i != big && explicit[i].is_none())
+            .collect();
+        for (i, slot) in pip_slots(windows, &small, area, config) {
+            slots[i] = slot;
+        }
+    }
+
+    windows
+        .iter()
+        .enumerate()
+        .filter_map(|(i, window)| {
+            let target_monitor = targets[i];
+
+            let explicit = explicit[i].map(|layout| {
+                let area = match target_monitor {
+                    Some(mon) => mon.usable_rect(config),
+                    None => fallback_area(monitors, config),
+                };
+                explicit_rect(area, layout, config)
+            });
+
+            let (x, y, size) = match (target_monitor, mode, explicit) {
+                (_, _, Some(rect)) => rect,
+                (Some(mon), _, None) if fills_monitor(i) => {
+                    let (area_x, area_y, area_w, area_h) = mon.usable_rect(config);
+                    (area_x, area_y, Some((area_w, area_h)))
+                }
+                (Some(mon), LayoutMode::Fullscreen, None) => {
+                    // Fullscreen on monitor
+                    let (area_x, area_y, area_w, area_h) = mon.usable_rect(config);
+                    (area_x, area_y, Some((area_w, area_h)))
+                }
+                (Some(mon), LayoutMode::Centered, None) => {
+                    // Centered with eve_width
+                    let (area_x, area_y, area_w, area_h) = mon.usable_rect(config);
+                    let eve_w = config.eve_width.min(area_w);
+                    let x = area_x + ((area_w - eve_w) / 2) as i32;
+                    (x, area_y, Some((eve_w, area_h)))
+                }
+                (Some(mon), LayoutMode::Grid, None) => {
+                    let count = per_monitor[mon.name.as_str()];
+                    let spec = config.grids.get(&mon.name);
+                    let gap = spec.and_then(|s| s.gap).unwrap_or(config.grid_gap);
+                    let (x, y, w, h) = grid_cell_in(
+                        mon.usable_rect(config),
+                        grid_shape(count, spec),
+                        gap,
+                        slots[i],
+                    );
+                    (x, y, Some((w, h)))
+                }
+                (Some(mon), LayoutMode::Cascade, None) => {
+                    let area = mon.usable_rect(config);
+                    let region = match &config.cascade_region {
+                        Some(frac) => sub_rect(area, frac),
+                        None => area,
+                    };
+                    let size = (config.eve_width, config.eve_height);
+                    let (x, y, w, h) = cascade_cell(region, size, config.cascade_offset, slots[i]);
+                    (x, y, Some((w, h)))
+                }
+                (Some(mon), LayoutMode::Pip, None) => {
+                    let area = mon.usable_rect(config);
+                    let (x, y, w, h) = pip_cell(area, config.pip_size, config.pip_edge, slots[i]);
+                    (x, y, Some((w, h)))
+                }
+                (Some(mon), LayoutMode::MonitorOnly, None) => {
+                    // Already where it belongs: leave EVE's remembered position alone
+                    if window.monitor.as_deref() == Some(mon.name.as_str()) {
+                        return None;
+                    }
+                    let (x, y) = monitor_anchor(window, mon, config);
+                    (x, y, None)
+                }
+                // No monitor info means no way to tell which monitor is "right"
+                (None, LayoutMode::MonitorOnly, None) => return None,
+                (None, _, None) => {
+                    // Fallback to global config
+                    let (area_x, area_y, area_w, area_h) = fallback_area(monitors, config);
+                    let eve_w = config.eve_width.min(area_w);
+                    let x = area_x + ((area_w - eve_w) / 2) as i32;
+                    let height = area_h.saturating_sub(config.panel_height);
+                    (x, area_y, Some((eve_w, height)))
+                }
+            };
+
+            Some(Placement {
+                window_id: window.id,
+                title: window.title.clone(),
+                monitor: target_monitor.map(|m| m.name.clone()),
+                x,
+                y,
+                size: if config.move_only { None } else { size },
+            })
+        })
+        .collect()
+}
+
+/// Split `length` pixels starting at `start` into `parts` spans that exactly
+/// cover it. Leftover pixels from the division go one each to the leading
+/// spans, so no column or row is left unfilled.
+pub fn split_span(start: i32, length: u32, parts: u32) -> Vec<(i32, u32)> {
+    let parts = parts.max(1);
+    let base = length / parts;
+    let remainder = length % parts;
+
+    let mut offset = start;
+    (0..parts)
+        .map(|i| {
+            let size = base + u32::from(i < remainder);
+            let span = (offset, size);
+            offset += size as i32;
+            span
+        })
+        .collect()
+}
+
+/// Columns and rows for a grid of `count` windows: what `spec` fixes, with
+/// rows added if that's too few cells, otherwise as near square as fits
+pub fn grid_shape(count: u32, spec: Option<&GridSpec>) -> (u32, u32) {
+    let count = count.max(1);
+    let columns = spec.and_then(|s| s.columns).filter(|&c| c > 0);
+    let rows = spec.and_then(|s| s.rows).filter(|&r| r > 0);
+    match (columns, rows) {
+        (Some(cols), rows) => (cols, rows.unwrap_or(0).max(count.div_ceil(cols))),
+        (None, Some(rows)) => (count.div_ceil(rows), rows),
+        (None, None) => {
+            let cols = (1..=count).find(|c| c * c >= count).unwrap_or(1);
+            (cols, count.div_ceil(cols))
+        }
+    }
+}
+
+/// Cell `slot` of a `shape` (columns, rows) grid in `area`, with `gap` pixels
+/// between neighbouring cells. A gap that would leave no room is dropped.
+pub fn grid_cell_in(
+    area: (i32, i32, u32, u32),
+    shape: (u32, u32),
+    gap: u32,
+    slot: u32,
+) -> (i32, i32, u32, u32) {
+    let (area_x, area_y, area_w, area_h) = area;
+    let (cols, rows) = (shape.0.max(1), shape.1.max(1));
+
+    let cell = |start: i32, length: u32, parts: u32, index: u32| {
+        let gaps = gap * (parts - 1);
+        let gap = if gaps < length { gap } else { 0 };
+        let (offset, size) = split_span(start, length - gap * (parts - 1), parts)[index as usize];
+        (offset + (gap * index) as i32, size)
+    };
+    let (x, w) = cell(area_x, area_w, cols, slot % cols);
+    let (y, h) = cell(area_y, area_h, rows, (slot / cols).min(rows - 1));
+    (x, y, w, h)
+}
+
+/// The part of `area` covered by `frac`, kept inside `area` even if the
+/// fractions run past 1.0
+pub fn sub_rect(area: (i32, i32, u32, u32), frac: &RectFrac) -> (i32, i32, u32, u32) {
+    let (area_x, area_y, area_w, area_h) = area;
+    let x = frac.x.clamp(0.0, 1.0);
+    let y = frac.y.clamp(0.0, 1.0);
+    let width = frac.width.clamp(0.0, 1.0 - x);
+    let height = frac.height.clamp(0.0, 1.0 - y);
+
+    let scale = |length: u32, f: f64| (length as f64 * f).round() as u32;
+    (
+        area_x + scale(area_w, x) as i32,
+        area_y + scale(area_h, y) as i32,
+        scale(area_w, width),
+        scale(area_h, height),
+    )
+}
+
+/// Window `slot` of a cascade in `area`: `size` windows (shrunk to fit) each
+/// `offset` from the last, wrapping back to the start before one would cross
+/// the area's edge. Negative offsets start from the right/bottom edge.
+pub fn cascade_cell(
+    area: (i32, i32, u32, u32),
+    size: (u32, u32),
+    offset: (i32, i32),
+    slot: u32,
+) -> (i32, i32, u32, u32) {
+    let (area_x, area_y, area_w, area_h) = area;
+    let (w, h) = (size.0.min(area_w), size.1.min(area_h));
+    let (room_x, room_y) = (area_w - w, area_h - h);
+
+    // How many positions fit along each axis, counting the first
+    let fit = |room: u32, step: i32| match step.unsigned_abs() {
+        0 => u32::MAX,
+        step => room / step + 1,
+    };
+    let steps = fit(room_x, offset.0).min(fit(room_y, offset.1));
+    let n = (slot % steps) as i32;
+
+    let start = |origin: i32, room: u32, step: i32| {
+        if step < 0 {
+            origin + room as i32
+        } else {
+            origin
+        }
+    };
+    (
+        start(area_x, room_x, offset.0) + n * offset.0,
+        start(area_y, room_y, offset.1) + n * offset.1,
+        w,
+        h,
+    )
+}
+
+/// The window the pip layout makes big: the primary (stack_windows makes
+/// that the active client), else the first
+fn pip_big(windows: &[EveWindow], config: &Config) -> Option<usize> {
+    windows
+        .iter()
+        .position(|w| is_primary(w, config))
+        .or(if windows.is_empty() { None } else { Some(0) })
+}
+
+/// Cell `slot` of the pip layout's line of `size` windows along `edge` of
+/// `area`, starting another line further in once one is full
+pub fn pip_cell(
+    area: (i32, i32, u32, u32),
+    size: (u32, u32),
+    edge: PipEdge,
+    slot: u32,
+) -> (i32, i32, u32, u32) {
+    let (area_x, area_y, area_w, area_h) = area;
+    let (w, h) = (
+        size.0.clamp(1, area_w.max(1)),
+        size.1.clamp(1, area_h.max(1)),
+    );
+    let per_line = match edge {
+        PipEdge::Left | PipEdge::Right => area_h / h,
+        PipEdge::Top | PipEdge::Bottom => area_w / w,
+    }
+    .max(1);
+    let (line, along) = ((slot / per_line) as i32, (slot % per_line) as i32);
+    let (w_i, h_i) = (w as i32, h as i32);
+
+    let (x, y) = match edge {
+        PipEdge::Right => (
+            area_x + area_w as i32 - w_i * (line + 1),
+            area_y + along * h_i,
+        ),
+        PipEdge::Left => (area_x + w_i * line, area_y + along * h_i),
+        PipEdge::Bottom => (
+            area_x + along * w_i,
+            area_y + area_h as i32 - h_i * (line + 1),
+        ),
+        PipEdge::Top => (area_x + along * w_i, area_y + h_i * line),
+    };
+    (x, y, w, h)
+}
+
+/// Pip slot for each of the `small` windows (indices into `windows`). One
+/// already sitting in a slot keeps it, so when the active client changes
+/// only it and the previous big window trade places; the rest fill the free
+/// slots in order.
+fn pip_slots(
+    windows: &[EveWindow],
+    small: &[usize],
+    area: (i32, i32, u32, u32),
+    config: &Config,
+) -> Vec<(usize, u32)> {
+    let cells: Vec<_> = (0..small.len() as u32)
+        .map(|slot| pip_cell(area, config.pip_size, config.pip_edge, slot))
+        .collect();
+    let in_cell = |window: &EveWindow, cell: &(i32, i32, u32, u32)| {
+        let Some((x, y, w, h)) = window.geometry else {
+            return false;
+        };
+        let close = |a: i64, b: i64| (a - b).abs() <= i64::from(config.heal_tolerance);
+        close(x.into(), cell.0.into())
+            && close(y.into(), cell.1.into())
+            && (config.move_only
+                || (close(w.into(), cell.2.into()) && close(h.into(), cell.3.into())))
+    };
+
+    let mut holders: Vec<Option<usize>> = vec![None; cells.len()];
+    for &i in small {
+        let kept =
+            (0..cells.len()).find(|&s| holders[s].is_none() && in_cell(&windows[i], &cells[s]));
+        if let Some(slot) = kept {
+            holders[slot] = Some(i);
+        }
+    }
+    let mut free = (0..cells.len())
+        .filter(|&s| holders[s].is_none())
+        .collect::<Vec<_>>()
+        .into_iter();
+    small
+        .iter()
+        .map(|&i| {
+            let slot = holders
+                .iter()
+                .position(|holder| *holder == Some(i))
+                .or_else(|| free.next())
+                .unwrap_or(0);
+            (i, slot as u32)
+        })
+        .collect()
+}
+
+/// Area used when a window can't be tied to a monitor: the bounding box of all
+/// known monitors (which may start left of or above 0,0), otherwise the
+/// configured display size at the origin
+pub fn fallback_area(monitors: &[Monitor], config: &Config) -> (i32, i32, u32, u32) {
+    bounding_box(monitors).unwrap_or((0, 0, config.display_width, config.display_height))
+}
+
+/// Move each window into the slot (current position and size) of the window
+/// `steps` after it in `windows`, wrapping around; negative steps rotate the
+/// other way. Windows without a known geometry neither move nor hold a slot.
+pub fn plan_rotation(windows: &[EveWindow], steps: isize) -> Vec<Placement> {
+    let slotted: Vec<_> = windows
+        .iter()
+        .filter_map(|w| w.geometry.map(|g| (w, g)))
+        .collect();
+    let count = slotted.len() as isize;
+    if count < 2 {
+        return Vec::new();
+    }
+
+    slotted
+        .iter()
+        .enumerate()
+        .map(|(i, (window, _))| {
+            let (_, (x, y, width, height)) =
+                slotted[(i as isize + steps).rem_euclid(count) as usize];
+            Placement {
+                window_id: window.id,
+                title: window.title.clone(),
+                monitor: None,
+                x,
+                y,
+                size: Some((width, height)),
+            }
+        })
+        .collect()
+}
+
+/// Exchange the places of two clients. `names` picks them: two characters,
+/// or one to trade places with primary_character, or none to swap the active
+/// client with the primary.
+pub fn plan_swap(
+    windows: &[EveWindow],
+    names: &[String],
+    active: u64,
+    config: &Config,
+) -> Result<Vec<Placement>> {
+    let find = |name: &str| {
+        windows
+            .iter()
+            .find(|w| w.title == name)
+            .ok_or_else(|| anyhow::anyhow!("No EVE client for {}", name))
+    };
+    let primary = || {
+        let name = config
+            .primary_character
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Name two characters or set primary_character"))?;
+        find(name)
+    };
+    let (first, second) = match names {
+        [a, b] => (find(a)?, find(b)?),
+        [a] => (find(a)?, primary()?),
+        [] => (
+            windows
+                .iter()
+                .find(|w| w.id == active)
+                .ok_or_else(|| anyhow::anyhow!("The active window isn't an EVE client"))?,
+            primary()?,
+        ),
+        _ => anyhow::bail!("Swap takes at most two characters"),
+    };
+    if first.id == second.id {
+        return Ok(Vec::new());
+    }
+
+    let place = |window: &EveWindow, into: &EveWindow| -> Result<Placement> {
+        let (x, y, width, height) = into
+            .geometry
+            .ok_or_else(|| anyhow::anyhow!("Don't know where {} is", into.title))?;
+        Ok(Placement {
+            window_id: window.id,
+            title: window.title.clone(),
+            monitor: into.monitor.clone(),
+            x,
+            y,
+            size: Some((width, height)),
+        })
+    };
+    Ok(vec![place(first, second)?, place(second, first)?])
+}
+
+/// A single restacking step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZOp {
+    Raise(u64),
+    Lower(u64),
+}
+
+/// Order raises and lowers so windows end up stacked by z_priority. Positive
+/// priorities are raised lowest-first, so the highest finishes on top;
+/// negative ones are lowered highest-first, so the lowest finishes at the
+/// bottom. Windows without a priority aren't touched, except that the pip
+/// layout raises its small windows over the big one first.
+pub fn plan_z_order(windows: &[EveWindow], config: &Config) -> Vec<ZOp> {
+    let mut prioritized: Vec<(i32, u64)> = windows
+        .iter()
+        .filter_map(|w| {
+            config
+                .character_layouts
+                .get(&w.title)
+                .and_then(|l| l.z_priority)
+                .map(|p| (p, w.id))
+        })
+        .collect();
+    // Stable sort keeps discovery order between equal priorities
+    prioritized.sort_by_key(|&(priority, _)| priority);
+
+    let lowers = prioritized
+        .iter()
+        .filter(|(priority, _)| *priority < 0)
+        .rev()
+        .map(|&(_, id)| ZOp::Lower(id));
+    let raises = prioritized
+        .iter()
+        .filter(|(priority, _)| *priority >= 0)
+        .map(|&(_, id)| ZOp::Raise(id));
+    let big = match config.layout_mode() {
+        LayoutMode::Pip => pip_big(windows, config),
+        _ => None,
+    };
+    let pip = windows
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| big.is_some_and(|big| big != i))
+        .map(|(_, w)| ZOp::Raise(w.id));
+
+    lowers.chain(pip).chain(raises).collect()
+}
+
+/// Where a character_layouts entry with its own geometry puts a window in
+/// `area`
+fn explicit_rect(
+    area: (i32, i32, u32, u32),
+    layout: &CharacterLayout,
+    config: &Config,
+) -> (i32, i32, Option<(u32, u32)>) {
+    let (area_x, area_y, area_w, area_h) = area;
+    if layout.fullscreen {
+        return (area_x, area_y, Some((area_w, area_h)));
+    }
+    (
+        area_x + layout.x.unwrap_or(0),
+        area_y + layout.y.unwrap_or(0),
+        Some((
+            layout.width.unwrap_or(config.eve_width),
+            layout.height.unwrap_or(area_h),
+        )),
+    )
+}
+
+/// Position for a window moved onto `mon` by the monitor_only layout
+fn monitor_anchor(window: &EveWindow, mon: &Monitor, config: &Config) -> (i32, i32) {
+    let (area_x, area_y, area_w, area_h) = mon.usable_rect(config);
+
+    match (config.monitor_only_anchor, window.geometry) {
+        (MonitorAnchor::Center, Some((_, _, width, height))) => (
+            area_x + (area_w.saturating_sub(width) / 2) as i32,
+            area_y + (area_h.saturating_sub(height) / 2) as i32,
+        ),
+        // Without a known size there's nothing to center, so use the corner
+        _ => (area_x, area_y),
+    }
+}
+
+fn is_primary(window: &EveWindow, config: &Config) -> bool {
+    config.primary_character.as_deref() == Some(window.title.as_str())
+}
+
+/// Determine target monitor:
+/// - A character_layouts monitor wins if it's connected, then a
+///   monitor_assignments entry
+/// - A character assigned to a monitor that isn't connected goes to
+///   fallback_monitor, if that's set
+/// - Primary character goes to primary_monitor, else fallback_monitor,
+///   else the output X11 calls primary
+/// - In the grid layout, others go to grid_monitor if it's set
+/// - Others stay on their current monitor (by name, else by geometry)
+/// - Anything left over goes to fallback_monitor, else the first monitor
+fn target_monitor<'a>(
+    window: &EveWindow,
+    monitors: &'a [Monitor],
+    config: &Config,
+) -> Option<&'a Monitor> {
+    let fallback = config
+        .fallback_monitor
+        .as_ref()
+        .and_then(|r| monitors.iter().find(|m| r.matches(&m.name, monitors)));
+    let layout_monitor = config
+        .character_layouts
+        .get(&window.title)
+        .and_then(|l| l.monitor.as_ref());
+    let assigned = config
+        .monitor_assignments
+        .iter()
+        .find(|(_, characters)| characters.contains(&window.title))
+        .map(|(name, _)| name);
+
+    let configured = layout_monitor
+        .and_then(|r| monitors.iter().find(|m| r.matches(&m.name, monitors)))
+        .or_else(|| assigned.and_then(|name| monitors.iter().find(|m| &m.name == name)));
+    if configured.is_some() {
+        return configured;
+    }
+    if (layout_monitor.is_some() || assigned.is_some()) && fallback.is_some() {
+        return fallback;
+    }
+
+    let is_primary = is_primary(window, config);
+    if !is_primary && config.layout_mode() == LayoutMode::Grid {
+        let grid_monitor = config
+            .grid_monitor
+            .as_ref()
+            .and_then(|r| monitors.iter().find(|m| r.matches(&m.name, monitors)));
+        if grid_monitor.is_some() {
+            return grid_monitor;
+        }
+    }
+
+    if is_primary {
+        config
+            .primary_monitor
+            .as_ref()
+            .and_then(|name| monitors.iter().find(|m| &m.name == name))
+            .or(fallback)
+            .or_else(|| monitors.iter().find(|m| m.primary))
+            .or_else(|| monitors.first())
+    } else {
+        window
+            .monitor
+            .as_ref()
+            .and_then(|name| monitors.iter().find(|m| &m.name == name))
+            .or_else(|| {
+                // Unnamed monitor: go by where the window's center actually is
+                let (x, y, width, height) = window.geometry?;
+                monitor_at(monitors, x + (width / 2) as i32, y + (height / 2) as i32)
+            })
+            .or(fallback)
+            .or_else(|| monitors.first())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MonitorRef;
+
+    fn monitors() -> Vec<Monitor> {
+        vec![
+            Monitor {
+                name: "DP-1".to_string(),
+                width: 2560,
+                height: 1440,
+                ..Default::default()
+            },
+            Monitor {
+                name: "HDMI-A-1".to_string(),
+                x: 2560,
+                width: 1920,
+                height: 1080,
+                ..Default::default()
+            },
+        ]
+    }
+
+    /// Cell `slot` of a near-square grid holding `count` windows in `area`
+    fn grid_cell(area: (i32, i32, u32, u32), count: u32, slot: u32) -> (i32, i32, u32, u32) {
+        grid_cell_in(area, grid_shape(count, None), 0, slot)
+    }
+
+    fn window(id: u64, title: &str, monitor: &str) -> EveWindow {
+        EveWindow {
+            id,
+            title: title.to_string(),
+            monitor: Some(monitor.to_string()),
+            geometry: Some((100, 50, 1280, 720)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_centered_layout_sizes_every_window() {
+        let config = Config::default();
+        let placements = plan_stack(&[window(1, "Main", "DP-1")], &monitors(), &config);
+
+        assert_eq!(placements.len(), 1);
+        assert_eq!(placements[0].x, (2560 - 1036) / 2);
+        assert_eq!(placements[0].size, Some((1036, 1440)));
+    }
+
+    #[test]
+    fn test_monitor_only_moves_without_resizing() {
+        let config = Config {
+            layout: LayoutMode::MonitorOnly,
+            primary_character: Some("Main".to_string()),
+            primary_monitor: Some("HDMI-A-1".to_string()),
+            ..Config::default()
+        };
+        let windows = [window(1, "Main", "DP-1"), window(2, "Alt", "DP-1")];
+
+        let placements = plan_stack(&windows, &monitors(), &config);
+
+        // Only the primary changes monitor; the alt is left where EVE put it
+        assert_eq!(
+            placements,
+            vec![Placement {
+                window_id: 1,
+                title: "Main".to_string(),
+                monitor: Some("HDMI-A-1".to_string()),
+                x: 2560,
+                y: 0,
+                size: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_monitor_only_center_anchor_uses_window_size() {
+        let config = Config {
+            layout: LayoutMode::MonitorOnly,
+            monitor_only_anchor: MonitorAnchor::Center,
+            primary_character: Some("Main".to_string()),
+            primary_monitor: Some("HDMI-A-1".to_string()),
+            ..Config::default()
+        };
+
+        let placements = plan_stack(&[window(1, "Main", "DP-1")], &monitors(), &config);
+
+        assert_eq!(
+            (placements[0].x, placements[0].y),
+            (2560 + (1920 - 1280) / 2, (1080 - 720) / 2)
+        );
+        assert_eq!(placements[0].size, None);
+    }
+
+    #[test]
+    fn test_move_only_drops_size_from_any_layout() {
+        let config = Config {
+            fullscreen_stack: true,
+            move_only: true,
+            ..Config::default()
+        };
+
+        let placements = plan_stack(&[window(1, "Main", "DP-1")], &monitors(), &config);
+
+        assert_eq!((placements[0].x, placements[0].y), (0, 0));
+        assert_eq!(placements[0].size, None);
+    }
+
+    #[test]
+    fn test_framed_windows_stay_inside_their_slot() {
+        let config = Config {
+            layout: LayoutMode::Grid,
+            ..Config::default()
+        };
+        let windows = [window(1, "A", "DP-1"), window(2, "B", "DP-1")];
+        let placements = plan_stack(&windows, &monitors()[..1], &config);
+
+        // A KDE-style titlebar on top and thin borders elsewhere
+        let clients: Vec<_> = placements
+            .iter()
+            .map(|p| p.inside_frame([2, 2, 30, 2]))
+            .collect();
+        for (slot, client) in placements.iter().zip(&clients) {
+            let (width, height) = slot.size.unwrap();
+            let (client_width, client_height) = client.size.unwrap();
+            assert_eq!((client.x, client.y), (slot.x + 2, slot.y + 30));
+            assert_eq!((client_width + 4, client_height + 32), (width, height));
+        }
+
+        let moved = Placement {
+            size: None,
+            ..placements[0].clone()
+        };
+        assert_eq!(moved.inside_frame([2, 2, 30, 2]).size, None);
+    }
+
+    #[test]
+    fn test_z_order_follows_priorities() {
+        let mut config = Config::default();
+        for (name, priority) in [("Main", 10), ("Scout", 5), ("Hauler", -1), ("Cyno", -5)] {
+            config.character_layouts.insert(
+                name.to_string(),
+                CharacterLayout {
+                    z_priority: Some(priority),
+                    ..Default::default()
+                },
+            );
+        }
+        let windows = [
+            window(1, "Hauler", "DP-1"),
+            window(2, "Main", "DP-1"),
+            window(3, "Unranked", "DP-1"),
+            window(4, "Scout", "DP-1"),
+            window(5, "Cyno", "DP-1"),
+        ];
+
+        assert_eq!(
+            plan_z_order(&windows, &config),
+            vec![ZOp::Lower(1), ZOp::Lower(5), ZOp::Raise(4), ZOp::Raise(2)]
+        );
+    }
+
+    #[test]
+    fn test_centered_layout_on_negative_origin_monitor() {
+        let monitors = vec![Monitor {
+            name: "HDMI-A-1".to_string(),
+            x: -1920,
+            y: -200,
+            width: 1920,
+            height: 1080,
+            ..Default::default()
+        }];
+
+        let placements = plan_stack(
+            &[window(1, "Main", "HDMI-A-1")],
+            &monitors,
+            &Config::default(),
+        );
+
+        assert_eq!(placements[0].x, -1920 + (1920 - 1036) / 2);
+        assert_eq!(placements[0].y, -200);
+    }
+
+    #[test]
+    fn test_unnamed_window_resolved_by_geometry_on_negative_monitor() {
+        let mut monitors = monitors();
+        monitors.push(Monitor {
+            name: "DP-2".to_string(),
+            x: -1920,
+            width: 1920,
+            height: 1080,
+            ..Default::default()
+        });
+        let window = EveWindow {
+            id: 1,
+            title: "Main".to_string(),
+            geometry: Some((-1800, 100, 1280, 720)),
+            ..Default::default()
+        };
+
+        let placements = plan_stack(&[window], &monitors, &Config::default());
+
+        assert_eq!(placements[0].monitor.as_deref(), Some("DP-2"));
+    }
+
+    #[test]
+    fn test_primary_follows_active_window() {
+        let windows = vec![window(1, "Main", "DP-1"), window(2, "Alt", "DP-1")];
+        let config = Config {
+            primary_character: Some("Main".to_string()),
+            primary_follows_active: true,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            resolve_primary(&windows, Some(2), &config).as_deref(),
+            Some("Alt")
+        );
+        // Focus on something that isn't EVE: keep the configured primary
+        assert_eq!(
+            resolve_primary(&windows, Some(99), &config).as_deref(),
+            Some("Main")
+        );
+        assert_eq!(
+            resolve_primary(&windows, None, &config).as_deref(),
+            Some("Main")
+        );
+
+        let fixed = Config {
+            primary_follows_active: false,
+            ..config
+        };
+        assert_eq!(
+            resolve_primary(&windows, Some(2), &fixed).as_deref(),
+            Some("Main")
+        );
+    }
+
+    /// Plan a rotation and apply it to the windows' geometry, like a backend would
+    fn rotate(windows: &mut [EveWindow], steps: isize) {
+        for p in plan_rotation(windows, steps) {
+            let window = windows.iter_mut().find(|w| w.id == p.window_id).unwrap();
+            let (width, height) = p.size.unwrap();
+            window.geometry = Some((p.x, p.y, width, height));
+        }
+    }
+
+    #[test]
+    fn test_rotation_cycles_slots_and_returns() {
+        let mut windows = vec![
+            window(1, "A", "DP-1"),
+            window(2, "B", "DP-1"),
+            window(3, "C", "HDMI-A-1"),
+        ];
+        windows[1].geometry = Some((1280, 0, 1280, 1440));
+        windows[2].geometry = Some((2560, 0, 1920, 1080));
+        let original: Vec<_> = windows.iter().map(|w| w.geometry).collect();
+
+        rotate(&mut windows, 1);
+        // A took B's slot, B took C's, C wrapped round to A's
+        assert_eq!(windows[0].geometry, original[1]);
+        assert_eq!(windows[1].geometry, original[2]);
+        assert_eq!(windows[2].geometry, original[0]);
+
+        // Rotating back undoes it
+        rotate(&mut windows, -1);
+        let after: Vec<_> = windows.iter().map(|w| w.geometry).collect();
+        assert_eq!(after, original);
+
+        // As does going all the way round
+        for _ in 0..3 {
+            rotate(&mut windows, 1);
+        }
+        let after: Vec<_> = windows.iter().map(|w| w.geometry).collect();
+        assert_eq!(after, original);
+    }
+
+    #[test]
+    fn test_split_span_distributes_remainder_to_leading_spans() {
+        assert_eq!(split_span(0, 10, 3), vec![(0, 4), (4, 3), (7, 3)]);
+        assert_eq!(split_span(-100, 9, 3), vec![(-100, 3), (-97, 3), (-94, 3)]);
+    }
+
+    #[test]
+    fn test_grid_cells_exactly_partition_area() {
+        for &(width, height) in &[(1921u32, 1079u32), (2560, 1437), (1000, 1000), (7, 5)] {
+            for count in 1..=10u32 {
+                let area = (-1920, 25, width, height);
+                let cells: Vec<_> = (0..count)
+                    .map(|slot| grid_cell(area, count, slot))
+                    .collect();
+
+                let cols = (1..=count).find(|c| c * c >= count).unwrap();
+                let rows = count.div_ceil(cols);
+
+                // Every full grid position is covered exactly once, with no
+                // gaps between neighbours and nothing past the area's edges
+                let mut covered: u64 = 0;
+                for slot in 0..cols * rows {
+                    let (x, y, w, h) = grid_cell(area, cols * rows, slot);
+                    covered += w as u64 * h as u64;
+                    if slot % cols + 1 < cols {
+                        let (next_x, ..) = grid_cell(area, cols * rows, slot + 1);
+                        assert_eq!(x + w as i32, next_x);
+                    } else {
+                        assert_eq!(x + w as i32, area.0 + width as i32);
+                    }
+                    if slot / cols + 1 < rows {
+                        let (_, below_y, ..) = grid_cell(area, cols * rows, slot + cols);
+                        assert_eq!(y + h as i32, below_y);
+                    } else {
+                        assert_eq!(y + h as i32, area.1 + height as i32);
+                    }
+                }
+                assert_eq!(covered, width as u64 * height as u64);
+
+                // Cells never differ by more than a pixel in either direction
+                let widths: Vec<u32> = cells.iter().map(|c| c.2).collect();
+                let heights: Vec<u32> = cells.iter().map(|c| c.3).collect();
+                assert!(widths.iter().max().unwrap() - widths.iter().min().unwrap() <= 1);
+                assert!(heights.iter().max().unwrap() - heights.iter().min().unwrap() <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cascade_stays_inside_half_monitor_region() {
+        let config = Config {
+            layout: LayoutMode::Cascade,
+            panel_height: 0,
+            eve_width: 600,
+            eve_height: 800,
+            cascade_region: Some(RectFrac {
+                x: 0.5,
+                y: 0.0,
+                width: 0.5,
+                height: 1.0,
+            }),
+            ..Config::default()
+        };
+        let windows: Vec<EveWindow> = (0..12)
+            .map(|i| window(i, &format!("Char{}", i), "HDMI-A-1"))
+            .collect();
+        let placements = plan_stack(&windows, &monitors(), &config);
+
+        // Right half of HDMI-A-1
+        let (rx, ry, rw, rh) = (2560 + 960, 0, 960, 1080);
+        for p in &placements {
+            let (w, h) = p.size.unwrap();
+            assert_eq!((w, h), (600, 800));
+            assert!(p.x >= rx && p.x + w as i32 <= rx + rw, "{:?}", p);
+            assert!(p.y >= ry && p.y + h as i32 <= ry + rh, "{:?}", p);
+        }
+
+        assert_eq!((placements[0].x, placements[0].y), (rx, ry));
+        assert_eq!((placements[1].x, placements[1].y), (rx + 30, ry + 30));
+        // 280px of vertical room fits ten 30px steps, so the eleventh wraps
+        assert_eq!((placements[9].x, placements[9].y), (rx + 270, ry + 270));
+        assert_eq!((placements[10].x, placements[10].y), (rx, ry));
+    }
+
+    #[test]
+    fn test_cascade_cell_shrinks_and_runs_from_far_edge() {
+        let area = (100, 0, 800, 600);
+
+        // Too big for the area: shrunk, and with no room every slot overlaps
+        assert_eq!(
+            cascade_cell(area, (1000, 700), (30, 30), 3),
+            (100, 0, 800, 600)
+        );
+
+        // Negative offsets start at the bottom right and step up and left
+        assert_eq!(
+            cascade_cell(area, (400, 300), (-50, -50), 0),
+            (500, 300, 400, 300)
+        );
+        assert_eq!(
+            cascade_cell(area, (400, 300), (-50, -50), 2),
+            (400, 200, 400, 300)
+        );
+        // 300px of vertical room fits seven steps
+        assert_eq!(
+            cascade_cell(area, (400, 300), (-50, -50), 7),
+            (500, 300, 400, 300)
+        );
+    }
+
+    #[test]
+    fn test_sub_rect_clamps_to_area() {
+        let frac = RectFrac {
+            x: 0.75,
+            y: -0.5,
+            width: 0.5,
+            height: 2.0,
+        };
+        assert_eq!(
+            sub_rect((-1920, 20, 1920, 1000), &frac),
+            (-480, 20, 480, 1000)
+        );
+    }
+
+    #[test]
+    fn test_character_layouts_place_main_and_tile_the_rest() {
+        let mut config = Config {
+            layout: LayoutMode::Grid,
+            panel_height: 0,
+            ..Config::default()
+        };
+        config.character_layouts.insert(
+            "Main".to_string(),
+            CharacterLayout {
+                monitor: Some(MonitorRef::Name("DP-1".to_string())),
+                fullscreen: true,
+                ..Default::default()
+            },
+        );
+        for miner in ["Miner1", "Miner2"] {
+            config.character_layouts.insert(
+                miner.to_string(),
+                CharacterLayout {
+                    monitor: Some(MonitorRef::Index(1)),
+                    ..Default::default()
+                },
+            );
+        }
+        config.character_layouts.insert(
+            "Scout".to_string(),
+            CharacterLayout {
+                x: Some(100),
+                width: Some(800),
+                height: Some(600),
+                ..Default::default()
+            },
+        );
+        let windows = vec![
+            window(1, "Main", "HDMI-A-1"),
+            window(2, "Miner1", "DP-1"),
+            window(3, "Miner2", "DP-1"),
+            window(4, "Scout", "HDMI-A-1"),
+        ];
+
+        let rects: Vec<_> = plan_stack(&windows, &monitors(), &config)
+            .into_iter()
+            .map(|p| (p.x, p.y, p.size))
+            .collect();
+        assert_eq!(
+            rects,
+            vec![
+                (0, 0, Some((2560, 1440))),
+                // The scout has its own geometry, so the miners split the
+                // second monitor between them
+                (2560, 0, Some((960, 1080))),
+                (3520, 0, Some((960, 1080))),
+                (2660, 0, Some((800, 600))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_grid_layout_tiles_each_monitor() {
+        let config = Config {
+            layout: LayoutMode::Grid,
+            panel_height: 0,
+            ..Config::default()
+        };
+        let windows = vec![
+            window(1, "A", "DP-1"),
+            window(2, "B", "DP-1"),
+            window(3, "C", "DP-1"),
+            window(4, "D", "HDMI-A-1"),
+        ];
+        let placements = plan_stack(&windows, &monitors(), &config);
+
+        let (x, y, w, h) = monitors()[0].usable_rect(&config);
+        let cols = split_span(x, w, 2);
+        let rows = split_span(y, h, 2);
+        assert_eq!((placements[0].x, placements[0].y), (cols[0].0, rows[0].0));
+        assert_eq!((placements[1].x, placements[1].y), (cols[1].0, rows[0].0));
+        assert_eq!((placements[2].x, placements[2].y), (cols[0].0, rows[1].0));
+        assert_eq!(placements[0].size, Some((cols[0].1, rows[0].1)));
+
+        // Alone on its monitor, so it gets the whole usable area
+        let (x, y, w, h) = monitors()[1].usable_rect(&config);
+        assert_eq!((placements[3].x, placements[3].y), (x, y));
+        assert_eq!(placements[3].size, Some((w, h)));
+    }
+
+    #[test]
+    fn test_swap_trades_geometry_and_monitor() {
+        let config = Config {
+            primary_character: Some("Main".to_string()),
+            ..Config::default()
+        };
+        let mut scout = window(2, "Scout", "HDMI-A-1");
+        scout.geometry = Some((2560, 0, 640, 360));
+        let windows = vec![window(1, "Main", "DP-1"), scout, window(3, "Alt", "DP-1")];
+        let names = |n: &[&str]| n.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let swap = plan_swap(&windows, &names(&["Scout"]), 0, &config).unwrap();
+        assert_eq!(swap[0].window_id, 2);
+        assert_eq!(swap[0].monitor.as_deref(), Some("DP-1"));
+        assert_eq!(
+            (swap[0].x, swap[0].y, swap[0].size),
+            (100, 50, Some((1280, 720)))
+        );
+        assert_eq!(swap[1].window_id, 1);
+        assert_eq!(swap[1].monitor.as_deref(), Some("HDMI-A-1"));
+        assert_eq!(
+            (swap[1].x, swap[1].y, swap[1].size),
+            (2560, 0, Some((640, 360)))
+        );
+
+        // The active client with the primary, or any two by name
+        let swap = plan_swap(&windows, &[], 3, &config).unwrap();
+        assert_eq!((swap[0].window_id, swap[1].window_id), (3, 1));
+        let swap = plan_swap(&windows, &names(&["Alt", "Scout"]), 0, &config).unwrap();
+        assert_eq!((swap[0].window_id, swap[1].window_id), (3, 2));
+
+        assert!(plan_swap(&windows, &[], 1, &config).unwrap().is_empty());
+        assert!(plan_swap(&windows, &names(&["Nobody"]), 0, &config).is_err());
+        assert!(plan_swap(&windows, &names(&["Scout"]), 0, &Config::default()).is_err());
+    }
+
+    #[test]
+    fn test_monitor_assignments_move_characters_across_monitors() {
+        let mut config = Config {
+            layout: LayoutMode::Fullscreen,
+            panel_height: 0,
+            ..Config::default()
+        };
+        config.monitor_assignments.insert(
+            "HDMI-A-1".to_string(),
+            vec!["Main".to_string(), "Scout".to_string()],
+        );
+        config
+            .monitor_assignments
+            .insert("DP-9".to_string(), vec!["Hauler".to_string()]);
+        let windows = vec![
+            window(1, "Main", "DP-1"),
+            window(2, "Scout", "HDMI-A-1"),
+            window(3, "Alt", "DP-1"),
+            window(4, "Hauler", "DP-1"),
+        ];
+        let placements = plan_stack(&windows, &monitors(), &config);
+
+        let monitor = |i: usize| placements[i].monitor.as_deref();
+        assert_eq!(monitor(0), Some("HDMI-A-1"));
+        assert_eq!((placements[0].x, placements[0].y), (2560, 0));
+        assert_eq!(monitor(1), Some("HDMI-A-1"));
+        assert_eq!(monitor(2), Some("DP-1"));
+        // Assigned to a monitor that isn't connected, so it stays put
+        assert_eq!(monitor(3), Some("DP-1"));
+    }
+
+    #[test]
+    fn test_fallback_monitor_takes_characters_whose_monitor_is_gone() {
+        let mut config = Config {
+            layout: LayoutMode::Fullscreen,
+            panel_height: 0,
+            fallback_monitor: Some(MonitorRef::Name("HDMI-A-1".to_string())),
+            ..Config::default()
+        };
+        config
+            .monitor_assignments
+            .insert("DP-9".to_string(), vec!["Hauler".to_string()]);
+        let mut stranded = window(3, "Alt", "DP-9");
+        stranded.geometry = Some((9000, 0, 1280, 720));
+        let windows = vec![
+            window(1, "Hauler", "DP-1"),
+            window(2, "Scout", "DP-1"),
+            stranded,
+        ];
+        let placements = plan_stack(&windows, &monitors(), &config);
+
+        let monitor = |i: usize| placements[i].monitor.as_deref();
+        assert_eq!(monitor(0), Some("HDMI-A-1"));
+        assert_eq!(monitor(1), Some("DP-1"));
+        // On a monitor that's gone and off every other one
+        assert_eq!(monitor(2), Some("HDMI-A-1"));
+    }
+
+    #[test]
+    fn test_grid_leaves_primary_fullscreen_and_tiles_the_rest_with_gaps() {
+        let mut config = Config {
+            layout: LayoutMode::Grid,
+            panel_height: 0,
+            primary_character: Some("Main".to_string()),
+            primary_monitor: Some("DP-1".to_string()),
+            grid_monitor: Some(MonitorRef::Name("HDMI-A-1".to_string())),
+            grid_gap: 10,
+            ..Config::default()
+        };
+        config.grids.insert(
+            "HDMI-A-1".to_string(),
+            GridSpec {
+                columns: Some(3),
+                gap: Some(20),
+                ..Default::default()
+            },
+        );
+        let windows = vec![
+            window(1, "Main", "HDMI-A-1"),
+            window(2, "A", "DP-1"),
+            window(3, "B", "DP-1"),
+            window(4, "C", "DP-1"),
+            window(5, "D", "DP-1"),
+        ];
+
+        let rects: Vec<_> = plan_stack(&windows, &monitors(), &config)
+            .into_iter()
+            .map(|p| (p.monitor.unwrap(), p.x, p.y, p.size))
+            .collect();
+        let on_hdmi = |x, y, w, h| ("HDMI-A-1".to_string(), x, y, Some((w, h)));
+        assert_eq!(
+            rects,
+            vec![
+                ("DP-1".to_string(), 0, 0, Some((2560, 1440))),
+                // Three columns of (1920 - 2 * 20) / 3, growing a second row
+                on_hdmi(2560, 0, 627, 530),
+                on_hdmi(3207, 0, 627, 530),
+                on_hdmi(3854, 0, 626, 530),
+                on_hdmi(2560, 550, 627, 530),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_grid_shape_follows_spec_and_grows_rows() {
+        let spec = |columns, rows| GridSpec {
+            columns,
+            rows,
+            gap: None,
+        };
+        assert_eq!(grid_shape(5, None), (3, 2));
+        assert_eq!(grid_shape(5, Some(&spec(Some(2), Some(2)))), (2, 3));
+        assert_eq!(grid_shape(2, Some(&spec(Some(2), Some(2)))), (2, 2));
+        assert_eq!(grid_shape(5, Some(&spec(None, Some(1)))), (5, 1));
+        // A gap wider than the area is dropped rather than overflowing it
+        assert_eq!(grid_cell_in((0, 0, 10, 10), (2, 1), 50, 1), (5, 0, 5, 10));
+    }
+
+    #[test]
+    fn test_pip_cells_line_each_edge_and_wrap_inwards() {
+        let area = (100, 0, 1920, 1080);
+        let size = (480, 400);
+        // Two fit down the side, so the third starts a second column
+        assert_eq!(pip_cell(area, size, PipEdge::Right, 0), (1540, 0, 480, 400));
+        assert_eq!(
+            pip_cell(area, size, PipEdge::Right, 1),
+            (1540, 400, 480, 400)
+        );
+        assert_eq!(pip_cell(area, size, PipEdge::Right, 2), (1060, 0, 480, 400));
+        assert_eq!(pip_cell(area, size, PipEdge::Left, 2), (580, 0, 480, 400));
+        assert_eq!(
+            pip_cell(area, size, PipEdge::Bottom, 1),
+            (580, 680, 480, 400)
+        );
+        assert_eq!(pip_cell(area, size, PipEdge::Top, 4), (100, 400, 480, 400));
+        // Never bigger than the area
+        assert_eq!(
+            pip_cell((0, 0, 300, 200), size, PipEdge::Right, 0),
+            (0, 0, 300, 200)
+        );
+    }
+
+    #[test]
+    fn test_fallback_area_uses_bounding_box() {
+        let config = Config::default();
+        let mut monitors = monitors();
+        monitors[1].x = -1920;
+
+        assert_eq!(fallback_area(&monitors, &config), (-1920, 0, 4480, 1440));
+        assert_eq!(fallback_area(&[], &config), (0, 0, 1920, 1080));
+    }
+
+    #[test]
+    fn test_no_monitors_use_global_config_keeps_fallback() {
+        let config = Config::default();
+
+        let monitors = resolve_monitors(Vec::new(), &config).unwrap();
+        assert!(monitors.is_empty());
+
+        let placements = plan_stack(&[window(1, "Main", "DP-1")], &monitors, &config);
+        assert_eq!(placements[0].monitor, None);
+        assert_eq!(placements[0].x, (1920 - 1036) / 2);
+    }
+
+    #[test]
+    fn test_no_monitors_abort_errors() {
+        let config = Config {
+            no_monitors: NoMonitorPolicy::Abort,
+            ..Config::default()
+        };
+
+        assert!(resolve_monitors(Vec::new(), &config).is_err());
+        assert_eq!(resolve_monitors(monitors(), &config).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_no_monitors_single_synthetic_monitor() {
+        let config = Config {
+            no_monitors: NoMonitorPolicy::SingleSynthetic,
+            display_width: 2560,
+            display_height: 1440,
+            ..Config::default()
+        };
+
+        let monitors = resolve_monitors(Vec::new(), &config).unwrap();
+        assert_eq!(monitors.len(), 1);
+        assert_eq!(
+            (
+                monitors[0].x,
+                monitors[0].y,
+                monitors[0].width,
+                monitors[0].height
+            ),
+            (0, 0, 2560, 1440)
+        );
+
+        let placements = plan_stack(&[window(1, "Main", "DP-1")], &monitors, &config);
+        assert_eq!(placements[0].monitor.as_deref(), Some("synthetic"));
+    }
+}
@@ -0,0 +1,125 @@
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+/// Days of logs kept, one file per day
+const KEEP_LOGS: usize = 7;
+
+/// Where the log files live: $XDG_STATE_HOME/nicotine, usually
+/// ~/.local/state/nicotine
+pub fn log_dir() -> Option<PathBuf> {
+    Some(
+        dirs::state_dir()
+            .or_else(dirs::data_local_dir)?
+            .join("nicotine"),
+    )
+}
+
+/// What nicotine logs at `verbosity`: info and up, -v for debug and -vv for
+/// trace. Other crates stay quiet unless RUST_LOG asks for them.
+fn directive(verbosity: u8) -> &'static str {
+    match verbosity {
+        0 => "nicotine=info",
+        1 => "nicotine=debug",
+        _ => "nicotine=trace",
+    }
+}
+
+/// Send tracing events to stderr and the log file, filtered by RUST_LOG if
+/// it's set and by `verbosity` otherwise. The file is what's left of a
+/// daemon started in the background. stdout is left to command output such
+/// as `list --json`.
+pub fn init(verbosity: u8) {
+    let filter = || {
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(directive(verbosity)))
+    };
+    let stderr = fmt::layer()
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .without_time()
+        .with_target(false)
+        .with_filter(filter());
+    let file = log_dir()
+        .and_then(|dir| log_file(dir).ok())
+        .map(|appender| {
+            fmt::layer()
+                .with_writer(appender)
+                .with_ansi(false)
+                .with_filter(filter())
+        });
+    let _ = tracing_subscriber::registry()
+        .with(stderr)
+        .with(file)
+        .try_init();
+}
+
+/// nicotine.<date>.log in `dir`, a new one each day, with nicotine.log
+/// pointing at the current one where symlinks are allowed
+fn log_file(dir: PathBuf) -> anyhow::Result<RollingFileAppender> {
+    // Created first, or pruning old files complains that it's missing
+    std::fs::create_dir_all(&dir)?;
+    let builder = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("nicotine")
+        .filename_suffix("log")
+        .max_log_files(KEEP_LOGS);
+    #[cfg(unix)]
+    let builder = builder.latest_symlink("nicotine.log");
+    Ok(builder.build(dir)?)
+}
+
+/// Run a backend's helper command, logging the command line and what it
+/// printed at debug level
+pub trait LoggedOutput {
+    fn logged_output(&mut self) -> io::Result<Output>;
+}
+
+impl LoggedOutput for Command {
+    fn logged_output(&mut self) -> io::Result<Output> {
+        let line = command_line(self);
+        let output = self.output();
+        match &output {
+            Ok(output) => tracing::debug!(
+                "$ {} -> {}\n  stdout: {}\n  stderr: {}",
+                line,
+                output.status,
+                String::from_utf8_lossy(&output.stdout).trim_end(),
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ),
+            Err(e) => tracing::debug!("$ {} -> failed to run: {}", line, e),
+        }
+        output
+    }
+}
+
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|part| part.to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity_only_raises_nicotine_levels() {
+        assert_eq!(directive(0), "nicotine=info");
+        assert_eq!(directive(1), "nicotine=debug");
+        assert_eq!(directive(2), "nicotine=trace");
+        assert_eq!(directive(5), "nicotine=trace");
+    }
+
+    #[test]
+    fn test_command_lines_are_logged_as_typed() {
+        let mut command = Command::new("wmctrl");
+        command.args(["-i", "-r", "0x01", "-e", "0,0,0,-1,-1"]);
+        assert_eq!(command_line(&command), "wmctrl -i -r 0x01 -e 0,0,0,-1,-1");
+    }
+}
@@ -0,0 +1,443 @@
+//! macOS backend for the native client: CoreGraphics lists the windows and
+//! displays, the Accessibility API reads titles and focuses, moves, resizes
+//! and minimizes them. nicotine needs the Accessibility permission (System
+//! Settings > Privacy & Security) for any of it to work.
+
+use crate::config::{Config, MonitorRef, WindowMatch};
+use crate::layout::Placement;
+use crate::window_manager::{
+    monitor_at, order_monitors, retain_on_monitors, window_center, EveWindow, Monitor,
+    WindowManager, WindowNotFound,
+};
+use anyhow::Result;
+use std::ffi::c_void;
+use std::os::raw::c_char;
+
+type CFTypeRef = *const c_void;
+type Pid = i32;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct CGPoint {
+    x: f64,
+    y: f64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct CGSize {
+    width: f64,
+    height: f64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct CGRect {
+    origin: CGPoint,
+    size: CGSize,
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    static kCFBooleanTrue: CFTypeRef;
+    static kCFBooleanFalse: CFTypeRef;
+    fn CFRetain(cf: CFTypeRef) -> CFTypeRef;
+    fn CFRelease(cf: CFTypeRef);
+    fn CFGetTypeID(cf: CFTypeRef) -> usize;
+    fn CFStringGetTypeID() -> usize;
+    fn CFBooleanGetTypeID() -> usize;
+    fn CFBooleanGetValue(boolean: CFTypeRef) -> u8;
+    fn CFStringCreateWithBytes(
+        alloc: CFTypeRef,
+        bytes: *const u8,
+        len: isize,
+        encoding: u32,
+        external: u8,
+    ) -> CFTypeRef;
+    fn CFStringGetLength(string: CFTypeRef) -> isize;
+    fn CFStringGetMaximumSizeForEncoding(len: isize, encoding: u32) -> isize;
+    fn CFStringGetCString(string: CFTypeRef, buffer: *mut c_char, size: isize, encoding: u32)
+        -> u8;
+    fn CFArrayGetCount(array: CFTypeRef) -> isize;
+    fn CFArrayGetValueAtIndex(array: CFTypeRef, index: isize) -> CFTypeRef;
+    fn CFDictionaryGetValue(dict: CFTypeRef, key: CFTypeRef) -> CFTypeRef;
+    fn CFNumberGetValue(number: CFTypeRef, kind: isize, out: *mut c_void) -> u8;
+}
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    static kCGWindowNumber: CFTypeRef;
+    static kCGWindowOwnerPID: CFTypeRef;
+    static kCGWindowOwnerName: CFTypeRef;
+    static kCGWindowLayer: CFTypeRef;
+    fn CGWindowListCopyWindowInfo(option: u32, relative_to: u32) -> CFTypeRef;
+    fn CGGetActiveDisplayList(max: u32, displays: *mut u32, count: *mut u32) -> i32;
+    fn CGDisplayBounds(display: u32) -> CGRect;
+    fn AXIsProcessTrusted() -> u8;
+    fn AXUIElementCreateApplication(pid: Pid) -> CFTypeRef;
+    fn AXUIElementCreateSystemWide() -> CFTypeRef;
+    fn AXUIElementCopyAttributeValue(
+        element: CFTypeRef,
+        attribute: CFTypeRef,
+        value: *mut CFTypeRef,
+    ) -> i32;
+    fn AXUIElementSetAttributeValue(
+        element: CFTypeRef,
+        attribute: CFTypeRef,
+        value: CFTypeRef,
+    ) -> i32;
+    fn AXUIElementPerformAction(element: CFTypeRef, action: CFTypeRef) -> i32;
+    fn AXValueCreate(kind: u32, value: *const c_void) -> CFTypeRef;
+    fn AXValueGetValue(value: CFTypeRef, kind: u32, out: *mut c_void) -> u8;
+    /// Private, but the only way from an AX window to its CGWindowID
+    fn _AXUIElementGetWindow(element: CFTypeRef, id: *mut u32) -> i32;
+}
+
+const UTF8: u32 = 0x0800_0100;
+const CF_NUMBER_SINT64: isize = 4;
+const AX_SUCCESS: i32 = 0;
+const AX_VALUE_CGPOINT: u32 = 1;
+const AX_VALUE_CGSIZE: u32 = 2;
+/// kCGWindowListOptionAll, so minimized clients are listed too
+const WINDOW_LIST_ALL: u32 = 0;
+
+/// An owned Core Foundation object, released when dropped
+struct Cf(CFTypeRef);
+
+impl Drop for Cf {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            // SAFETY: every Cf is made from a Create/Copy call, which we own
+            unsafe { CFRelease(self.0) }
+        }
+    }
+}
+
+fn cf_string(s: &str) -> Cf {
+    // SAFETY: the bytes are valid UTF-8 of the given length
+    Cf(unsafe { CFStringCreateWithBytes(std::ptr::null(), s.as_ptr(), s.len() as isize, UTF8, 0) })
+}
+
+/// A CFString's text, or None if `value` isn't a string
+fn string_value(value: CFTypeRef) -> Option<String> {
+    // SAFETY: type-checked before being read as a string
+    unsafe {
+        if value.is_null() || CFGetTypeID(value) != CFStringGetTypeID() {
+            return None;
+        }
+        let size = CFStringGetMaximumSizeForEncoding(CFStringGetLength(value), UTF8) + 1;
+        let mut buffer = vec![0u8; size as usize];
+        if CFStringGetCString(value, buffer.as_mut_ptr() as *mut c_char, size, UTF8) == 0 {
+            return None;
+        }
+        let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+        buffer.truncate(len);
+        String::from_utf8(buffer).ok()
+    }
+}
+
+fn number_value(value: CFTypeRef) -> Option<i64> {
+    let mut n = 0i64;
+    // SAFETY: the window info values read here are CFNumbers
+    (!value.is_null()
+        && unsafe { CFNumberGetValue(value, CF_NUMBER_SINT64, &mut n as *mut _ as *mut c_void) }
+            != 0)
+        .then_some(n)
+}
+
+fn copy_attribute(element: CFTypeRef, name: &str) -> Option<Cf> {
+    let name = cf_string(name);
+    let mut value: CFTypeRef = std::ptr::null();
+    // SAFETY: `value` receives a +1 reference, which Cf takes over
+    let error = unsafe { AXUIElementCopyAttributeValue(element, name.0, &mut value) };
+    (error == AX_SUCCESS && !value.is_null()).then(|| Cf(value))
+}
+
+fn set_attribute(element: CFTypeRef, name: &str, value: CFTypeRef) -> Result<()> {
+    let attribute = cf_string(name);
+    // SAFETY: plain call on live objects
+    match unsafe { AXUIElementSetAttributeValue(element, attribute.0, value) } {
+        AX_SUCCESS => Ok(()),
+        error => anyhow::bail!("Setting {} failed (AXError {})", name, error),
+    }
+}
+
+fn bool_attribute(element: CFTypeRef, name: &str) -> Option<bool> {
+    let value = copy_attribute(element, name)?;
+    // SAFETY: type-checked before being read as a boolean
+    unsafe {
+        (CFGetTypeID(value.0) == CFBooleanGetTypeID()).then(|| CFBooleanGetValue(value.0) != 0)
+    }
+}
+
+fn ax_value<T: Default>(element: CFTypeRef, name: &str, kind: u32) -> Option<T> {
+    let value = copy_attribute(element, name)?;
+    let mut out = T::default();
+    // SAFETY: `kind` names the type of T
+    (unsafe { AXValueGetValue(value.0, kind, &mut out as *mut T as *mut c_void) } != 0)
+        .then_some(out)
+}
+
+fn window_id(window: CFTypeRef) -> Option<u64> {
+    let mut id = 0u32;
+    // SAFETY: `id` is a valid u32 to write to
+    (unsafe { _AXUIElementGetWindow(window, &mut id) } == AX_SUCCESS && id != 0)
+        .then_some(id as u64)
+}
+
+fn geometry(window: CFTypeRef) -> Option<(i32, i32, u32, u32)> {
+    let origin: CGPoint = ax_value(window, "AXPosition", AX_VALUE_CGPOINT)?;
+    let size: CGSize = ax_value(window, "AXSize", AX_VALUE_CGSIZE)?;
+    Some((
+        origin.x as i32,
+        origin.y as i32,
+        size.width.max(0.0) as u32,
+        size.height.max(0.0) as u32,
+    ))
+}
+
+/// A top-level window as CoreGraphics lists it
+struct CgWindow {
+    id: u64,
+    pid: Pid,
+    owner: Option<String>,
+}
+
+fn cg_windows() -> Vec<CgWindow> {
+    // SAFETY: Copy call; the Cf releases the array
+    let list = Cf(unsafe { CGWindowListCopyWindowInfo(WINDOW_LIST_ALL, 0) });
+    if list.0.is_null() {
+        return Vec::new();
+    }
+    // SAFETY: reads from a live array of dictionaries owned by `list`
+    unsafe {
+        (0..CFArrayGetCount(list.0))
+            .filter_map(|i| {
+                let info = CFArrayGetValueAtIndex(list.0, i);
+                // Layer 0 holds normal app windows; menus, docks and the
+                // like are above it
+                if number_value(CFDictionaryGetValue(info, kCGWindowLayer))? != 0 {
+                    return None;
+                }
+                Some(CgWindow {
+                    id: number_value(CFDictionaryGetValue(info, kCGWindowNumber))? as u64,
+                    pid: number_value(CFDictionaryGetValue(info, kCGWindowOwnerPID))? as Pid,
+                    owner: string_value(CFDictionaryGetValue(info, kCGWindowOwnerName)),
+                })
+            })
+            .collect()
+    }
+}
+
+/// The Accessibility elements of one application's windows, by window id
+fn app_windows(pid: Pid) -> Vec<(u64, Cf)> {
+    // SAFETY: Create call; the Cf releases it
+    let app = Cf(unsafe { AXUIElementCreateApplication(pid) });
+    let Some(windows) = copy_attribute(app.0, "AXWindows") else {
+        return Vec::new();
+    };
+    // SAFETY: reads from a live array owned by `windows`, retaining each
+    // element kept so it outlives the array
+    unsafe {
+        (0..CFArrayGetCount(windows.0))
+            .map(|i| CFArrayGetValueAtIndex(windows.0, i))
+            .filter_map(|window| Some((window_id(window)?, Cf(CFRetain(window)))))
+            .collect()
+    }
+}
+
+pub struct MacManager {
+    discover_monitors: Option<Vec<MonitorRef>>,
+    window_match: WindowMatch,
+    monitor_order: Vec<MonitorRef>,
+}
+
+impl MacManager {
+    pub fn new(config: &Config) -> Result<Self> {
+        // SAFETY: no arguments
+        if unsafe { AXIsProcessTrusted() } == 0 {
+            anyhow::bail!(
+                "nicotine needs the Accessibility permission \
+                 (System Settings > Privacy & Security > Accessibility)"
+            );
+        }
+        Ok(Self {
+            discover_monitors: config.discover_monitors.clone(),
+            window_match: config.window_match.clone(),
+            monitor_order: config.monitor_order.clone(),
+        })
+    }
+
+    /// The client window `window_id`, with its owner's pid
+    fn element(&self, window_id: u64) -> Result<(Pid, Cf)> {
+        cg_windows()
+            .into_iter()
+            .find(|w| w.id == window_id)
+            .and_then(|w| {
+                let (_, window) = app_windows(w.pid)
+                    .into_iter()
+                    .find(|(id, _)| *id == window_id)?;
+                Some((w.pid, window))
+            })
+            .ok_or_else(|| WindowNotFound(window_id).into())
+    }
+
+    fn get_monitors_internal(&self) -> Vec<Monitor> {
+        let mut displays = [0u32; 16];
+        let mut count = 0u32;
+        // SAFETY: the max passed is the array's length
+        if unsafe { CGGetActiveDisplayList(16, displays.as_mut_ptr(), &mut count) } != 0 {
+            return Vec::new();
+        }
+        displays[..count as usize]
+            .iter()
+            .map(|&display| {
+                // SAFETY: plain call on a display id
+                let bounds = unsafe { CGDisplayBounds(display) };
+                Monitor {
+                    name: format!("Display {}", display),
+                    x: bounds.origin.x as i32,
+                    y: bounds.origin.y as i32,
+                    width: bounds.size.width as u32,
+                    height: bounds.size.height as u32,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+impl WindowManager for MacManager {
+    fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
+        let monitors = self.get_monitors().unwrap_or_default();
+        let mut pids: Vec<(Pid, Option<String>)> =
+            cg_windows().into_iter().map(|w| (w.pid, w.owner)).collect();
+        pids.sort();
+        pids.dedup();
+
+        let mut eve_windows = Vec::new();
+        for (pid, owner) in pids {
+            for (id, window) in app_windows(pid) {
+                let Some(title) =
+                    copy_attribute(window.0, "AXTitle").and_then(|t| string_value(t.0))
+                else {
+                    continue;
+                };
+                let Some(character) = self.window_match.client_name(&title, owner.as_deref())
+                else {
+                    continue;
+                };
+                let geometry = geometry(window.0);
+                eve_windows.push(EveWindow {
+                    id,
+                    title: character.to_string(),
+                    monitor: geometry
+                        .and_then(|g| {
+                            let (x, y) = window_center(g);
+                            monitor_at(&monitors, x, y)
+                        })
+                        .map(|m| m.name.clone()),
+                    geometry,
+                    pid: Some(pid as u32),
+                    class: owner.clone(),
+                });
+            }
+        }
+
+        if let Some(allowed) = &self.discover_monitors {
+            retain_on_monitors(&mut eve_windows, &monitors, allowed);
+        }
+        Ok(eve_windows)
+    }
+
+    fn activate_window(&self, window_id: u64) -> Result<()> {
+        let (pid, window) = self.element(window_id)?;
+        // SAFETY: Create call; the Cf releases it
+        let app = Cf(unsafe { AXUIElementCreateApplication(pid) });
+        // SAFETY: the CFBoolean constants are static
+        let yes = unsafe { kCFBooleanTrue };
+
+        let _ = set_attribute(window.0, "AXMinimized", unsafe { kCFBooleanFalse });
+        set_attribute(app.0, "AXFrontmost", yes)?;
+        set_attribute(window.0, "AXMain", yes)?;
+        let raise = cf_string("AXRaise");
+        // SAFETY: plain call on live objects
+        unsafe { AXUIElementPerformAction(window.0, raise.0) };
+        Ok(())
+    }
+
+    fn apply_placement(&self, placement: &Placement) -> Result<()> {
+        let (_, window) = self.element(placement.window_id)?;
+        let origin = CGPoint {
+            x: placement.x as f64,
+            y: placement.y as f64,
+        };
+        // SAFETY: the kind matches the pointed-to type
+        let position =
+            Cf(unsafe { AXValueCreate(AX_VALUE_CGPOINT, &origin as *const _ as *const c_void) });
+        set_attribute(window.0, "AXPosition", position.0)?;
+
+        if let Some((width, height)) = placement.size {
+            let size = CGSize {
+                width: width as f64,
+                height: height as f64,
+            };
+            // SAFETY: the kind matches the pointed-to type
+            let size =
+                Cf(unsafe { AXValueCreate(AX_VALUE_CGSIZE, &size as *const _ as *const c_void) });
+            set_attribute(window.0, "AXSize", size.0)?;
+        }
+        Ok(())
+    }
+
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        Ok(order_monitors(
+            self.get_monitors_internal(),
+            &self.monitor_order,
+        ))
+    }
+
+    fn get_active_window(&self) -> Result<u64> {
+        // SAFETY: Create call; the Cf releases it
+        let system = Cf(unsafe { AXUIElementCreateSystemWide() });
+        copy_attribute(system.0, "AXFocusedApplication")
+            .and_then(|app| copy_attribute(app.0, "AXFocusedWindow"))
+            .and_then(|window| window_id(window.0))
+            .ok_or_else(|| anyhow::anyhow!("No active window found"))
+    }
+
+    fn find_window_by_title(&self, title: &str) -> Result<Option<u64>> {
+        Ok(self
+            .get_eve_windows()?
+            .into_iter()
+            .find(|w| w.title == title)
+            .map(|w| w.id))
+    }
+
+    fn minimize_window(&self, window_id: u64) -> Result<()> {
+        let (_, window) = self.element(window_id)?;
+        // SAFETY: the CFBoolean constants are static
+        set_attribute(window.0, "AXMinimized", unsafe { kCFBooleanTrue })
+    }
+
+    fn restore_window(&self, window_id: u64) -> Result<()> {
+        let (_, window) = self.element(window_id)?;
+        // SAFETY: the CFBoolean constants are static
+        set_attribute(window.0, "AXMinimized", unsafe { kCFBooleanFalse })
+    }
+
+    fn is_minimized(&self, window_id: u64) -> Result<bool> {
+        let (_, window) = self.element(window_id)?;
+        Ok(bool_attribute(window.0, "AXMinimized").unwrap_or(false))
+    }
+
+    fn raise_window(&self, window_id: u64) -> Result<()> {
+        let (_, window) = self.element(window_id)?;
+        let raise = cf_string("AXRaise");
+        // SAFETY: plain call on live objects
+        match unsafe { AXUIElementPerformAction(window.0, raise.0) } {
+            AX_SUCCESS => Ok(()),
+            error => anyhow::bail!("Raising window {} failed (AXError {})", window_id, error),
+        }
+    }
+}
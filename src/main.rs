@@ -1,74 +1,94 @@
+// Much of the shared code only has callers in the Linux backends
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+
+mod bench;
+mod cli;
 mod config;
+#[cfg(target_os = "linux")]
+mod config_watch;
 mod cycle_state;
 mod daemon;
+#[cfg(target_os = "linux")]
+mod dbus_service;
+mod dry_run;
+mod effects;
+mod engage;
+mod esi;
+mod events;
+mod focus_guard;
+mod heal;
+mod hooks;
+#[cfg(target_os = "linux")]
+mod hotkeys;
+mod hotplug;
+#[cfg(target_os = "linux")]
+mod hyprland_ipc;
+mod keybinds;
+#[cfg(target_os = "linux")]
 mod keyboard_listener;
+#[cfg(target_os = "linux")]
+mod kwin_script;
+mod launch;
+mod launcher;
+mod layout;
+mod logging;
+#[cfg(target_os = "macos")]
+mod macos_manager;
+mod matcher;
+mod metrics;
+#[cfg(test)]
+mod mock_window_manager;
+mod monitor_cache;
+#[cfg(target_os = "linux")]
 mod mouse_listener;
+mod notifications;
 mod overlay;
+mod preview;
+mod report;
+mod saved_layout;
+mod snapshot;
+mod solo;
+#[cfg(target_os = "linux")]
+mod sway_ipc;
+#[cfg(target_os = "linux")]
+mod thumbnails;
+mod timers;
 mod version_check;
+mod wait;
+#[cfg(target_os = "linux")]
 mod wayland_backends;
 mod window_manager;
+#[cfg(target_os = "windows")]
+mod windows_manager;
+#[cfg(target_os = "linux")]
+mod wlr_toplevel;
+#[cfg(target_os = "linux")]
 mod x11_manager;
+#[cfg(target_os = "linux")]
+mod xrandr;
 
 use anyhow::Result;
-use config::Config;
+use clap::ArgMatches;
+use config::{Config, HelperWindowAction};
 use cycle_state::CycleState;
 use daemon::Daemon;
+#[cfg(unix)]
 use daemonize::Daemonize;
-#[allow(deprecated)]
-use nix::fcntl::{flock, FlockArg};
+use dry_run::DryRun;
+use effects::{EffectsLedger, HelperLedger};
 use overlay::run_overlay;
-use std::env;
+use saved_layout::SavedLayout;
+use solo::SoloLedger;
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
-use std::os::unix::fs::OpenOptionsExt;
-use std::os::unix::io::AsRawFd;
+use std::io::{IsTerminal, Write};
 use std::sync::{Arc, Mutex};
-use wayland_backends::{HyprlandManager, KWinManager, SwayManager};
-use window_manager::{
-    detect_display_server, detect_wayland_compositor, DisplayServer, WaylandCompositor,
-    WindowManager,
-};
-use x11_manager::X11Manager;
-
-fn create_window_manager() -> Result<Arc<dyn WindowManager>> {
-    let display_server = detect_display_server();
-
-    match display_server {
-        DisplayServer::X11 => {
-            println!("Detected X11 display server");
-            Ok(Arc::new(X11Manager::new()?))
-        }
-        DisplayServer::Wayland => {
-            let compositor = detect_wayland_compositor();
-            println!(
-                "Detected Wayland display server with {:?} compositor",
-                compositor
-            );
-
-            match compositor {
-                WaylandCompositor::Kde => {
-                    println!("Using KDE/KWin backend");
-                    Ok(Arc::new(KWinManager::new()?))
-                }
-                WaylandCompositor::Sway => {
-                    println!("Using Sway backend");
-                    Ok(Arc::new(SwayManager::new()?))
-                }
-                WaylandCompositor::Hyprland => {
-                    println!("Using Hyprland backend");
-                    Ok(Arc::new(HyprlandManager::new()?))
-                }
-                WaylandCompositor::Gnome => {
-                    anyhow::bail!("GNOME Shell is not yet supported due to restrictive window management APIs")
-                }
-                WaylandCompositor::Other => {
-                    anyhow::bail!(
-                        "Unknown Wayland compositor. Supported: KDE Plasma, Sway, Hyprland"
-                    )
-                }
-            }
-        }
-    }
-}
+use std::time::Instant;
+#[cfg(target_os = "linux")]
+use wayland_backends::{HyprlandManager, MutterManager};
+#[cfg(target_os = "linux")]
+use window_manager::{detect_wayland_compositor, WaylandCompositor};
+use window_manager::{window_monitor, WindowManager};
 
 /// Validate that the window manager can perform basic operations.
 /// This is called before daemonizing to ensure errors are visible to the user.
@@ -86,8 +106,8 @@ fn validate_window_manager(wm: &Arc<dyn WindowManager>) -> Result<()> {
             anyhow::bail!(
                 "Window manager validation failed: {}\n\
                  Make sure the required tools are installed and working.\n\
-                 For Sway: swaymsg must be available\n\
-                 For Hyprland: hyprctl must be available\n\
+                 For Sway: SWAYSOCK must point at the running sway\n\
+                 For Hyprland: HYPRLAND_INSTANCE_SIGNATURE must be set\n\
                  For KDE: wmctrl must be installed (sudo pacman -S wmctrl)",
                 e
             )
@@ -95,12 +115,243 @@ fn validate_window_manager(wm: &Arc<dyn WindowManager>) -> Result<()> {
     }
 }
 
+/// A string argument of `args`, if the subcommand has one by that name
+fn arg<'a>(args: &'a ArgMatches, name: &str) -> Option<&'a str> {
+    args.try_get_one::<String>(name)
+        .ok()
+        .flatten()
+        .map(String::as_str)
+}
+
+/// Every value of a multi-value string argument
+fn names(args: &ArgMatches, name: &str) -> Vec<String> {
+    args.try_get_many::<String>(name)
+        .ok()
+        .flatten()
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Ask a yes/no question on the terminal, taking anything but yes as no
+fn confirm(question: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("No terminal to confirm on; pass --yes");
+    }
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// The daemon command for a client invocation, if the daemon can run it
+fn daemon_command(command: &str, args: &ArgMatches) -> Option<daemon::Command> {
+    match command {
+        "forward" => Some(daemon::Command::Forward),
+        "backward" => Some(daemon::Command::Backward),
+        "cycle" => match arg(args, "direction")? {
+            "next" => Some(daemon::Command::Forward),
+            _ => Some(daemon::Command::Backward),
+        },
+        "activate" => Some(daemon::Command::Activate(
+            arg(args, "character")?.to_string(),
+        )),
+        "switch" => args
+            .get_one::<usize>("n")
+            .copied()
+            .map(daemon::Command::Switch),
+        "group" => match (arg(args, "name")?, arg(args, "direction")) {
+            ("clear", None) => Some(daemon::Command::SetGroup(None)),
+            (name, Some("forward" | "f")) => Some(daemon::Command::GroupForward(name.to_string())),
+            (name, Some("backward" | "b")) => {
+                Some(daemon::Command::GroupBackward(name.to_string()))
+            }
+            (name, Some("set")) => Some(daemon::Command::SetGroup(Some(name.to_string()))),
+            _ => None,
+        },
+        "focus-last" => Some(daemon::Command::FocusLast),
+        "swap" => Some(daemon::Command::Swap(names(args, "characters"))),
+        cmd => cmd.parse().ok().map(daemon::Command::Switch),
+    }
+}
+
+fn print_status(status: &daemon::Status, json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(status)?);
+        return Ok(());
+    }
+
+    match &status.profile {
+        Some(profile) if status.daemon => println!("Daemon: running (profile {})", profile),
+        _ if status.daemon => println!("Daemon: running"),
+        _ => println!("Daemon: stopped"),
+    }
+    if let Some(group) = &status.active_group {
+        println!("Cycling limited to group {}", group);
+    }
+    if status.combat_lock {
+        println!("Combat lock on");
+    }
+    for (i, client) in status.clients.iter().enumerate() {
+        let marker = if Some(i) == status.current_index {
+            ">"
+        } else {
+            " "
+        };
+        println!("{} {}. {}", marker, i + 1, client.title);
+    }
+    if status.clients.is_empty() {
+        println!("No EVE clients");
+    }
+    Ok(())
+}
+
+fn cycle_lock_path() -> std::path::PathBuf {
+    std::env::temp_dir().join("nicotine-cycle.lock")
+}
+
+/// Held while a cycle runs without the daemon, so a burst of presses
+/// doesn't run several at once. None if another press holds it.
+fn cycle_lock() -> Option<std::fs::File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(cycle_lock_path())
+        .ok()?;
+    file.try_lock().ok()?;
+    Some(file)
+}
+
+/// Put back what nicotine changed on other windows: dimming, borderless
+/// stacking, helper windows hidden by tidy and clients minimized by solo
+fn restore_on_stop(
+    wm: &dyn WindowManager,
+    effects: &mut EffectsLedger,
+    helpers: &mut HelperLedger,
+    solo: &mut SoloLedger,
+    focus: bool,
+) {
+    effects.restore_all(wm);
+    helpers.restore_all(wm);
+    solo.unsolo(wm, focus);
+}
+
+/// Kill every other nicotine process: the daemon, overlay and listeners.
+/// `pkill nicotine` would match the process running `stop` as well.
+fn kill_other_instances() {
+    let Ok(output) = std::process::Command::new("pgrep").arg("nicotine").output() else {
+        return;
+    };
+    let own = std::process::id();
+    let pids = String::from_utf8_lossy(&output.stdout);
+    for pid in pids.lines().filter(|pid| pid.trim().parse() != Ok(own)) {
+        let _ = std::process::Command::new("kill")
+            .arg("-9")
+            .arg(pid.trim())
+            .output();
+    }
+}
+
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let command = args.get(1).map(|s| s.as_str()).unwrap_or("");
+    let matches = cli::command(&cli::Hints::default()).get_matches();
+    logging::init(matches.get_count("verbose"));
+    let backend = arg(&matches, "backend");
+    // Through the environment so the daemon and overlay started from here
+    // read the same file
+    if let Some(path) = arg(&matches, "config") {
+        std::env::set_var("NICOTINE_CONFIG", path);
+    }
+    let Some((command, args)) = matches.subcommand() else {
+        cli::command(&cli::Hints::default()).print_help()?;
+        return Ok(());
+    };
+    // Anything clap doesn't know can only be the `nicotine N` shorthand
+    let known = cli::command(&cli::Hints::default())
+        .find_subcommand(command)
+        .is_some();
+    if !known && command.parse::<usize>().is_err() {
+        anyhow::bail!("Unknown command: {} (see nicotine --help)", command);
+    }
+
+    // Before Config::load, which may print while generating a config
+    if command == "completions" {
+        let shell = arg(args, "shell").unwrap_or("");
+        let mut groups: Vec<String> = Config::read_existing()
+            .map(|c| c.groups.into_keys().collect())
+            .unwrap_or_default();
+        groups.sort();
+        let hints = cli::Hints {
+            characters: Config::load_characters().unwrap_or_default(),
+            groups,
+        };
+        return cli::generate_completions(shell, &hints, &mut std::io::stdout());
+    }
+
+    // Needs no window manager: the extension is what makes GNOME usable
+    #[cfg(target_os = "linux")]
+    if command == "gnome-extension" {
+        match arg(args, "action") {
+            Some("install") => {
+                let dir = MutterManager::install_extension()?;
+                println!("✓ Installed GNOME Shell extension to {}", dir.display());
+                println!("  Log out and back in, then: gnome-extensions enable nicotine@nicotine");
+            }
+            Some("uninstall") => {
+                MutterManager::uninstall_extension()?;
+                println!("✓ Removed GNOME Shell extension");
+            }
+            _ => {
+                eprintln!("Usage: nicotine gnome-extension install|uninstall");
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
 
-    let config = Config::load()?;
-    let wm = create_window_manager()?;
+    if command == "status" {
+        if let Some(status) = daemon::query_status()? {
+            return print_status(&status, args.get_flag("json"));
+        }
+    }
+
+    // `layout restore` has it one level down
+    let flagged = |args: &ArgMatches| matches!(args.try_get_one::<bool>("dry-run"), Ok(Some(true)));
+    let dry_run = flagged(args) || args.subcommand().is_some_and(|(_, sub)| flagged(sub));
+
+    // Client commands go straight to a running daemon, which already has the
+    // window list, without connecting to the window manager first. A dry run
+    // is planned here instead, since the daemon would carry it out.
+    if let Some(command) = daemon_command(command, args).filter(|_| !dry_run) {
+        if let Some(stream) = daemon::connect() {
+            return daemon::request(stream, &command);
+        }
+    }
+
+    let mut config = Config::load()?;
+    if let Some(backend) = backend {
+        config.backend = backend.parse()?;
+    }
+    // Needs no window manager, only the browser
+    if command == "esi" && matches!(args.subcommand(), Some(("login", _))) {
+        let esi_config = config
+            .esi
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Add an [esi] section to config.toml first"))?;
+        let token = esi::login(esi_config)?;
+        println!("✓ Logged in as {}", token.character);
+        let mut tokens = esi::Tokens::load();
+        tokens.insert(token);
+        return tokens.save();
+    }
+
+    let wm = window_manager::detect(&config)?;
+    let wm = if dry_run {
+        dry_run::disable_side_effects(&mut config);
+        DryRun::wrap(wm)
+    } else {
+        wm
+    };
 
     match command {
         "start" => {
@@ -109,56 +360,67 @@ fn main() -> Result<()> {
             // Validate window manager before daemonizing so errors are visible
             validate_window_manager(&wm)?;
 
+            #[cfg(target_os = "linux")]
+            if config.hyprland_window_rules
+                && detect_wayland_compositor() == WaylandCompositor::Hyprland
+            {
+                if let Err(e) = HyprlandManager::install_window_rules(&config) {
+                    eprintln!("Warning: Could not install Hyprland window rules: {}", e);
+                }
+            }
+
             // Check for updates (non-blocking, silent on errors)
             if let Ok(Some((new_version, url))) = version_check::check_for_updates() {
                 version_check::print_update_notification(&new_version, &url);
             }
 
-            // Daemonize the process (safe Rust wrapper)
-            let daemonize = Daemonize::new().working_directory("/tmp").umask(0o027);
-
-            match daemonize.start() {
-                Ok(_) => {
-                    // We're now in the daemon process
-                    // Start daemon in background thread
-                    let wm_daemon = Arc::clone(&wm);
-                    let config_daemon = config.clone();
-                    let daemon_thread = std::thread::spawn(move || {
-                        let mut daemon = Daemon::new(wm_daemon, config_daemon);
-                        if let Err(e) = daemon.run() {
-                            eprintln!("Daemon error: {}", e);
-                        }
-                    });
+            // Detach from the terminal; elsewhere it stays in the foreground
+            #[cfg(unix)]
+            if let Err(e) = Daemonize::new()
+                .working_directory("/tmp")
+                .umask(0o027)
+                .start()
+            {
+                eprintln!("Failed to daemonize: {}", e);
+                std::process::exit(1);
+            }
 
-                    // Wait a bit for daemon to initialize
-                    std::thread::sleep(std::time::Duration::from_millis(100));
+            // Start daemon in background thread
+            let wm_daemon = Arc::clone(&wm);
+            let config_daemon = config.clone();
+            let daemon_thread = std::thread::spawn(move || {
+                let mut daemon = Daemon::new(wm_daemon, config_daemon);
+                if let Err(e) = daemon.run() {
+                    eprintln!("Daemon error: {}", e);
+                }
+            });
 
-                    if config.show_overlay {
-                        // Run overlay in main thread
-                        let state = Arc::new(Mutex::new(CycleState::new()));
-                        if let Ok(windows) = wm.get_eve_windows() {
-                            state.lock().unwrap().update_windows(windows);
-                        }
+            // Wait a bit for daemon to initialize
+            std::thread::sleep(std::time::Duration::from_millis(100));
 
-                        if let Err(e) =
-                            run_overlay(wm, state, config.overlay_x, config.overlay_y, config)
-                        {
-                            eprintln!("Overlay error: {}", e);
-                            std::process::exit(1);
-                        }
-                    } else {
-                        // No overlay - just keep daemon running
-                        println!("Overlay disabled - daemon running in background");
-                        daemon_thread.join().unwrap();
-                    }
+            if config.show_overlay {
+                // Run overlay in main thread
+                let state = Arc::new(Mutex::new(CycleState::from_config(&config)));
+                if let Ok(windows) = wm.get_eve_windows() {
+                    state.lock().unwrap().update_windows(windows);
                 }
-                Err(e) => {
-                    eprintln!("Failed to daemonize: {}", e);
+
+                if let Err(e) = run_overlay(wm, state, config.overlay_x, config.overlay_y, config) {
+                    eprintln!("Overlay error: {}", e);
                     std::process::exit(1);
                 }
+            } else {
+                // No overlay - just keep daemon running
+                println!("Overlay disabled - daemon running in background");
+                daemon_thread.join().unwrap();
             }
         }
 
+        #[cfg(target_os = "linux")]
+        "thumbnails" => {
+            thumbnails::run_thumbnails(wm, config)?;
+        }
+
         "daemon" => {
             println!("Starting EVE Multibox daemon...");
             let mut daemon = Daemon::new(wm, config);
@@ -167,7 +429,7 @@ fn main() -> Result<()> {
 
         "overlay" => {
             println!("Starting EVE Multibox Overlay...");
-            let state = Arc::new(Mutex::new(CycleState::new()));
+            let state = Arc::new(Mutex::new(CycleState::from_config(&config)));
 
             // Initialize windows
             if let Ok(windows) = wm.get_eve_windows() {
@@ -183,6 +445,7 @@ fn main() -> Result<()> {
         "stack" => {
             println!("Stacking EVE windows...");
             let windows = wm.get_eve_windows()?;
+            window_manager::check_wine_desktop(&*wm, &windows, &config)?;
 
             println!(
                 "Centering {} EVE clients ({}x{}) on {}x{} display",
@@ -193,86 +456,126 @@ fn main() -> Result<()> {
                 config.display_height
             );
 
-            wm.stack_windows(&windows, &config)?;
+            let started = Instant::now();
+            let report = wait::stack_in_game(&*wm, &windows, &config)?;
+            let active = wm.get_active_window().ok();
+            metrics::export(&config, &windows, active, Some(started.elapsed()));
+            for title in &report.skipped {
+                println!("Skipping {} (still at login)", title);
+            }
 
-            println!("✓ Stacked {} windows", windows.len());
+            report.print("Stacked");
+            if !report.failed.is_empty() {
+                anyhow::bail!("{} windows could not be stacked", report.failed.len());
+            }
         }
 
-        "cycle-forward" | "forward" | "f" => {
-            // Try daemon first
-            if daemon::send_command("forward").is_ok() {
+        "list" => {
+            let windows = wm.get_eve_windows()?;
+            let pinned: Vec<bool> = windows
+                .iter()
+                .map(|w| wm.is_pinned(w.id).unwrap_or(false))
+                .collect();
+            if args.get_flag("json") {
+                let identities = match &config.esi {
+                    Some(_) => {
+                        let names: Vec<String> = windows.iter().map(|w| w.title.clone()).collect();
+                        esi::Directory::load().resolve(&names).unwrap_or_else(|e| {
+                            eprintln!("ESI lookup failed: {:#}", e);
+                            Default::default()
+                        })
+                    }
+                    None => Default::default(),
+                };
+                let mut listed = Vec::new();
+                for (window, &pinned) in windows.iter().zip(&pinned) {
+                    let mut entry = serde_json::to_value(window)?;
+                    entry["pinned"] = pinned.into();
+                    if let Some(identity) = identities.get(&window.title) {
+                        entry["character_id"] = identity.character_id.into();
+                        entry["corporation"] = identity.corporation.clone().into();
+                        entry["alliance"] = identity.alliance.clone().into();
+                        entry["portrait"] = identity.portrait_url(64).into();
+                    }
+                    listed.push(entry);
+                }
+                println!("{}", serde_json::to_string_pretty(&listed)?);
                 return Ok(());
             }
 
-            // Fallback to direct mode
-
-            // Try to acquire lock, exit immediately if already running
-            let lock_file = "/tmp/nicotine-cycle.lock";
-            let file = match OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .mode(0o644)
-                .open(lock_file)
-            {
-                Ok(f) => f,
-                Err(_) => return Ok(()), // Can't get lock, skip
-            };
-
-            // Try to lock (non-blocking)
-            #[allow(deprecated)]
-            if flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock).is_err() {
-                return Ok(()); // Already running, skip this cycle
+            let active = wm.get_active_window().ok();
+            for (window, &pinned) in windows.iter().zip(&pinned) {
+                let marker = if Some(window.id) == active { ">" } else { " " };
+                let pinned = if pinned { " [pinned]" } else { "" };
+                match &window.monitor {
+                    Some(monitor) => {
+                        println!("{} {} ({}){}", marker, window.title, monitor, pinned)
+                    }
+                    None => println!("{} {}{}", marker, window.title, pinned),
+                }
             }
+            println!("{} EVE clients", windows.len());
+        }
 
-            let mut state = CycleState::new();
-            let windows = wm.get_eve_windows()?;
-
-            if windows.is_empty() {
+        "monitors" => {
+            let monitors = wm.get_layout_monitors(&config)?;
+            if args.get_flag("json") {
+                println!("{}", serde_json::to_string_pretty(&monitors)?);
                 return Ok(());
             }
+            if monitors.is_empty() {
+                println!("No monitors detected; using display_width x display_height");
+            }
+            for (i, monitor) in monitors.iter().enumerate() {
+                let (x, y, width, height) = monitor.usable_rect(&config);
+                println!(
+                    "{}: {} {}x{}+{}+{}{}",
+                    i,
+                    monitor.name,
+                    monitor.width,
+                    monitor.height,
+                    monitor.x,
+                    monitor.y,
+                    if monitor.primary { " (primary)" } else { "" }
+                );
+                println!("   usable {}x{}+{}+{}", width, height, x, y);
+            }
+        }
 
-            state.update_windows(windows);
-
-            // Sync with current active window
+        "status" => {
+            // Daemon not running: describe the live window list instead
+            let mut state = CycleState::from_config(&config);
+            state.update_windows(wm.get_eve_windows()?);
             if let Ok(active) = wm.get_active_window() {
                 state.sync_with_active(active);
             }
-
-            let skip = config.primary_character.as_deref();
-            state.cycle_forward(&*wm, config.minimize_inactive, skip)?;
-
-            // Lock is automatically released when file is dropped
+            let status = daemon::Status::of(&state, false, None);
+            print_status(&status, args.get_flag("json"))?;
         }
 
-        "cycle-backward" | "backward" | "b" => {
-            // Try daemon first
-            if daemon::send_command("backward").is_ok() {
-                return Ok(());
+        "engage" => {
+            let report = engage::engage(&*wm, &config);
+            for (step, outcome) in &report.steps {
+                match outcome {
+                    engage::Outcome::Done => println!("✓ {}", step.name()),
+                    engage::Outcome::Skipped(why) => println!("- {} skipped: {}", step.name(), why),
+                    engage::Outcome::Failed(e) => eprintln!("✗ {} failed: {:#}", step.name(), e),
+                }
             }
+            if report.failures() > 0 {
+                anyhow::bail!("engage finished with {} failed steps", report.failures());
+            }
+        }
 
-            // Fallback to direct mode
+        "forward" | "backward" | "cycle" => {
+            // Daemon not running: direct mode
 
-            // Try to acquire lock, exit immediately if already running
-            let lock_file = "/tmp/nicotine-cycle.lock";
-            let file = match OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .mode(0o644)
-                .open(lock_file)
-            {
-                Ok(f) => f,
-                Err(_) => return Ok(()), // Can't get lock, skip
+            // Skip this press if another one is still running
+            let Some(_lock) = cycle_lock() else {
+                return Ok(());
             };
 
-            // Try to lock (non-blocking)
-            #[allow(deprecated)]
-            if flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock).is_err() {
-                return Ok(()); // Already running, skip this cycle
-            }
-
-            let mut state = CycleState::new();
+            let mut state = CycleState::from_config(&config);
             let windows = wm.get_eve_windows()?;
 
             if windows.is_empty() {
@@ -287,7 +590,11 @@ fn main() -> Result<()> {
             }
 
             let skip = config.primary_character.as_deref();
-            state.cycle_backward(&*wm, config.minimize_inactive, skip)?;
+            if command == "forward" || arg(args, "direction") == Some("next") {
+                state.cycle_forward(&*wm, config.minimize_inactive, skip)?;
+            } else {
+                state.cycle_backward(&*wm, config.minimize_inactive, skip)?;
+            }
 
             // Lock is automatically released when file is dropped
         }
@@ -295,57 +602,57 @@ fn main() -> Result<()> {
         "stop" => {
             println!("Stopping Nicotine...");
 
-            // Kill all nicotine processes
-            let _ = std::process::Command::new("pkill")
-                .arg("-9")
-                .arg("nicotine")
-                .output();
+            let mut ledger = EffectsLedger::load();
+            let mut helpers = HelperLedger::load();
+            let mut solo = SoloLedger::load();
+            let focus = config.focus_on_restore;
+            restore_on_stop(&*wm, &mut ledger, &mut helpers, &mut solo, focus);
+            ledger.save()?;
+            helpers.save()?;
+            solo.save()?;
+
+            kill_other_instances();
 
             println!("✓ Nicotine stopped");
 
             // Clean up socket and lock files
-            let _ = std::fs::remove_file("/tmp/nicotine.sock");
-            let _ = std::fs::remove_file("/tmp/nicotine-cycle.lock");
+            daemon::remove_socket();
+            let _ = std::fs::remove_file(cycle_lock_path());
+        }
+
+        "activate" => {
+            let character = arg(args, "character").unwrap_or_default();
+
+            // Daemon not running: direct mode
+            let mut state = CycleState::new();
+            state.update_windows(wm.get_eve_windows()?);
+            if let Ok(active) = wm.get_active_window() {
+                state.sync_with_active(active);
+            }
+            state.activate(character, &*wm, config.minimize_inactive)?;
         }
 
         "group" => {
-            // Usage: nicotine group <name> forward|backward
-            let group_name = args.get(2).map(|s| s.as_str());
-            let direction = args.get(3).map(|s| s.as_str());
+            // Usage: nicotine group <name> forward|backward|set, nicotine group clear
+            let group_name = arg(args, "name");
+            let direction = arg(args, "direction");
+            let groups = launcher::resolve_groups(&config, &*wm);
 
             match (group_name, direction) {
                 (Some(name), Some("forward") | Some("f")) => {
                     // Check if group exists
-                    if !config.groups.contains_key(name) {
+                    if !groups.contains_key(name) {
                         eprintln!("Unknown group: {}", name);
-                        eprintln!("Available groups: {:?}", config.groups.keys().collect::<Vec<_>>());
+                        eprintln!("Available groups: {:?}", groups.keys().collect::<Vec<_>>());
                         std::process::exit(1);
                     }
 
-                    // Try daemon first
-                    if daemon::send_command(&format!("group-forward:{}", name)).is_ok() {
+                    // Daemon not running: direct mode
+                    let Some(_lock) = cycle_lock() else {
                         return Ok(());
-                    }
-
-                    // Fallback to direct mode
-                    let lock_file = "/tmp/nicotine-cycle.lock";
-                    let file = match OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .truncate(true)
-                        .mode(0o644)
-                        .open(lock_file)
-                    {
-                        Ok(f) => f,
-                        Err(_) => return Ok(()),
                     };
 
-                    #[allow(deprecated)]
-                    if flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock).is_err() {
-                        return Ok(());
-                    }
-
-                    let mut state = CycleState::new();
+                    let mut state = CycleState::from_config(&config);
                     let windows = wm.get_eve_windows()?;
 
                     if windows.is_empty() {
@@ -358,41 +665,23 @@ fn main() -> Result<()> {
                         state.sync_with_active(active);
                     }
 
-                    let group_members = config.groups.get(name).unwrap();
+                    let group_members = groups.get(name).unwrap();
                     state.cycle_group_forward(&*wm, config.minimize_inactive, group_members)?;
                 }
                 (Some(name), Some("backward") | Some("b")) => {
                     // Check if group exists
-                    if !config.groups.contains_key(name) {
+                    if !groups.contains_key(name) {
                         eprintln!("Unknown group: {}", name);
-                        eprintln!("Available groups: {:?}", config.groups.keys().collect::<Vec<_>>());
+                        eprintln!("Available groups: {:?}", groups.keys().collect::<Vec<_>>());
                         std::process::exit(1);
                     }
 
-                    // Try daemon first
-                    if daemon::send_command(&format!("group-backward:{}", name)).is_ok() {
+                    // Daemon not running: direct mode
+                    let Some(_lock) = cycle_lock() else {
                         return Ok(());
-                    }
-
-                    // Fallback to direct mode
-                    let lock_file = "/tmp/nicotine-cycle.lock";
-                    let file = match OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .truncate(true)
-                        .mode(0o644)
-                        .open(lock_file)
-                    {
-                        Ok(f) => f,
-                        Err(_) => return Ok(()),
                     };
 
-                    #[allow(deprecated)]
-                    if flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock).is_err() {
-                        return Ok(());
-                    }
-
-                    let mut state = CycleState::new();
+                    let mut state = CycleState::from_config(&config);
                     let windows = wm.get_eve_windows()?;
 
                     if windows.is_empty() {
@@ -405,15 +694,18 @@ fn main() -> Result<()> {
                         state.sync_with_active(active);
                     }
 
-                    let group_members = config.groups.get(name).unwrap();
+                    let group_members = groups.get(name).unwrap();
                     state.cycle_group_backward(&*wm, config.minimize_inactive, group_members)?;
                 }
+                (Some(_), Some("set")) | (Some("clear"), None) => {
+                    anyhow::bail!("Daemon not running; the active group only lasts while it runs");
+                }
                 (Some(name), None) | (Some(name), Some(_)) => {
-                    eprintln!("Usage: nicotine group {} forward|backward", name);
+                    eprintln!("Usage: nicotine group {} forward|backward|set", name);
                     std::process::exit(1);
                 }
                 (None, _) => {
-                    if config.groups.is_empty() {
+                    if groups.is_empty() {
                         println!("No groups configured.");
                         println!("Add groups to ~/.config/nicotine/config.toml:");
                         println!();
@@ -422,11 +714,11 @@ fn main() -> Result<()> {
                         println!("combat = [\"DPS1\", \"DPS2\", \"Logi\"]");
                     } else {
                         println!("Available groups:");
-                        for (name, members) in &config.groups {
+                        for (name, members) in &groups {
                             println!("  {} = {:?}", name, members);
                         }
                         println!();
-                        println!("Usage: nicotine group <name> forward|backward");
+                        println!("Usage: nicotine group <name> forward|backward|set");
                     }
                 }
             }
@@ -436,91 +728,542 @@ fn main() -> Result<()> {
             Config::save_default()?;
         }
 
-        // Handle switch command or numeric shorthand
-        cmd => {
-            // Check for "switch N" format
-            let target = if cmd == "switch" {
-                args.get(2).and_then(|s| s.parse::<usize>().ok())
+        "config" => match args.subcommand() {
+            Some(("init", _)) => Config::save_default()?,
+            // Config::load has already rejected anything invalid
+            Some(("check", _)) => println!("✓ {} is valid", Config::config_path().display()),
+            Some(("show", show)) => println!("{}", config.render(show.get_flag("json"))?),
+            _ => unreachable!("clap requires a config subcommand"),
+        },
+
+        "refresh" => {
+            if daemon::send_command(&daemon::Command::Refresh).is_ok() {
+                println!("✓ Daemon window list refreshed");
             } else {
-                // Check if it's just a number (shorthand)
-                cmd.parse::<usize>().ok()
+                // Without the daemon every command reads the live list anyway
+                let windows = wm.get_eve_windows()?;
+                println!("Daemon not running; found {} EVE windows", windows.len());
+            }
+        }
+
+        "focus-last" => {
+            // Only reached without a daemon, which is what keeps the history
+            anyhow::bail!("Daemon not running; focus-last needs it to track focus");
+        }
+
+        "reload" => {
+            let Some(stream) = daemon::connect() else {
+                anyhow::bail!("Daemon not running; config.toml is read when it starts");
             };
+            daemon::request(stream, &daemon::Command::Reload)?;
+            println!("✓ Daemon reloaded config.toml");
+        }
+
+        "profile" => {
+            let name = arg(args, "name").unwrap_or_default();
+            // Fail here rather than in the daemon if the profile doesn't exist
+            Config::load_profile(Some(name))?;
+            let Some(stream) = daemon::connect() else {
+                anyhow::bail!(
+                    "Daemon not running; set NICOTINE_PROFILE={} or profile in config.toml instead",
+                    name
+                );
+            };
+            daemon::request(stream, &daemon::Command::Profile(name.to_string()))?;
+            println!("✓ Switched to profile {}", name);
+        }
 
-            if let Some(target) = target {
-                // Try daemon first
-                if daemon::send_command(&format!("switch:{}", target)).is_ok() {
-                    return Ok(());
+        "wait-for-windows" => {
+            let verbose = args.get_count("verbose") > 0;
+            let expected = args.get_one::<usize>("count").copied().unwrap_or(1);
+
+            let mut schedule = wait::PollSchedule::from_config(&config);
+            if let Some(&timeout) = args.get_one::<u64>("secs") {
+                schedule.timeout = std::time::Duration::from_secs(timeout);
+            }
+
+            println!("Waiting for {} EVE clients...", expected);
+            let outcome = wait::wait_for_windows(&*wm, &config, expected, &schedule, verbose)?;
+
+            if outcome.timed_out {
+                eprintln!(
+                    "Timed out after {}s: stacked {} of {} ({} still at login)",
+                    schedule.timeout.as_secs(),
+                    outcome.stacked,
+                    expected,
+                    outcome.deferred
+                );
+                std::process::exit(1);
+            }
+            println!("✓ Stacked {} windows", outcome.stacked);
+        }
+
+        "launch" => {
+            let verbose = args.get_count("verbose") > 0;
+            let accounts = launch::select(&config.launch, &names(args, "accounts"))?;
+            let schedule = wait::PollSchedule::from_config(&config);
+
+            // Subscribed before launching so no client's window is missed
+            let events = wm.subscribe_events()?;
+            let mut known: HashSet<u64> = wm.get_eve_windows()?.iter().map(|w| w.id).collect();
+            let mut opened = 0;
+            for account in accounts {
+                println!("Launching {}...", account.name);
+                launch::spawn(&config.launch, account)?;
+                let window = launch::wait_for_new_window(
+                    &*wm,
+                    &known,
+                    events.as_ref(),
+                    schedule.timeout,
+                    schedule.interval,
+                )?;
+                match window {
+                    Some(window) => {
+                        println!("✓ {} opened a client", account.name);
+                        known.insert(window.id);
+                        opened += 1;
+                    }
+                    None => eprintln!(
+                        "No client from {} after {}s",
+                        account.name,
+                        schedule.timeout.as_secs()
+                    ),
                 }
+            }
+            if opened == 0 {
+                anyhow::bail!("No clients opened");
+            }
+
+            println!("Waiting for {} EVE clients to log in...", known.len());
+            let outcome = wait::wait_for_windows(&*wm, &config, known.len(), &schedule, verbose)?;
+            if outcome.timed_out {
+                eprintln!(
+                    "Timed out after {}s: stacked {} of {} ({} still at login)",
+                    schedule.timeout.as_secs(),
+                    outcome.stacked,
+                    known.len(),
+                    outcome.deferred
+                );
+                std::process::exit(1);
+            }
+            println!("✓ Stacked {} windows", outcome.stacked);
+        }
+
+        "dim" => {
+            let opacity = args.get_one::<f64>("opacity").copied().unwrap_or(0.6);
+            let active = wm.get_active_window().unwrap_or(0);
+            let windows = wm.get_eve_windows()?;
+
+            let mut ledger = EffectsLedger::load();
+            for window in windows.iter().filter(|w| w.id != active) {
+                ledger.apply(&*wm, window.id, opacity)?;
+            }
+            if active != 0 {
+                ledger.restore(&*wm, active)?;
+            }
+            ledger.save()?;
+
+            println!("✓ Dimmed inactive EVE windows to {:.0}%", opacity * 100.0);
+        }
+
+        "heal" => {
+            let windows = wm.get_eve_windows()?;
+            let report = heal::heal(&*wm, &windows, &config)?;
+
+            for repair in &report.healed {
+                println!("Healed {} ({:?})", repair.title, repair.drift);
+            }
+            if report.healed.is_empty() && report.actions.failed.is_empty() {
+                println!("✓ All {} windows are where they belong", windows.len());
+            } else {
+                report.actions.print("Healed");
+            }
+        }
+
+        "preview" => {
+            let windows = wm.get_eve_windows()?;
+            let monitors = wm.get_layout_monitors(&config)?;
+            let placements = layout::plan_stack(&windows, &monitors, &config);
 
-                // Fallback to direct mode
-                let lock_file = "/tmp/nicotine-cycle.lock";
-                let file = match OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .mode(0o644)
-                    .open(lock_file)
-                {
-                    Ok(f) => f,
-                    Err(_) => return Ok(()),
+            if monitors.is_empty() {
+                println!("(no monitors detected; showing the fallback placement only)");
+            }
+            println!("{}", preview::render(&monitors, &placements, &windows, 80));
+        }
+
+        "rotate" => {
+            let steps: isize = if arg(args, "direction") == Some("back") {
+                -1
+            } else {
+                1
+            };
+            // The daemon would really move them, so a dry run is planned here
+            if let Some(stream) = daemon::connect().filter(|_| !dry_run) {
+                return daemon::request(stream, &daemon::Command::Rotate(steps));
+            }
+
+            // Without the daemon there's no offset to track; just move
+            let windows = wm.get_eve_windows()?;
+            let placements = layout::plan_rotation(&windows, steps);
+            for placement in &placements {
+                wm.apply_placement(placement)?;
+            }
+            println!("✓ Rotated {} windows", placements.len());
+        }
+
+        "swap" => {
+            let names = names(args, "characters");
+            wm.swap_windows(&names, &config)?;
+            println!("✓ Swapped windows");
+        }
+
+        "pin" | "unpin" => {
+            let pinned = command == "pin";
+            let title = wm.pin_character(arg(args, "character"), pinned)?;
+            if pinned {
+                println!("✓ Pinned {}", title);
+            } else {
+                println!("✓ Unpinned {}", title);
+            }
+        }
+
+        "esi" => {
+            if config.esi.is_none() {
+                anyhow::bail!("Add an [esi] section to config.toml first");
+            }
+            let names: Vec<String> = wm.get_eve_windows()?.into_iter().map(|w| w.title).collect();
+            let identities = esi::Directory::load().resolve(&names)?;
+            let tokens = esi::Tokens::load();
+            for name in &names {
+                let Some(identity) = identities.get(name) else {
+                    println!("{}: not found on ESI", name);
+                    continue;
                 };
+                let alliance = identity
+                    .alliance
+                    .as_ref()
+                    .map(|a| format!(" / {}", a))
+                    .unwrap_or_default();
+                let logged_in = if tokens.contains(name) {
+                    " [logged in]"
+                } else {
+                    ""
+                };
+                println!(
+                    "{} ({}): {}{}{}",
+                    name, identity.character_id, identity.corporation, alliance, logged_in
+                );
+            }
+        }
 
-                #[allow(deprecated)]
-                if flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock).is_err() {
-                    return Ok(());
+        "timers" => {
+            let timers = match daemon::query_status()? {
+                Some(status) => status.timers,
+                None => {
+                    let esi_config = config.esi.as_ref().ok_or_else(|| {
+                        anyhow::anyhow!("Add an [esi] section to config.toml first")
+                    })?;
+                    let mut timers = timers::fetch(esi_config)?;
+                    timers::attach_windows(&mut timers, &wm.get_eve_windows().unwrap_or_default());
+                    timers
                 }
+            };
+            if args.get_flag("json") {
+                println!("{}", serde_json::to_string_pretty(&timers)?);
+                return Ok(());
+            }
+            let now = esi::unix_now();
+            for timer in &timers {
+                let running = if timer.window_id.is_some() {
+                    ""
+                } else {
+                    " (not running)"
+                };
+                println!(
+                    "{:>8}  {}{}: {}",
+                    timers::remaining(timer.ends, now),
+                    timer.character,
+                    running,
+                    timer.describe()
+                );
+            }
+            if timers.is_empty() {
+                println!("No timers (log characters in with nicotine esi login)");
+            }
+        }
 
-                let mut state = CycleState::new();
-                let windows = wm.get_eve_windows()?;
+        "urgent" => {
+            let window = wm.character_window(arg(args, "character"))?;
+            wm.mark_urgent(window.id)?;
+            println!("✓ Flagged {}", window.title);
+        }
+
+        "quit-all" => {
+            let group = arg(args, "group");
+            let groups = match group {
+                Some(_) => launcher::resolve_groups(&config, &*wm),
+                None => HashMap::new(),
+            };
+            let windows = launch::quit_targets(&*wm, &groups, group)?;
 
-                if windows.is_empty() {
-                    return Ok(());
+            if windows.is_empty() {
+                println!("No EVE clients to close");
+            } else {
+                for window in &windows {
+                    println!("  {}", window.title);
                 }
+                let question = format!("Close these {} clients?", windows.len());
+                if dry_run || args.get_flag("yes") || confirm(&question)? {
+                    launch::close_clients(&*wm, &windows).print("Closing");
+                } else {
+                    println!("Nothing closed");
+                }
+            }
+        }
 
-                state.update_windows(windows);
+        "layout" => match args.subcommand() {
+            Some(("save", save)) => {
+                let name = arg(save, "name").unwrap_or_default();
+                let windows = wm.get_eve_windows()?;
+                let (layout, unknown) = SavedLayout::capture(&windows);
+                for title in &unknown {
+                    println!("Skipping {} (backend doesn't report its geometry)", title);
+                }
+                if layout.windows.is_empty() {
+                    anyhow::bail!("No EVE windows to save");
+                }
+                let path = layout.save(name)?;
+                println!(
+                    "✓ Saved {} windows as {} ({})",
+                    layout.windows.len(),
+                    name,
+                    path.display()
+                );
+            }
+            Some(("restore", restore)) => {
+                let name = arg(restore, "name").unwrap_or_default();
+                let layout = SavedLayout::load(name)?;
+                let windows = wm.get_eve_windows()?;
+                for character in layout.windows.keys() {
+                    if !windows.iter().any(|w| &w.title == character) {
+                        println!("Not running: {}", character);
+                    }
+                }
+                let report = layout.restore(&*wm, &windows);
+                report.print("Restored");
+                if !report.failed.is_empty() {
+                    anyhow::bail!("{} windows could not be restored", report.failed.len());
+                }
+            }
+            _ => unreachable!("clap requires a layout subcommand"),
+        },
 
-                if let Ok(active) = wm.get_active_window() {
-                    state.sync_with_active(active);
+        "solo" => {
+            let windows = wm.get_eve_windows()?;
+            let target = match arg(args, "character") {
+                Some(name) => windows.iter().find(|w| w.title == name),
+                None => {
+                    let active = wm.get_active_window().unwrap_or(0);
+                    windows.iter().find(|w| w.id == active)
                 }
+            };
+            let target = match target {
+                Some(target) => target,
+                None => {
+                    eprintln!("No EVE client to solo (give a character name or focus one)");
+                    std::process::exit(1);
+                }
+            };
 
-                let character_order = Config::load_characters();
-                state.switch_to(
-                    target,
-                    &*wm,
-                    config.minimize_inactive,
-                    character_order.as_deref(),
-                )?;
+            let monitors = wm.get_monitors()?;
+            // A window the backend can't place goes with the monitor in use
+            let active = wm.get_active_monitor().ok().flatten();
+            let monitor = match window_monitor(target, &monitors)
+                .or(active.as_ref())
+                .or_else(|| monitors.first())
+            {
+                Some(monitor) => monitor,
+                None => anyhow::bail!("No monitors detected, can't tell what to solo"),
+            };
+            let others = if config.solo_minimize_all {
+                wm.get_process_windows()?
             } else {
-                println!();
-                println!("🚬 N I C O T I N E 🚬");
-                println!();
-                println!("Questions or suggestions?");
-                println!("Reach out to isomerc on Discord or open a Github issue");
-                println!();
-                println!("Usage:");
-                println!("  nicotine start         - Start everything (daemon + overlay)");
-                println!("  nicotine stop          - Stop all Nicotine processes");
-                println!("  nicotine stack         - Stack all EVE windows");
-                println!("  nicotine forward       - Cycle forward");
-                println!("  nicotine backward      - Cycle backward");
-                println!("  nicotine switch N      - Switch to client N (targeted cycling)");
-                println!("  nicotine N             - Shorthand for switch N");
-                println!("  nicotine init-config   - Create default config.toml");
-                println!();
-                println!("Group cycling:");
-                println!("  nicotine group         - List configured groups");
-                println!("  nicotine group <name> forward  - Cycle forward within group");
-                println!("  nicotine group <name> backward - Cycle backward within group");
-                println!();
-                println!("Advanced:");
-                println!("  nicotine daemon        - Start daemon only");
-                println!("  nicotine overlay       - Start overlay only");
-                println!();
-                println!("Quick start:");
-                println!("  nicotine start         # Starts in background automatically");
+                Vec::new()
+            };
+            let minimize = solo::solo_targets(
+                target,
+                monitor,
+                &monitors,
+                &windows,
+                &others,
+                config.solo_minimize_all,
+            );
+
+            // Bring back anything an earlier solo hid before hiding again
+            let mut ledger = SoloLedger::load();
+            ledger.unsolo(&*wm, config.focus_on_restore);
+            let report = ledger.solo(&*wm, target, monitor, &minimize)?;
+            ledger.save()?;
+
+            println!("✓ Solo {} on {}", target.title, monitor.name);
+            report.print("Minimized");
+        }
+
+        "minimize-all" => {
+            let windows = wm.get_eve_windows()?;
+            let active = wm.get_active_window().ok();
+            let minimize =
+                solo::minimize_all_targets(&windows, active, config.minimize_all_keep_active);
+
+            // Shares the solo ledger, so unsolo brings everything back
+            let mut ledger = SoloLedger::load();
+            let report = ledger.minimize_all(&*wm, &minimize);
+            ledger.save()?;
+            report.print("Minimized");
+        }
+
+        "unsolo" => {
+            let mut ledger = SoloLedger::load();
+            let restored = ledger.unsolo(&*wm, config.focus_on_restore);
+            ledger.save()?;
+
+            println!("✓ Restored {} windows", restored);
+        }
+
+        #[cfg(target_os = "linux")]
+        "hyprland-rules" => match arg(args, "action") {
+            Some("install") => {
+                HyprlandManager::install_window_rules(&config)?;
+                println!("✓ Installed Hyprland window rules:");
+                for rule in HyprlandManager::window_rules(&config) {
+                    println!("  windowrulev2 = {}", rule);
+                }
+            }
+            Some("uninstall") => {
+                HyprlandManager::uninstall_window_rules(&config)?;
+                println!("✓ Removed Hyprland window rules");
             }
+            _ => {
+                eprintln!("Usage: nicotine hyprland-rules install|uninstall");
+                std::process::exit(1);
+            }
+        },
+
+        #[cfg(not(target_os = "linux"))]
+        "thumbnails" | "hyprland-rules" | "gnome-extension" => {
+            anyhow::bail!("{} needs Linux", command);
+        }
+
+        "tidy" => {
+            if config.helper_windows == HelperWindowAction::Leave {
+                println!("helper_windows is \"leave\" in config.toml; nothing to do");
+                return Ok(());
+            }
+            let characters = wm.get_eve_windows()?;
+            let helpers = effects::helper_windows(&wm.get_process_windows()?, &characters);
+            let offscreen = effects::offscreen_position(&wm.get_monitors()?);
+
+            let mut ledger = HelperLedger::load();
+            let mut tidied = 0;
+            for helper in &helpers {
+                if ledger.tidy(&*wm, helper, config.helper_windows, offscreen)? {
+                    tidied += 1;
+                }
+            }
+            ledger.save()?;
+
+            println!("✓ Tidied {} launcher helper windows", tidied);
+        }
+
+        "reset" => {
+            let mut ledger = EffectsLedger::load();
+            let restored = ledger.restore_all(&*wm);
+            ledger.save()?;
+
+            let mut helpers = HelperLedger::load();
+            let returned = helpers.restore_all(&*wm);
+            helpers.save()?;
+
+            println!("✓ Restored opacity and decorations on {} windows", restored);
+            if returned > 0 {
+                println!("✓ Brought back {} helper windows", returned);
+            }
+        }
+
+        "bench" => {
+            let iterations = args.get_one::<usize>("iterations").copied().unwrap_or(20);
+            let timings = bench::run(&*wm, &config, iterations)?;
+            bench::print_report(&timings, iterations);
+        }
+
+        // Switch, or its numeric shorthand
+        _ => {
+            let target = match args.try_get_one::<usize>("n") {
+                Ok(Some(&n)) => n,
+                _ => command.parse::<usize>()?,
+            };
+
+            // Daemon not running: direct mode
+            let Some(_lock) = cycle_lock() else {
+                return Ok(());
+            };
+
+            let mut state = CycleState::from_config(&config);
+            let windows = wm.get_eve_windows()?;
+
+            if windows.is_empty() {
+                return Ok(());
+            }
+
+            state.update_windows(windows);
+
+            if let Ok(active) = wm.get_active_window() {
+                state.sync_with_active(active);
+            }
+
+            let character_order = Config::load_characters();
+            state.switch_to(
+                target,
+                &*wm,
+                config.minimize_inactive,
+                character_order.as_deref(),
+            )?;
         }
     }
 
+    if dry_run {
+        println!("Dry run: no windows were changed");
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_window_manager::{window, MockWindowManager};
+
+    #[test]
+    fn test_stop_restores_effects_helper_windows_and_solo() {
+        let alt = window(2, "Alt");
+        let launcher = window(3, "EVE Launcher");
+        let wm = MockWindowManager::new(vec![window(1, "Main"), alt.clone(), launcher.clone()]);
+        let mut effects = EffectsLedger::default();
+        effects.apply(&wm, 1, 0.5).unwrap();
+        let mut helpers = HelperLedger::default();
+        helpers
+            .tidy(&wm, &launcher, HelperWindowAction::Minimize, (0, 0))
+            .unwrap();
+        let mut solo = SoloLedger::default();
+        solo.minimize_all(&wm, &[&alt]);
+
+        restore_on_stop(&wm, &mut effects, &mut helpers, &mut solo, false);
+
+        assert_eq!(wm.get_opacity(1).unwrap(), None);
+        assert!(!wm.is_minimized(2).unwrap());
+        assert!(!wm.is_minimized(3).unwrap());
+        // Nothing left for a later stop, reset or unsolo to undo
+        assert_eq!(effects.restore_all(&wm), 0);
+        assert_eq!(helpers.restore_all(&wm), 0);
+        assert_eq!(solo.unsolo(&wm, false), 0);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::config::WindowMatch;
+
+impl WindowMatch {
+    /// Character name from a raw client title, or None if the window isn't
+    /// an in-game client. Covers the launcher and the login/character select
+    /// screen, where the client is titled just "EVE" until a character is
+    /// chosen. `class` is the window's WM_CLASS or app_id; backends that
+    /// can't report one skip the wm_class / app_id check.
+    pub fn character_name<'a>(&self, title: &'a str, class: Option<&str>) -> Option<&'a str> {
+        if is_wine_virtual_desktop(title, None)
+            || self.exclude.iter().any(|p| capture(p, title).is_some())
+            || !self.class_matches(class)
+        {
+            return None;
+        }
+
+        let name = match (&self.title_regex, &self.title_pattern) {
+            (Some(regex), _) => regex.0.captures(title)?.name("name")?.as_str(),
+            (None, Some(pattern)) => capture(pattern, title)??,
+            (None, None) => title.strip_prefix(self.title_prefix.as_str())?,
+        };
+        Some(name.trim()).filter(|name| !name.is_empty())
+    }
+
+    fn class_matches(&self, class: Option<&str>) -> bool {
+        let mut wanted = self.wm_class.iter().chain(&self.app_id).peekable();
+        wanted.peek().is_none() || class.is_none_or(|class| wanted.any(|w| w == class))
+    }
+
+    /// Name for a window being discovered. With wm_class or app_id set and
+    /// the class known, the class alone decides whether it's a client and
+    /// the title only supplies the name, so a client at character select
+    /// is found too, named by its whole title ("EVE").
+    pub fn client_name<'a>(&self, title: &'a str, class: Option<&str>) -> Option<&'a str> {
+        let by_class = class.is_some() && (self.wm_class.is_some() || self.app_id.is_some());
+        if !by_class {
+            return self.character_name(title, class);
+        }
+        if !self.class_matches(class)
+            || is_wine_virtual_desktop(title, class)
+            || self.exclude.iter().any(|p| capture(p, title).is_some())
+        {
+            return None;
+        }
+        self.character_name(title, class)
+            .or(Some(title.trim()))
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Character name for a window being discovered. With match_property
+    /// set, `marker` (that property's value on the window) decides whether
+    /// it's a client instead of the title; the name still comes from the
+    /// title where it has one, else it's the whole title.
+    pub fn discovered_name<'a>(
+        &self,
+        title: &'a str,
+        class: Option<&str>,
+        marker: Option<&str>,
+        match_property: Option<&(String, String)>,
+    ) -> Option<&'a str> {
+        let Some((_, expected)) = match_property else {
+            return self.client_name(title, class);
+        };
+        if marker != Some(expected.as_str()) {
+            return None;
+        }
+        self.character_name(title, None)
+            .or(Some(title.trim()))
+            .filter(|name| !name.is_empty())
+    }
+}
+
+/// Match `text` against `pattern`, where `*` is any run of characters and
+/// `{name}` is one that's captured. Some(None) is a match without a capture.
+fn capture<'a>(pattern: &str, text: &'a str) -> Option<Option<&'a str>> {
+    fn matches(pattern: &str, text: &str, pos: usize, name: &mut Option<(usize, usize)>) -> bool {
+        let (rest, captured) = if let Some(rest) = pattern.strip_prefix("{name}") {
+            (rest, true)
+        } else if let Some(rest) = pattern.strip_prefix('*') {
+            (rest, false)
+        } else {
+            return match pattern.chars().next() {
+                None => pos == text.len(),
+                Some(c) => {
+                    text[pos..].starts_with(c)
+                        && matches(&pattern[c.len_utf8()..], text, pos + c.len_utf8(), name)
+                }
+            };
+        };
+
+        for end in (pos..=text.len()).filter(|&end| text.is_char_boundary(end)) {
+            if matches(rest, text, end, name) {
+                if captured {
+                    *name = Some((pos, end));
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    let mut name = None;
+    matches(pattern, text, 0, &mut name).then(|| name.map(|(start, end)| &text[start..end]))
+}
+
+/// Whether a window is a Wine virtual desktop: explorer.exe's single root
+/// window, titled "<name> - Wine desktop", that every client runs inside.
+/// The compositor then sees that one window instead of the EVE clients.
+pub fn is_wine_virtual_desktop(title: &str, class: Option<&str>) -> bool {
+    title.ends_with(" - Wine desktop")
+        || class.is_some_and(|c| c.eq_ignore_ascii_case("explorer.exe"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, TitleRegex};
+
+    fn character_name(title: &str) -> Option<&str> {
+        WindowMatch::default().character_name(title, None)
+    }
+
+    fn discovered_name<'a>(
+        title: &'a str,
+        marker: Option<&str>,
+        match_property: Option<&(String, String)>,
+    ) -> Option<&'a str> {
+        WindowMatch::default().discovered_name(title, None, marker, match_property)
+    }
+
+    #[test]
+    fn test_character_name_in_game() {
+        assert_eq!(character_name("EVE - Pilot One"), Some("Pilot One"));
+    }
+
+    #[test]
+    fn test_character_name_rejects_login_and_launcher() {
+        assert_eq!(character_name("EVE"), None);
+        assert_eq!(character_name("EVE - "), None);
+        assert_eq!(character_name("EVE Launcher"), None);
+        assert_eq!(character_name("EVE - Launcher"), None);
+        assert_eq!(character_name("Firefox"), None);
+    }
+
+    #[test]
+    fn test_wine_virtual_desktop_signature() {
+        assert!(is_wine_virtual_desktop("Default - Wine desktop", None));
+        assert!(is_wine_virtual_desktop(
+            "EVE - Wine desktop",
+            Some("steam_app_8500")
+        ));
+        assert!(is_wine_virtual_desktop("", Some("explorer.exe")));
+        // A real client, even one titled after its desktop, isn't the desktop
+        assert!(!is_wine_virtual_desktop(
+            "EVE - Pilot One",
+            Some("steam_app_8500")
+        ));
+        assert!(!is_wine_virtual_desktop("EVE - Wine desktop fan", None));
+        // A desktop named EVE isn't a character
+        assert_eq!(character_name("EVE - Wine desktop"), None);
+    }
+
+    #[test]
+    fn test_marker_replaces_title_matching() {
+        let marker = ("_NICOTINE_CLIENT".to_string(), "eve".to_string());
+
+        assert_eq!(
+            discovered_name("Main", Some("eve"), Some(&marker)),
+            Some("Main")
+        );
+        assert_eq!(
+            discovered_name("EVE - Alt", Some("eve"), Some(&marker)),
+            Some("Alt")
+        );
+        // A title that looks like a client is ignored without the marker
+        assert_eq!(discovered_name("EVE - Alt", None, Some(&marker)), None);
+        assert_eq!(
+            discovered_name("EVE - Alt", Some("other"), Some(&marker)),
+            None
+        );
+        assert_eq!(discovered_name("EVE - Alt", None, None), Some("Alt"));
+    }
+
+    #[test]
+    fn test_custom_title_rules() {
+        let rules = WindowMatch {
+            title_pattern: Some("星战前夜 * - {name}".to_string()),
+            exclude: vec!["*登录*".to_string()],
+            ..WindowMatch::default()
+        };
+        assert_eq!(
+            rules.character_name("星战前夜 晨曦 - 飞行员", None),
+            Some("飞行员")
+        );
+        assert_eq!(rules.character_name("EVE - Pilot One", None), None);
+        assert_eq!(rules.character_name("星战前夜 晨曦 - 登录", None), None);
+
+        let rules = WindowMatch {
+            title_prefix: "WoW - ".to_string(),
+            wm_class: Some("wow.exe".to_string()),
+            app_id: Some("wow".to_string()),
+            ..WindowMatch::default()
+        };
+        assert_eq!(
+            rules.character_name("WoW - Healer", Some("wow")),
+            Some("Healer")
+        );
+        assert_eq!(
+            rules.character_name("WoW - Healer", Some("wow.exe")),
+            Some("Healer")
+        );
+        assert_eq!(rules.character_name("WoW - Healer", Some("firefox")), None);
+        // Unknown class: decided by title alone
+        assert_eq!(rules.character_name("WoW - Healer", None), Some("Healer"));
+    }
+
+    #[test]
+    fn test_title_regex_captures_the_name() {
+        let rules = WindowMatch {
+            title_regex: Some(
+                TitleRegex::new(r"^(?:EVE|Serenity) - (?P<name>.+?)(?: \[\w+\])?$").unwrap(),
+            ),
+            ..WindowMatch::default()
+        };
+        assert_eq!(
+            rules.character_name("EVE - Pilot One", None),
+            Some("Pilot One")
+        );
+        assert_eq!(
+            rules.character_name("Serenity - Pilot Two [CN]", None),
+            Some("Pilot Two")
+        );
+        assert_eq!(rules.character_name("EVE", None), None);
+        assert_eq!(rules.character_name("Firefox - EVE - Pilot", None), None);
+
+        assert!(TitleRegex::new("EVE - (.+)").is_err());
+        assert!(TitleRegex::new("EVE - (?P<name>.+").is_err());
+        let config = |toml: &str| Config::from_toml_str(toml, None);
+        assert!(config("[window_match]\ntitle_regex = \"EVE - (.+)\"\n").is_err());
+        assert!(config(
+            "[window_match]\ntitle_regex = \"EVE - (?P<name>.+)\"\ntitle_pattern = \"EVE - {name}\"\n"
+        )
+        .is_err());
+        let rules = config("[window_match]\ntitle_regex = \"^EVE - (?P<name>.+)$\"\n")
+            .unwrap()
+            .window_match;
+        assert_eq!(rules.character_name("EVE - Main", None), Some("Main"));
+    }
+
+    #[test]
+    fn test_pattern_wildcards() {
+        assert_eq!(capture("*", ""), Some(None));
+        assert_eq!(capture("a*c", "abbc"), Some(None));
+        assert_eq!(capture("a*c", "abbd"), None);
+        assert_eq!(capture("[{name}]*", "[x] y"), Some(Some("x")));
+    }
+
+    #[test]
+    fn test_class_detects_clients_at_character_select() {
+        let rules = WindowMatch {
+            wm_class: Some("steam_app_8500".to_string()),
+            ..WindowMatch::default()
+        };
+        let eve = Some("steam_app_8500");
+
+        assert_eq!(rules.client_name("EVE - Main", eve), Some("Main"));
+        assert_eq!(rules.client_name("EVE", eve), Some("EVE"));
+        assert_eq!(rules.client_name("EVE - Main", Some("firefox")), None);
+        assert_eq!(rules.client_name("EVE Launcher", eve), None);
+        assert_eq!(rules.client_name("Default - Wine desktop", eve), None);
+        // Still not in game, for defer_login_windows
+        assert_eq!(rules.character_name("EVE", eve), None);
+        // Without a class filter, titles decide as before
+        assert_eq!(WindowMatch::default().client_name("EVE", eve), None);
+    }
+}
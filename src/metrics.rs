@@ -0,0 +1,168 @@
+use crate::config::Config;
+use crate::window_manager::EveWindow;
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+const STACK_DURATION: &str = "nicotine_last_stack_duration_seconds";
+
+/// What the textfile reports
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub managed_windows: usize,
+    pub active_character: Option<String>,
+    pub last_stack_duration: Option<f64>,
+}
+
+/// Escape a label value per the Prometheus text format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn gauge(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+}
+
+/// Render in the Prometheus text exposition format
+pub fn render(snapshot: &Snapshot) -> String {
+    let mut out = String::new();
+
+    gauge(
+        &mut out,
+        "nicotine_managed_windows",
+        "EVE clients nicotine is managing",
+    );
+    let _ = writeln!(out, "nicotine_managed_windows {}", snapshot.managed_windows);
+
+    gauge(
+        &mut out,
+        "nicotine_active_character",
+        "Set to 1 for the character whose client is active",
+    );
+    if let Some(character) = &snapshot.active_character {
+        let _ = writeln!(
+            out,
+            "nicotine_active_character{{character=\"{}\"}} 1",
+            escape_label(character)
+        );
+    }
+
+    if let Some(seconds) = snapshot.last_stack_duration {
+        gauge(&mut out, STACK_DURATION, "How long the last stack took");
+        let _ = writeln!(out, "{} {:.6}", STACK_DURATION, seconds);
+    }
+
+    out
+}
+
+/// The stack duration recorded in an earlier textfile, so operations other
+/// than stack don't drop it
+fn previous_stack_duration(contents: &str) -> Option<f64> {
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix(STACK_DURATION)?.trim().parse().ok())
+}
+
+/// Replace the textfile atomically, so node_exporter never reads it half
+/// written. The temp file's name doesn't end in .prom, so it's never collected.
+pub fn write_textfile(path: &Path, mut snapshot: Snapshot) -> Result<()> {
+    if snapshot.last_stack_duration.is_none() {
+        snapshot.last_stack_duration = fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| previous_stack_duration(&contents));
+    }
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, render(&snapshot))
+        .with_context(|| format!("Failed to write {}", Path::new(&tmp).display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// Update metrics_textfile, if configured. Failures are only logged.
+pub fn export(
+    config: &Config,
+    windows: &[EveWindow],
+    active_window: Option<u64>,
+    stack_duration: Option<Duration>,
+) {
+    let Some(path) = &config.metrics_textfile else {
+        return;
+    };
+
+    let snapshot = Snapshot {
+        managed_windows: windows.len(),
+        active_character: active_window
+            .and_then(|id| windows.iter().find(|w| w.id == id))
+            .map(|w| w.title.clone()),
+        last_stack_duration: stack_duration.map(|d| d.as_secs_f64()),
+    };
+    if let Err(e) = write_textfile(path, snapshot) {
+        tracing::warn!("{:#}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_textfile_matches_metric_format() {
+        let snapshot = Snapshot {
+            managed_windows: 3,
+            active_character: Some("Main \"Alt\"".to_string()),
+            last_stack_duration: Some(0.125),
+        };
+
+        assert_eq!(
+            render(&snapshot),
+            "\
+# HELP nicotine_managed_windows EVE clients nicotine is managing
+# TYPE nicotine_managed_windows gauge
+nicotine_managed_windows 3
+# HELP nicotine_active_character Set to 1 for the character whose client is active
+# TYPE nicotine_active_character gauge
+nicotine_active_character{character=\"Main \\\"Alt\\\"\"} 1
+# HELP nicotine_last_stack_duration_seconds How long the last stack took
+# TYPE nicotine_last_stack_duration_seconds gauge
+nicotine_last_stack_duration_seconds 0.125000
+"
+        );
+    }
+
+    #[test]
+    fn test_stack_duration_survives_later_writes() {
+        let dir =
+            std::env::temp_dir().join(format!("nicotine-metrics-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("nicotine.prom");
+
+        let stacked = Snapshot {
+            managed_windows: 2,
+            active_character: None,
+            last_stack_duration: Some(0.5),
+        };
+        write_textfile(&path, stacked).unwrap();
+
+        let cycled = Snapshot {
+            managed_windows: 2,
+            active_character: Some("Alt".to_string()),
+            last_stack_duration: None,
+        };
+        write_textfile(&path, cycled).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("nicotine_active_character{character=\"Alt\"} 1\n"));
+        assert_eq!(previous_stack_duration(&contents), Some(0.5));
+        assert!(!dir.join("nicotine.prom.tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,572 @@
+use crate::config::FocusWorkaround;
+use crate::events::WindowEvent;
+use crate::layout::Placement;
+use crate::window_manager::{
+    apply_focus_workarounds, EveWindow, Monitor, WindowManager, WindowNotFound,
+};
+use anyhow::Result;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::mpsc::Receiver;
+use std::sync::Mutex;
+
+/// A backend that keeps its windows and monitors in memory, for testing
+/// stacking, cycling and the rest without a compositor. Placements move the
+/// simulated windows, activation moves focus, and every call is recorded as
+/// e.g. "activate 2" or "place 1".
+#[derive(Default)]
+pub struct MockWindowManager {
+    windows: Mutex<Vec<EveWindow>>,
+    monitors: Mutex<Vec<Monitor>>,
+    active: Mutex<u64>,
+    minimized: Mutex<HashSet<u64>>,
+    pinned: Mutex<HashSet<u64>>,
+    failing: HashSet<u64>,
+    failing_calls: HashSet<String>,
+    workarounds: Vec<FocusWorkaround>,
+    calls: Mutex<Vec<String>>,
+    placements: Mutex<Vec<Placement>>,
+    opacity: Mutex<HashMap<u64, Option<f64>>>,
+    titles: Mutex<HashMap<u64, VecDeque<String>>>,
+    events: Mutex<Option<Receiver<WindowEvent>>>,
+}
+
+impl MockWindowManager {
+    pub fn new(windows: Vec<EveWindow>) -> Self {
+        Self {
+            windows: Mutex::new(windows),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_monitors(self, monitors: Vec<Monitor>) -> Self {
+        *self.monitors.lock().unwrap() = monitors;
+        self
+    }
+
+    pub fn with_active(self, window_id: u64) -> Self {
+        *self.active.lock().unwrap() = window_id;
+        self
+    }
+
+    pub fn with_minimized(self, window_id: u64) -> Self {
+        self.minimized.lock().unwrap().insert(window_id);
+        self
+    }
+
+    /// Have every change to `window_id` fail, the way a hung client does
+    pub fn with_failing(mut self, window_id: u64) -> Self {
+        self.failing.insert(window_id);
+        self
+    }
+
+    /// Have just one kind of call fail, e.g. ("restore", 7)
+    pub fn with_failing_call(mut self, call: &str, window_id: u64) -> Self {
+        self.failing_calls.insert(format!("{} {}", call, window_id));
+        self
+    }
+
+    /// Run `workarounds` before each activation, the way the real backends do
+    pub fn with_focus_workarounds(mut self, workarounds: Vec<FocusWorkaround>) -> Self {
+        self.workarounds = workarounds;
+        self
+    }
+
+    /// Start a window at `opacity`, None being no explicit opacity
+    pub fn with_opacity(self, window_id: u64, opacity: Option<f64>) -> Self {
+        self.opacity.lock().unwrap().insert(window_id, opacity);
+        self
+    }
+
+    /// Have get_title read `titles` in turn, e.g. a client still at the
+    /// login screen before it shows a character. The last one sticks.
+    pub fn with_titles(self, window_id: u64, titles: &[&str]) -> Self {
+        let titles = titles.iter().map(|t| t.to_string()).collect();
+        self.titles.lock().unwrap().insert(window_id, titles);
+        self
+    }
+
+    /// Hand out `events` as the backend's event stream
+    pub fn with_events(self, events: Receiver<WindowEvent>) -> Self {
+        *self.events.lock().unwrap() = Some(events);
+        self
+    }
+
+    /// Plug in or unplug monitors
+    pub fn set_monitors(&self, monitors: Vec<Monitor>) {
+        *self.monitors.lock().unwrap() = monitors;
+    }
+
+    /// Close a client; acting on it afterwards fails with WindowNotFound
+    pub fn close(&self, window_id: u64) {
+        self.windows.lock().unwrap().retain(|w| w.id != window_id);
+    }
+
+    /// Open a client, e.g. one relogging under a new id
+    pub fn open(&self, window: EveWindow) {
+        self.windows.lock().unwrap().push(window);
+    }
+
+    /// A window as it is now, after any placements
+    pub fn window(&self, window_id: u64) -> Option<EveWindow> {
+        self.find(window_id)
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn placements(&self) -> Vec<Placement> {
+        self.placements.lock().unwrap().clone()
+    }
+
+    /// The windows activation was asked for, in order, including ones that
+    /// failed
+    pub fn activated(&self) -> Vec<u64> {
+        self.calls()
+            .iter()
+            .filter_map(|call| call.strip_prefix("activate "))
+            .map(|id| id.parse().unwrap())
+            .collect()
+    }
+
+    /// Each window's opacity as last set, None where it was cleared
+    pub fn opacities(&self) -> HashMap<u64, Option<f64>> {
+        self.opacity.lock().unwrap().clone()
+    }
+
+    pub fn clear_calls(&self) {
+        self.calls.lock().unwrap().clear();
+        self.placements.lock().unwrap().clear();
+    }
+
+    fn find(&self, window_id: u64) -> Option<EveWindow> {
+        let windows = self.windows.lock().unwrap();
+        windows.iter().find(|w| w.id == window_id).cloned()
+    }
+
+    /// Record a call, failing it if the window is closed or set to fail
+    fn act(&self, call: &str, window_id: u64) -> Result<()> {
+        let call = format!("{} {}", call, window_id);
+        self.calls.lock().unwrap().push(call.clone());
+        if self.find(window_id).is_none() {
+            return Err(WindowNotFound(window_id).into());
+        }
+        if self.failing.contains(&window_id) || self.failing_calls.contains(&call) {
+            anyhow::bail!("{} failed", call);
+        }
+        Ok(())
+    }
+}
+
+/// A client named `title`, with no monitor or geometry known yet
+pub fn window(id: u64, title: &str) -> EveWindow {
+    EveWindow {
+        id,
+        title: title.to_string(),
+        ..Default::default()
+    }
+}
+
+/// A monitor at (x, y) with nothing reserved
+pub fn monitor(name: &str, x: i32, y: i32, width: u32, height: u32) -> Monitor {
+    Monitor {
+        name: name.to_string(),
+        x,
+        y,
+        width,
+        height,
+        ..Default::default()
+    }
+}
+
+impl WindowManager for MockWindowManager {
+    fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
+        Ok(self.windows.lock().unwrap().clone())
+    }
+
+    fn activate_window(&self, window_id: u64) -> Result<()> {
+        apply_focus_workarounds(self, window_id, &self.workarounds);
+        self.act("activate", window_id)?;
+        *self.active.lock().unwrap() = window_id;
+        Ok(())
+    }
+
+    fn apply_placement(&self, placement: &Placement) -> Result<()> {
+        self.act("place", placement.window_id)?;
+        self.placements.lock().unwrap().push(placement.clone());
+
+        let mut windows = self.windows.lock().unwrap();
+        if let Some(window) = windows.iter_mut().find(|w| w.id == placement.window_id) {
+            let (width, height) = placement
+                .size
+                .or(window.geometry.map(|(_, _, w, h)| (w, h)))
+                .unwrap_or_default();
+            window.geometry = Some((placement.x, placement.y, width, height));
+            window.monitor = placement.monitor.clone();
+        }
+        Ok(())
+    }
+
+    fn get_title(&self, window_id: u64) -> Result<Option<String>> {
+        let mut titles = self.titles.lock().unwrap();
+        if let Some(queue) = titles.get_mut(&window_id) {
+            let title = if queue.len() > 1 {
+                queue.pop_front()
+            } else {
+                queue.front().cloned()
+            };
+            if title.is_some() {
+                return Ok(title);
+            }
+        }
+        Ok(self.find(window_id).map(|w| w.title))
+    }
+
+    fn get_active_window(&self) -> Result<u64> {
+        Ok(*self.active.lock().unwrap())
+    }
+
+    fn find_window_by_title(&self, title: &str) -> Result<Option<u64>> {
+        let windows = self.windows.lock().unwrap();
+        Ok(windows.iter().find(|w| w.title == title).map(|w| w.id))
+    }
+
+    fn minimize_window(&self, window_id: u64) -> Result<()> {
+        self.act("minimize", window_id)?;
+        self.minimized.lock().unwrap().insert(window_id);
+        Ok(())
+    }
+
+    fn restore_window(&self, window_id: u64) -> Result<()> {
+        self.act("restore", window_id)?;
+        self.minimized.lock().unwrap().remove(&window_id);
+        Ok(())
+    }
+
+    fn is_minimized(&self, window_id: u64) -> Result<bool> {
+        Ok(self.minimized.lock().unwrap().contains(&window_id))
+    }
+
+    fn set_pinned(&self, window_id: u64, pinned: bool) -> Result<()> {
+        self.act(if pinned { "pin" } else { "unpin" }, window_id)?;
+        let mut pins = self.pinned.lock().unwrap();
+        if pinned {
+            pins.insert(window_id);
+        } else {
+            pins.remove(&window_id);
+        }
+        Ok(())
+    }
+
+    fn is_pinned(&self, window_id: u64) -> Result<bool> {
+        Ok(self.pinned.lock().unwrap().contains(&window_id))
+    }
+
+    fn mark_urgent(&self, window_id: u64) -> Result<()> {
+        self.act("urgent", window_id)
+    }
+
+    fn close_window(&self, window_id: u64) -> Result<()> {
+        self.act("close", window_id)?;
+        self.close(window_id);
+        Ok(())
+    }
+
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        Ok(self.monitors.lock().unwrap().clone())
+    }
+
+    fn raise_window(&self, window_id: u64) -> Result<()> {
+        self.act("raise", window_id)
+    }
+
+    fn lower_window(&self, window_id: u64) -> Result<()> {
+        self.act("lower", window_id)
+    }
+
+    fn warp_pointer(&self, window_id: u64) -> Result<()> {
+        self.act("warp", window_id)
+    }
+
+    fn subscribe_events(&self) -> Result<Option<Receiver<WindowEvent>>> {
+        Ok(self.events.lock().unwrap().take())
+    }
+
+    fn set_decorations(&self, window_id: u64, decorated: bool) -> Result<()> {
+        self.act(if decorated { "decorate" } else { "undecorate" }, window_id)
+    }
+
+    fn get_opacity(&self, window_id: u64) -> Result<Option<f64>> {
+        let opacity = self.opacity.lock().unwrap();
+        Ok(opacity.get(&window_id).copied().flatten())
+    }
+
+    fn set_opacity(&self, window_id: u64, opacity: Option<f64>) -> Result<()> {
+        self.act("opacity", window_id)?;
+        self.opacity.lock().unwrap().insert(window_id, opacity);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, LayoutMode, MonitorRef, NoMonitorPolicy};
+    use crate::cycle_state::CycleState;
+
+    fn clients() -> Vec<EveWindow> {
+        vec![window(1, "Main"), window(2, "Alt"), window(3, "Scout")]
+    }
+
+    /// A 2560x1440 monitor with a 1920x1080 one to its right
+    fn two_monitors() -> Vec<Monitor> {
+        vec![
+            monitor("DP-1", 0, 0, 2560, 1440),
+            monitor("HDMI-A-1", 2560, 0, 1920, 1080),
+        ]
+    }
+
+    fn geometry(wm: &MockWindowManager, id: u64) -> (Option<String>, (i32, i32, u32, u32)) {
+        let window = wm.window(id).unwrap();
+        (window.monitor, window.geometry.unwrap())
+    }
+
+    fn on(name: &str, geometry: (i32, i32, u32, u32)) -> (Option<String>, (i32, i32, u32, u32)) {
+        (Some(name.to_string()), geometry)
+    }
+
+    #[test]
+    fn test_centered_stack_centres_eve_width_on_each_monitor() {
+        let wm = MockWindowManager::new(clients()).with_monitors(two_monitors());
+        let mut scout = window(3, "Scout");
+        scout.monitor = Some("HDMI-A-1".to_string());
+        wm.close(3);
+        wm.open(scout);
+        let config = Config {
+            panel_height: 40,
+            ..Config::default()
+        };
+
+        let report = wm
+            .stack_windows(&wm.get_eve_windows().unwrap(), &config)
+            .unwrap();
+
+        assert_eq!(report.succeeded.len(), 3);
+        // (2560 - 1036) / 2 across, the panel off the bottom
+        assert_eq!(geometry(&wm, 1), on("DP-1", (762, 0, 1036, 1400)));
+        assert_eq!(geometry(&wm, 2), on("DP-1", (762, 0, 1036, 1400)));
+        assert_eq!(geometry(&wm, 3), on("HDMI-A-1", (3002, 0, 1036, 1040)));
+        assert_eq!(wm.calls(), ["place 1", "place 2", "place 3"]);
+    }
+
+    #[test]
+    fn test_grid_stack_tiles_around_the_primary() {
+        let wm = MockWindowManager::new(clients()).with_monitors(two_monitors());
+        let config = Config {
+            layout: LayoutMode::Grid,
+            primary_character: Some("Main".to_string()),
+            primary_monitor: Some("DP-1".to_string()),
+            grid_monitor: Some(MonitorRef::Name("HDMI-A-1".to_string())),
+            ..Config::default()
+        };
+
+        wm.stack_windows(&wm.get_eve_windows().unwrap(), &config)
+            .unwrap();
+
+        assert_eq!(geometry(&wm, 1), on("DP-1", (0, 0, 2560, 1440)));
+        // Two clients side by side on the other monitor, splitting its width
+        assert_eq!(geometry(&wm, 2), on("HDMI-A-1", (2560, 0, 960, 1080)));
+        assert_eq!(geometry(&wm, 3), on("HDMI-A-1", (3520, 0, 960, 1080)));
+    }
+
+    #[test]
+    fn test_primary_goes_to_primary_monitor_else_fallback_else_primary_output() {
+        let mut main = window(1, "Main");
+        main.monitor = Some("HDMI-A-1".to_string());
+        let wm = MockWindowManager::new(vec![main]).with_monitors(two_monitors());
+        let windows = wm.get_eve_windows().unwrap();
+        let placed_on = |config: &Config| {
+            wm.place_windows(&windows, config).unwrap();
+            wm.placements().pop().unwrap().monitor.unwrap()
+        };
+
+        let mut config = Config {
+            layout: LayoutMode::Fullscreen,
+            primary_character: Some("Main".to_string()),
+            primary_monitor: Some("HDMI-A-1".to_string()),
+            ..Config::default()
+        };
+        assert_eq!(placed_on(&config), "HDMI-A-1");
+
+        // Configured monitor unplugged: fallback_monitor, then the first one
+        config.primary_monitor = Some("DP-3".to_string());
+        config.fallback_monitor = Some(MonitorRef::Index(1));
+        assert_eq!(placed_on(&config), "HDMI-A-1");
+        config.fallback_monitor = None;
+        assert_eq!(placed_on(&config), "DP-1");
+
+        // Without either, the output X11 marks primary beats the first one
+        let mut monitors = two_monitors();
+        monitors[1].primary = true;
+        wm.set_monitors(monitors);
+        assert_eq!(placed_on(&config), "HDMI-A-1");
+    }
+
+    #[test]
+    fn test_primary_follows_the_focused_client() {
+        let wm = MockWindowManager::new(clients())
+            .with_monitors(vec![monitor("DP-1", 0, 0, 1920, 1080)])
+            .with_active(2);
+        let config = Config {
+            layout: LayoutMode::Grid,
+            primary_character: Some("Main".to_string()),
+            primary_follows_active: true,
+            ..Config::default()
+        };
+
+        wm.stack_windows(&wm.get_eve_windows().unwrap(), &config)
+            .unwrap();
+
+        assert_eq!(geometry(&wm, 2), on("DP-1", (0, 0, 1920, 1080)));
+        assert_ne!(wm.window(1).unwrap().geometry, Some((0, 0, 1920, 1080)));
+    }
+
+    #[test]
+    fn test_no_monitors_falls_back_per_policy() {
+        let wm = MockWindowManager::new(clients());
+        let windows = wm.get_eve_windows().unwrap();
+
+        // The default: the configured display size at 0,0
+        wm.stack_windows(&windows, &Config::default()).unwrap();
+        assert_eq!(geometry(&wm, 1), (None, (442, 0, 1036, 1080)));
+
+        let synthetic = Config {
+            no_monitors: NoMonitorPolicy::SingleSynthetic,
+            ..Config::default()
+        };
+        wm.stack_windows(&windows, &synthetic).unwrap();
+        assert_eq!(geometry(&wm, 1), on("synthetic", (442, 0, 1036, 1080)));
+
+        wm.clear_calls();
+        let abort = Config {
+            no_monitors: NoMonitorPolicy::Abort,
+            ..Config::default()
+        };
+        assert!(wm.stack_windows(&windows, &abort).is_err());
+        assert!(wm.calls().is_empty());
+    }
+
+    #[test]
+    fn test_unplugged_assignment_lands_on_fallback_monitor() {
+        let wm = MockWindowManager::new(clients()).with_monitors(two_monitors());
+        let mut config = Config {
+            layout: LayoutMode::Fullscreen,
+            fallback_monitor: Some(MonitorRef::Name("HDMI-A-1".to_string())),
+            ..Config::default()
+        };
+        config
+            .monitor_assignments
+            .insert("DP-2".to_string(), vec!["Scout".to_string()]);
+
+        wm.stack_windows(&wm.get_eve_windows().unwrap(), &config)
+            .unwrap();
+        assert_eq!(geometry(&wm, 3), on("HDMI-A-1", (2560, 0, 1920, 1080)));
+
+        // DP-2 plugged in: the assignment applies again
+        let mut monitors = two_monitors();
+        monitors.push(monitor("DP-2", -1920, 0, 1920, 1080));
+        wm.set_monitors(monitors);
+        wm.stack_windows(&wm.get_eve_windows().unwrap(), &config)
+            .unwrap();
+        assert_eq!(geometry(&wm, 3), on("DP-2", (-1920, 0, 1920, 1080)));
+    }
+
+    #[test]
+    fn test_failing_window_does_not_stop_the_stack() {
+        let wm = MockWindowManager::new(clients())
+            .with_monitors(two_monitors())
+            .with_failing(2);
+
+        let report = wm
+            .stack_windows(&wm.get_eve_windows().unwrap(), &Config::default())
+            .unwrap();
+
+        assert_eq!(report.succeeded, ["Main", "Scout"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(wm.window(2).unwrap().geometry, None);
+    }
+
+    #[test]
+    fn test_stack_pins_configured_characters_and_unpin_lets_go() {
+        let wm = MockWindowManager::new(clients())
+            .with_monitors(two_monitors())
+            .with_active(1);
+        let config = Config {
+            pinned: vec!["Scout".to_string()],
+            ..Config::default()
+        };
+
+        wm.stack_windows(&wm.get_eve_windows().unwrap(), &config)
+            .unwrap();
+        assert!(wm.is_pinned(3).unwrap());
+        assert!(!wm.is_pinned(1).unwrap());
+
+        assert_eq!(wm.pin_character(None, true).unwrap(), "Main");
+        assert_eq!(wm.pin_character(Some("Scout"), false).unwrap(), "Scout");
+        assert!(wm.is_pinned(1).unwrap() && !wm.is_pinned(3).unwrap());
+        assert!(wm.pin_character(Some("Nobody"), true).is_err());
+    }
+
+    #[test]
+    fn test_urgent_flags_a_client_without_focusing_it() {
+        let wm = MockWindowManager::new(clients()).with_active(1);
+
+        let scout = wm.character_window(Some("Scout")).unwrap();
+        wm.mark_urgent(scout.id).unwrap();
+        assert_eq!(wm.get_active_window().unwrap(), 1);
+        assert_eq!(wm.calls(), ["urgent 3"]);
+    }
+
+    #[test]
+    fn test_closed_clients_leave_the_window_list() {
+        let wm = MockWindowManager::new(clients());
+
+        wm.close_window(3).unwrap();
+        assert_eq!(wm.calls(), ["close 3"]);
+        assert!(wm.get_eve_windows().unwrap().iter().all(|w| w.id != 3));
+        assert!(wm.close_window(3).is_err());
+    }
+
+    #[test]
+    fn test_cycling_moves_focus_and_minimizes_behind_it() {
+        let wm = MockWindowManager::new(clients()).with_active(1);
+        let mut state = CycleState::new();
+        state.update_windows(wm.get_eve_windows().unwrap());
+
+        state.cycle_forward(&wm, true, None).unwrap();
+        assert_eq!(wm.get_active_window().unwrap(), 2);
+        assert!(wm.is_minimized(1).unwrap());
+
+        state.cycle_backward(&wm, true, None).unwrap();
+        state.cycle_backward(&wm, true, None).unwrap();
+        assert_eq!(wm.get_active_window().unwrap(), 3);
+        assert!(!wm.is_minimized(3).unwrap());
+        assert!(wm.is_minimized(1).unwrap() && wm.is_minimized(2).unwrap());
+    }
+
+    #[test]
+    fn test_cycling_to_a_relogged_client_finds_its_new_window() {
+        let wm = MockWindowManager::new(clients());
+        let mut state = CycleState::new();
+        state.update_windows(wm.get_eve_windows().unwrap());
+
+        // Alt relogs: its old window is gone and it comes back as 20
+        wm.close(2);
+        wm.open(window(20, "Alt"));
+        state.cycle_forward(&wm, false, None).unwrap();
+
+        assert_eq!(wm.calls(), ["activate 2", "activate 20"]);
+        assert_eq!(wm.get_active_window().unwrap(), 20);
+        assert_eq!(state.get_windows()[state.get_current_index()].id, 20);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::window_manager::Monitor;
+use anyhow::Result;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a monitor query is reused: long enough to cover one stack or
+/// window listing, short enough that a change nobody reported isn't
+/// missed for long
+const TTL: Duration = Duration::from_secs(2);
+
+/// A backend's last monitor query, so that placing or listing a dozen
+/// windows asks xrandr or the compositor once rather than once per window.
+/// Hotplugs drop it through WindowManager::invalidate_monitors.
+pub struct MonitorCache {
+    ttl: Duration,
+    cached: Mutex<Option<(Instant, Vec<Monitor>)>>,
+}
+
+impl Default for MonitorCache {
+    fn default() -> Self {
+        Self::with_ttl(TTL)
+    }
+}
+
+impl MonitorCache {
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// The cached monitors, or the result of `query` if they're missing or
+    /// stale. Callers arriving during a query wait for it rather than
+    /// running their own; failures aren't cached.
+    pub fn get(&self, query: impl FnOnce() -> Result<Vec<Monitor>>) -> Result<Vec<Monitor>> {
+        let mut cached = self.cached.lock().unwrap();
+        if let Some((at, monitors)) = cached.as_ref() {
+            if at.elapsed() < self.ttl {
+                return Ok(monitors.clone());
+            }
+        }
+        let monitors = query()?;
+        *cached = Some((Instant::now(), monitors.clone()));
+        Ok(monitors)
+    }
+
+    pub fn invalidate(&self) {
+        *self.cached.lock().unwrap() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn counted<'a>(queries: &'a Cell<u32>) -> impl Fn() -> Result<Vec<Monitor>> + 'a {
+        move || {
+            queries.set(queries.get() + 1);
+            Ok(vec![Monitor {
+                name: format!("DP-{}", queries.get()),
+                ..Default::default()
+            }])
+        }
+    }
+
+    #[test]
+    fn test_queries_once_until_invalidated() {
+        let cache = MonitorCache::default();
+        let queries = Cell::new(0);
+        let query = counted(&queries);
+
+        for _ in 0..30 {
+            assert_eq!(cache.get(&query).unwrap()[0].name, "DP-1");
+        }
+        assert_eq!(queries.get(), 1);
+
+        cache.invalidate();
+        assert_eq!(cache.get(&query).unwrap()[0].name, "DP-2");
+        assert_eq!(queries.get(), 2);
+    }
+
+    #[test]
+    fn test_stale_and_failed_queries_are_not_reused() {
+        let cache = MonitorCache::with_ttl(Duration::ZERO);
+        let queries = Cell::new(0);
+
+        cache.get(counted(&queries)).unwrap();
+        cache.get(counted(&queries)).unwrap();
+        assert_eq!(queries.get(), 2);
+
+        let cache = MonitorCache::default();
+        assert!(cache.get(|| anyhow::bail!("xrandr missing")).is_err());
+        cache.get(counted(&queries)).unwrap();
+        assert_eq!(queries.get(), 3);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::config::Config;
 use crate::cycle_state::CycleState;
+use crate::keybinds::SharedKeybinds;
 use crate::window_manager::WindowManager;
 use anyhow::{Context, Result};
 use evdev::{Device, InputEventKind, Key};
@@ -25,7 +26,7 @@ impl MouseListener {
 
         // 1. Try configured device name first (highest priority)
         if let Some(device_name) = configured_name {
-            println!("Searching for device by name: {}", device_name);
+            tracing::info!("Searching for device by name: {}", device_name);
 
             for entry in std::fs::read_dir(devices_path)? {
                 let entry = entry?;
@@ -37,7 +38,7 @@ impl MouseListener {
                             if let Ok(device) = Device::open(&path) {
                                 if let Some(dev_name) = device.name() {
                                     if dev_name == device_name {
-                                        println!(
+                                        tracing::info!(
                                             "Using configured mouse device by name: {} ({})",
                                             dev_name,
                                             path.display()
@@ -51,8 +52,8 @@ impl MouseListener {
                 }
             }
 
-            eprintln!(
-                "Warning: Failed to find device with name '{}'. Trying other methods...",
+            tracing::warn!(
+                "Failed to find device with name '{}'. Trying other methods...",
                 device_name
             );
         }
@@ -62,7 +63,7 @@ impl MouseListener {
             let path = Path::new(path_str);
             match Device::open(path) {
                 Ok(device) => {
-                    println!(
+                    tracing::info!(
                         "Using configured mouse device by path: {} ({})",
                         device.name().unwrap_or("Unknown"),
                         path.display()
@@ -70,11 +71,12 @@ impl MouseListener {
                     return Ok(device);
                 }
                 Err(e) => {
-                    eprintln!(
-                        "Warning: Failed to open configured mouse device '{}': {}",
-                        path_str, e
+                    tracing::warn!(
+                        "Failed to open configured mouse device '{}': {}",
+                        path_str,
+                        e
                     );
-                    eprintln!("Falling back to automatic device detection...");
+                    tracing::info!("Falling back to automatic device detection...");
                 }
             }
         }
@@ -92,7 +94,7 @@ impl MouseListener {
                             if device.supported_keys().is_some_and(|keys| {
                                 keys.contains(Key::BTN_SIDE) || keys.contains(Key::BTN_EXTRA)
                             }) {
-                                println!(
+                                tracing::info!(
                                     "Found mouse device: {} ({})",
                                     device.name().unwrap_or("Unknown"),
                                     path.display()
@@ -108,52 +110,37 @@ impl MouseListener {
         anyhow::bail!("No mouse device with side buttons found in /dev/input")
     }
 
-    /// Run the mouse event listener in a background thread
+    /// Run the mouse event listener in a background thread. Buttons are read
+    /// from `keybinds` on every event, so a profile switch applies at once.
     pub fn spawn(
         &self,
         wm: Arc<dyn WindowManager>,
         state: Arc<Mutex<CycleState>>,
+        keybinds: SharedKeybinds,
     ) -> Result<std::thread::JoinHandle<()>> {
         if !self.config.enable_mouse_buttons {
             anyhow::bail!("Mouse buttons are disabled in config");
         }
 
-        let forward_button = self.config.forward_button;
-        let backward_button = self.config.backward_button;
         let mouse_device_name = self.config.mouse_device_name.clone();
         let mouse_device_path = self.config.mouse_device_path.clone();
-        let minimize_inactive = self.config.minimize_inactive;
-        let primary_character = self.config.primary_character.clone();
 
         let handle = std::thread::spawn(move || {
-            match Self::run_listener(
-                wm,
-                state,
-                forward_button,
-                backward_button,
-                mouse_device_name,
-                mouse_device_path,
-                minimize_inactive,
-                primary_character,
-            ) {
-                Ok(_) => println!("Mouse listener stopped"),
-                Err(e) => eprintln!("Mouse listener error: {}", e),
+            match Self::run_listener(wm, state, keybinds, mouse_device_name, mouse_device_path) {
+                Ok(_) => tracing::info!("Mouse listener stopped"),
+                Err(e) => tracing::error!("Mouse listener error: {}", e),
             }
         });
 
         Ok(handle)
     }
 
-    #[allow(clippy::too_many_arguments)]
     fn run_listener(
         wm: Arc<dyn WindowManager>,
         state: Arc<Mutex<CycleState>>,
-        forward_button: u16,
-        backward_button: u16,
+        keybinds: SharedKeybinds,
         mouse_device_name: Option<String>,
         mouse_device_path: Option<String>,
-        minimize_inactive: bool,
-        primary_character: Option<String>,
     ) -> Result<()> {
         let mut device = Self::find_mouse_device(
             mouse_device_name.as_deref(),
@@ -166,10 +153,14 @@ impl MouseListener {
         // DON'T grab the device - we only want to passively listen to events
         // Grabbing would prevent normal mouse usage!
 
-        println!(
-            "Listening for mouse buttons: forward={}, backward={}",
-            forward_button, backward_button
-        );
+        {
+            let binds = keybinds.read().unwrap();
+            tracing::info!(
+                "Listening for mouse buttons: forward={}, backward={}",
+                binds.forward_button,
+                binds.backward_button
+            );
+        }
 
         loop {
             for event in device.fetch_events()? {
@@ -178,15 +169,24 @@ impl MouseListener {
 
                     // Only handle button press (value 1), ignore release (value 0)
                     if event.value() == 1 {
-                        if code == forward_button {
-                            println!("Forward button pressed");
-                            if let Err(e) = Self::cycle_forward(&wm, &state, minimize_inactive, primary_character.as_deref()) {
-                                eprintln!("Failed to cycle forward: {}", e);
+                        let binds = keybinds.read().unwrap().clone();
+                        if !binds.mouse_enabled {
+                            continue;
+                        }
+                        let skip = binds.primary_character.as_deref();
+                        if code == binds.forward_button {
+                            tracing::debug!("Forward button pressed");
+                            if let Err(e) =
+                                Self::cycle_forward(&wm, &state, binds.minimize_inactive, skip)
+                            {
+                                tracing::error!("Failed to cycle forward: {}", e);
                             }
-                        } else if code == backward_button {
-                            println!("Backward button pressed");
-                            if let Err(e) = Self::cycle_backward(&wm, &state, minimize_inactive, primary_character.as_deref()) {
-                                eprintln!("Failed to cycle backward: {}", e);
+                        } else if code == binds.backward_button {
+                            tracing::debug!("Backward button pressed");
+                            if let Err(e) =
+                                Self::cycle_backward(&wm, &state, binds.minimize_inactive, skip)
+                            {
+                                tracing::error!("Failed to cycle backward: {}", e);
                             }
                         }
                     }
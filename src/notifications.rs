@@ -0,0 +1,86 @@
+use crate::config::{Notifications, NotifyUrgency};
+use notify_rust::Notification;
+
+/// Something the daemon can put up a desktop notification for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Login,
+    ConnectionLost,
+    Error,
+    Timer,
+}
+
+impl Event {
+    fn summary(&self) -> &'static str {
+        match self {
+            Event::Login => "EVE client logged in",
+            Event::ConnectionLost => "Lost connection to the display server",
+            Event::Error => "nicotine error",
+            Event::Timer => "EVE timer finished",
+        }
+    }
+}
+
+/// How urgent `event` is under `config`, None if it's turned off
+fn urgency(config: &Notifications, event: Event) -> Option<NotifyUrgency> {
+    let urgency = match event {
+        Event::Login => config.login,
+        Event::ConnectionLost => config.connection_lost,
+        Event::Error => config.error,
+        Event::Timer => config.timers,
+    };
+    Some(urgency).filter(|u| *u != NotifyUrgency::Off)
+}
+
+/// Notify `event` if config.toml asks for it. Sent from its own thread, as
+/// a notification server that isn't running can hold the call up; failures
+/// are only logged.
+pub fn send(config: &Notifications, event: Event, body: impl Into<String>) {
+    let Some(urgency) = urgency(config, event) else {
+        return;
+    };
+    let body = body.into();
+    std::thread::spawn(move || {
+        let mut notification = Notification::new();
+        notification
+            .appname("nicotine")
+            .summary(event.summary())
+            .body(&body);
+        #[cfg(all(unix, not(target_os = "macos")))]
+        notification.urgency(match urgency {
+            NotifyUrgency::Low => notify_rust::Urgency::Low,
+            NotifyUrgency::Critical => notify_rust::Urgency::Critical,
+            _ => notify_rust::Urgency::Normal,
+        });
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        let _ = urgency;
+        if let Err(e) = notification.show() {
+            tracing::debug!("Could not show notification {:?}: {}", body, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_only_events_given_an_urgency_are_notified() {
+        let config = Config::from_toml_str(
+            "[notifications]\nlogin = \"low\"\nerror = \"critical\"\n",
+            None,
+        )
+        .unwrap()
+        .notifications;
+
+        assert_eq!(urgency(&config, Event::Login), Some(NotifyUrgency::Low));
+        assert_eq!(
+            urgency(&config, Event::Error),
+            Some(NotifyUrgency::Critical)
+        );
+        assert_eq!(urgency(&config, Event::ConnectionLost), None);
+        assert_eq!(urgency(&Notifications::default(), Event::Login), None);
+        assert!(Config::from_toml_str("[notifications]\nlogin = \"loud\"\n", None).is_err());
+    }
+}
@@ -1,6 +1,10 @@
+use crate::config::{OverlayStyle, OverlaySync};
 use crate::cycle_state::CycleState;
+use crate::events::{self, WindowEvent};
+use crate::wait;
 use crate::window_manager::WindowManager;
 use eframe::egui;
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
@@ -13,6 +17,9 @@ pub struct OverlayApp {
     overlay_window_id: Option<u64>,
     last_sync: Instant,
     last_index: usize,
+    /// Redraws are driven by the backend's event stream rather than polling
+    event_driven: bool,
+    last_client_count: Option<usize>,
 }
 
 impl OverlayApp {
@@ -56,6 +63,24 @@ impl OverlayApp {
 
         cc.egui_ctx.set_fonts(fonts);
 
+        let event_driven = config.overlay_sync == OverlaySync::Events
+            && match wm.subscribe_events() {
+                Ok(Some(events)) => {
+                    spawn_event_sync(
+                        cc.egui_ctx.clone(),
+                        Arc::clone(&wm),
+                        Arc::clone(&state),
+                        events,
+                    );
+                    true
+                }
+                Ok(None) => false,
+                Err(e) => {
+                    tracing::warn!("Overlay event subscription failed, polling instead: {}", e);
+                    false
+                }
+            };
+
         Self {
             wm,
             state,
@@ -65,12 +90,14 @@ impl OverlayApp {
             overlay_window_id: None,
             last_sync: Instant::now(),
             last_index: 0,
+            event_driven,
+            last_client_count: None,
         }
     }
-}
 
-impl eframe::App for OverlayApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    /// Redraw every frame, following the daemon's index file and re-reading
+    /// the window list periodically
+    fn poll(&mut self, ctx: &egui::Context) {
         // Request repaint for smooth updates
         ctx.request_repaint();
 
@@ -89,24 +116,33 @@ impl eframe::App for OverlayApp {
             self.last_sync = now;
 
             if let Ok(windows) = self.wm.get_eve_windows() {
-                let mut state = self.state.lock().unwrap();
-                state.update_windows(windows);
-
-                // Resize window based on client count
-                let client_count = state.get_windows().len();
-                let base_height = 320.0_f32;
-                let per_client = 20.0_f32;
-                let min_clients = 10;
-                let extra_clients = client_count.saturating_sub(min_clients);
-                let target_height = base_height + (extra_clients as f32 * per_client);
-
-                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(
-                    220.0,
-                    target_height,
-                )));
+                self.state.lock().unwrap().update_windows(windows);
             }
         }
+    }
 
+    /// Resize the window based on client count
+    fn fit_to_clients(&mut self, ctx: &egui::Context) {
+        let client_count = self.state.lock().unwrap().get_windows().len();
+        if self.last_client_count == Some(client_count) {
+            return;
+        }
+        self.last_client_count = Some(client_count);
+
+        let base_height = 320.0_f32;
+        let per_client = 20.0_f32;
+        let min_clients = 10;
+        let extra_clients = client_count.saturating_sub(min_clients);
+        let target_height = base_height + (extra_clients as f32 * per_client);
+
+        ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(
+            220.0,
+            target_height,
+        )));
+    }
+
+    /// Logo, client list and restack button
+    fn draw_full(&mut self, ctx: &egui::Context) {
         let red = egui::Color32::from_rgb(196, 30, 58);
         let gold = egui::Color32::from_rgb(180, 155, 105);
         let cream = egui::Color32::from_rgb(252, 250, 242);
@@ -188,7 +224,7 @@ impl eframe::App for OverlayApp {
                         let config = self.config.clone();
                         std::thread::spawn(move || {
                             if let Ok(windows) = wm_clone.get_eve_windows() {
-                                let _ = wm_clone.stack_windows(&windows, &config);
+                                let _ = wait::stack_in_game(&*wm_clone, &windows, &config);
                             }
                         });
                     }
@@ -196,6 +232,73 @@ impl eframe::App for OverlayApp {
                     ui.add_space(6.0);
                 });
             });
+    }
+
+    /// One line: the active character and where it is in the cycle
+    fn draw_compact(&mut self, ctx: &egui::Context) {
+        let red = egui::Color32::from_rgb(196, 30, 58);
+        let gold = egui::Color32::from_rgb(180, 155, 105);
+        let cream = egui::Color32::from_rgb(252, 250, 242);
+
+        let label = position_label(&self.state.lock().unwrap());
+        egui::CentralPanel::default()
+            .frame(
+                egui::Frame::none()
+                    .fill(red)
+                    .inner_margin(egui::Margin::symmetric(10.0, 6.0))
+                    .stroke(egui::Stroke::new(2.0, gold)),
+            )
+            .show(ctx, |ui| {
+                ui.horizontal_centered(|ui| match label {
+                    Some((title, position)) => {
+                        ui.colored_label(cream, egui::RichText::new(title).size(14.0).strong());
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.colored_label(gold, egui::RichText::new(position).size(13.0));
+                        });
+                    }
+                    None => {
+                        ui.colored_label(gold, "No clients");
+                    }
+                });
+            });
+    }
+}
+
+/// The current client's title and its place in the cycle, e.g. ("Main", "3/9")
+fn position_label(state: &CycleState) -> Option<(String, String)> {
+    let windows = state.get_windows();
+    let index = state.get_current_index();
+    let window = windows.get(index)?;
+    Some((
+        window.title.clone(),
+        format!("{}/{}", index + 1, windows.len()),
+    ))
+}
+
+/// Keep `state` current from the backend's events, waking the overlay only
+/// when something it shows has changed
+fn spawn_event_sync(
+    ctx: egui::Context,
+    wm: Arc<dyn WindowManager>,
+    state: Arc<Mutex<CycleState>>,
+    events: Receiver<WindowEvent>,
+) {
+    std::thread::spawn(move || {
+        events::sync_state(&*wm, &state, events, || ctx.request_repaint());
+    });
+}
+
+impl eframe::App for OverlayApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if !self.event_driven {
+            self.poll(ctx);
+        }
+        if self.config.overlay_style == OverlayStyle::Compact {
+            self.draw_compact(ctx);
+        } else {
+            self.fit_to_clients(ctx);
+            self.draw_full(ctx);
+        }
 
         // Handle dragging with middle mouse button
         // Note: Overlay dragging is X11-only. On Wayland, use your compositor's window
@@ -254,10 +357,14 @@ pub fn run_overlay(
     overlay_y: f32,
     config: crate::config::Config,
 ) -> Result<(), eframe::Error> {
+    let size = match config.overlay_style {
+        OverlayStyle::Full => [220.0, 320.0],
+        OverlayStyle::Compact => [220.0, 32.0],
+    };
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([220.0, 320.0])
-            .with_min_inner_size([220.0, 320.0])
+            .with_inner_size(size)
+            .with_min_inner_size(size)
             .with_position([overlay_x, overlay_y])
             .with_decorations(false)
             .with_always_on_top()
@@ -270,21 +377,55 @@ pub fn run_overlay(
         "Nicotine",
         options,
         Box::new(move |cc| {
-            // Set window properties after window is created
-            std::thread::spawn(|| {
-                // Try multiple times with increasing delays (window might not be ready immediately)
-                for delay in [300, 500, 1000] {
-                    std::thread::sleep(std::time::Duration::from_millis(delay));
-                    if std::process::Command::new("wmctrl")
-                        .args(["-F", "-r", "Nicotine", "-b", "add,above,sticky"])
-                        .output()
-                        .is_ok()
-                    {
-                        break;
-                    }
-                }
-            });
+            pin_above("Nicotine");
             Ok(Box::new(OverlayApp::new(cc, wm, state, config)))
         }),
     )
 }
+
+/// Keep the window titled `title` above others and on every desktop, once
+/// it has been created
+pub fn pin_above(title: &'static str) {
+    std::thread::spawn(move || {
+        // Try multiple times with increasing delays (window might not be ready immediately)
+        for delay in [300, 500, 1000] {
+            std::thread::sleep(std::time::Duration::from_millis(delay));
+            if std::process::Command::new("wmctrl")
+                .args(["-F", "-r", title, "-b", "add,above,sticky"])
+                .output()
+                .is_ok()
+            {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::window_manager::EveWindow;
+
+    #[test]
+    fn test_position_label_counts_from_one() {
+        let mut state = CycleState::new();
+        assert_eq!(position_label(&state), None);
+
+        state.update_windows(
+            ["Main", "Scout", "Hauler"]
+                .iter()
+                .enumerate()
+                .map(|(i, title)| EveWindow {
+                    id: i as u64,
+                    title: title.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        state.set_current_index(2);
+        assert_eq!(
+            position_label(&state),
+            Some(("Hauler".to_string(), "3/3".to_string()))
+        );
+    }
+}
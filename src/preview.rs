@@ -0,0 +1,245 @@
+use crate::layout::Placement;
+use crate::window_manager::{bounding_box, EveWindow, Monitor};
+use std::collections::HashMap;
+
+/// Terminal cells are roughly twice as tall as they are wide
+const CELL_ASPECT: f64 = 2.0;
+
+struct Canvas {
+    cells: Vec<Vec<char>>,
+    origin: (i64, i64),
+    scale: f64,
+}
+
+impl Canvas {
+    fn new(area: (i32, i32, u32, u32), columns: usize) -> Self {
+        let (x, y, width, height) = area;
+        let columns = columns.max(10);
+        let scale = width.max(1) as f64 / (columns - 1) as f64;
+        let rows = ((height as f64 / scale / CELL_ASPECT).round() as usize + 1).max(3);
+
+        Self {
+            cells: vec![vec![' '; columns]; rows],
+            origin: (x as i64, y as i64),
+            scale,
+        }
+    }
+
+    /// Screen rect to (left, top, right, bottom) cells, clamped to the canvas
+    fn to_cells(&self, x: i32, y: i32, width: u32, height: u32) -> (usize, usize, usize, usize) {
+        let columns = self.cells[0].len() as f64;
+        let rows = self.cells.len() as f64;
+        let col = |px: i64| {
+            ((px - self.origin.0) as f64 / self.scale)
+                .round()
+                .clamp(0.0, columns - 1.0) as usize
+        };
+        let row = |px: i64| {
+            ((px - self.origin.1) as f64 / self.scale / CELL_ASPECT)
+                .round()
+                .clamp(0.0, rows - 1.0) as usize
+        };
+
+        let (x, y) = (x as i64, y as i64);
+        (
+            col(x),
+            row(y),
+            col(x + width as i64 - 1),
+            row(y + height as i64 - 1),
+        )
+    }
+
+    fn rect(&mut self, cells: (usize, usize, usize, usize), edge: char, side: char, corner: char) {
+        let (left, top, right, bottom) = cells;
+        for col in left..=right {
+            self.cells[top][col] = edge;
+            self.cells[bottom][col] = edge;
+        }
+        for row in top..=bottom {
+            self.cells[row][left] = side;
+            self.cells[row][right] = side;
+        }
+        for (col, row) in [(left, top), (right, top), (left, bottom), (right, bottom)] {
+            self.cells[row][col] = corner;
+        }
+    }
+
+    /// Write `text` starting at a cell, cut off at `max_col`
+    fn text(&mut self, col: usize, row: usize, text: &str, max_col: usize) {
+        if row >= self.cells.len() {
+            return;
+        }
+        for (i, c) in text.chars().enumerate() {
+            let col = col + i;
+            if col > max_col || col >= self.cells[row].len() {
+                break;
+            }
+            self.cells[row][col] = c;
+        }
+    }
+
+    fn render(&self) -> String {
+        self.cells
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Draw monitors (`+--+`) and planned windows (`#==#`) to scale, labelling
+/// each window with its character. Windows that keep their size are drawn
+/// with the size they have now. Stacked windows share a box and list their
+/// labels one per line.
+pub fn render(
+    monitors: &[Monitor],
+    placements: &[Placement],
+    windows: &[EveWindow],
+    columns: usize,
+) -> String {
+    let sized: Vec<(&Placement, (u32, u32))> = placements
+        .iter()
+        .filter_map(|p| {
+            let size = p.size.or_else(|| {
+                windows
+                    .iter()
+                    .find(|w| w.id == p.window_id)
+                    .and_then(|w| w.geometry)
+                    .map(|(_, _, width, height)| (width, height))
+            })?;
+            Some((p, size))
+        })
+        .collect();
+
+    let window_rects: Vec<Monitor> = sized
+        .iter()
+        .map(|(p, (width, height))| Monitor {
+            x: p.x,
+            y: p.y,
+            width: *width,
+            height: *height,
+            ..Default::default()
+        })
+        .collect();
+    let everything: Vec<Monitor> = monitors.iter().cloned().chain(window_rects).collect();
+    let area = match bounding_box(&everything) {
+        Some(area) => area,
+        None => return "Nothing to preview".to_string(),
+    };
+
+    let mut canvas = Canvas::new(area, columns);
+
+    for mon in monitors {
+        let cells = canvas.to_cells(mon.x, mon.y, mon.width, mon.height);
+        canvas.rect(cells, '-', '|', '+');
+        canvas.text(cells.0 + 1, cells.1, &mon.name, cells.2.saturating_sub(1));
+    }
+
+    let mut labels_in_box: HashMap<(usize, usize, usize, usize), usize> = HashMap::new();
+    for (placement, (width, height)) in &sized {
+        let cells = canvas.to_cells(placement.x, placement.y, *width, *height);
+        canvas.rect(cells, '=', '#', '#');
+    }
+    for (placement, (width, height)) in &sized {
+        let cells = canvas.to_cells(placement.x, placement.y, *width, *height);
+        let line = labels_in_box.entry(cells).or_insert(0);
+        *line += 1;
+        if cells.1 + *line < cells.3 {
+            canvas.text(
+                cells.0 + 1,
+                cells.1 + *line,
+                &placement.title,
+                cells.2.saturating_sub(1),
+            );
+        }
+    }
+
+    canvas.render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> Monitor {
+        Monitor {
+            name: "DP-1".to_string(),
+            width: 1920,
+            height: 1080,
+            ..Default::default()
+        }
+    }
+
+    fn placement(id: u64, title: &str, x: i32, y: i32, size: Option<(u32, u32)>) -> Placement {
+        Placement {
+            window_id: id,
+            title: title.to_string(),
+            monitor: Some("DP-1".to_string()),
+            x,
+            y,
+            size,
+        }
+    }
+
+    /// (column, row) of the first occurrence of `needle`
+    fn find(map: &str, needle: &str) -> (usize, usize) {
+        map.lines()
+            .enumerate()
+            .find_map(|(row, line)| line.find(needle).map(|col| (col, row)))
+            .unwrap_or_else(|| panic!("{} not in map:\n{}", needle, map))
+    }
+
+    #[test]
+    fn test_window_lands_in_expected_quadrant() {
+        let map = render(
+            &[monitor()],
+            &[placement(1, "Main", 960, 540, Some((960, 540)))],
+            &[],
+            81,
+        );
+
+        let rows = map.lines().count();
+        let (col, row) = find(&map, "Main");
+        assert!(col > 40, "label not in right half:\n{}", map);
+        assert!(row > rows / 2, "label not in bottom half:\n{}", map);
+        // Monitor outline and name are drawn at the top left
+        assert_eq!(find(&map, "DP-1"), (1, 0));
+    }
+
+    #[test]
+    fn test_stacked_windows_list_every_label() {
+        let map = render(
+            &[monitor()],
+            &[
+                placement(1, "Main", 442, 0, Some((1036, 1080))),
+                placement(2, "Alt", 442, 0, Some((1036, 1080))),
+            ],
+            &[],
+            81,
+        );
+
+        let (main_col, main_row) = find(&map, "Main");
+        let (alt_col, alt_row) = find(&map, "Alt");
+        assert_eq!(main_col, alt_col);
+        assert_eq!(alt_row, main_row + 1);
+    }
+
+    #[test]
+    fn test_move_only_placement_uses_current_size() {
+        let windows = vec![EveWindow {
+            id: 1,
+            title: "Main".to_string(),
+            geometry: Some((0, 0, 960, 540)),
+            ..Default::default()
+        }];
+        let map = render(
+            &[monitor()],
+            &[placement(1, "Main", 0, 0, None)],
+            &windows,
+            81,
+        );
+
+        let (col, row) = find(&map, "Main");
+        assert!(col < 40 && row < map.lines().count() / 2, "{}", map);
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+
+/// What an action did to each window it was given, so one window failing
+/// doesn't hide what happened to the rest
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ActionReport {
+    pub succeeded: Vec<String>,
+    /// Window and why it failed
+    pub failed: Vec<(String, String)>,
+    pub skipped: Vec<String>,
+}
+
+impl ActionReport {
+    pub fn succeed(&mut self, name: &str) {
+        self.succeeded.push(name.to_string());
+    }
+
+    pub fn fail(&mut self, name: &str, error: &anyhow::Error) {
+        self.failed.push((name.to_string(), format!("{:#}", error)));
+    }
+
+    pub fn skip(&mut self, name: &str) {
+        self.skipped.push(name.to_string());
+    }
+
+    pub fn record(&mut self, name: &str, result: Result<()>) {
+        match result {
+            Ok(()) => self.succeed(name),
+            Err(e) => self.fail(name, &e),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.succeeded.len() + self.failed.len() + self.skipped.len()
+    }
+
+    /// e.g. "Stacked 2 of 4 windows (1 failed, 1 skipped)"
+    pub fn summary(&self, verb: &str) -> String {
+        let total = self.total();
+        let mut line = if self.succeeded.len() == total {
+            format!("{} {} windows", verb, total)
+        } else {
+            format!("{} {} of {} windows", verb, self.succeeded.len(), total)
+        };
+
+        let mut notes = Vec::new();
+        if !self.failed.is_empty() {
+            notes.push(format!("{} failed", self.failed.len()));
+        }
+        if !self.skipped.is_empty() {
+            notes.push(format!("{} skipped", self.skipped.len()));
+        }
+        if !notes.is_empty() {
+            line.push_str(&format!(" ({})", notes.join(", ")));
+        }
+        line
+    }
+
+    /// Print each failure, then the summary
+    pub fn print(&self, verb: &str) {
+        for (name, error) in &self.failed {
+            eprintln!("✗ {}: {}", name, error);
+        }
+        let mark = if self.failed.is_empty() { "✓" } else { "✗" };
+        println!("{} {}", mark, self.summary(verb));
+    }
+
+    /// The same as print, for the daemon's log
+    pub fn log(&self, verb: &str) {
+        for (name, error) in &self.failed {
+            tracing::warn!("✗ {}: {}", name, error);
+        }
+        let mark = if self.failed.is_empty() { "✓" } else { "✗" };
+        tracing::info!("{} {}", mark, self.summary(verb));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_counts_each_outcome() {
+        let mut report = ActionReport::default();
+        report.succeed("Main");
+        report.record("Alt", Err(anyhow::anyhow!("window is gone")));
+        report.skip("Scout");
+        report.record("Hauler", Ok(()));
+
+        assert_eq!(report.succeeded, vec!["Main", "Hauler"]);
+        assert_eq!(
+            report.failed,
+            vec![("Alt".to_string(), "window is gone".to_string())]
+        );
+        assert_eq!(
+            report.summary("Stacked"),
+            "Stacked 2 of 4 windows (1 failed, 1 skipped)"
+        );
+
+        let clean = ActionReport {
+            succeeded: vec!["Main".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(clean.summary("Stacked"), "Stacked 1 windows");
+    }
+}
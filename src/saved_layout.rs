@@ -0,0 +1,200 @@
+use crate::layout::Placement;
+use crate::report::ActionReport;
+use crate::window_manager::{EveWindow, WindowManager};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Where one character's window was when the layout was saved
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedWindow {
+    pub monitor: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A named arrangement of clients, keyed by character name so it survives
+/// relogs and compositor restarts
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedLayout {
+    pub windows: BTreeMap<String, SavedWindow>,
+}
+
+impl SavedLayout {
+    /// Record every window whose geometry the backend reports; the titles of
+    /// the rest come back separately
+    pub fn capture(windows: &[EveWindow]) -> (Self, Vec<String>) {
+        let mut layout = Self::default();
+        let mut unknown = Vec::new();
+        for window in windows {
+            match window.geometry {
+                Some((x, y, width, height)) => {
+                    layout.windows.insert(
+                        window.title.clone(),
+                        SavedWindow {
+                            monitor: window.monitor.clone(),
+                            x,
+                            y,
+                            width,
+                            height,
+                        },
+                    );
+                }
+                None => unknown.push(window.title.clone()),
+            }
+        }
+        (layout, unknown)
+    }
+
+    /// Placements putting each live window back where its character was
+    pub fn plan(&self, windows: &[EveWindow]) -> Vec<Placement> {
+        windows
+            .iter()
+            .filter_map(|window| {
+                let saved = self.windows.get(&window.title)?;
+                Some(Placement {
+                    window_id: window.id,
+                    title: window.title.clone(),
+                    monitor: saved.monitor.clone(),
+                    x: saved.x,
+                    y: saved.y,
+                    size: Some((saved.width, saved.height)),
+                })
+            })
+            .collect()
+    }
+
+    /// $XDG_DATA_HOME/nicotine/layouts
+    fn dir() -> Result<PathBuf> {
+        Ok(dirs::data_dir()
+            .context("Could not determine data directory")?
+            .join("nicotine/layouts"))
+    }
+
+    fn path(name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            anyhow::bail!("Invalid layout name: {:?}", name);
+        }
+        Ok(Self::dir()?.join(format!("{}.json", name)))
+    }
+
+    /// Names of the saved layouts, sorted
+    pub fn list() -> Vec<String> {
+        let Ok(entries) = Self::dir().and_then(|dir| Ok(fs::read_dir(dir)?)) else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                (path.extension()? == "json").then_some(())?;
+                Some(path.file_stem()?.to_string_lossy().into_owned())
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn load(name: &str) -> Result<Self> {
+        let path = Self::path(name)?;
+        let Ok(contents) = fs::read_to_string(&path) else {
+            let saved = Self::list();
+            if saved.is_empty() {
+                anyhow::bail!("No layout named {}; none have been saved", name);
+            }
+            anyhow::bail!("No layout named {} (saved: {})", name, saved.join(", "));
+        };
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, name: &str) -> Result<PathBuf> {
+        let path = Self::path(name)?;
+        fs::create_dir_all(Self::dir()?)?;
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    /// Move every running character back into place. Windows that aren't in
+    /// the layout are skipped and left where they are.
+    pub fn restore(&self, wm: &dyn WindowManager, windows: &[EveWindow]) -> ActionReport {
+        let placements = self.plan(windows);
+        let mut results: Vec<_> = placements
+            .iter()
+            .map(|p| p.window_id)
+            .zip(wm.apply_placements(&placements))
+            .collect();
+        let mut report = ActionReport::default();
+        for window in windows {
+            match results.iter().position(|(id, _)| *id == window.id) {
+                Some(i) => report.record(&window.title, results.swap_remove(i).1),
+                None => report.skip(&window.title),
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(id: u64, title: &str, geometry: Option<(i32, i32, u32, u32)>) -> EveWindow {
+        EveWindow {
+            id,
+            title: title.to_string(),
+            monitor: Some("DP-1".to_string()),
+            geometry,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_restore_puts_characters_back_by_name() {
+        let (layout, unknown) = SavedLayout::capture(&[
+            window(1, "Main", Some((0, 0, 1920, 1080))),
+            window(2, "Scout", Some((1920, 0, 1280, 720))),
+            window(3, "Hauler", None),
+        ]);
+        assert_eq!(unknown, vec!["Hauler".to_string()]);
+
+        // After a relog the ids are new and the order differs
+        let placements = layout.plan(&[
+            window(20, "Scout", None),
+            window(30, "Newbie", None),
+            window(10, "Main", None),
+        ]);
+        assert_eq!(
+            placements,
+            vec![
+                Placement {
+                    window_id: 20,
+                    title: "Scout".to_string(),
+                    monitor: Some("DP-1".to_string()),
+                    x: 1920,
+                    y: 0,
+                    size: Some((1280, 720)),
+                },
+                Placement {
+                    window_id: 10,
+                    title: "Main".to_string(),
+                    monitor: Some("DP-1".to_string()),
+                    x: 0,
+                    y: 0,
+                    size: Some((1920, 1080)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layout_names_stay_inside_the_layouts_dir() {
+        assert!(SavedLayout::path("mining day").is_ok());
+        assert!(SavedLayout::path("../config").is_err());
+        assert!(SavedLayout::path(".hidden").is_err());
+        assert!(SavedLayout::path("").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::cycle_state::CycleState;
+use crate::effects::EffectsLedger;
+use crate::window_manager::EveWindow;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+
+const SNAPSHOT_FILE: &str = "/tmp/nicotine-snapshot.json";
+
+/// Everything the daemon is managing, so a restarted daemon can carry on
+/// with the same windows, cycle position and effects
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// In cycle order, with the monitor and geometry they had when saved
+    pub windows: Vec<EveWindow>,
+    /// Stable key of the current window
+    pub current_key: Option<String>,
+    pub rotation: isize,
+    pub effects: EffectsLedger,
+}
+
+/// A snapshot matched up against the windows that exist now
+#[derive(Debug, Default)]
+pub struct Reconciled {
+    /// Live windows, saved ones first in their saved order, new ones after
+    pub windows: Vec<EveWindow>,
+    /// Stable key of the live window that was current, if it's still there
+    pub current_key: Option<String>,
+    pub rotation: isize,
+    /// Effects ledger with ids moved onto the live windows
+    pub effects: EffectsLedger,
+    /// Titles of saved windows that are gone
+    pub gone: Vec<String>,
+}
+
+impl Snapshot {
+    pub fn capture(state: &CycleState, rotation: isize) -> Self {
+        let windows = state.get_windows().to_vec();
+        Self {
+            current_key: windows
+                .get(state.get_current_index())
+                .map(|w| w.stable_key()),
+            windows,
+            rotation,
+            effects: EffectsLedger::load(),
+        }
+    }
+
+    pub fn load() -> Option<Self> {
+        fs::read_to_string(SNAPSHOT_FILE)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::write(SNAPSHOT_FILE, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Match each saved window to a live one, by stable key and then by
+    /// character name, since a restarted client keeps neither id nor pid
+    pub fn reconcile(mut self, live: Vec<EveWindow>) -> Reconciled {
+        let mut unmatched: Vec<Option<EveWindow>> = live.into_iter().map(Some).collect();
+        let mut take = |pred: &dyn Fn(&EveWindow) -> bool| {
+            let slot = unmatched
+                .iter_mut()
+                .find(|w| w.as_ref().is_some_and(pred))?;
+            slot.take()
+        };
+
+        let mut matched = Vec::new();
+        let mut ids = HashMap::new();
+        let mut keys = HashMap::new();
+        let mut gone = Vec::new();
+        for saved in &self.windows {
+            let key = saved.stable_key();
+            let found =
+                take(&|w| w.stable_key() == key).or_else(|| take(&|w| w.title == saved.title));
+            match found {
+                Some(window) => {
+                    ids.insert(saved.id, window.id);
+                    keys.insert(key, window.stable_key());
+                    matched.push(window);
+                }
+                None => gone.push(saved.title.clone()),
+            }
+        }
+        matched.extend(unmatched.into_iter().flatten());
+
+        self.effects.remap(&ids);
+        Reconciled {
+            windows: matched,
+            current_key: self.current_key.and_then(|key| keys.remove(&key)),
+            rotation: self.rotation,
+            effects: self.effects,
+            gone,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(id: u64, title: &str, pid: u32) -> EveWindow {
+        EveWindow {
+            id,
+            title: title.to_string(),
+            monitor: Some("DP-1".to_string()),
+            geometry: Some((500, 0, 1000, 1000)),
+            pid: Some(pid),
+            class: Some("steam_app_8500".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trips_and_reconciles_against_live_windows() {
+        let mut state = CycleState::new();
+        state.update_windows(vec![
+            window(1, "Main", 100),
+            window(2, "Alt", 200),
+            window(3, "Scout", 300),
+        ]);
+        state.set_current_index(1);
+        let mut snapshot = Snapshot::capture(&state, 2);
+        snapshot.effects =
+            serde_json::from_str(r#"{"original":{"1":0.8,"2":null},"undecorated":[2,3]}"#).unwrap();
+
+        let saved = serde_json::to_string(&snapshot).unwrap();
+        let reloaded: Snapshot = serde_json::from_str(&saved).unwrap();
+        assert_eq!(reloaded.windows, snapshot.windows);
+
+        // Alt's client restarted (new id and pid), Scout logged off and a
+        // new character logged in
+        let live = vec![
+            window(40, "Hauler", 400),
+            window(20, "Alt", 201),
+            window(1, "Main", 100),
+        ];
+        let reconciled = reloaded.reconcile(live);
+
+        let titles: Vec<&str> = reconciled
+            .windows
+            .iter()
+            .map(|w| w.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["Main", "Alt", "Hauler"]);
+        assert_eq!(
+            reconciled.current_key.as_deref(),
+            Some("201:steam_app_8500:Alt")
+        );
+        assert_eq!(reconciled.rotation, 2);
+        assert_eq!(reconciled.gone, vec!["Scout"]);
+        assert_eq!(
+            reconciled.effects.touched().into_iter().collect::<Vec<_>>(),
+            vec![1, 3, 20]
+        );
+    }
+}
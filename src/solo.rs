@@ -0,0 +1,250 @@
+use crate::report::ActionReport;
+use crate::window_manager::{restore_and_focus, window_monitor, EveWindow, Monitor, WindowManager};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+
+const SOLO_FILE: &str = "/tmp/nicotine-solo.json";
+
+/// Whether a window sits on `monitor`, by name or else by its centre
+fn is_on_monitor(window: &EveWindow, monitor: &Monitor, monitors: &[Monitor]) -> bool {
+    window_monitor(window, monitors).is_some_and(|m| m.name == monitor.name)
+}
+
+/// Windows to minimize so `target` is alone on `monitor`: the other EVE
+/// clients there, plus (with minimize_all) every other window there too
+pub fn solo_targets<'a>(
+    target: &EveWindow,
+    monitor: &Monitor,
+    monitors: &[Monitor],
+    eve_windows: &'a [EveWindow],
+    other_windows: &'a [EveWindow],
+    minimize_all: bool,
+) -> Vec<&'a EveWindow> {
+    let mut seen: HashSet<u64> = HashSet::from([target.id]);
+    let others: &[EveWindow] = if minimize_all { other_windows } else { &[] };
+
+    eve_windows
+        .iter()
+        .chain(others)
+        .filter(|w| is_on_monitor(w, monitor, monitors))
+        .filter(|w| seen.insert(w.id))
+        .collect()
+}
+
+/// Managed windows a minimize-all hides: every one, or with `keep_active`
+/// every one but the client the user is in
+pub fn minimize_all_targets(
+    windows: &[EveWindow],
+    active: Option<u64>,
+    keep_active: bool,
+) -> Vec<&EveWindow> {
+    windows
+        .iter()
+        .filter(|w| !(keep_active && Some(w.id) == active))
+        .collect()
+}
+
+/// Windows minimized by the current solo or minimize-all, so unsolo can
+/// bring them back
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SoloLedger {
+    pub monitor: Option<String>,
+    minimized: Vec<u64>,
+}
+
+impl SoloLedger {
+    pub fn load() -> Self {
+        fs::read_to_string(SOLO_FILE)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if self.minimized.is_empty() && self.monitor.is_none() {
+            let _ = fs::remove_file(SOLO_FILE);
+        } else {
+            fs::write(SOLO_FILE, serde_json::to_string(self)?)?;
+        }
+        Ok(())
+    }
+
+    /// Focus `target` and minimize everything in `minimize`, recording what
+    /// was minimized. Only failing to focus `target` is an error; a window
+    /// that won't minimize is reported and the rest still are.
+    pub fn solo(
+        &mut self,
+        wm: &dyn WindowManager,
+        target: &EveWindow,
+        monitor: &Monitor,
+        minimize: &[&EveWindow],
+    ) -> Result<ActionReport> {
+        wm.activate_window(target.id)?;
+        let report = self.minimize_all(wm, minimize);
+        self.monitor = Some(monitor.name.clone());
+        Ok(report)
+    }
+
+    /// Minimize every window in `minimize`, recording the ones that went.
+    /// A window that won't minimize is reported and the rest still are.
+    pub fn minimize_all(
+        &mut self,
+        wm: &dyn WindowManager,
+        minimize: &[&EveWindow],
+    ) -> ActionReport {
+        let mut report = ActionReport::default();
+        for window in minimize {
+            let result = wm.minimize_window(window.id);
+            if result.is_ok() && !self.minimized.contains(&window.id) {
+                self.minimized.push(window.id);
+            }
+            report.record(&window.title, result);
+        }
+        report
+    }
+
+    /// Restore everything the solo minimized, activating each one as it comes
+    /// back with `focus`. Windows that have since closed are dropped without
+    /// failing the rest.
+    pub fn unsolo(&mut self, wm: &dyn WindowManager, focus: bool) -> usize {
+        let restored = self
+            .minimized
+            .drain(..)
+            .filter(|&window_id| restore_and_focus(wm, window_id, focus).is_ok())
+            .count();
+        self.monitor = None;
+        restored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_window_manager::MockWindowManager;
+
+    fn monitors() -> Vec<Monitor> {
+        vec![
+            Monitor {
+                name: "DP-1".to_string(),
+                width: 2560,
+                height: 1440,
+                ..Default::default()
+            },
+            Monitor {
+                name: "DP-2".to_string(),
+                x: 2560,
+                width: 1920,
+                height: 1080,
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn window(id: u64, title: &str, geometry: (i32, i32, u32, u32)) -> EveWindow {
+        EveWindow {
+            id,
+            title: title.to_string(),
+            geometry: Some(geometry),
+            ..Default::default()
+        }
+    }
+
+    fn scene() -> (Vec<EveWindow>, Vec<EveWindow>) {
+        let eve = vec![
+            window(1, "Main", (0, 0, 1280, 1440)),
+            window(2, "Alt", (100, 0, 1280, 1440)),
+            window(3, "Scout", (2600, 0, 1280, 1080)),
+        ];
+        let all = vec![
+            window(1, "EVE - Main", (0, 0, 1280, 1440)),
+            window(2, "EVE - Alt", (100, 0, 1280, 1440)),
+            window(3, "EVE - Scout", (2600, 0, 1280, 1080)),
+            window(10, "Firefox", (200, 200, 1600, 900)),
+            window(11, "Discord", (2700, 100, 1200, 800)),
+        ];
+        (eve, all)
+    }
+
+    #[test]
+    fn test_solo_minimizes_only_eve_windows_by_default() {
+        let (eve, all) = scene();
+        let monitors = monitors();
+
+        let targets = solo_targets(&eve[0], &monitors[0], &monitors, &eve, &all, false);
+        let ids: Vec<u64> = targets.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![2]);
+    }
+
+    #[test]
+    fn test_solo_minimize_all_includes_and_restores_non_eve_windows() {
+        let (eve, all) = scene();
+        let monitors = monitors();
+
+        let targets = solo_targets(&eve[0], &monitors[0], &monitors, &eve, &all, true);
+        // Discord is on the other monitor, so it's left alone
+        let ids: Vec<u64> = targets.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![2, 10]);
+
+        let wm = MockWindowManager::new(all.clone());
+        let mut ledger = SoloLedger::default();
+        let report = ledger.solo(&wm, &eve[0], &monitors[0], &targets).unwrap();
+        assert_eq!(report.succeeded, vec!["Alt", "Firefox"]);
+        assert_eq!(ledger.monitor.as_deref(), Some("DP-1"));
+
+        assert_eq!(ledger.unsolo(&wm, false), 2);
+        assert_eq!(
+            wm.calls(),
+            [
+                "activate 1",
+                "minimize 2",
+                "minimize 10",
+                "restore 2",
+                "restore 10"
+            ]
+        );
+        assert!(ledger.monitor.is_none());
+    }
+
+    #[test]
+    fn test_minimize_all_keep_active_excludes_active_window() {
+        let (eve, _) = scene();
+
+        let ids = |targets: Vec<&EveWindow>| targets.iter().map(|w| w.id).collect::<Vec<u64>>();
+        assert_eq!(ids(minimize_all_targets(&eve, Some(2), true)), vec![1, 3]);
+        assert_eq!(
+            ids(minimize_all_targets(&eve, Some(2), false)),
+            vec![1, 2, 3]
+        );
+        // Focus outside EVE: nothing to keep
+        assert_eq!(
+            ids(minimize_all_targets(&eve, Some(99), true)),
+            vec![1, 2, 3]
+        );
+
+        let wm = MockWindowManager::new(eve.clone());
+        let mut ledger = SoloLedger::default();
+        let report = ledger.minimize_all(&wm, &minimize_all_targets(&eve, Some(2), true));
+        assert_eq!(report.succeeded, vec!["Main", "Scout"]);
+        assert_eq!(ledger.unsolo(&wm, false), 2);
+    }
+
+    #[test]
+    fn test_unsolo_activates_each_window_after_restoring_it() {
+        let (eve, _) = scene();
+        let monitors = monitors();
+        let wm = MockWindowManager::new(eve.clone());
+        let mut ledger = SoloLedger::default();
+        ledger
+            .solo(&wm, &eve[0], &monitors[0], &[&eve[1], &eve[2]])
+            .unwrap();
+        wm.clear_calls();
+
+        assert_eq!(ledger.unsolo(&wm, true), 2);
+        assert_eq!(
+            wm.calls(),
+            ["restore 2", "activate 2", "restore 3", "activate 3"]
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use crate::events::{self, WindowEvent};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+
+pub const RUN_COMMAND: u32 = 0;
+pub const GET_WORKSPACES: u32 = 1;
+pub const SUBSCRIBE: u32 = 2;
+pub const GET_OUTPUTS: u32 = 3;
+pub const GET_TREE: u32 = 4;
+
+/// Events have the high bit of the message type set
+const EVENT_WINDOW: u32 = 0x8000_0003;
+
+fn write_message(stream: &mut impl Write, kind: u32, payload: &str) -> std::io::Result<()> {
+    let mut message = Vec::with_capacity(14 + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(payload.as_bytes());
+    stream.write_all(&message)
+}
+
+fn read_message(stream: &mut impl Read) -> Result<(u32, Vec<u8>)> {
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != MAGIC {
+        anyhow::bail!("Not a sway IPC reply");
+    }
+    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    Ok((kind, payload))
+}
+
+/// First failure in a RUN_COMMAND reply, which has one entry per command
+fn command_error(reply: &Value) -> Option<String> {
+    first_error(reply.as_array()?)
+}
+
+fn first_error(outcomes: &[Value]) -> Option<String> {
+    outcomes.iter().find_map(|outcome| {
+        if outcome.get("success").and_then(|s| s.as_bool()) == Some(true) {
+            return None;
+        }
+        Some(
+            outcome
+                .get("error")
+                .and_then(|e| e.as_str())
+                .unwrap_or("command failed")
+                .to_string(),
+        )
+    })
+}
+
+/// One RUN_COMMAND payload running each container's commands on it, e.g.
+/// "[con_id=1] floating enable, move absolute position 0 0; [con_id=2] ..."
+fn batch_payload(batch: &[(u64, Vec<String>)]) -> String {
+    batch
+        .iter()
+        .map(|(con_id, commands)| format!("[con_id={}] {}", con_id, commands.join(", ")))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Hand each container in `batch` the outcomes of its own commands. Sway
+/// answers every command in turn but gives up at one it can't parse, so a
+/// short reply means the rest never ran.
+fn split_outcomes(reply: &Value, batch: &[(u64, Vec<String>)]) -> Vec<Result<()>> {
+    let mut outcomes = reply.as_array().map(Vec::as_slice).unwrap_or_default();
+    batch
+        .iter()
+        .map(|(_, commands)| {
+            let (mine, rest) = outcomes.split_at(commands.len().min(outcomes.len()));
+            outcomes = rest;
+            if let Some(error) = first_error(mine) {
+                anyhow::bail!("{}", error);
+            }
+            if mine.len() < commands.len() {
+                anyhow::bail!("sway stopped before running every command");
+            }
+            Ok(())
+        })
+        .collect()
+}
+
+/// A request sway never got, or dropped the connection on before answering
+/// any of. Only these are safe to send again: a reply that arrived but
+/// couldn't be read may be for a command sway already ran.
+#[derive(Debug)]
+struct Undelivered(std::io::Error);
+
+impl std::fmt::Display for Undelivered {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "sway closed the connection: {}", self.0)
+    }
+}
+
+impl std::error::Error for Undelivered {}
+
+/// Wait for the first byte of a reply
+fn first_byte(stream: &mut impl Read) -> Result<u8, Undelivered> {
+    let mut byte = [0u8; 1];
+    loop {
+        match stream.read(&mut byte) {
+            Ok(0) => return Err(Undelivered(std::io::ErrorKind::UnexpectedEof.into())),
+            Ok(_) => return Ok(byte[0]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Undelivered(e)),
+        }
+    }
+}
+
+/// A connection to sway's IPC socket, kept open for the life of the backend
+/// instead of starting a swaymsg per request.
+///
+/// This speaks the socket protocol itself rather than going through the
+/// swayipc crate. swayipc parses replies into its own types, which drop any
+/// tree field they don't model, and match_property can name any of them. It
+/// also reports a dropped connection the same way whether or not sway had
+/// started answering, and only requests sway never got are safe to resend.
+pub struct SwayIpc {
+    path: PathBuf,
+    stream: Mutex<UnixStream>,
+}
+
+impl SwayIpc {
+    pub fn connect() -> Result<Self> {
+        let path = std::env::var_os("SWAYSOCK")
+            .map(PathBuf::from)
+            .context("SWAYSOCK is not set. Make sure you're running Sway")?;
+        let stream = UnixStream::connect(&path)
+            .with_context(|| format!("Failed to connect to sway at {}", path.display()))?;
+        Ok(Self {
+            path,
+            stream: Mutex::new(stream),
+        })
+    }
+
+    fn exchange(stream: &mut UnixStream, kind: u32, payload: &str) -> Result<Value> {
+        write_message(stream, kind, payload).map_err(Undelivered)?;
+        let first = first_byte(stream)?;
+        let (_, reply) = read_message(&mut [first].as_slice().chain(&mut *stream))?;
+        tracing::debug!("sway {} {:?} -> {} bytes", kind, payload, reply.len());
+        tracing::trace!("sway reply: {}", String::from_utf8_lossy(&reply));
+        serde_json::from_slice(&reply).context("Failed to parse sway reply")
+    }
+
+    /// Send one request and wait for its reply, reconnecting once if sway
+    /// dropped the connection (e.g. after a reload) before answering
+    pub fn request(&self, kind: u32, payload: &str) -> Result<Value> {
+        let mut stream = self.stream.lock().unwrap();
+        match Self::exchange(&mut stream, kind, payload) {
+            Err(e) if e.is::<Undelivered>() => {
+                *stream = UnixStream::connect(&self.path).context("Lost connection to sway")?;
+                Self::exchange(&mut stream, kind, payload)
+            }
+            result => result,
+        }
+    }
+
+    /// Run a sway command, failing with sway's error if it was rejected
+    pub fn command(&self, command: &str) -> Result<()> {
+        let reply = self.request(RUN_COMMAND, command)?;
+        match command_error(&reply) {
+            Some(error) => anyhow::bail!("{}", error),
+            None => Ok(()),
+        }
+    }
+
+    /// Run each container's commands, all in one request. One result per
+    /// container: the first error among its commands.
+    pub fn command_batch(&self, batch: &[(u64, Vec<String>)]) -> Result<Vec<Result<()>>> {
+        let reply = self.request(RUN_COMMAND, &batch_payload(batch))?;
+        Ok(split_outcomes(&reply, batch))
+    }
+
+    /// Window events on their own connection, read on a background thread
+    pub fn subscribe_windows(&self) -> Result<Receiver<WindowEvent>> {
+        let mut stream = UnixStream::connect(&self.path).context("Failed to connect to sway")?;
+        let reply = Self::exchange(&mut stream, SUBSCRIBE, r#"["window"]"#)?;
+        if reply.get("success").and_then(|s| s.as_bool()) != Some(true) {
+            anyhow::bail!("sway refused the window event subscription");
+        }
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok((kind, payload)) = read_message(&mut stream) {
+                if kind != EVENT_WINDOW {
+                    continue;
+                }
+                let payload = String::from_utf8_lossy(&payload);
+                if let Some(event) = events::parse_sway_event(&payload) {
+                    if tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_reply_over_socket() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+
+        let sway = std::thread::spawn(move || {
+            let (kind, payload) = read_message(&mut server).unwrap();
+            assert_eq!(kind, RUN_COMMAND);
+            assert_eq!(payload, b"[con_id=7] focus");
+            let reply = r#"[{"success":false,"error":"No matching node."}]"#;
+            write_message(&mut server, RUN_COMMAND, reply).unwrap();
+        });
+
+        let reply = SwayIpc::exchange(&mut client, RUN_COMMAND, "[con_id=7] focus").unwrap();
+        sway.join().unwrap();
+        assert_eq!(command_error(&reply).as_deref(), Some("No matching node."));
+        assert_eq!(command_error(&serde_json::json!([{"success": true}])), None);
+    }
+
+    #[test]
+    fn test_only_unanswered_requests_count_as_undelivered() {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        drop(server);
+        let error = SwayIpc::exchange(&mut client, GET_TREE, "").unwrap_err();
+        assert!(error.is::<Undelivered>());
+
+        // sway answered, so the command may have run: not safe to resend
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        let sway = std::thread::spawn(move || {
+            read_message(&mut server).unwrap();
+            write_message(&mut server, RUN_COMMAND, "not json").unwrap();
+        });
+        let error = SwayIpc::exchange(&mut client, RUN_COMMAND, "focus").unwrap_err();
+        sway.join().unwrap();
+        assert!(!error.is::<Undelivered>());
+    }
+
+    #[test]
+    fn test_messages_are_framed_with_magic_length_and_type() {
+        let mut message = Vec::new();
+        write_message(&mut message, GET_TREE, "{}").unwrap();
+        assert_eq!(&message[..6], b"i3-ipc");
+        assert_eq!(message[6..10], 2u32.to_ne_bytes());
+        assert_eq!(message[10..14], GET_TREE.to_ne_bytes());
+        assert_eq!(&message[14..], b"{}");
+
+        let (kind, payload) = read_message(&mut message.as_slice()).unwrap();
+        assert_eq!((kind, payload.as_slice()), (GET_TREE, b"{}".as_slice()));
+    }
+
+    #[test]
+    fn test_bad_or_cut_off_replies_are_errors() {
+        let mut message = Vec::new();
+        write_message(&mut message, GET_TREE, "[]").unwrap();
+
+        let mut wrong_magic = message.clone();
+        wrong_magic[..6].copy_from_slice(b"i3-ipx");
+        assert!(read_message(&mut wrong_magic.as_slice()).is_err());
+        // Header promises two bytes of payload, only one arrives
+        assert!(read_message(&mut &message[..15]).is_err());
+        assert!(read_message(&mut &message[..10]).is_err());
+
+        // A rejection without a message still fails
+        assert_eq!(
+            command_error(&serde_json::json!([{"success": false}])).as_deref(),
+            Some("command failed")
+        );
+    }
+
+    #[test]
+    fn test_window_events_are_read_off_their_own_connection() {
+        let path = std::env::temp_dir().join(format!("nicotine-sway-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let (client, _) = UnixStream::pair().unwrap();
+        let ipc = SwayIpc {
+            path: path.clone(),
+            stream: Mutex::new(client),
+        };
+
+        let sway = std::thread::spawn(move || {
+            let (mut events, _) = listener.accept().unwrap();
+            let (kind, payload) = read_message(&mut events).unwrap();
+            assert_eq!(
+                (kind, payload.as_slice()),
+                (SUBSCRIBE, br#"["window"]"#.as_slice())
+            );
+            write_message(&mut events, SUBSCRIBE, r#"{"success":true}"#).unwrap();
+            // A workspace event, which isn't asked for, then a focus change
+            write_message(&mut events, 0x8000_0000, r#"{"change":"focus"}"#).unwrap();
+            let focus = r#"{"change":"focus","container":{"id":42}}"#;
+            write_message(&mut events, EVENT_WINDOW, focus).unwrap();
+        });
+
+        let rx = ipc.subscribe_windows().unwrap();
+        sway.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(rx.recv().unwrap(), WindowEvent::Focused(42));
+        // sway went away, so the stream ends
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn test_batch_outcomes_go_to_their_own_container() {
+        let commands = |n: usize| vec!["floating enable".to_string(); n];
+        let batch = vec![(1, commands(2)), (2, commands(3)), (3, commands(1))];
+        assert_eq!(
+            batch_payload(&batch[..1]),
+            "[con_id=1] floating enable, floating enable"
+        );
+
+        let ok = serde_json::json!({"success": true});
+        let missing = serde_json::json!({"success": false, "error": "No matching node."});
+        // Container 2's window is gone, and sway stopped before container 3
+        let reply = serde_json::json!([ok, ok, missing, missing, missing]);
+        let results = split_outcomes(&reply, &batch);
+
+        assert!(results[0].is_ok());
+        assert_eq!(
+            results[1].as_ref().unwrap_err().to_string(),
+            "No matching node."
+        );
+        assert!(results[2].is_err());
+    }
+}
@@ -0,0 +1,480 @@
+use crate::config::{Config, WindowMatch};
+use crate::overlay;
+use crate::window_manager::WindowManager;
+use anyhow::{Context, Result};
+use eframe::egui;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use x11rb::connection::Connection;
+use x11rb::protocol::composite::{ConnectionExt as _, Redirect};
+use x11rb::protocol::render::{
+    ConnectionExt as _, CreatePictureAux, PictOp, PictType, Pictformat, QueryPictFormatsReply,
+    Transform,
+};
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, ImageFormat, Window};
+use x11rb::rust_connection::RustConnection;
+
+const TITLE: &str = "Nicotine Previews";
+
+/// A scaled-down copy of a client's contents
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Thumbnail size for a `width` x `height` window scaled to `target_width`
+fn scaled_size(width: u32, height: u32, target_width: u32) -> (u32, u32) {
+    let target_width = target_width.clamp(1, width.max(1));
+    let target_height = (height as u64 * target_width as u64 / width.max(1) as u64).max(1);
+    (target_width, target_height as u32)
+}
+
+/// 16.16 fixed point, as XRender transforms take
+fn fixed(value: f64) -> i32 {
+    (value * 65536.0).round() as i32
+}
+
+/// Z-pixmap pixels of a 24/32-bit little-endian image (B, G, R, pad) as RGBA
+fn bgrx_to_rgba(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(4)
+        .flat_map(|px| [px[2], px[1], px[0], 255])
+        .collect()
+}
+
+/// Captures EVE clients through XComposite, scaled on the X server with
+/// XRender so only thumbnail-sized images cross the socket. EVE runs under
+/// Wine as an X client, so this works under XWayland as well, whichever
+/// backend is managing the windows.
+pub struct Capturer {
+    conn: RustConnection,
+    root: Window,
+    formats: QueryPictFormatsReply,
+    redirected: HashSet<Window>,
+    window_match: WindowMatch,
+}
+
+impl Capturer {
+    pub fn connect(window_match: WindowMatch) -> Result<Self> {
+        let (conn, screen_num) =
+            x11rb::connect(None).context("Thumbnails need an X11 or XWayland display")?;
+        conn.composite_query_version(0, 4)?
+            .reply()
+            .context("X server has no Composite extension")?;
+        conn.render_query_version(0, 11)?
+            .reply()
+            .context("X server has no Render extension")?;
+        let formats = conn.render_query_pict_formats()?.reply()?;
+        let root = conn.setup().roots[screen_num].root;
+        Ok(Self {
+            conn,
+            root,
+            formats,
+            redirected: HashSet::new(),
+            window_match,
+        })
+    }
+
+    fn title(&self, window: Window) -> Option<String> {
+        let utf8_string = self
+            .conn
+            .intern_atom(false, b"UTF8_STRING")
+            .ok()?
+            .reply()
+            .ok()?
+            .atom;
+        let net_wm_name = self
+            .conn
+            .intern_atom(false, b"_NET_WM_NAME")
+            .ok()?
+            .reply()
+            .ok()?
+            .atom;
+        [
+            (net_wm_name, utf8_string),
+            (AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()),
+        ]
+        .into_iter()
+        .find_map(|(property, kind)| {
+            let reply = self
+                .conn
+                .get_property(false, window, property, kind, 0, 1024)
+                .ok()?
+                .reply()
+                .ok()?;
+            Some(String::from_utf8_lossy(&reply.value).to_string()).filter(|t| !t.is_empty())
+        })
+    }
+
+    /// The class half of WM_CLASS ("instance\0class\0")
+    fn class(&self, window: Window) -> Option<String> {
+        let reply = self
+            .conn
+            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)
+            .ok()?
+            .reply()
+            .ok()?;
+        let class = reply.value.split(|&b| b == 0).nth(1)?;
+        Some(String::from_utf8_lossy(class).into_owned())
+    }
+
+    /// EVE clients by character name. Under a reparenting window manager the
+    /// client is a child of its frame, so frames are looked inside too.
+    pub fn clients(&self) -> Result<Vec<(String, Window)>> {
+        let mut clients = Vec::new();
+        for top in self.conn.query_tree(self.root)?.reply()?.children {
+            let candidates = match self.title(top) {
+                Some(title) => vec![(top, title)],
+                None => self
+                    .conn
+                    .query_tree(top)?
+                    .reply()?
+                    .children
+                    .into_iter()
+                    .filter_map(|child| self.title(child).map(|title| (child, title)))
+                    .collect(),
+            };
+            for (window, title) in candidates {
+                if let Some(character) = self
+                    .window_match
+                    .client_name(&title, self.class(window).as_deref())
+                {
+                    clients.push((character.to_string(), window));
+                }
+            }
+        }
+        Ok(clients)
+    }
+
+    fn format_for_visual(&self, visual: u32) -> Option<Pictformat> {
+        self.formats
+            .screens
+            .iter()
+            .flat_map(|s| &s.depths)
+            .flat_map(|d| &d.visuals)
+            .find(|v| v.visual == visual)
+            .map(|v| v.format)
+    }
+
+    fn rgb24_format(&self) -> Option<Pictformat> {
+        self.formats
+            .formats
+            .iter()
+            .find(|f| f.type_ == PictType::DIRECT && f.depth == 24 && f.direct.alpha_mask == 0)
+            .map(|f| f.id)
+    }
+
+    /// The window's current contents scaled to `target_width`. Fails for
+    /// windows that aren't mapped (minimized), which have no contents.
+    pub fn capture(&mut self, window: Window, target_width: u32) -> Result<Thumbnail> {
+        if self.redirected.insert(window) {
+            // Automatic redirection can be shared with a compositor
+            let _ = self
+                .conn
+                .composite_redirect_window(window, Redirect::AUTOMATIC)?
+                .check();
+        }
+
+        let geometry = self.conn.get_geometry(window)?.reply()?;
+        let attributes = self.conn.get_window_attributes(window)?.reply()?;
+        let (width, height) = (geometry.width as u32, geometry.height as u32);
+        let (thumb_w, thumb_h) = scaled_size(width, height, target_width);
+        let src_format = self
+            .format_for_visual(attributes.visual)
+            .context("No picture format for the client's visual")?;
+        let dst_format = self.rgb24_format().context("No 24-bit picture format")?;
+
+        let contents = self.conn.generate_id()?;
+        let src = self.conn.generate_id()?;
+        let scaled = self.conn.generate_id()?;
+        let dst = self.conn.generate_id()?;
+
+        self.conn
+            .composite_name_window_pixmap(window, contents)?
+            .check()
+            .context("Client isn't mapped")?;
+        self.conn
+            .render_create_picture(src, contents, src_format, &CreatePictureAux::new())?;
+        self.conn.render_set_picture_transform(
+            src,
+            Transform {
+                matrix11: fixed(width as f64 / thumb_w as f64),
+                matrix12: 0,
+                matrix13: 0,
+                matrix21: 0,
+                matrix22: fixed(height as f64 / thumb_h as f64),
+                matrix23: 0,
+                matrix31: 0,
+                matrix32: 0,
+                matrix33: fixed(1.0),
+            },
+        )?;
+        self.conn.render_set_picture_filter(src, b"bilinear", &[])?;
+        self.conn
+            .create_pixmap(24, scaled, self.root, thumb_w as u16, thumb_h as u16)?;
+        self.conn
+            .render_create_picture(dst, scaled, dst_format, &CreatePictureAux::new())?;
+        self.conn.render_composite(
+            PictOp::SRC,
+            src,
+            x11rb::NONE,
+            dst,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            thumb_w as u16,
+            thumb_h as u16,
+        )?;
+        let image = self.conn.get_image(
+            ImageFormat::Z_PIXMAP,
+            scaled,
+            0,
+            0,
+            thumb_w as u16,
+            thumb_h as u16,
+            !0,
+        )?;
+        let image = image.reply();
+
+        let _ = self.conn.render_free_picture(dst);
+        let _ = self.conn.render_free_picture(src);
+        let _ = self.conn.free_pixmap(scaled);
+        let _ = self.conn.free_pixmap(contents);
+        self.conn.flush()?;
+
+        Ok(Thumbnail {
+            width: thumb_w,
+            height: thumb_h,
+            rgba: bgrx_to_rgba(&image?.data),
+        })
+    }
+}
+
+/// What the capture thread hands the strip
+#[derive(Default)]
+struct Frame {
+    /// Character, the backend's window id and its latest thumbnail
+    clients: Vec<(String, Option<u64>, Thumbnail)>,
+    active: Option<u64>,
+    /// Bumped on every capture so textures are only re-uploaded when new
+    generation: u64,
+}
+
+/// Recapture every client each interval. A client that can't be captured
+/// right now (minimized) keeps its last thumbnail.
+fn spawn_capture(
+    ctx: egui::Context,
+    mut capturer: Capturer,
+    wm: Arc<dyn WindowManager>,
+    frame: Arc<Mutex<Frame>>,
+    config: &Config,
+) {
+    let width = config.thumbnail_width;
+    let interval = Duration::from_millis(config.thumbnail_interval_ms.max(16));
+    std::thread::spawn(move || {
+        let mut last: HashMap<String, Thumbnail> = HashMap::new();
+        loop {
+            let ids: HashMap<String, u64> = wm
+                .get_eve_windows()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|w| (w.title, w.id))
+                .collect();
+
+            let mut clients = Vec::new();
+            for (character, window) in capturer.clients().unwrap_or_default() {
+                if let Ok(thumbnail) = capturer.capture(window, width) {
+                    last.insert(character.clone(), thumbnail);
+                }
+                if let Some(thumbnail) = last.get(&character) {
+                    let id = ids.get(&character).copied();
+                    clients.push((character, id, thumbnail.clone()));
+                }
+            }
+
+            {
+                let mut frame = frame.lock().unwrap();
+                frame.clients = clients;
+                frame.active = wm.get_active_window().ok();
+                frame.generation += 1;
+            }
+            ctx.request_repaint();
+            std::thread::sleep(interval);
+        }
+    });
+}
+
+pub struct ThumbnailApp {
+    wm: Arc<dyn WindowManager>,
+    frame: Arc<Mutex<Frame>>,
+    textures: HashMap<String, egui::TextureHandle>,
+    uploaded: u64,
+    last_size: Option<egui::Vec2>,
+}
+
+impl ThumbnailApp {
+    fn upload(&mut self, ctx: &egui::Context, frame: &Frame) {
+        if frame.generation == self.uploaded {
+            return;
+        }
+        self.uploaded = frame.generation;
+        for (character, _, thumbnail) in &frame.clients {
+            let image = egui::ColorImage::from_rgba_unmultiplied(
+                [thumbnail.width as usize, thumbnail.height as usize],
+                &thumbnail.rgba,
+            );
+            match self.textures.get_mut(character) {
+                Some(texture) => texture.set(image, egui::TextureOptions::LINEAR),
+                None => {
+                    let texture = ctx.load_texture(character, image, egui::TextureOptions::LINEAR);
+                    self.textures.insert(character.clone(), texture);
+                }
+            }
+        }
+    }
+
+    /// Grow or shrink the strip to fit the thumbnails
+    fn fit(&mut self, ctx: &egui::Context, frame: &Frame) {
+        let width: f32 = frame
+            .clients
+            .iter()
+            .map(|(_, _, t)| t.width as f32 + 8.0)
+            .sum::<f32>()
+            + 8.0;
+        let height = frame
+            .clients
+            .iter()
+            .map(|(_, _, t)| t.height as f32)
+            .fold(60.0, f32::max)
+            + 32.0;
+        let size = egui::vec2(width.max(160.0), height);
+        if self.last_size != Some(size) {
+            self.last_size = Some(size);
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+        }
+    }
+}
+
+impl eframe::App for ThumbnailApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let frame = Arc::clone(&self.frame);
+        let frame = frame.lock().unwrap();
+        self.upload(ctx, &frame);
+        self.fit(ctx, &frame);
+
+        let red = egui::Color32::from_rgb(196, 30, 58);
+        let gold = egui::Color32::from_rgb(180, 155, 105);
+        let cream = egui::Color32::from_rgb(252, 250, 242);
+
+        egui::CentralPanel::default()
+            .frame(
+                egui::Frame::none()
+                    .fill(egui::Color32::from_rgb(30, 30, 30))
+                    .inner_margin(4.0)
+                    .stroke(egui::Stroke::new(2.0, gold)),
+            )
+            .show(ctx, |ui| {
+                if frame.clients.is_empty() {
+                    ui.centered_and_justified(|ui| ui.colored_label(gold, "No clients"));
+                    return;
+                }
+                ui.horizontal_top(|ui| {
+                    for (character, id, _) in &frame.clients {
+                        let Some(texture) = self.textures.get(character) else {
+                            continue;
+                        };
+                        let is_active = id.is_some() && *id == frame.active;
+                        ui.vertical(|ui| {
+                            let response =
+                                ui.add(egui::Image::new(texture).sense(egui::Sense::click()));
+                            if is_active {
+                                ui.painter().rect_stroke(
+                                    response.rect,
+                                    0.0,
+                                    egui::Stroke::new(2.0, red),
+                                );
+                            }
+                            ui.colored_label(
+                                if is_active { red } else { cream },
+                                egui::RichText::new(character).size(12.0).strong(),
+                            );
+                            if let (true, Some(id)) = (response.clicked(), *id) {
+                                let wm = Arc::clone(&self.wm);
+                                std::thread::spawn(move || {
+                                    if let Err(e) = wm.activate_window(id) {
+                                        tracing::error!("Failed to activate client: {}", e);
+                                    }
+                                });
+                            }
+                        });
+                    }
+                });
+            });
+    }
+}
+
+/// Show live thumbnails of every client in an always-on-top strip; clicking
+/// one activates that client
+pub fn run_thumbnails(wm: Arc<dyn WindowManager>, config: Config) -> Result<()> {
+    let capturer = Capturer::connect(config.window_match.clone())?;
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title(TITLE)
+            .with_inner_size([config.thumbnail_width as f32 + 16.0, 200.0])
+            .with_position([config.thumbnail_x, config.thumbnail_y])
+            .with_decorations(false)
+            .with_always_on_top()
+            .with_resizable(false),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        TITLE,
+        options,
+        Box::new(move |cc| {
+            overlay::pin_above(TITLE);
+            let frame = Arc::new(Mutex::new(Frame::default()));
+            spawn_capture(
+                cc.egui_ctx.clone(),
+                capturer,
+                Arc::clone(&wm),
+                Arc::clone(&frame),
+                &config,
+            );
+            Ok(Box::new(ThumbnailApp {
+                wm,
+                frame,
+                textures: HashMap::new(),
+                uploaded: 0,
+                last_size: None,
+            }))
+        }),
+    )
+    .map_err(|e| anyhow::anyhow!("Thumbnail window failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnail_keeps_aspect_and_never_upscales() {
+        assert_eq!(scaled_size(2560, 1440, 240), (240, 135));
+        assert_eq!(scaled_size(1382, 1440, 240), (240, 250));
+        assert_eq!(scaled_size(200, 100, 240), (200, 100));
+        assert_eq!(scaled_size(0, 0, 240), (1, 1));
+        assert_eq!(fixed(2560.0 / 240.0), 699051);
+    }
+
+    #[test]
+    fn test_pixels_converted_to_rgba() {
+        let bgrx = [10, 20, 30, 0, 1, 2, 3, 99];
+        assert_eq!(bgrx_to_rgba(&bgrx), vec![30, 20, 10, 255, 3, 2, 1, 255]);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::config::EsiConfig;
+use crate::esi::{self, Token, Tokens};
+use crate::window_manager::EveWindow;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerKind {
+    /// The last skill in the queue finishing
+    SkillQueue,
+    Industry,
+    /// A planet's extractors stopping
+    Planet,
+}
+
+/// Something a character has running that finishes at a known time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timer {
+    pub character: String,
+    pub kind: TimerKind,
+    /// The industry activity or the planet, e.g. "Manufacturing" or "Jita IV"
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+    /// Unix time it finishes
+    pub ends: u64,
+    /// The character's client, if it's running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_id: Option<u64>,
+}
+
+impl Timer {
+    /// e.g. "skill queue", "Manufacturing job", "extractors on Jita IV"
+    pub fn describe(&self) -> String {
+        match self.kind {
+            TimerKind::SkillQueue => "skill queue".to_string(),
+            TimerKind::Industry => format!("{} job", self.label),
+            TimerKind::Planet => format!("extractors on {}", self.label),
+        }
+    }
+}
+
+/// Seconds since the Unix epoch of an ESI timestamp, e.g.
+/// "2026-10-16T12:34:56Z"
+pub fn parse_esi_time(time: &str) -> Option<u64> {
+    let (date, clock) = time.trim_end_matches('Z').split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|n| n.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let mut clock = clock.splitn(3, ':').map(|n| n.parse::<f64>().ok());
+    let (hour, minute, second) = (clock.next()??, clock.next()??, clock.next()??);
+
+    // Days from 1970-01-01 in the proleptic Gregorian calendar
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let seconds = days * 86400 + (hour as i64) * 3600 + (minute as i64) * 60 + second as i64;
+    u64::try_from(seconds).ok()
+}
+
+/// Time left until `ends`, e.g. "2d 4h", "3h 15m", "12m", or "done"
+pub fn remaining(ends: u64, now: u64) -> String {
+    let left = ends.saturating_sub(now);
+    let (days, hours, minutes) = (left / 86400, left % 86400 / 3600, left % 3600 / 60);
+    match left {
+        0 => "done".to_string(),
+        _ if days > 0 => format!("{}d {}h", days, hours),
+        _ if hours > 0 => format!("{}h {}m", hours, minutes),
+        _ => format!("{}m", minutes.max(1)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct QueuedSkill {
+    finish_date: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndustryJob {
+    activity_id: u32,
+    end_date: String,
+    status: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Colony {
+    planet_id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ColonyLayout {
+    #[serde(default)]
+    pins: Vec<ColonyPin>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ColonyPin {
+    expiry_time: Option<String>,
+    extractor_details: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Planet {
+    name: String,
+}
+
+fn activity_name(activity_id: u32) -> &'static str {
+    match activity_id {
+        1 => "Manufacturing",
+        3 => "TE research",
+        4 => "ME research",
+        5 => "Copying",
+        8 => "Invention",
+        9 | 11 => "Reaction",
+        _ => "Industry",
+    }
+}
+
+/// When the queue runs dry. A paused queue has no finish dates and no timer.
+fn skill_queue_ends(queue: &[QueuedSkill]) -> Option<u64> {
+    queue
+        .iter()
+        .filter_map(|skill| parse_esi_time(skill.finish_date.as_deref()?))
+        .max()
+}
+
+/// Jobs still running or waiting to be delivered, by activity and end
+fn industry_ends(jobs: &[IndustryJob]) -> Vec<(&'static str, u64)> {
+    jobs.iter()
+        .filter(|job| job.status == "active" || job.status == "ready")
+        .filter_map(|job| {
+            Some((
+                activity_name(job.activity_id),
+                parse_esi_time(&job.end_date)?,
+            ))
+        })
+        .collect()
+}
+
+/// When the first of a colony's extractors stops
+fn extractors_end(layout: &ColonyLayout) -> Option<u64> {
+    layout
+        .pins
+        .iter()
+        .filter(|pin| pin.extractor_details.is_some())
+        .filter_map(|pin| parse_esi_time(pin.expiry_time.as_deref()?))
+        .min()
+}
+
+fn character_timers(token: &Token) -> Result<Vec<Timer>> {
+    let id = token.character_id;
+    let timer = |kind, label: &str, ends| Timer {
+        character: token.character.clone(),
+        kind,
+        label: label.to_string(),
+        ends,
+        window_id: None,
+    };
+    let mut timers = Vec::new();
+
+    let queue: Vec<QueuedSkill> =
+        esi::get_json(Some(token), &format!("/characters/{}/skillqueue/", id))?;
+    if let Some(ends) = skill_queue_ends(&queue) {
+        timers.push(timer(TimerKind::SkillQueue, "", ends));
+    }
+
+    let jobs: Vec<IndustryJob> =
+        esi::get_json(Some(token), &format!("/characters/{}/industry/jobs/", id))?;
+    for (activity, ends) in industry_ends(&jobs) {
+        timers.push(timer(TimerKind::Industry, activity, ends));
+    }
+
+    let colonies: Vec<Colony> =
+        esi::get_json(Some(token), &format!("/characters/{}/planets/", id))?;
+    for colony in colonies {
+        let path = format!("/characters/{}/planets/{}/", id, colony.planet_id);
+        let layout: ColonyLayout = esi::get_json(Some(token), &path)?;
+        if let Some(ends) = extractors_end(&layout) {
+            let planet: Planet =
+                esi::get_json(None, &format!("/universe/planets/{}/", colony.planet_id))?;
+            timers.push(timer(TimerKind::Planet, &planet.name, ends));
+        }
+    }
+    Ok(timers)
+}
+
+/// Every logged-in character's timers, soonest first. A character whose
+/// timers can't be read is left out with a warning.
+pub fn fetch(config: &EsiConfig) -> Result<Vec<Timer>> {
+    let client_id = config
+        .client_id
+        .as_deref()
+        .context("Set [esi] client_id to read timers")?;
+    let mut tokens = Tokens::load();
+    let mut timers = Vec::new();
+    for character in tokens.characters() {
+        let result = tokens
+            .access_token(&character, client_id)
+            .and_then(|token| character_timers(&token));
+        match result {
+            Ok(found) => timers.extend(found),
+            Err(e) => tracing::warn!("No timers for {}: {:#}", character, e),
+        }
+    }
+    tokens.save()?;
+    timers.sort_by_key(|t| t.ends);
+    Ok(timers)
+}
+
+/// Tie each timer to its character's client, if it's running
+pub fn attach_windows(timers: &mut [Timer], windows: &[EveWindow]) {
+    for timer in timers {
+        timer.window_id = windows
+            .iter()
+            .find(|w| w.title == timer.character)
+            .map(|w| w.id);
+    }
+}
+
+/// Timers that finished after `since`, up to `now`
+pub fn finished_between(timers: &[Timer], since: u64, now: u64) -> Vec<&Timer> {
+    timers
+        .iter()
+        .filter(|t| t.ends > since && t.ends <= now)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_esi_times_and_time_left() {
+        assert_eq!(parse_esi_time("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_esi_time("2000-03-01T00:00:00Z"), Some(951868800));
+        assert_eq!(parse_esi_time("2026-10-16T12:34:56Z"), Some(1792154096));
+        assert_eq!(parse_esi_time("2026-10-16"), None);
+
+        assert_eq!(remaining(100, 200), "done");
+        assert_eq!(remaining(200 + 30, 200), "1m");
+        assert_eq!(remaining(3 * 3600 + 15 * 60, 0), "3h 15m");
+        assert_eq!(remaining(2 * 86400 + 4 * 3600 + 59, 0), "2d 4h");
+    }
+
+    #[test]
+    fn test_timers_come_from_esi_answers() {
+        let queue: Vec<QueuedSkill> = serde_json::from_str(
+            r#"[{"skill_id":3300,"finish_date":"2026-10-17T00:00:00Z"},
+                {"skill_id":3301,"finish_date":"2026-10-20T00:00:00Z"},
+                {"skill_id":3302}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            skill_queue_ends(&queue),
+            parse_esi_time("2026-10-20T00:00:00Z")
+        );
+
+        let jobs: Vec<IndustryJob> = serde_json::from_str(
+            r#"[{"activity_id":1,"end_date":"2026-10-17T00:00:00Z","status":"active"},
+                {"activity_id":8,"end_date":"2026-10-15T00:00:00Z","status":"delivered"}]"#,
+        )
+        .unwrap();
+        let ends = industry_ends(&jobs);
+        assert_eq!(ends.len(), 1);
+        assert_eq!(ends[0].0, "Manufacturing");
+
+        let layout: ColonyLayout = serde_json::from_str(
+            r#"{"pins":[{"pin_id":1,"expiry_time":"2026-10-18T00:00:00Z","extractor_details":{}},
+                        {"pin_id":2,"expiry_time":"2026-10-17T00:00:00Z"},
+                        {"pin_id":3,"expiry_time":"2026-10-19T00:00:00Z","extractor_details":{}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            extractors_end(&layout),
+            parse_esi_time("2026-10-18T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn test_timers_finish_once_and_find_their_client() {
+        let timer = |character: &str, ends| Timer {
+            character: character.to_string(),
+            kind: TimerKind::SkillQueue,
+            label: String::new(),
+            ends,
+            window_id: None,
+        };
+        let mut timers = vec![timer("Main", 100), timer("Alt", 200)];
+
+        assert_eq!(finished_between(&timers, 50, 150), vec![&timers[0]]);
+        assert!(finished_between(&timers, 150, 160).is_empty());
+
+        let windows = vec![EveWindow {
+            id: 7,
+            title: "Alt".to_string(),
+            ..Default::default()
+        }];
+        attach_windows(&mut timers, &windows);
+        assert_eq!(timers[0].window_id, None);
+        assert_eq!(timers[1].window_id, Some(7));
+    }
+}
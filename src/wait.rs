@@ -0,0 +1,457 @@
+use crate::config::Config;
+use crate::hooks;
+use crate::layout;
+use crate::report::ActionReport;
+use crate::window_manager::{EveWindow, WindowManager};
+use anyhow::Result;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Time source for polling, so tests can run the schedule without sleeping
+pub trait Clock {
+    /// Time elapsed since some fixed starting point
+    fn now(&self) -> Duration;
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// When wait-for-windows gives up and how long it sleeps between polls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollSchedule {
+    pub timeout: Duration,
+    pub interval: Duration,
+    pub backoff: bool,
+    pub max_interval: Duration,
+}
+
+impl PollSchedule {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.wait_timeout),
+            interval: Duration::from_millis(config.wait_poll_interval_ms),
+            backoff: config.wait_backoff,
+            max_interval: Duration::from_millis(config.wait_max_poll_interval_ms),
+        }
+    }
+
+    /// Delay to use after a poll that waited `current`
+    fn next_interval(&self, current: Duration) -> Duration {
+        if self.backoff {
+            (current * 2).min(self.max_interval.max(self.interval))
+        } else {
+            current
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaitOutcome {
+    pub stacked: usize,
+    pub deferred: usize,
+    pub timed_out: bool,
+}
+
+/// Whether a window's live title still shows an in-game character. Backends
+/// that can't read titles are assumed ready.
+fn is_in_game(wm: &dyn WindowManager, window: &EveWindow, config: &Config) -> bool {
+    match wm.get_title(window.id) {
+        Ok(Some(title)) => config
+            .window_match
+            .character_name(&title, window.class.as_deref())
+            .is_some(),
+        _ => true,
+    }
+}
+
+/// Stack windows, holding back any that are sitting at login/character select
+/// when defer_login_windows is on. Those held back are reported as skipped.
+/// post_stack_command is run for the windows that were stacked.
+pub fn stack_in_game(
+    wm: &dyn WindowManager,
+    windows: &[EveWindow],
+    config: &Config,
+) -> Result<ActionReport> {
+    let report = stack_ready(wm, windows, config)?;
+    let stacked: Vec<EveWindow> = windows
+        .iter()
+        .filter(|w| report.succeeded.contains(&w.title))
+        .cloned()
+        .collect();
+    hooks::after_stack(config, &stacked);
+    Ok(report)
+}
+
+fn stack_ready(
+    wm: &dyn WindowManager,
+    windows: &[EveWindow],
+    config: &Config,
+) -> Result<ActionReport> {
+    if !config.defer_login_windows {
+        return wm.stack_windows(windows, config);
+    }
+
+    let (ready, deferred): (Vec<EveWindow>, Vec<EveWindow>) = windows
+        .iter()
+        .cloned()
+        .partition(|window| is_in_game(wm, window, config));
+
+    let mut report = if ready.is_empty() {
+        ActionReport::default()
+    } else {
+        wm.stack_windows(&ready, config)?
+    };
+    for window in &deferred {
+        report.skip(&window.title);
+    }
+    Ok(report)
+}
+
+/// Remembers which clients are in game, so auto_stack_on_login can place
+/// just the ones that logged in since it last looked
+#[derive(Debug, Default)]
+pub struct LoginWatcher {
+    in_game: Option<HashSet<u64>>,
+}
+
+/// What a LoginWatcher saw: every client in game, and those of them that
+/// logged in since it last looked
+#[derive(Debug, Default)]
+pub struct Logins {
+    pub in_game: Vec<EveWindow>,
+    pub new: Vec<EveWindow>,
+}
+
+impl LoginWatcher {
+    /// Which clients have come into game since the last call. The first
+    /// call only notes what's there.
+    pub fn poll(
+        &mut self,
+        wm: &dyn WindowManager,
+        windows: &[EveWindow],
+        config: &Config,
+    ) -> Logins {
+        let known = self.in_game.take();
+        let in_game: Vec<EveWindow> = windows
+            .iter()
+            .filter(|w| {
+                known.as_ref().is_some_and(|k| k.contains(&w.id)) || is_in_game(wm, w, config)
+            })
+            .cloned()
+            .collect();
+        self.in_game = Some(in_game.iter().map(|w| w.id).collect());
+
+        let new = match known {
+            Some(known) => in_game
+                .iter()
+                .filter(|w| !known.contains(&w.id))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        Logins { in_game, new }
+    }
+}
+
+impl Logins {
+    /// Place the new clients where a full stack of every client in game
+    /// would put them, leaving the others where they are
+    pub fn stack(&self, wm: &dyn WindowManager, config: &Config) -> Result<ActionReport> {
+        let mut report = ActionReport::default();
+        if self.new.is_empty() {
+            return Ok(report);
+        }
+
+        let monitors = wm.get_layout_monitors(config)?;
+        for placement in layout::plan_stack(&self.in_game, &monitors, config) {
+            if let Some(window) = self.new.iter().find(|w| w.id == placement.window_id) {
+                report.record(&window.title, wm.apply_placement(&placement));
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Poll until `expected` windows have been stacked or the schedule's timeout
+/// passes, stacking each window as soon as it's in game
+pub fn wait_for_windows(
+    wm: &dyn WindowManager,
+    config: &Config,
+    expected: usize,
+    schedule: &PollSchedule,
+    verbose: bool,
+) -> Result<WaitOutcome> {
+    wait_for_windows_with(wm, config, expected, schedule, &SystemClock::new(), verbose)
+}
+
+pub fn wait_for_windows_with(
+    wm: &dyn WindowManager,
+    config: &Config,
+    expected: usize,
+    schedule: &PollSchedule,
+    clock: &dyn Clock,
+    verbose: bool,
+) -> Result<WaitOutcome> {
+    let start = clock.now();
+    let mut interval = schedule.interval;
+    let mut stacked: HashSet<u64> = HashSet::new();
+
+    loop {
+        let pending: Vec<EveWindow> = wm
+            .get_eve_windows()?
+            .into_iter()
+            .filter(|w| !stacked.contains(&w.id))
+            .collect();
+
+        // Deferred and failed windows are tried again on the next poll
+        let report = stack_in_game(wm, &pending, config)?;
+        for window in &pending {
+            if report.succeeded.contains(&window.title) {
+                stacked.insert(window.id);
+            }
+        }
+        let deferred = report.skipped;
+
+        if verbose {
+            println!(
+                "Found {} of {} ({} at login)",
+                stacked.len(),
+                expected,
+                deferred.len()
+            );
+        }
+
+        let elapsed = clock.now().saturating_sub(start);
+        let timed_out = elapsed >= schedule.timeout;
+        if stacked.len() >= expected || timed_out {
+            return Ok(WaitOutcome {
+                stacked: stacked.len(),
+                deferred: deferred.len(),
+                timed_out: stacked.len() < expected,
+            });
+        }
+
+        // Don't oversleep the deadline
+        clock.sleep(interval.min(schedule.timeout - elapsed));
+        interval = schedule.next_interval(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_window_manager::{window, MockWindowManager};
+    use std::cell::RefCell;
+
+    /// Advances only when slept on, recording each sleep
+    struct FakeClock {
+        now: RefCell<Duration>,
+        sleeps: RefCell<Vec<Duration>>,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Duration {
+            *self.now.borrow()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            *self.now.borrow_mut() += duration;
+            self.sleeps.borrow_mut().push(duration);
+        }
+    }
+
+    /// One client that shows the login screen for the first `login_polls`
+    /// title reads, then becomes "EVE - Main"
+    fn logging_in(login_polls: usize) -> MockWindowManager {
+        let mut titles = vec!["EVE"; login_polls];
+        titles.push("EVE - Main");
+        MockWindowManager::new(vec![window(1, "Main")])
+            .with_active(1)
+            .with_titles(1, &titles)
+    }
+
+    fn placed(wm: &MockWindowManager) -> Vec<u64> {
+        wm.placements().iter().map(|p| p.window_id).collect()
+    }
+
+    fn guarded_config() -> Config {
+        Config {
+            defer_login_windows: true,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_login_window_is_deferred_until_it_becomes_a_character() {
+        let wm = logging_in(1);
+        let windows = wm.get_eve_windows().unwrap();
+
+        let report = stack_in_game(&wm, &windows, &guarded_config()).unwrap();
+        assert_eq!(report.skipped.len(), 1);
+        assert!(report.succeeded.is_empty());
+        assert!(placed(&wm).is_empty());
+
+        let report = stack_in_game(&wm, &windows, &guarded_config()).unwrap();
+        assert!(report.skipped.is_empty());
+        assert_eq!(report.succeeded.len(), 1);
+        assert_eq!(placed(&wm), vec![1]);
+    }
+
+    #[test]
+    fn test_post_stack_hook_gets_only_the_windows_stacked() {
+        let out = std::env::temp_dir().join(format!("nicotine-wait-hook-{}", std::process::id()));
+        let wm = MockWindowManager::new(vec![window(1, "Main"), window(2, "Alt")])
+            .with_titles(1, &["EVE - Main"])
+            .with_titles(2, &["EVE"]);
+        let config = Config {
+            post_stack_command: Some(format!("echo \"$NICOTINE_CHARACTERS\" > {}", out.display())),
+            ..guarded_config()
+        };
+
+        let report = stack_in_game(&wm, &wm.get_eve_windows().unwrap(), &config).unwrap();
+        let hooked = std::fs::read_to_string(&out).unwrap();
+        let _ = std::fs::remove_file(&out);
+
+        assert_eq!(report.skipped, ["Alt"]);
+        assert_eq!(hooked.trim(), "Main");
+    }
+
+    #[test]
+    fn test_wait_for_windows_retries_deferred_window() {
+        let wm = logging_in(2);
+
+        let schedule = PollSchedule {
+            timeout: Duration::from_secs(5),
+            interval: Duration::from_millis(1),
+            backoff: false,
+            max_interval: Duration::from_millis(1),
+        };
+        let outcome = wait_for_windows(&wm, &guarded_config(), 1, &schedule, false).unwrap();
+
+        assert_eq!(
+            outcome,
+            WaitOutcome {
+                stacked: 1,
+                deferred: 0,
+                timed_out: false
+            }
+        );
+        assert_eq!(placed(&wm), vec![1]);
+    }
+
+    #[test]
+    fn test_guard_off_stacks_login_window_immediately() {
+        let wm = logging_in(5);
+        let windows = wm.get_eve_windows().unwrap();
+
+        let report = stack_in_game(&wm, &windows, &Config::default()).unwrap();
+        assert!(report.skipped.is_empty());
+        assert_eq!(placed(&wm), vec![1]);
+    }
+
+    #[test]
+    fn test_login_watcher_places_client_once_it_logs_in() {
+        let wm = logging_in(2);
+        let windows = wm.get_eve_windows().unwrap();
+        let mut logins = LoginWatcher::default();
+        let config = Config::default();
+
+        // At character select when first seen, then still there
+        assert_eq!(
+            logins
+                .poll(&wm, &windows, &config)
+                .stack(&wm, &config)
+                .unwrap()
+                .total(),
+            0
+        );
+        assert_eq!(
+            logins
+                .poll(&wm, &windows, &config)
+                .stack(&wm, &config)
+                .unwrap()
+                .total(),
+            0
+        );
+        assert!(placed(&wm).is_empty());
+
+        let report = logins
+            .poll(&wm, &windows, &config)
+            .stack(&wm, &config)
+            .unwrap();
+        assert_eq!(report.succeeded, vec!["Main".to_string()]);
+        assert_eq!(placed(&wm), vec![1]);
+
+        // Already placed; not moved again
+        logins
+            .poll(&wm, &windows, &config)
+            .stack(&wm, &config)
+            .unwrap();
+        assert_eq!(placed(&wm), vec![1]);
+    }
+
+    #[test]
+    fn test_backoff_intervals_double_up_to_cap() {
+        // Never enough windows, so this polls until the timeout
+        let wm = logging_in(0);
+        let clock = FakeClock {
+            now: RefCell::new(Duration::ZERO),
+            sleeps: RefCell::new(Vec::new()),
+        };
+        let schedule = PollSchedule {
+            timeout: Duration::from_millis(3500),
+            interval: Duration::from_millis(100),
+            backoff: true,
+            max_interval: Duration::from_millis(1000),
+        };
+
+        let outcome =
+            wait_for_windows_with(&wm, &Config::default(), 2, &schedule, &clock, false).unwrap();
+
+        let sleeps: Vec<u64> = clock
+            .sleeps
+            .borrow()
+            .iter()
+            .map(|d| d.as_millis() as u64)
+            .collect();
+        // 100+200+400+800+1000 = 2500, then the last sleep stops at the deadline
+        assert_eq!(sleeps, vec![100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(clock.now(), schedule.timeout);
+        assert!(outcome.timed_out);
+        assert_eq!(outcome.stacked, 1);
+    }
+
+    #[test]
+    fn test_without_backoff_interval_stays_fixed() {
+        let schedule = PollSchedule {
+            timeout: Duration::from_secs(10),
+            interval: Duration::from_millis(500),
+            backoff: false,
+            max_interval: Duration::from_secs(5),
+        };
+        assert_eq!(
+            schedule.next_interval(Duration::from_millis(500)),
+            Duration::from_millis(500)
+        );
+    }
+}
@@ -1,29 +1,88 @@
-use crate::config::Config;
-use crate::window_manager::{EveWindow, Monitor, WindowManager};
+use crate::config::{
+    Config, FocusWorkaround, HyprlandMinimize, MonitorRef, SwayMinimize, SwayTitleField,
+    WarpAnchor, WindowMatch,
+};
+use crate::events::{self, WindowEvent};
+use crate::hyprland_ipc::{self, HyprlandIpc};
+use crate::kwin_script;
+use crate::layout::Placement;
+use crate::logging::LoggedOutput;
+use crate::monitor_cache::MonitorCache;
+use crate::sway_ipc::{self, SwayIpc};
+use crate::window_manager::{
+    apply_focus_workarounds, is_missing_window_reply, monitor_at, order_monitors,
+    retain_on_monitors, warp_target, work_area_struts, EveWindow, Monitor, Struts, WindowManager,
+    WindowNotFound,
+};
+use crate::xrandr;
 use anyhow::{Context, Result};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::path::PathBuf;
 use std::process::Command;
+use std::sync::mpsc::Receiver;
+use std::sync::Mutex;
+
+/// match_property's field on a Sway tree node or Hyprland client, looked up
+/// at the top level (app_id, class) and then in window_properties (Xwayland
+/// windows on Sway)
+fn marker_value(window: &Value, match_property: Option<&(String, String)>) -> Option<String> {
+    let (field, _) = match_property?;
+    let value = [Some(window), window.get("window_properties")]
+        .into_iter()
+        .flatten()
+        .filter_map(|node| node.get(field))
+        .find(|value| !value.is_null())?;
+    match value {
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// A Sway node's app_id, or its WM_CLASS class for Xwayland windows
+fn window_class(window: &Value) -> Option<String> {
+    window
+        .get("app_id")
+        .and_then(|id| id.as_str())
+        .or_else(|| window.pointer("/window_properties/class")?.as_str())
+        .map(String::from)
+}
 
 // ============================================================================
 // KDE Plasma / KWin Backend (via wmctrl through XWayland)
 // ============================================================================
 
-pub struct KWinManager;
+pub struct KWinManager {
+    discover_monitors: Option<Vec<MonitorRef>>,
+    window_match: WindowMatch,
+    monitor_order: Vec<MonitorRef>,
+    monitor_cache: MonitorCache,
+    focus_workarounds: Vec<FocusWorkaround>,
+}
 
 impl KWinManager {
-    pub fn new() -> Result<Self> {
+    pub fn new(config: &Config) -> Result<Self> {
         Command::new("wmctrl")
             .arg("-m")
-            .output()
+            .logged_output()
             .context("wmctrl not found. Install wmctrl package")?;
 
-        Ok(Self)
+        Ok(Self {
+            discover_monitors: config.discover_monitors.clone(),
+            window_match: config.window_match.clone(),
+            monitor_order: config.monitor_order.clone(),
+            monitor_cache: MonitorCache::default(),
+            focus_workarounds: config.focus_workarounds.clone(),
+        })
     }
 
-    fn get_all_windows(&self) -> Result<Vec<(String, String)>> {
+    /// (id, class, title) from `wmctrl -lx`: id, desktop, instance.class,
+    /// host, title
+    fn get_all_windows(&self) -> Result<Vec<(String, String, String)>> {
         let output = Command::new("wmctrl")
-            .arg("-l")
-            .output()
+            .arg("-lx")
+            .logged_output()
             .context("Failed to execute wmctrl")?;
 
         if !output.status.success() {
@@ -35,18 +94,49 @@ impl KWinManager {
 
         for line in lines.lines() {
             let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 4 {
+            if parts.len() >= 5 {
                 let window_id = parts[0];
-                let title = parts[3..].join(" ");
-                windows.push((window_id.to_string(), title));
+                let class = parts[2]
+                    .split_once('.')
+                    .map_or(parts[2], |(_, class)| class);
+                let title = parts[4..].join(" ");
+                windows.push((window_id.to_string(), class.to_string(), title));
             }
         }
 
         Ok(windows)
     }
 
+    /// Windows from `wmctrl -lGx`: id, desktop, x, y, width, height,
+    /// instance.class, host, title
+    fn parse_geometry_listing(listing: &str) -> Vec<EveWindow> {
+        listing
+            .lines()
+            .filter_map(|line| {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                let id = u64::from_str_radix(parts.first()?.trim_start_matches("0x"), 16).ok()?;
+                let geometry = (
+                    parts.get(2)?.parse().ok()?,
+                    parts.get(3)?.parse().ok()?,
+                    parts.get(4)?.parse().ok()?,
+                    parts.get(5)?.parse().ok()?,
+                );
+                let wm_class = parts.get(6)?;
+                let class = wm_class
+                    .split_once('.')
+                    .map_or(*wm_class, |(_, class)| class);
+                Some(EveWindow {
+                    id,
+                    class: Some(class.to_string()),
+                    geometry: Some(geometry),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
     fn get_window_title_by_id(&self, hex_id: &str) -> Option<String> {
-        let output = Command::new("wmctrl").arg("-l").output().ok()?;
+        let output = Command::new("wmctrl").arg("-l").logged_output().ok()?;
         if !output.status.success() {
             return None;
         }
@@ -63,62 +153,32 @@ impl KWinManager {
         None
     }
 
-    /// Get monitor geometry using xrandr (works through XWayland)
     fn get_monitors_internal(&self) -> Result<Vec<Monitor>> {
+        self.monitor_cache.get(|| self.query_monitors())
+    }
+
+    /// Get monitor geometry using xrandr (works through XWayland)
+    fn query_monitors(&self) -> Result<Vec<Monitor>> {
         let output = Command::new("xrandr")
             .arg("--query")
-            .output()
+            .logged_output()
             .context("Failed to execute xrandr")?;
 
         if !output.status.success() {
             return Ok(Vec::new());
         }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let mut monitors = Vec::new();
-
-        // Parse xrandr output: "DP-1 connected primary 2560x1440+0+0 ..."
-        for line in stdout.lines() {
-            if line.contains(" connected") {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                let name = parts.first().map(|s| s.to_string()).unwrap_or_default();
-
-                for part in &parts {
-                    // Match pattern like "2560x1440+0+0"
-                    if part.contains('x') && part.contains('+') {
-                        if let Some((res, pos)) = part.split_once('+') {
-                            if let Some((width_str, height_str)) = res.split_once('x') {
-                                let pos_parts: Vec<&str> = pos.split('+').collect();
-                                if pos_parts.len() >= 2 {
-                                    if let (Ok(width), Ok(height), Ok(x), Ok(y)) = (
-                                        width_str.parse::<u32>(),
-                                        height_str.parse::<u32>(),
-                                        pos_parts[0].parse::<i32>(),
-                                        pos_parts[1].parse::<i32>(),
-                                    ) {
-                                        monitors.push(Monitor {
-                                            name,
-                                            x,
-                                            y,
-                                            width,
-                                            height,
-                                        });
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        Ok(monitors)
+        Ok(xrandr::parse_monitors(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
     }
 
-    /// Determine which monitor a window is on using wmctrl -lG
-    fn get_window_monitor(&self, hex_id: &str, monitors: &[Monitor]) -> Option<String> {
-        let output = Command::new("wmctrl").args(["-l", "-G"]).output().ok()?;
+    /// Read a window's (x, y, width, height) using wmctrl -lG
+    fn get_window_geometry(&self, hex_id: &str) -> Option<(i32, i32, u32, u32)> {
+        let output = Command::new("wmctrl")
+            .args(["-l", "-G"])
+            .logged_output()
+            .ok()?;
         if !output.status.success() {
             return None;
         }
@@ -131,27 +191,33 @@ impl KWinManager {
                 if parts.len() >= 6 {
                     let x: i32 = parts[2].parse().ok()?;
                     let y: i32 = parts[3].parse().ok()?;
-                    let w: i32 = parts[4].parse().ok()?;
-                    let h: i32 = parts[5].parse().ok()?;
-
-                    // Window center
-                    let center_x = x + w / 2;
-                    let center_y = y + h / 2;
-
-                    // Find containing monitor
-                    for mon in monitors {
-                        if center_x >= mon.x
-                            && center_x < mon.x + mon.width as i32
-                            && center_y >= mon.y
-                            && center_y < mon.y + mon.height as i32
-                        {
-                            return Some(mon.name.clone());
-                        }
-                    }
+                    let w: u32 = parts[4].parse().ok()?;
+                    let h: u32 = parts[5].parse().ok()?;
+                    return Some((x, y, w, h));
                 }
             }
         }
 
+        None
+    }
+
+    /// Determine which monitor a window is on from its geometry
+    fn get_window_monitor(
+        &self,
+        geometry: Option<(i32, i32, u32, u32)>,
+        monitors: &[Monitor],
+    ) -> Option<String> {
+        if let Some((x, y, w, h)) = geometry {
+            // Window center
+            let center_x = x + (w / 2) as i32;
+            let center_y = y + (h / 2) as i32;
+
+            // Find containing monitor
+            if let Some(mon) = monitor_at(monitors, center_x, center_y) {
+                return Some(mon.name.clone());
+            }
+        }
+
         monitors.first().map(|m| m.name.clone())
     }
 }
@@ -162,8 +228,8 @@ impl WindowManager for KWinManager {
         let monitors = self.get_monitors().unwrap_or_default();
         let mut eve_windows = Vec::new();
 
-        for (id_str, title) in windows {
-            if title.starts_with("EVE - ") && !title.contains("Launcher") {
+        for (id_str, class, title) in windows {
+            if let Some(character) = self.window_match.client_name(&title, Some(&class)) {
                 // Parse hex window ID (e.g., "0x06e00008") to u64
                 let id = if let Some(hex) = id_str.strip_prefix("0x") {
                     u64::from_str_radix(hex, 16).unwrap_or(0)
@@ -173,123 +239,106 @@ impl WindowManager for KWinManager {
 
                 if id != 0 {
                     // Determine which monitor the window is on based on its geometry
-                    let monitor = self.get_window_monitor(&id_str, &monitors);
+                    let geometry = self.get_window_geometry(&id_str);
+                    let monitor = self.get_window_monitor(geometry, &monitors);
                     eve_windows.push(EveWindow {
                         id,
-                        title: title.trim_start_matches("EVE - ").to_string(),
+                        title: character.to_string(),
                         monitor,
+                        geometry,
+                        pid: None,
+                        class: Some(class),
                     });
                 }
             }
         }
 
+        if let Some(allowed) = &self.discover_monitors {
+            let monitors = self.get_monitors().unwrap_or_default();
+            retain_on_monitors(&mut eve_windows, &monitors, allowed);
+        }
+
         Ok(eve_windows)
     }
 
     fn activate_window(&self, window_id: u64) -> Result<()> {
+        apply_focus_workarounds(self, window_id, &self.focus_workarounds);
         let hex_id = format!("0x{:08x}", window_id);
 
-        if let Some(title) = self.get_window_title_by_id(&hex_id) {
-            if Command::new("kdotool")
-                .args(["search", "--name", &title, "windowactivate"])
-                .output()
-                .map(|o| o.status.success())
-                .unwrap_or(false)
-            {
-                return Ok(());
-            }
+        // A window missing from wmctrl's list has been closed
+        let title = self
+            .get_window_title_by_id(&hex_id)
+            .ok_or(WindowNotFound(window_id))?;
+
+        if Command::new("kdotool")
+            .args(["search", "--name", &title, "windowactivate"])
+            .logged_output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+        {
+            return Ok(());
         }
 
         Command::new("wmctrl")
             .args(["-i", "-a", &hex_id])
-            .output()
+            .logged_output()
             .context("Failed to activate window")?;
 
         Ok(())
     }
 
-    fn stack_windows(&self, windows: &[EveWindow], config: &Config) -> Result<()> {
-        let monitors = self.get_monitors()?;
-
-        for window in windows {
-            // Determine target monitor:
-            // - Primary character goes to primary_monitor
-            // - Others stay on their current monitor
-            let is_primary = config
-                .primary_character
-                .as_ref()
-                .map(|c| window.title == *c)
-                .unwrap_or(false);
-
-            let target_monitor = if is_primary {
-                // Primary character goes to primary_monitor
-                config
-                    .primary_monitor
-                    .as_ref()
-                    .and_then(|name| monitors.iter().find(|m| &m.name == name))
-                    .or_else(|| monitors.first())
-            } else {
-                // Others stay on current monitor
-                window
-                    .monitor
-                    .as_ref()
-                    .and_then(|name| monitors.iter().find(|m| &m.name == name))
-                    .or_else(|| monitors.first())
-            };
+    fn apply_placement(&self, placement: &Placement) -> Result<()> {
+        // Convert u32 to hex format for wmctrl
+        let hex_id = format!("0x{:08x}", placement.window_id);
 
-            let (x, y, width, height) = if let Some(mon) = target_monitor {
-                if config.fullscreen_stack {
-                    // Fullscreen on monitor
-                    let height = mon.height.saturating_sub(config.panel_height);
-                    (mon.x, mon.y, mon.width, height)
-                } else {
-                    // Centered with eve_width
-                    let eve_w = config.eve_width.min(mon.width);
-                    let x = mon.x + ((mon.width - eve_w) / 2) as i32;
-                    let height = mon.height.saturating_sub(config.panel_height);
-                    (x, mon.y, eve_w, height)
-                }
-            } else {
-                // Fallback to global config
-                let x = ((config.display_width - config.eve_width) / 2) as i32;
-                let height = config.display_height - config.panel_height;
-                (x, 0, config.eve_width, height)
-            };
+        // Move and resize window using wmctrl (-1 leaves a dimension unchanged)
+        let (width, height) = placement
+            .size
+            .map(|(w, h)| (w as i64, h as i64))
+            .unwrap_or((-1, -1));
+        let output = Command::new("wmctrl")
+            .arg("-i")
+            .arg("-r")
+            .arg(&hex_id)
+            .arg("-e")
+            .arg(format!(
+                "0,{},{},{},{}",
+                placement.x, placement.y, width, height
+            ))
+            .logged_output()
+            .context("Failed to execute wmctrl")?;
 
-            // Convert u32 to hex format for wmctrl
-            let hex_id = format!("0x{:08x}", window.id);
-
-            // Move and resize window using wmctrl
-            let output = Command::new("wmctrl")
-                .arg("-i")
-                .arg("-r")
-                .arg(&hex_id)
-                .arg("-e")
-                .arg(format!("0,{},{},{},{}", x, y, width, height))
-                .output()
-                .context("Failed to execute wmctrl")?;
-
-            if !output.status.success() {
-                anyhow::bail!(
-                    "wmctrl failed to stack window {}: {}",
-                    hex_id,
-                    String::from_utf8_lossy(&output.stderr)
-                );
-            }
+        if !output.status.success() {
+            anyhow::bail!(
+                "wmctrl failed to stack window {}: {}",
+                hex_id,
+                String::from_utf8_lossy(&output.stderr)
+            );
         }
 
         Ok(())
     }
 
     fn get_monitors(&self) -> Result<Vec<Monitor>> {
-        self.get_monitors_internal()
+        Ok(order_monitors(
+            self.get_monitors_internal()?,
+            &self.monitor_order,
+        ))
+    }
+
+    fn invalidate_monitors(&self) {
+        self.monitor_cache.invalidate();
+    }
+
+    fn get_title(&self, window_id: u64) -> Result<Option<String>> {
+        Ok(self.get_window_title_by_id(&format!("0x{:08x}", window_id)))
     }
 
     fn get_active_window(&self) -> Result<u64> {
         // Use xdotool to get active window (works through XWayland)
         let output = Command::new("xdotool")
             .arg("getactivewindow")
-            .output()
+            .logged_output()
             .context("Failed to get active window")?;
 
         let window_id = String::from_utf8_lossy(&output.stdout)
@@ -303,7 +352,7 @@ impl WindowManager for KWinManager {
     fn find_window_by_title(&self, title: &str) -> Result<Option<u64>> {
         let windows = self.get_all_windows()?;
 
-        for (id_str, window_title) in windows {
+        for (id_str, _, window_title) in windows {
             if window_title == title {
                 // Parse hex window ID (e.g., "0x06e00008") to u64
                 let id = if let Some(hex) = id_str.strip_prefix("0x") {
@@ -321,11 +370,30 @@ impl WindowManager for KWinManager {
         Ok(None)
     }
 
+    fn raise_window(&self, window_id: u64) -> Result<()> {
+        let hex_id = format!("0x{:08x}", window_id);
+        Command::new("xdotool")
+            .args(["windowraise", &hex_id])
+            .logged_output()
+            .context("Failed to raise window")?;
+        Ok(())
+    }
+
+    fn get_panel_windows(&self) -> Result<Vec<EveWindow>> {
+        let output = Command::new("wmctrl")
+            .arg("-lGx")
+            .logged_output()
+            .context("Failed to execute wmctrl")?;
+        Ok(Self::parse_geometry_listing(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+
     fn minimize_window(&self, window_id: u64) -> Result<()> {
         let hex_id = format!("0x{:08x}", window_id);
         Command::new("xdotool")
             .args(["windowminimize", &hex_id])
-            .output()
+            .logged_output()
             .context("Failed to minimize window")?;
         Ok(())
     }
@@ -335,381 +403,1346 @@ impl WindowManager for KWinManager {
         // wmctrl -i -a activates and restores from minimized state
         Command::new("wmctrl")
             .args(["-i", "-a", &hex_id])
-            .output()
+            .logged_output()
             .context("Failed to restore window")?;
         Ok(())
     }
+
+    // wmctrl -c asks the client to close, as its close button would
+    fn close_window(&self, window_id: u64) -> Result<()> {
+        let hex_id = format!("0x{:08x}", window_id);
+        Command::new("wmctrl")
+            .args(["-i", "-c", &hex_id])
+            .logged_output()
+            .context("Failed to close window")?;
+        Ok(())
+    }
 }
 
 // ============================================================================
-// Sway Backend (via swaymsg)
+// KDE Plasma / KWin Backend (via KWin D-Bus scripting)
 // ============================================================================
 
-pub struct SwayManager;
+/// A window as listed by the enumeration script
+#[derive(Debug, Clone, PartialEq)]
+struct KWinWindow {
+    /// KWin's internalId, which scripts look windows up by
+    uuid: String,
+    /// Raw caption in `title`
+    window: EveWindow,
+    minimized: bool,
+}
 
-impl SwayManager {
-    pub fn new() -> Result<Self> {
-        // Verify swaymsg is available
-        Command::new("swaymsg")
-            .arg("--version")
-            .output()
-            .context("swaymsg not found. Make sure you're running Sway")?;
+/// Talks to KWin itself through temporary KWin scripts, so native Wayland
+/// clients are visible too, not just the XWayland ones wmctrl can see
+pub struct KWinDbusManager {
+    discover_monitors: Option<Vec<MonitorRef>>,
+    window_match: WindowMatch,
+    monitor_order: Vec<MonitorRef>,
+    monitor_cache: MonitorCache,
+    focus_workarounds: Vec<FocusWorkaround>,
+    warp_on_activate: bool,
+    /// internalId of each window id handed out, filled in by enumeration
+    uuids: Mutex<HashMap<u64, String>>,
+}
 
-        Ok(Self)
+const KWIN_LIST_WINDOWS: &str = r#"
+var out = [];
+var list = nicotineWindows();
+for (var i = 0; i < list.length; i++) {
+    var w = list[i];
+    if (!w.normalWindow) continue;
+    var g = w.frameGeometry;
+    out.push({
+        uuid: String(w.internalId),
+        caption: w.caption,
+        pid: w.pid,
+        class: String(w.resourceClass),
+        x: g.x, y: g.y, width: g.width, height: g.height,
+        output: w.output ? w.output.name : null,
+        minimized: w.minimized
+    });
+}
+return out;
+"#;
+
+const KWIN_LIST_SCREENS: &str = r#"
+var out = [];
+var screens = workspace.screens || [];
+for (var i = 0; i < screens.length; i++) {
+    var g = screens[i].geometry;
+    out.push({ name: screens[i].name, x: g.x, y: g.y, width: g.width, height: g.height });
+}
+return out;
+"#;
+
+impl KWinDbusManager {
+    pub fn new(config: &Config) -> Result<Self> {
+        let manager = Self {
+            discover_monitors: config.discover_monitors.clone(),
+            window_match: config.window_match.clone(),
+            monitor_order: config.monitor_order.clone(),
+            monitor_cache: MonitorCache::default(),
+            focus_workarounds: config.focus_workarounds.clone(),
+            warp_on_activate: config.warp_on_activate,
+            uuids: Mutex::new(HashMap::new()),
+        };
+        // Proves both the script interface and the D-Bus read-back work
+        manager
+            .list_windows()
+            .context("KWin scripting is not available")?;
+        Ok(manager)
     }
 
-    fn get_all_windows(&self) -> Result<Vec<(Value, Option<String>)>> {
-        let output = Command::new("swaymsg")
-            .arg("-t")
-            .arg("get_tree")
-            .output()
-            .context("Failed to execute swaymsg")?;
-
-        if !output.status.success() {
-            anyhow::bail!(
-                "swaymsg failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
-        }
-
-        let tree: Value =
-            serde_json::from_slice(&output.stdout).context("Failed to parse swaymsg output")?;
-
-        let mut windows = Vec::new();
-        Self::extract_windows(&tree, &mut windows, None);
-
-        Ok(windows)
+    /// Stable u64 for a KWin internalId (FNV-1a)
+    fn window_id(uuid: &str) -> u64 {
+        uuid.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
     }
 
-    fn get_monitors_internal(&self) -> Result<Vec<Monitor>> {
-        let output = Command::new("swaymsg")
-            .args(["-t", "get_outputs"])
-            .output()
-            .context("Failed to execute swaymsg")?;
-
-        if !output.status.success() {
-            return Ok(Vec::new());
-        }
-
-        let outputs: Vec<Value> =
-            serde_json::from_slice(&output.stdout).context("Failed to parse swaymsg output")?;
-
-        let mut monitors = Vec::new();
-        for output in outputs {
-            if let (Some(name), Some(rect)) = (
-                output.get("name").and_then(|n| n.as_str()),
-                output.get("rect"),
-            ) {
-                if let (Some(x), Some(y), Some(width), Some(height)) = (
-                    rect.get("x").and_then(|v| v.as_i64()),
-                    rect.get("y").and_then(|v| v.as_i64()),
-                    rect.get("width").and_then(|v| v.as_u64()),
-                    rect.get("height").and_then(|v| v.as_u64()),
-                ) {
-                    monitors.push(Monitor {
-                        name: name.to_string(),
-                        x: x as i32,
-                        y: y as i32,
-                        width: width as u32,
-                        height: height as u32,
-                    });
-                }
-            }
-        }
-
-        Ok(monitors)
+    fn parse_windows(reply: &Value) -> Vec<KWinWindow> {
+        reply
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|w| {
+                let uuid = w.get("uuid")?.as_str()?.to_string();
+                let geometry = (
+                    w.get("x")?.as_f64()? as i32,
+                    w.get("y")?.as_f64()? as i32,
+                    w.get("width")?.as_f64()? as u32,
+                    w.get("height")?.as_f64()? as u32,
+                );
+                Some(KWinWindow {
+                    window: EveWindow {
+                        id: Self::window_id(&uuid),
+                        title: w.get("caption")?.as_str()?.to_string(),
+                        monitor: w.get("output").and_then(|o| o.as_str()).map(String::from),
+                        geometry: Some(geometry),
+                        pid: w.get("pid").and_then(|p| p.as_u64()).map(|p| p as u32),
+                        class: w.get("class").and_then(|c| c.as_str()).map(String::from),
+                    },
+                    minimized: w.get("minimized").and_then(|m| m.as_bool()) == Some(true),
+                    uuid,
+                })
+            })
+            .collect()
     }
 
-    fn extract_windows(
-        node: &Value,
-        windows: &mut Vec<(Value, Option<String>)>,
-        current_output: Option<&str>,
-    ) {
-        let node_type = node.get("type").and_then(|t| t.as_str());
-
-        // Track output name when we encounter an output node
-        let output_name = if node_type == Some("output") {
-            node.get("name").and_then(|n| n.as_str())
-        } else {
-            current_output
-        };
-
-        if let Some(nt) = node_type {
-            if nt == "con" || nt == "floating_con" {
-                if let Some(app_id) = node.get("app_id") {
-                    if !app_id.is_null() {
-                        windows.push((node.clone(), output_name.map(|s| s.to_string())));
-                    }
-                } else if let Some(window_properties) = node.get("window_properties") {
-                    if !window_properties.is_null() {
-                        windows.push((node.clone(), output_name.map(|s| s.to_string())));
-                    }
-                }
-            }
+    fn list_windows(&self) -> Result<Vec<KWinWindow>> {
+        let windows = Self::parse_windows(&kwin_script::query(KWIN_LIST_WINDOWS)?);
+        let mut uuids = self.uuids.lock().unwrap();
+        for w in &windows {
+            uuids.insert(w.window.id, w.uuid.clone());
         }
+        Ok(windows)
+    }
 
-        if let Some(nodes) = node.get("nodes").and_then(|n| n.as_array()) {
-            for child in nodes {
-                Self::extract_windows(child, windows, output_name);
-            }
+    fn uuid(&self, window_id: u64) -> Result<String> {
+        if let Some(uuid) = self.uuids.lock().unwrap().get(&window_id) {
+            return Ok(uuid.clone());
         }
+        self.list_windows()?;
+        self.uuids
+            .lock()
+            .unwrap()
+            .get(&window_id)
+            .cloned()
+            .ok_or_else(|| WindowNotFound(window_id).into())
+    }
 
-        if let Some(floating_nodes) = node.get("floating_nodes").and_then(|n| n.as_array()) {
-            for child in floating_nodes {
-                Self::extract_windows(child, windows, output_name);
-            }
+    /// Run `action` with `w` bound to the window, failing if it's gone
+    fn with_window(&self, window_id: u64, action: &str) -> Result<()> {
+        let uuid = self.uuid(window_id)?;
+        let found = kwin_script::query(&format!(
+            "var w = nicotineFind({:?}); if (!w) return false; {} return true;",
+            uuid, action
+        ))?;
+        if found != Value::Bool(true) {
+            self.uuids.lock().unwrap().remove(&window_id);
+            return Err(WindowNotFound(window_id).into());
         }
+        Ok(())
     }
 
-    fn get_window_title(window: &Value) -> Option<String> {
-        window
-            .get("name")
-            .and_then(|n| n.as_str())
-            .map(|s| s.to_string())
+    fn get_monitors_internal(&self) -> Result<Vec<Monitor>> {
+        self.monitor_cache.get(|| self.query_monitors())
     }
 
-    fn get_window_id(window: &Value) -> Option<u64> {
-        window.get("id").and_then(|i| i.as_u64())
+    fn query_monitors(&self) -> Result<Vec<Monitor>> {
+        let screens = kwin_script::query(KWIN_LIST_SCREENS)?;
+        let monitors: Vec<Monitor> = screens
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|s| {
+                Some(Monitor {
+                    name: s.get("name")?.as_str()?.to_string(),
+                    x: s.get("x")?.as_f64()? as i32,
+                    y: s.get("y")?.as_f64()? as i32,
+                    width: s.get("width")?.as_f64()? as u32,
+                    height: s.get("height")?.as_f64()? as u32,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        if !monitors.is_empty() {
+            return Ok(monitors);
+        }
+
+        // KWin 5 has no workspace.screens; xrandr sees the outputs through XWayland
+        let output = Command::new("xrandr")
+            .arg("--query")
+            .logged_output()
+            .context("Failed to execute xrandr")?;
+        Ok(xrandr::parse_monitors(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
     }
 }
 
-impl WindowManager for SwayManager {
+impl WindowManager for KWinDbusManager {
     fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
-        let windows = self.get_all_windows()?;
-        let mut eve_windows = Vec::new();
-
-        for (window, output_name) in windows {
-            if let Some(title) = Self::get_window_title(&window) {
-                if title.starts_with("EVE - ") && !title.contains("Launcher") {
-                    if let Some(id) = Self::get_window_id(&window) {
-                        eve_windows.push(EveWindow {
-                            id,
-                            title: title.trim_start_matches("EVE - ").to_string(),
-                            monitor: output_name,
-                        });
-                    }
-                }
-            }
+        let mut eve_windows: Vec<EveWindow> = self
+            .list_windows()?
+            .into_iter()
+            .filter_map(|w| {
+                let character = self
+                    .window_match
+                    .client_name(&w.window.title, w.window.class.as_deref())?
+                    .to_string();
+                Some(EveWindow {
+                    title: character,
+                    ..w.window
+                })
+            })
+            .collect();
+
+        if let Some(allowed) = &self.discover_monitors {
+            let monitors = self.get_monitors().unwrap_or_default();
+            retain_on_monitors(&mut eve_windows, &monitors, allowed);
         }
 
         Ok(eve_windows)
     }
 
-    fn activate_window(&self, window_id: u64) -> Result<()> {
-        let output = Command::new("swaymsg")
-            .arg(format!("[con_id={}] focus", window_id))
-            .output()
-            .context("Failed to activate window")?;
+    fn get_process_windows(&self) -> Result<Vec<EveWindow>> {
+        Ok(self.list_windows()?.into_iter().map(|w| w.window).collect())
+    }
 
-        if !output.status.success() {
-            anyhow::bail!(
-                "Failed to activate window: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
+    fn activate_window(&self, window_id: u64) -> Result<()> {
+        apply_focus_workarounds(self, window_id, &self.focus_workarounds);
+        self.with_window(
+            window_id,
+            r#"w.minimized = false;
+               if ("activeWindow" in workspace) workspace.activeWindow = w;
+               else workspace.activeClient = w;"#,
+        )?;
+
+        if self.warp_on_activate {
+            let _ = self.warp_pointer(window_id);
         }
-
         Ok(())
     }
 
-    fn stack_windows(&self, windows: &[EveWindow], config: &Config) -> Result<()> {
-        let monitors = self.get_monitors()?;
-
-        for window in windows {
-            // Determine target monitor:
-            // - Primary character goes to primary_monitor
-            // - Others stay on their current monitor
-            let is_primary = config
-                .primary_character
-                .as_ref()
-                .map(|c| window.title == *c)
-                .unwrap_or(false);
-
-            let target_monitor = if is_primary {
-                // Primary character goes to primary_monitor
-                config
-                    .primary_monitor
-                    .as_ref()
-                    .and_then(|name| monitors.iter().find(|m| &m.name == name))
-                    .or_else(|| monitors.first())
-            } else {
-                // Others stay on current monitor
-                window
-                    .monitor
-                    .as_ref()
-                    .and_then(|name| monitors.iter().find(|m| &m.name == name))
-                    .or_else(|| monitors.first())
-            };
-
-            let (x, y, width, height) = if let Some(mon) = target_monitor {
-                if config.fullscreen_stack {
-                    // Fullscreen on monitor
-                    let height = mon.height.saturating_sub(config.panel_height);
-                    (mon.x, mon.y, mon.width as i32, height as i32)
-                } else {
-                    // Centered with eve_width
-                    let eve_w = config.eve_width.min(mon.width);
-                    let x = mon.x + ((mon.width - eve_w) / 2) as i32;
-                    let height = mon.height.saturating_sub(config.panel_height);
-                    (x, mon.y, eve_w as i32, height as i32)
-                }
-            } else {
-                // Fallback to global config
-                let x = ((config.display_width - config.eve_width) / 2) as i32;
-                let height = (config.display_height - config.panel_height) as i32;
-                (x, 0, config.eve_width as i32, height)
-            };
-
-            // Sway uses floating mode for positioning
-            let output = Command::new("swaymsg")
-                .arg(format!("[con_id={}] floating enable", window.id))
-                .output()
-                .context("Failed to execute swaymsg")?;
-
-            if !output.status.success() {
-                anyhow::bail!(
-                    "swaymsg failed to enable floating for window {}: {}",
-                    window.id,
-                    String::from_utf8_lossy(&output.stderr)
-                );
-            }
-
-            let output = Command::new("swaymsg")
-                .arg(format!("[con_id={}] move position {} {}", window.id, x, y))
-                .output()
-                .context("Failed to execute swaymsg")?;
-
-            if !output.status.success() {
-                anyhow::bail!(
-                    "swaymsg failed to move window {}: {}",
-                    window.id,
-                    String::from_utf8_lossy(&output.stderr)
-                );
-            }
-
-            let output = Command::new("swaymsg")
-                .arg(format!(
-                    "[con_id={}] resize set {} {}",
-                    window.id, width, height
-                ))
-                .output()
-                .context("Failed to execute swaymsg")?;
-
-            if !output.status.success() {
-                anyhow::bail!(
-                    "swaymsg failed to resize window {}: {}",
-                    window.id,
-                    String::from_utf8_lossy(&output.stderr)
-                );
-            }
-        }
-
-        Ok(())
+    fn apply_placement(&self, placement: &Placement) -> Result<()> {
+        let (width, height) = match placement.size {
+            Some((w, h)) => (w.to_string(), h.to_string()),
+            None => ("g.width".to_string(), "g.height".to_string()),
+        };
+        self.with_window(
+            placement.window_id,
+            &format!(
+                "var g = w.frameGeometry; \
+                 w.frameGeometry = {{ x: {}, y: {}, width: {}, height: {} }};",
+                placement.x, placement.y, width, height
+            ),
+        )
     }
 
     fn get_monitors(&self) -> Result<Vec<Monitor>> {
-        self.get_monitors_internal()
+        Ok(order_monitors(
+            self.get_monitors_internal()?,
+            &self.monitor_order,
+        ))
     }
 
-    fn get_active_window(&self) -> Result<u64> {
-        let windows = self.get_all_windows()?;
+    fn invalidate_monitors(&self) {
+        self.monitor_cache.invalidate();
+    }
 
-        for (window, _output) in windows {
-            if let Some(focused) = window.get("focused").and_then(|f| f.as_bool()) {
-                if focused {
-                    if let Some(id) = Self::get_window_id(&window) {
-                        return Ok(id);
-                    }
-                }
-            }
-        }
+    fn get_title(&self, window_id: u64) -> Result<Option<String>> {
+        Ok(self
+            .list_windows()?
+            .into_iter()
+            .find(|w| w.window.id == window_id)
+            .map(|w| w.window.title))
+    }
 
-        anyhow::bail!("No active window found")
+    fn get_active_window(&self) -> Result<u64> {
+        let active = kwin_script::query(
+            "var w = nicotineActive(); return w ? String(w.internalId) : null;",
+        )?;
+        let uuid = active.as_str().context("No active window found")?;
+        let id = Self::window_id(uuid);
+        self.uuids.lock().unwrap().insert(id, uuid.to_string());
+        Ok(id)
     }
 
     fn find_window_by_title(&self, title: &str) -> Result<Option<u64>> {
-        let windows = self.get_all_windows()?;
+        Ok(self
+            .list_windows()?
+            .into_iter()
+            .find(|w| w.window.title == title)
+            .map(|w| w.window.id))
+    }
 
-        for (window, _output) in windows {
-            if let Some(window_title) = Self::get_window_title(&window) {
-                if window_title == title {
-                    if let Some(id) = Self::get_window_id(&window) {
-                        return Ok(Some(id));
-                    }
-                }
-            }
-        }
+    fn raise_window(&self, window_id: u64) -> Result<()> {
+        self.with_window(
+            window_id,
+            "if (workspace.raiseWindow) workspace.raiseWindow(w);",
+        )
+    }
 
-        Ok(None)
+    fn set_decorations(&self, window_id: u64, decorated: bool) -> Result<()> {
+        self.with_window(window_id, &format!("w.noBorder = {};", !decorated))
+    }
+
+    fn is_minimized(&self, window_id: u64) -> Result<bool> {
+        self.list_windows()?
+            .into_iter()
+            .find(|w| w.window.id == window_id)
+            .map(|w| w.minimized)
+            .ok_or_else(|| WindowNotFound(window_id).into())
     }
 
     fn minimize_window(&self, window_id: u64) -> Result<()> {
-        Command::new("swaymsg")
-            .arg(format!("[con_id={}] move scratchpad", window_id))
-            .output()
-            .context("Failed to minimize window")?;
-        Ok(())
+        self.with_window(window_id, "w.minimized = true;")
     }
 
     fn restore_window(&self, window_id: u64) -> Result<()> {
-        // Show from scratchpad restores it
-        Command::new("swaymsg")
-            .arg(format!("[con_id={}] scratchpad show", window_id))
-            .output()
-            .context("Failed to restore window")?;
-        Ok(())
+        self.with_window(window_id, "w.minimized = false;")
+    }
+
+    fn close_window(&self, window_id: u64) -> Result<()> {
+        self.with_window(window_id, "w.closeWindow();")
     }
 }
 
 // ============================================================================
-// Hyprland Backend (via hyprctl)
+// GNOME / Mutter Backend (via the companion Shell extension)
 // ============================================================================
 
-pub struct HyprlandManager;
+const GNOME_EXTENSION_UUID: &str = "nicotine@nicotine";
+const GNOME_EXTENSION_METADATA: &str =
+    include_str!("../gnome-extension/nicotine@nicotine/metadata.json");
+const GNOME_EXTENSION_JS: &str = include_str!("../gnome-extension/nicotine@nicotine/extension.js");
+
+/// Mutter won't let clients move or focus other windows, so this drives the
+/// nicotine Shell extension, which does it from inside GNOME Shell and
+/// answers on D-Bus
+pub struct MutterManager {
+    discover_monitors: Option<Vec<MonitorRef>>,
+    window_match: WindowMatch,
+    monitor_order: Vec<MonitorRef>,
+    monitor_cache: MonitorCache,
+    focus_workarounds: Vec<FocusWorkaround>,
+    warp_on_activate: bool,
+}
 
-impl HyprlandManager {
-    pub fn new() -> Result<Self> {
-        // Verify hyprctl is available
-        Command::new("hyprctl")
-            .arg("version")
-            .output()
-            .context("hyprctl not found. Make sure you're running Hyprland")?;
+impl MutterManager {
+    pub fn new(config: &Config) -> Result<Self> {
+        Self::call("List", "", &[]).context(
+            "The nicotine GNOME Shell extension isn't answering. \
+             Run `nicotine gnome-extension install`, log out and back in, then \
+             `gnome-extensions enable nicotine@nicotine`",
+        )?;
+
+        Ok(Self {
+            discover_monitors: config.discover_monitors.clone(),
+            window_match: config.window_match.clone(),
+            monitor_order: config.monitor_order.clone(),
+            monitor_cache: MonitorCache::default(),
+            focus_workarounds: config.focus_workarounds.clone(),
+            warp_on_activate: config.warp_on_activate,
+        })
+    }
 
-        Ok(Self)
+    fn extension_dir() -> Result<PathBuf> {
+        Ok(dirs::data_dir()
+            .context("Could not determine data directory")?
+            .join("gnome-shell/extensions")
+            .join(GNOME_EXTENSION_UUID))
     }
 
-    fn get_all_windows(&self) -> Result<Vec<Value>> {
-        let output = Command::new("hyprctl")
-            .arg("clients")
-            .arg("-j")
-            .output()
-            .context("Failed to execute hyprctl")?;
+    /// Write the extension into the user's extensions directory. GNOME only
+    /// picks up new extensions at login on Wayland.
+    pub fn install_extension() -> Result<PathBuf> {
+        let dir = Self::extension_dir()?;
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("metadata.json"), GNOME_EXTENSION_METADATA)?;
+        std::fs::write(dir.join("extension.js"), GNOME_EXTENSION_JS)?;
+        Ok(dir)
+    }
+
+    pub fn uninstall_extension() -> Result<()> {
+        let _ = Command::new("gnome-extensions")
+            .args(["disable", GNOME_EXTENSION_UUID])
+            .logged_output();
+        let dir = Self::extension_dir()?;
+        if dir.exists() {
+            std::fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Call a method on the extension and return its single result.
+    /// busctl's JSON output saves parsing GVariant text.
+    fn call(method: &str, signature: &str, args: &[String]) -> Result<Value> {
+        let output = Command::new("busctl")
+            .args(["--user", "--json=short", "--"])
+            .args([
+                "call",
+                "org.gnome.Shell",
+                "/org/nicotine/Windows",
+                "org.nicotine.Windows",
+                method,
+            ])
+            .args((!signature.is_empty()).then_some(signature))
+            .args(args)
+            .logged_output()
+            .context("Failed to execute busctl")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "hyprctl failed: {}",
-                String::from_utf8_lossy(&output.stderr)
+                "GNOME Shell {} failed: {}",
+                method,
+                String::from_utf8_lossy(&output.stderr).trim()
             );
         }
+        Self::parse_reply(&output.stdout)
+    }
 
-        let windows: Vec<Value> =
-            serde_json::from_slice(&output.stdout).context("Failed to parse hyprctl output")?;
-
-        Ok(windows)
+    /// The value out of busctl's `{"type":"s","data":[...]}`
+    fn parse_reply(stdout: &[u8]) -> Result<Value> {
+        let reply: Value =
+            serde_json::from_slice(stdout).context("Failed to parse busctl output")?;
+        reply
+            .get("data")
+            .and_then(|d| d.get(0))
+            .cloned()
+            .context("Empty reply from GNOME Shell")
     }
 
-    fn get_monitors_internal(&self) -> Result<Vec<Monitor>> {
-        let output = Command::new("hyprctl")
-            .args(["monitors", "-j"])
-            .output()
-            .context("Failed to execute hyprctl")?;
+    /// A method returning a JSON document as a string
+    fn call_json(method: &str) -> Result<Value> {
+        let reply = Self::call(method, "", &[])?;
+        let text = reply
+            .as_str()
+            .context("Expected a string from GNOME Shell")?;
+        serde_json::from_str(text).context("Failed to parse GNOME Shell reply")
+    }
 
-        if !output.status.success() {
-            return Ok(Vec::new());
+    /// Call a method taking a window id (and maybe more), mapping a false
+    /// reply to WindowNotFound
+    fn window_call(method: &str, signature: &str, window_id: u64, extra: &[i64]) -> Result<()> {
+        let args: Vec<String> = std::iter::once(window_id.to_string())
+            .chain(extra.iter().map(|v| v.to_string()))
+            .collect();
+        match Self::call(method, signature, &args)? {
+            Value::Bool(true) => Ok(()),
+            _ => Err(WindowNotFound(window_id).into()),
         }
+    }
 
-        let monitors_json: Vec<Value> =
-            serde_json::from_slice(&output.stdout).context("Failed to parse hyprctl output")?;
+    /// Windows from the extension's List, with their minimized state
+    fn parse_windows(list: &Value) -> Vec<(EveWindow, bool)> {
+        list.as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|w| {
+                let window = EveWindow {
+                    id: w.get("id")?.as_u64()?,
+                    title: w.get("title")?.as_str()?.to_string(),
+                    monitor: w
+                        .get("monitor")
+                        .and_then(|m| m.as_i64())
+                        .map(|m| m.to_string()),
+                    geometry: Some((
+                        w.get("x")?.as_i64()? as i32,
+                        w.get("y")?.as_i64()? as i32,
+                        w.get("width")?.as_u64()? as u32,
+                        w.get("height")?.as_u64()? as u32,
+                    )),
+                    pid: w.get("pid").and_then(|p| p.as_u64()).map(|p| p as u32),
+                    class: w.get("class").and_then(|c| c.as_str()).map(String::from),
+                };
+                let minimized = w.get("minimized").and_then(|m| m.as_bool()) == Some(true);
+                Some((window, minimized))
+            })
+            .collect()
+    }
+
+    fn list_windows(&self) -> Result<Vec<(EveWindow, bool)>> {
+        Ok(Self::parse_windows(&Self::call_json("List")?))
+    }
+
+    fn get_monitors_internal(&self) -> Result<Vec<Monitor>> {
+        self.monitor_cache.get(|| self.query_monitors())
+    }
+
+    /// Mutter identifies monitors by index, which is also what the
+    /// extension reports as a window's monitor
+    fn query_monitors(&self) -> Result<Vec<Monitor>> {
+        let monitors = Self::call_json("Monitors")?;
+        Ok(monitors
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| {
+                Some(Monitor {
+                    name: m.get("index")?.as_u64()?.to_string(),
+                    x: m.get("x")?.as_i64()? as i32,
+                    y: m.get("y")?.as_i64()? as i32,
+                    width: m.get("width")?.as_u64()? as u32,
+                    height: m.get("height")?.as_u64()? as u32,
+                    focused: m.get("focused").and_then(|f| f.as_bool()) == Some(true),
+                    ..Default::default()
+                })
+            })
+            .collect())
+    }
+}
+
+impl WindowManager for MutterManager {
+    fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
+        let mut eve_windows: Vec<EveWindow> = self
+            .list_windows()?
+            .into_iter()
+            .filter_map(|(window, _)| {
+                let character = self
+                    .window_match
+                    .client_name(&window.title, window.class.as_deref())?
+                    .to_string();
+                Some(EveWindow {
+                    title: character,
+                    ..window
+                })
+            })
+            .collect();
+
+        if let Some(allowed) = &self.discover_monitors {
+            let monitors = self.get_monitors().unwrap_or_default();
+            retain_on_monitors(&mut eve_windows, &monitors, allowed);
+        }
+
+        Ok(eve_windows)
+    }
+
+    fn get_process_windows(&self) -> Result<Vec<EveWindow>> {
+        Ok(self.list_windows()?.into_iter().map(|(w, _)| w).collect())
+    }
+
+    fn activate_window(&self, window_id: u64) -> Result<()> {
+        apply_focus_workarounds(self, window_id, &self.focus_workarounds);
+        Self::window_call("Activate", "t", window_id, &[])?;
+
+        if self.warp_on_activate {
+            let _ = self.warp_pointer(window_id);
+        }
+        Ok(())
+    }
+
+    fn apply_placement(&self, placement: &Placement) -> Result<()> {
+        let (width, height) = placement
+            .size
+            .map(|(w, h)| (w as i64, h as i64))
+            .unwrap_or((-1, -1));
+        Self::window_call(
+            "Place",
+            "tiiii",
+            placement.window_id,
+            &[placement.x as i64, placement.y as i64, width, height],
+        )
+    }
+
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        Ok(order_monitors(
+            self.get_monitors_internal()?,
+            &self.monitor_order,
+        ))
+    }
+
+    fn invalidate_monitors(&self) {
+        self.monitor_cache.invalidate();
+    }
+
+    fn get_title(&self, window_id: u64) -> Result<Option<String>> {
+        Ok(self
+            .list_windows()?
+            .into_iter()
+            .find(|(w, _)| w.id == window_id)
+            .map(|(w, _)| w.title))
+    }
+
+    fn get_active_window(&self) -> Result<u64> {
+        match Self::call("Active", "", &[])?.as_u64() {
+            Some(id) if id != 0 => Ok(id),
+            _ => anyhow::bail!("No active window found"),
+        }
+    }
+
+    fn find_window_by_title(&self, title: &str) -> Result<Option<u64>> {
+        Ok(self
+            .list_windows()?
+            .into_iter()
+            .find(|(w, _)| w.title == title)
+            .map(|(w, _)| w.id))
+    }
+
+    fn raise_window(&self, window_id: u64) -> Result<()> {
+        Self::window_call("Raise", "t", window_id, &[])
+    }
+
+    fn is_minimized(&self, window_id: u64) -> Result<bool> {
+        self.list_windows()?
+            .into_iter()
+            .find(|(w, _)| w.id == window_id)
+            .map(|(_, minimized)| minimized)
+            .ok_or_else(|| WindowNotFound(window_id).into())
+    }
+
+    fn minimize_window(&self, window_id: u64) -> Result<()> {
+        Self::window_call("Minimize", "t", window_id, &[])
+    }
+
+    fn restore_window(&self, window_id: u64) -> Result<()> {
+        Self::window_call("Restore", "t", window_id, &[])
+    }
+
+    fn close_window(&self, window_id: u64) -> Result<()> {
+        Self::window_call("Close", "t", window_id, &[])
+    }
+}
+
+// ============================================================================
+// Sway Backend (via the sway IPC socket)
+// ============================================================================
+
+pub struct SwayManager {
+    ipc: SwayIpc,
+    title_field: SwayTitleField,
+    match_property: Option<(String, String)>,
+    discover_monitors: Option<Vec<MonitorRef>>,
+    window_match: WindowMatch,
+    monitor_order: Vec<MonitorRef>,
+    monitor_cache: MonitorCache,
+    minimize: SwayMinimize,
+    focus_workarounds: Vec<FocusWorkaround>,
+    warp_on_activate: bool,
+    warp_anchor: WarpAnchor,
+}
+
+impl SwayManager {
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            ipc: SwayIpc::connect()?,
+            title_field: config.sway_title_field,
+            match_property: config.match_property.clone(),
+            discover_monitors: config.discover_monitors.clone(),
+            window_match: config.window_match.clone(),
+            monitor_order: config.monitor_order.clone(),
+            monitor_cache: MonitorCache::default(),
+            minimize: config.minimize_strategy.sway.clone(),
+            focus_workarounds: config.focus_workarounds.clone(),
+            warp_on_activate: config.warp_on_activate,
+            warp_anchor: config.warp_anchor,
+        })
+    }
+
+    fn minimize_command(window_id: u64, strategy: &SwayMinimize) -> String {
+        match strategy {
+            SwayMinimize::Scratchpad => format!("[con_id={}] move scratchpad", window_id),
+            SwayMinimize::Workspace(name) => {
+                format!(
+                    "[con_id={}] move container to workspace {}",
+                    window_id, name
+                )
+            }
+        }
+    }
+
+    fn restore_command(window_id: u64, strategy: &SwayMinimize, current_workspace: &str) -> String {
+        match strategy {
+            // Show from scratchpad restores it
+            SwayMinimize::Scratchpad => format!("[con_id={}] scratchpad show", window_id),
+            SwayMinimize::Workspace(_) => format!(
+                "[con_id={}] move container to workspace {}",
+                window_id, current_workspace
+            ),
+        }
+    }
+
+    fn get_focused_workspace(&self) -> Result<String> {
+        let workspaces = self.ipc.request(sway_ipc::GET_WORKSPACES, "")?;
+
+        workspaces
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|ws| ws.get("focused").and_then(|f| f.as_bool()) == Some(true))
+            .and_then(|ws| ws.get("name").and_then(|n| n.as_str()))
+            .map(|name| name.to_string())
+            .context("No focused Sway workspace")
+    }
+
+    /// Commands putting a container where `placement` says. Sway positions
+    /// floating windows only; moving to the target output first makes the
+    /// window join that output's workspace, and the position is in the
+    /// global layout space.
+    /// Unlike on X11 and Hyprland, a floating container's position and size
+    /// already take in its border and titlebar, so nothing is taken off.
+    fn placement_commands(placement: &Placement) -> Vec<String> {
+        let mut commands = vec!["floating enable".to_string()];
+        if let Some(output) = &placement.monitor {
+            commands.push(format!("move container to output {}", output));
+        }
+        commands.push(format!(
+            "move absolute position {} {}",
+            placement.x, placement.y
+        ));
+        if let Some((width, height)) = placement.size {
+            commands.push(format!("resize set {} {}", width, height));
+        }
+        commands
+    }
+
+    fn get_all_windows(&self) -> Result<Vec<(Value, Option<String>)>> {
+        let tree = self.ipc.request(sway_ipc::GET_TREE, "")?;
+
+        let mut windows = Vec::new();
+        Self::extract_windows(&tree, &mut windows, None);
+
+        Ok(windows)
+    }
+
+    fn get_monitors_internal(&self) -> Result<Vec<Monitor>> {
+        self.monitor_cache.get(|| self.query_monitors())
+    }
+
+    fn query_monitors(&self) -> Result<Vec<Monitor>> {
+        let outputs = self.ipc.request(sway_ipc::GET_OUTPUTS, "")?;
+        let outputs = outputs.as_array().map(Vec::as_slice).unwrap_or_default();
+        let mut monitors = Self::parse_outputs(outputs);
+
+        let workspaces = self.ipc.request(sway_ipc::GET_WORKSPACES, "")?;
+        let workspaces = workspaces.as_array().map(Vec::as_slice).unwrap_or_default();
+        Self::reserve_bars(&mut monitors, workspaces);
+        Ok(monitors)
+    }
 
+    /// Monitors from a GET_OUTPUTS reply
+    fn parse_outputs(outputs: &[Value]) -> Vec<Monitor> {
+        let mut monitors = Vec::new();
+        for output in outputs {
+            if let (Some(name), Some((x, y, width, height))) = (
+                output.get("name").and_then(|n| n.as_str()),
+                output.get("rect").and_then(Self::parse_rect),
+            ) {
+                monitors.push(Monitor {
+                    name: name.to_string(),
+                    x,
+                    y,
+                    width,
+                    height,
+                    focused: output.get("focused").and_then(|f| f.as_bool()) == Some(true),
+                    ..Default::default()
+                });
+            }
+        }
+
+        monitors
+    }
+
+    fn parse_rect(rect: &Value) -> Option<(i32, i32, u32, u32)> {
+        Some((
+            rect.get("x")?.as_i64()? as i32,
+            rect.get("y")?.as_i64()? as i32,
+            rect.get("width")?.as_u64()? as u32,
+            rect.get("height")?.as_u64()? as u32,
+        ))
+    }
+
+    /// Reserve what the workspace showing on each output leaves out of it:
+    /// Sway sizes workspaces to the output less its bars' exclusive zones
+    /// and the outer gaps
+    fn reserve_bars(monitors: &mut [Monitor], workspaces: &[Value]) {
+        for workspace in workspaces {
+            if workspace.get("visible").and_then(|v| v.as_bool()) != Some(true) {
+                continue;
+            }
+            let output = workspace.get("output").and_then(|o| o.as_str());
+            let area = workspace.get("rect").and_then(Self::parse_rect);
+            let (Some(output), Some(area)) = (output, area) else {
+                continue;
+            };
+            if let Some(monitor) = monitors.iter_mut().find(|m| m.name == output) {
+                monitor.reserve(work_area_struts(monitor, area));
+            }
+        }
+    }
+
+    fn extract_windows(
+        node: &Value,
+        windows: &mut Vec<(Value, Option<String>)>,
+        current_output: Option<&str>,
+    ) {
+        let node_type = node.get("type").and_then(|t| t.as_str());
+
+        // Track output name when we encounter an output node
+        let output_name = if node_type == Some("output") {
+            node.get("name").and_then(|n| n.as_str())
+        } else {
+            current_output
+        };
+
+        if let Some(nt) = node_type {
+            if nt == "con" || nt == "floating_con" {
+                if let Some(app_id) = node.get("app_id") {
+                    if !app_id.is_null() {
+                        windows.push((node.clone(), output_name.map(|s| s.to_string())));
+                    }
+                } else if let Some(window_properties) = node.get("window_properties") {
+                    if !window_properties.is_null() {
+                        windows.push((node.clone(), output_name.map(|s| s.to_string())));
+                    }
+                }
+            }
+        }
+
+        if let Some(nodes) = node.get("nodes").and_then(|n| n.as_array()) {
+            for child in nodes {
+                Self::extract_windows(child, windows, output_name);
+            }
+        }
+
+        if let Some(floating_nodes) = node.get("floating_nodes").and_then(|n| n.as_array()) {
+            for child in floating_nodes {
+                Self::extract_windows(child, windows, output_name);
+            }
+        }
+    }
+
+    /// Read a window title from a tree node. Xwayland clients may leave `name`
+    /// empty while `window_properties.title` still carries the real title.
+    fn get_window_title(window: &Value, field: SwayTitleField) -> Option<String> {
+        let name = || {
+            window
+                .get("name")
+                .and_then(|n| n.as_str())
+                .filter(|s| !s.is_empty())
+        };
+        let properties_title = || {
+            window
+                .get("window_properties")
+                .and_then(|p| p.get("title"))
+                .and_then(|t| t.as_str())
+                .filter(|s| !s.is_empty())
+        };
+
+        let title = match field {
+            SwayTitleField::Auto => name().or_else(properties_title),
+            SwayTitleField::Name => name(),
+            SwayTitleField::WindowProperties => properties_title(),
+        };
+
+        title.map(|s| s.to_string())
+    }
+
+    fn get_window_id(window: &Value) -> Option<u64> {
+        window.get("id").and_then(|i| i.as_u64())
+    }
+
+    fn get_window_geometry(window: &Value) -> Option<(i32, i32, u32, u32)> {
+        let rect = window.get("rect")?;
+        Some((
+            rect.get("x")?.as_i64()? as i32,
+            rect.get("y")?.as_i64()? as i32,
+            rect.get("width")?.as_u64()? as u32,
+            rect.get("height")?.as_u64()? as u32,
+        ))
+    }
+}
+
+impl WindowManager for SwayManager {
+    fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
+        let windows = self.get_all_windows()?;
+        let mut eve_windows = Vec::new();
+
+        for (window, output_name) in windows {
+            if let Some(title) = Self::get_window_title(&window, self.title_field) {
+                let marker = marker_value(&window, self.match_property.as_ref());
+                let class = window_class(&window);
+                if let Some(character) = self.window_match.discovered_name(
+                    &title,
+                    class.as_deref(),
+                    marker.as_deref(),
+                    self.match_property.as_ref(),
+                ) {
+                    if let Some(id) = Self::get_window_id(&window) {
+                        eve_windows.push(EveWindow {
+                            id,
+                            title: character.to_string(),
+                            monitor: output_name,
+                            geometry: Self::get_window_geometry(&window),
+                            pid: window.get("pid").and_then(|p| p.as_u64()).map(|p| p as u32),
+                            class,
+                        });
+                    }
+                }
+            }
+        }
+
+        if let Some(allowed) = &self.discover_monitors {
+            let monitors = self.get_monitors().unwrap_or_default();
+            retain_on_monitors(&mut eve_windows, &monitors, allowed);
+        }
+
+        Ok(eve_windows)
+    }
+
+    fn activate_window(&self, window_id: u64) -> Result<()> {
+        apply_focus_workarounds(self, window_id, &self.focus_workarounds);
+        if let Err(e) = self.ipc.command(&format!("[con_id={}] focus", window_id)) {
+            if is_missing_window_reply(&e.to_string()) {
+                return Err(WindowNotFound(window_id).into());
+            }
+            return Err(e.context("Failed to activate window"));
+        }
+
+        if self.warp_on_activate {
+            let _ = self.warp_pointer(window_id);
+        }
+        Ok(())
+    }
+
+    fn apply_placement(&self, placement: &Placement) -> Result<()> {
+        let mut results = self.apply_placements(std::slice::from_ref(placement));
+        results.pop().unwrap_or(Ok(()))
+    }
+
+    // The whole stack goes to sway as one request, rather than up to four
+    // per window
+    fn apply_placements(&self, placements: &[Placement]) -> Vec<Result<()>> {
+        if placements.is_empty() {
+            return Vec::new();
+        }
+        let batch: Vec<(u64, Vec<String>)> = placements
+            .iter()
+            .map(|p| (p.window_id, Self::placement_commands(p)))
+            .collect();
+        match self.ipc.command_batch(&batch) {
+            Ok(results) => results
+                .into_iter()
+                .zip(placements)
+                .map(|(result, p)| {
+                    result.with_context(|| format!("sway failed to place window {}", p.window_id))
+                })
+                .collect(),
+            Err(e) => placements
+                .iter()
+                .map(|_| Err(anyhow::anyhow!("Failed to talk to sway: {:#}", e)))
+                .collect(),
+        }
+    }
+
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        Ok(order_monitors(
+            self.get_monitors_internal()?,
+            &self.monitor_order,
+        ))
+    }
+
+    fn invalidate_monitors(&self) {
+        self.monitor_cache.invalidate();
+    }
+
+    fn get_active_window(&self) -> Result<u64> {
+        let windows = self.get_all_windows()?;
+
+        for (window, _output) in windows {
+            if let Some(focused) = window.get("focused").and_then(|f| f.as_bool()) {
+                if focused {
+                    if let Some(id) = Self::get_window_id(&window) {
+                        return Ok(id);
+                    }
+                }
+            }
+        }
+
+        anyhow::bail!("No active window found")
+    }
+
+    fn get_title(&self, window_id: u64) -> Result<Option<String>> {
+        let windows = self.get_all_windows()?;
+
+        Ok(windows
+            .iter()
+            .find(|(window, _output)| Self::get_window_id(window) == Some(window_id))
+            .and_then(|(window, _output)| Self::get_window_title(window, self.title_field)))
+    }
+
+    fn find_window_by_title(&self, title: &str) -> Result<Option<u64>> {
+        let windows = self.get_all_windows()?;
+
+        for (window, _output) in windows {
+            if let Some(window_title) = Self::get_window_title(&window, self.title_field) {
+                if window_title == title {
+                    if let Some(id) = Self::get_window_id(&window) {
+                        return Ok(Some(id));
+                    }
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn subscribe_events(&self) -> Result<Option<Receiver<WindowEvent>>> {
+        let events = self
+            .ipc
+            .subscribe_windows()
+            .context("Failed to subscribe to sway events")?;
+        Ok(Some(events))
+    }
+
+    fn warp_pointer(&self, window_id: u64) -> Result<()> {
+        let geometry = self
+            .get_all_windows()?
+            .iter()
+            .find(|(w, _)| Self::get_window_id(w) == Some(window_id))
+            .and_then(|(w, _)| Self::get_window_geometry(w))
+            .ok_or(WindowNotFound(window_id))?;
+        let (x, y) = warp_target(geometry, self.warp_anchor);
+
+        self.ipc
+            .command(&format!("seat - cursor set {} {}", x, y))
+            .context("Failed to warp pointer")
+    }
+
+    fn set_decorations(&self, window_id: u64, decorated: bool) -> Result<()> {
+        let border = if decorated { "normal" } else { "none" };
+        self.ipc
+            .command(&format!("[con_id={}] border {}", window_id, border))
+            .context("Failed to set window border")
+    }
+
+    // Sway doesn't report opacity, and 1 is what a window has by default
+    fn set_opacity(&self, window_id: u64, opacity: Option<f64>) -> Result<()> {
+        self.ipc
+            .command(&format!(
+                "[con_id={}] opacity {}",
+                window_id,
+                opacity.unwrap_or(1.0)
+            ))
+            .context("Failed to set window opacity")
+    }
+
+    // Sticky floating windows stay on screen across workspaces, above the
+    // tiled ones
+    fn set_pinned(&self, window_id: u64, pinned: bool) -> Result<()> {
+        let command = if pinned {
+            format!("[con_id={}] floating enable, sticky enable", window_id)
+        } else {
+            format!("[con_id={}] sticky disable", window_id)
+        };
+        self.ipc.command(&command).context("Failed to pin window")
+    }
+
+    fn is_pinned(&self, window_id: u64) -> Result<bool> {
+        let (node, _) = self
+            .get_all_windows()?
+            .into_iter()
+            .find(|(node, _)| node.get("id").and_then(|id| id.as_u64()) == Some(window_id))
+            .ok_or(WindowNotFound(window_id))?;
+        Ok(node.get("sticky").and_then(|s| s.as_bool()) == Some(true))
+    }
+
+    fn mark_urgent(&self, window_id: u64) -> Result<()> {
+        self.ipc
+            .command(&format!("[con_id={}] urgent enable", window_id))
+            .context("Failed to mark window urgent")
+    }
+
+    // `kill` asks politely, like the close button; it doesn't kill the process
+    fn close_window(&self, window_id: u64) -> Result<()> {
+        self.ipc
+            .command(&format!("[con_id={}] kill", window_id))
+            .context("Failed to close window")
+    }
+
+    fn minimize_window(&self, window_id: u64) -> Result<()> {
+        self.ipc
+            .command(&Self::minimize_command(window_id, &self.minimize))
+            .context("Failed to minimize window")
+    }
+
+    fn restore_window(&self, window_id: u64) -> Result<()> {
+        let current_workspace = match self.minimize {
+            SwayMinimize::Scratchpad => String::new(),
+            SwayMinimize::Workspace(_) => self.get_focused_workspace()?,
+        };
+        self.ipc
+            .command(&Self::restore_command(
+                window_id,
+                &self.minimize,
+                &current_workspace,
+            ))
+            .context("Failed to restore window")
+    }
+}
+
+// ============================================================================
+// Hyprland Backend (via the Hyprland IPC sockets)
+// ============================================================================
+
+pub struct HyprlandManager {
+    ipc: HyprlandIpc,
+    match_property: Option<(String, String)>,
+    discover_monitors: Option<Vec<MonitorRef>>,
+    window_match: WindowMatch,
+    monitor_order: Vec<MonitorRef>,
+    monitor_cache: MonitorCache,
+    minimize: HyprlandMinimize,
+    focus_workarounds: Vec<FocusWorkaround>,
+    warp_on_activate: bool,
+    warp_anchor: WarpAnchor,
+}
+
+impl HyprlandManager {
+    pub fn new(config: &Config) -> Result<Self> {
+        let ipc = HyprlandIpc::locate()?;
+        // Fails early if the socket isn't answering
+        ipc.request("version")?;
+
+        Ok(Self {
+            ipc,
+            match_property: config.match_property.clone(),
+            discover_monitors: config.discover_monitors.clone(),
+            window_match: config.window_match.clone(),
+            monitor_order: config.monitor_order.clone(),
+            monitor_cache: MonitorCache::default(),
+            minimize: config.minimize_strategy.hyprland.clone(),
+            focus_workarounds: config.focus_workarounds.clone(),
+            warp_on_activate: config.warp_on_activate,
+            warp_anchor: config.warp_anchor,
+        })
+    }
+
+    /// windowrulev2 rules that stop Hyprland fighting the layout: EVE floats,
+    /// ignores its own borderless-fullscreen requests, and opens at the
+    /// centered stack size and position
+    pub fn window_rules(config: &Config) -> Vec<String> {
+        let matcher = Self::class_matcher(&config.eve_window_class);
+        let height = config.display_height.saturating_sub(config.panel_height);
+        let width = config.eve_width.min(config.display_width);
+        let x = (config.display_width - width) / 2;
+
+        vec![
+            format!("float,{}", matcher),
+            format!("nofullscreenrequest,{}", matcher),
+            format!("size {} {},{}", width, height, matcher),
+            format!("move {} 0,{}", x, matcher),
+        ]
+    }
+
+    /// Rule that clears every rule nicotine installed for the class
+    pub fn unset_rule(config: &Config) -> String {
+        format!("unset,{}", Self::class_matcher(&config.eve_window_class))
+    }
+
+    fn class_matcher(class: &str) -> String {
+        let escaped: String = class
+            .chars()
+            .flat_map(|c| {
+                let escape = "\\.+*?()|[]{}^$".contains(c);
+                escape.then_some('\\').into_iter().chain(Some(c))
+            })
+            .collect();
+        format!("class:^({})$", escaped)
+    }
+
+    fn keyword_windowrule(ipc: &HyprlandIpc, rule: &str) -> Result<()> {
+        let reply = ipc.request(&format!("keyword windowrulev2 {}", rule))?;
+        if !reply.trim().eq_ignore_ascii_case("ok") {
+            anyhow::bail!("Hyprland rejected windowrulev2 {}: {}", rule, reply.trim());
+        }
+        Ok(())
+    }
+
+    /// Add the rules for this Hyprland session. They're runtime keywords, so
+    /// a Hyprland restart or config reload drops them again.
+    pub fn install_window_rules(config: &Config) -> Result<()> {
+        let ipc = HyprlandIpc::locate()?;
+        for rule in Self::window_rules(config) {
+            Self::keyword_windowrule(&ipc, &rule)?;
+        }
+        Ok(())
+    }
+
+    pub fn uninstall_window_rules(config: &Config) -> Result<()> {
+        Self::keyword_windowrule(&HyprlandIpc::locate()?, &Self::unset_rule(config))
+    }
+
+    fn minimize_target(address: &str, strategy: &HyprlandMinimize) -> String {
+        match strategy {
+            HyprlandMinimize::Special(name) if name.is_empty() => {
+                format!("special,address:{}", address)
+            }
+            HyprlandMinimize::Special(name) => format!("special:{},address:{}", name, address),
+            HyprlandMinimize::Workspace(name) => format!("name:{},address:{}", name, address),
+        }
+    }
+
+    /// Whether a window on `workspace` is hidden by the minimize strategy
+    fn is_minimized_workspace(workspace: &str, strategy: &HyprlandMinimize) -> bool {
+        match strategy {
+            HyprlandMinimize::Special(_) => workspace.starts_with("special"),
+            HyprlandMinimize::Workspace(name) => workspace == name,
+        }
+    }
+
+    fn get_all_windows(&self) -> Result<Vec<Value>> {
+        match self.ipc.json("clients")? {
+            Value::Array(windows) => Ok(windows),
+            _ => anyhow::bail!("Unexpected Hyprland clients reply"),
+        }
+    }
+
+    /// Active workspace of `monitor` when the window at `address` is on
+    /// some other monitor, so it can be sent across before being placed
+    fn workspace_on_monitor(
+        clients: &[Value],
+        monitors: &[Value],
+        address: &str,
+        monitor: &str,
+    ) -> Option<i64> {
+        let target = monitors
+            .iter()
+            .find(|m| m.get("name").and_then(|n| n.as_str()) == Some(monitor))?;
+        let current = clients
+            .iter()
+            .find(|c| c.get("address").and_then(|a| a.as_str()) == Some(address))?
+            .get("monitor")
+            .and_then(|m| m.as_i64());
+        if current == target.get("id").and_then(|id| id.as_i64()) {
+            return None;
+        }
+        target.get("activeWorkspace")?.get("id")?.as_i64()
+    }
+
+    /// Dispatches floating each window, moving it to the active workspace
+    /// of its monitor and setting its position and size, as apply_placement
+    /// does for one
+    fn placement_dispatches(&self, placements: &[Placement]) -> Result<Vec<String>> {
+        let clients = self.get_all_windows()?;
+        let monitors = self.ipc.json("monitors")?;
+        let monitors = monitors.as_array().map(Vec::as_slice).unwrap_or_default();
+        let border = self.border_extents();
+
+        let mut dispatches = Vec::new();
+        for placement in placements {
+            let placement = &placement.inside_frame(border);
+            let address = format!("0x{:x}", placement.window_id);
+            dispatches.push(format!("dispatch setfloating address:{}", address));
+            if let Some(workspace) = placement.monitor.as_ref().and_then(|monitor| {
+                Self::workspace_on_monitor(&clients, monitors, &address, monitor)
+            }) {
+                dispatches.push(format!(
+                    "dispatch movetoworkspacesilent {},address:{}",
+                    workspace, address
+                ));
+            }
+            dispatches.push(format!(
+                "dispatch movewindowpixel exact {} {},address:{}",
+                placement.x, placement.y, address
+            ));
+            if let Some((width, height)) = placement.size {
+                dispatches.push(format!(
+                    "dispatch resizewindowpixel exact {} {},address:{}",
+                    width, height, address
+                ));
+            }
+        }
+        Ok(dispatches)
+    }
+
+    /// Hyprland draws borders outside the window's own geometry, which is
+    /// what movewindowpixel and resizewindowpixel set, so a placement has
+    /// to leave room for them
+    fn border_extents(&self) -> [u32; 4] {
+        let border = self
+            .ipc
+            .json("getoption general:border_size")
+            .map(|option| Self::parse_border_size(&option))
+            .unwrap_or_default();
+        [border; 4]
+    }
+
+    fn parse_border_size(option: &Value) -> u32 {
+        option
+            .get("int")
+            .and_then(|size| size.as_u64())
+            .unwrap_or_default() as u32
+    }
+
+    fn get_monitors_internal(&self) -> Result<Vec<Monitor>> {
+        self.monitor_cache.get(|| self.query_monitors())
+    }
+
+    fn query_monitors(&self) -> Result<Vec<Monitor>> {
+        let monitors_json = self.ipc.json("monitors")?;
+        let monitors_json = monitors_json
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        Ok(Self::parse_monitors(monitors_json))
+    }
+
+    /// Layer surfaces from the `layers` query, which groups them by monitor
+    /// and then by layer level. The namespace (e.g. "waybar") is the class.
+    fn parse_layers(layers_json: &Value) -> Vec<EveWindow> {
+        let Some(monitors) = layers_json.as_object() else {
+            return Vec::new();
+        };
+
+        monitors
+            .values()
+            .filter_map(|mon| mon.get("levels")?.as_object())
+            .flat_map(|levels| levels.values())
+            .filter_map(|level| level.as_array())
+            .flatten()
+            .filter_map(|layer| {
+                let address = layer.get("address")?.as_str()?;
+                Some(EveWindow {
+                    id: u64::from_str_radix(address.trim_start_matches("0x"), 16).unwrap_or(0),
+                    class: Some(layer.get("namespace")?.as_str()?.to_string()),
+                    geometry: Some((
+                        layer.get("x")?.as_i64()? as i32,
+                        layer.get("y")?.as_i64()? as i32,
+                        layer.get("w")?.as_u64()? as u32,
+                        layer.get("h")?.as_u64()? as u32,
+                    )),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
+    /// Monitors from the `monitors` query
+    fn parse_monitors(monitors_json: &[Value]) -> Vec<Monitor> {
         let mut monitors = Vec::new();
         for mon in monitors_json {
             if let (Some(name), Some(x), Some(y), Some(width), Some(height)) = (
@@ -719,28 +1752,72 @@ impl HyprlandManager {
                 mon.get("width").and_then(|v| v.as_u64()),
                 mon.get("height").and_then(|v| v.as_u64()),
             ) {
-                monitors.push(Monitor {
+                let mut monitor = Monitor {
                     name: name.to_string(),
                     x: x as i32,
                     y: y as i32,
                     width: width as u32,
                     height: height as u32,
-                });
+                    focused: mon.get("focused").and_then(|f| f.as_bool()) == Some(true),
+                    ..Default::default()
+                };
+                monitor.reserve(Self::parse_reserved(mon));
+                monitors.push(monitor);
             }
         }
 
-        Ok(monitors)
+        monitors
+    }
+
+    /// Space a monitor keeps for layer surfaces such as bars, which
+    /// Hyprland reports as [left, top, right, bottom]
+    fn parse_reserved(monitor: &Value) -> Struts {
+        let reserved: Vec<u32> = monitor
+            .get("reserved")
+            .and_then(|r| r.as_array())
+            .into_iter()
+            .flatten()
+            .map(|edge| edge.as_u64().unwrap_or_default() as u32)
+            .collect();
+        match reserved[..] {
+            [left, top, right, bottom] => Struts {
+                top,
+                bottom,
+                left,
+                right,
+            },
+            _ => Struts::default(),
+        }
+    }
+
+    fn get_window_geometry(window: &Value) -> Option<(i32, i32, u32, u32)> {
+        let at = window.get("at")?.as_array()?;
+        let size = window.get("size")?.as_array()?;
+        Some((
+            at.first()?.as_i64()? as i32,
+            at.get(1)?.as_i64()? as i32,
+            size.first()?.as_u64()? as u32,
+            size.get(1)?.as_u64()? as u32,
+        ))
     }
 }
 
 impl WindowManager for HyprlandManager {
     fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
         let windows = self.get_all_windows()?;
+        let monitors = self.get_monitors_internal().unwrap_or_default();
         let mut eve_windows = Vec::new();
 
         for window in windows {
             if let Some(title) = window.get("title").and_then(|t| t.as_str()) {
-                if title.starts_with("EVE - ") && !title.contains("Launcher") {
+                let marker = marker_value(&window, self.match_property.as_ref());
+                let class = window.get("class").and_then(|c| c.as_str());
+                if let Some(character) = self.window_match.discovered_name(
+                    title,
+                    class,
+                    marker.as_deref(),
+                    self.match_property.as_ref(),
+                ) {
                     // Hyprland uses hex addresses - must use u64 to avoid truncation
                     if let Some(address) = window.get("address").and_then(|a| a.as_str()) {
                         // Convert hex address like "0x55ade765da10" to u64
@@ -750,175 +1827,145 @@ impl WindowManager for HyprlandManager {
                             0
                         };
 
-                        // Hyprland clients JSON has a "monitor" field with monitor ID
-                        // We need to map this to the monitor name
-                        let monitor =
-                            window
-                                .get("monitor")
-                                .and_then(|m| m.as_i64())
-                                .and_then(|mon_id| {
-                                    // Get monitors to find name by ID
-                                    self.get_monitors_internal().ok().and_then(|monitors| {
-                                        // Monitor ID in clients corresponds to the order in monitors list
-                                        monitors.get(mon_id as usize).map(|m| m.name.clone())
-                                    })
-                                });
+                        // Hyprland clients JSON has a "monitor" field with monitor ID,
+                        // which corresponds to the order in the monitors list
+                        let monitor = window
+                            .get("monitor")
+                            .and_then(|m| m.as_i64())
+                            .and_then(|mon_id| monitors.get(mon_id as usize))
+                            .map(|m| m.name.clone());
 
                         eve_windows.push(EveWindow {
                             id,
-                            title: title.trim_start_matches("EVE - ").to_string(),
+                            title: character.to_string(),
                             monitor,
+                            geometry: Self::get_window_geometry(&window),
+                            pid: window.get("pid").and_then(|p| p.as_u64()).map(|p| p as u32),
+                            class: class.map(|c| c.to_string()),
                         });
                     }
                 }
             }
         }
 
+        if let Some(allowed) = &self.discover_monitors {
+            let monitors = self.get_monitors().unwrap_or_default();
+            retain_on_monitors(&mut eve_windows, &monitors, allowed);
+        }
+
         Ok(eve_windows)
     }
 
     fn activate_window(&self, window_id: u64) -> Result<()> {
+        apply_focus_workarounds(self, window_id, &self.focus_workarounds);
         // Convert u64 back to hex address
         let address = format!("0x{:x}", window_id);
 
-        let output = Command::new("hyprctl")
-            .arg("dispatch")
-            .arg("focuswindow")
-            .arg(format!("address:{}", address))
-            .output()
+        let reply = self
+            .ipc
+            .dispatch("focuswindow", &format!("address:{}", address))
             .context("Failed to activate window")?;
 
-        if !output.status.success() {
-            anyhow::bail!(
-                "Failed to activate window: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
+        if is_missing_window_reply(&reply) {
+            return Err(WindowNotFound(window_id).into());
         }
 
+        if !reply.trim().eq_ignore_ascii_case("ok") {
+            anyhow::bail!("Failed to activate window: {}", reply.trim());
+        }
+
+        if self.warp_on_activate {
+            let _ = self.warp_pointer(window_id);
+        }
         Ok(())
     }
 
-    fn stack_windows(&self, windows: &[EveWindow], config: &Config) -> Result<()> {
-        let monitors = self.get_monitors()?;
-
-        for window in windows {
-            // Determine target monitor:
-            // - Primary character goes to primary_monitor
-            // - Others stay on their current monitor
-            let is_primary = config
-                .primary_character
-                .as_ref()
-                .map(|c| window.title == *c)
-                .unwrap_or(false);
-
-            let target_monitor = if is_primary {
-                // Primary character goes to primary_monitor
-                config
-                    .primary_monitor
-                    .as_ref()
-                    .and_then(|name| monitors.iter().find(|m| &m.name == name))
-                    .or_else(|| monitors.first())
-            } else {
-                // Others stay on current monitor
-                window
-                    .monitor
-                    .as_ref()
-                    .and_then(|name| monitors.iter().find(|m| &m.name == name))
-                    .or_else(|| monitors.first())
-            };
-
-            let (x, y, width, height) = if let Some(mon) = target_monitor {
-                if config.fullscreen_stack {
-                    // Fullscreen on monitor
-                    let height = mon.height.saturating_sub(config.panel_height);
-                    (mon.x, mon.y, mon.width as i32, height as i32)
-                } else {
-                    // Centered with eve_width
-                    let eve_w = config.eve_width.min(mon.width);
-                    let x = mon.x + ((mon.width - eve_w) / 2) as i32;
-                    let height = mon.height.saturating_sub(config.panel_height);
-                    (x, mon.y, eve_w as i32, height as i32)
-                }
-            } else {
-                // Fallback to global config
-                let x = ((config.display_width - config.eve_width) / 2) as i32;
-                let height = (config.display_height - config.panel_height) as i32;
-                (x, 0, config.eve_width as i32, height)
-            };
-
-            let address = format!("0x{:x}", window.id);
-
-            // Enable floating (setfloating 1 = always float, unlike togglefloating)
-            let _ = Command::new("hyprctl")
-                .arg("dispatch")
-                .arg("setfloating")
-                .arg(format!("address:{}", address))
-                .output();
-
-            // Try to move window - if fullscreen, exit fullscreen and retry
-            let output = Command::new("hyprctl")
-                .arg("dispatch")
-                .arg("movewindowpixel")
-                .arg(format!("exact {} {},address:{}", x, y, address))
-                .output()
-                .context("Failed to execute hyprctl")?;
-
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            if stdout.contains("Window is fullscreen") {
-                // Exit fullscreen: focus window, use fullscreen 0 to exit, then retry move
-                let _ = Command::new("hyprctl")
-                    .arg("dispatch")
-                    .arg("focuswindow")
-                    .arg(format!("address:{}", address))
-                    .output();
-                let _ = Command::new("hyprctl")
-                    .arg("dispatch")
-                    .arg("fullscreen")
-                    .arg("0")
-                    .output();
-                let _ = Command::new("hyprctl")
-                    .arg("dispatch")
-                    .arg("movewindowpixel")
-                    .arg(format!("exact {} {},address:{}", x, y, address))
-                    .output();
+    fn apply_placement(&self, placement: &Placement) -> Result<()> {
+        let placement = &placement.inside_frame(self.border_extents());
+        let address = format!("0x{:x}", placement.window_id);
+
+        // Enable floating (setfloating 1 = always float, unlike togglefloating)
+        let _ = self
+            .ipc
+            .dispatch("setfloating", &format!("address:{}", address));
+
+        if let Some(monitor) = &placement.monitor {
+            let clients = self.get_all_windows()?;
+            let monitors = self.ipc.json("monitors")?;
+            let monitors = monitors.as_array().map(Vec::as_slice).unwrap_or_default();
+            if let Some(workspace) =
+                Self::workspace_on_monitor(&clients, monitors, &address, monitor)
+            {
+                let _ = self.ipc.dispatch(
+                    "movetoworkspacesilent",
+                    &format!("{},address:{}", workspace, address),
+                );
             }
+        }
+
+        // Try to move window - if fullscreen, exit fullscreen and retry
+        let move_args = format!("exact {} {},address:{}", placement.x, placement.y, address);
+        let reply = self.ipc.dispatch("movewindowpixel", &move_args)?;
+
+        if reply.contains("Window is fullscreen") {
+            // Exit fullscreen: focus window, use fullscreen 0 to exit, then retry move
+            let _ = self
+                .ipc
+                .dispatch("focuswindow", &format!("address:{}", address));
+            let _ = self.ipc.dispatch("fullscreen", "0");
+            let _ = self.ipc.dispatch("movewindowpixel", &move_args);
+        }
 
-            // Resize window (also retry if fullscreen)
-            let output = Command::new("hyprctl")
-                .arg("dispatch")
-                .arg("resizewindowpixel")
-                .arg(format!("exact {} {},address:{}", width, height, address))
-                .output()
-                .context("Failed to execute hyprctl")?;
+        // Resize window (also retry if fullscreen)
+        if let Some((width, height)) = placement.size {
+            let resize_args = format!("exact {} {},address:{}", width, height, address);
+            let reply = self.ipc.dispatch("resizewindowpixel", &resize_args)?;
 
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            if stdout.contains("Window is fullscreen") {
+            if reply.contains("Window is fullscreen") {
                 // Already exited fullscreen above, just retry
-                let _ = Command::new("hyprctl")
-                    .arg("dispatch")
-                    .arg("resizewindowpixel")
-                    .arg(format!("exact {} {},address:{}", width, height, address))
-                    .output();
+                let _ = self.ipc.dispatch("resizewindowpixel", &resize_args);
             }
         }
 
         Ok(())
     }
 
+    // One request for the whole stack. Anything short of every dispatch
+    // answering "ok", such as a fullscreen window, is redone one window at a
+    // time, which knows how to deal with it.
+    fn apply_placements(&self, placements: &[Placement]) -> Vec<Result<()>> {
+        let one_by_one = || placements.iter().map(|p| self.apply_placement(p)).collect();
+        if placements.len() < 2 {
+            return one_by_one();
+        }
+        let batched = self
+            .placement_dispatches(placements)
+            .and_then(|dispatches| self.ipc.batch(&dispatches));
+        match batched {
+            Ok(reply) if hyprland_ipc::all_ok(&reply) => {
+                placements.iter().map(|_| Ok(())).collect()
+            }
+            _ => one_by_one(),
+        }
+    }
+
     fn get_monitors(&self) -> Result<Vec<Monitor>> {
-        self.get_monitors_internal()
+        Ok(order_monitors(
+            self.get_monitors_internal()?,
+            &self.monitor_order,
+        ))
+    }
+
+    fn invalidate_monitors(&self) {
+        self.monitor_cache.invalidate();
     }
 
     fn get_active_window(&self) -> Result<u64> {
-        let output = Command::new("hyprctl")
-            .arg("activewindow")
-            .arg("-j")
-            .output()
+        let window = self
+            .ipc
+            .json("activewindow")
             .context("Failed to get active window")?;
 
-        let window: Value =
-            serde_json::from_slice(&output.stdout).context("Failed to parse hyprctl output")?;
-
         if let Some(address) = window.get("address").and_then(|a| a.as_str()) {
             let id = if let Some(hex) = address.strip_prefix("0x") {
                 u64::from_str_radix(hex, 16).unwrap_or(0)
@@ -931,6 +1978,43 @@ impl WindowManager for HyprlandManager {
         anyhow::bail!("Failed to get active window ID")
     }
 
+    fn get_process_windows(&self) -> Result<Vec<EveWindow>> {
+        Ok(self
+            .get_all_windows()?
+            .iter()
+            .filter_map(|window| {
+                let address = window.get("address")?.as_str()?;
+                let id = u64::from_str_radix(address.strip_prefix("0x")?, 16).ok()?;
+                Some(EveWindow {
+                    id,
+                    title: window
+                        .get("title")
+                        .and_then(|t| t.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    monitor: None,
+                    geometry: Self::get_window_geometry(window),
+                    pid: Some(window.get("pid")?.as_u64()? as u32),
+                    class: window
+                        .get("class")
+                        .and_then(|c| c.as_str())
+                        .map(|c| c.to_string()),
+                })
+            })
+            .collect())
+    }
+
+    fn get_title(&self, window_id: u64) -> Result<Option<String>> {
+        let address = format!("0x{:x}", window_id);
+        let windows = self.get_all_windows()?;
+
+        Ok(windows
+            .iter()
+            .find(|w| w.get("address").and_then(|a| a.as_str()) == Some(address.as_str()))
+            .and_then(|w| w.get("title").and_then(|t| t.as_str()))
+            .map(|t| t.to_string()))
+    }
+
     fn find_window_by_title(&self, title: &str) -> Result<Option<u64>> {
         let windows = self.get_all_windows()?;
 
@@ -952,15 +2036,141 @@ impl WindowManager for HyprlandManager {
         Ok(None)
     }
 
+    fn raise_window(&self, window_id: u64) -> Result<()> {
+        let address = format!("0x{:x}", window_id);
+        self.ipc
+            .dispatch("alterzorder", &format!("top,address:{}", address))
+            .context("Failed to raise window")?;
+        Ok(())
+    }
+
+    fn lower_window(&self, window_id: u64) -> Result<()> {
+        let address = format!("0x{:x}", window_id);
+        self.ipc
+            .dispatch("alterzorder", &format!("bottom,address:{}", address))
+            .context("Failed to lower window")?;
+        Ok(())
+    }
+
+    fn set_decorations(&self, window_id: u64, decorated: bool) -> Result<()> {
+        let address = format!("0x{:x}", window_id);
+        self.ipc
+            .request(&format!(
+                "setprop address:{} noborder {}",
+                address,
+                if decorated { "0" } else { "1" }
+            ))
+            .context("Failed to set window border")?;
+        Ok(())
+    }
+
+    fn warp_pointer(&self, window_id: u64) -> Result<()> {
+        let address = format!("0x{:x}", window_id);
+        let geometry = self
+            .get_all_windows()?
+            .iter()
+            .find(|w| w.get("address").and_then(|a| a.as_str()) == Some(address.as_str()))
+            .and_then(Self::get_window_geometry)
+            .ok_or(WindowNotFound(window_id))?;
+        let (x, y) = warp_target(geometry, self.warp_anchor);
+
+        self.ipc
+            .dispatch("movecursor", &format!("{} {}", x, y))
+            .context("Failed to warp pointer")?;
+        Ok(())
+    }
+
+    fn get_panel_windows(&self) -> Result<Vec<EveWindow>> {
+        let layers = self.ipc.json("layers")?;
+        Ok(Self::parse_layers(&layers))
+    }
+
+    fn subscribe_events(&self) -> Result<Option<Receiver<WindowEvent>>> {
+        let socket = self.ipc.events()?;
+
+        Ok(Some(events::spawn_reader(
+            BufReader::new(socket),
+            events::parse_hyprland_event,
+        )))
+    }
+
+    // An alpha set here overrides the active/inactive_opacity decoration
+    // settings until it's unset
+    fn set_opacity(&self, window_id: u64, opacity: Option<f64>) -> Result<()> {
+        let alpha = opacity.map_or("unset".to_string(), |o| o.to_string());
+        self.ipc
+            .request(&format!(
+                "setprop address:0x{:x} alpha {}",
+                window_id, alpha
+            ))
+            .context("Failed to set window opacity")?;
+        Ok(())
+    }
+
+    // Only floating windows can be pinned, and `pin` toggles
+    fn set_pinned(&self, window_id: u64, pinned: bool) -> Result<()> {
+        if self.is_pinned(window_id)? == pinned {
+            return Ok(());
+        }
+        let address = format!("address:0x{:x}", window_id);
+        if pinned {
+            self.ipc.dispatch("setfloating", &address)?;
+        }
+        self.ipc
+            .dispatch("pin", &address)
+            .context("Failed to pin window")?;
+        Ok(())
+    }
+
+    // closewindow asks the client to close; forcekillwindow would kill it
+    fn close_window(&self, window_id: u64) -> Result<()> {
+        let reply = self
+            .ipc
+            .dispatch("closewindow", &format!("address:0x{:x}", window_id))
+            .context("Failed to close window")?;
+
+        if is_missing_window_reply(&reply) {
+            return Err(WindowNotFound(window_id).into());
+        }
+
+        if !reply.trim().eq_ignore_ascii_case("ok") {
+            anyhow::bail!("Failed to close window: {}", reply.trim());
+        }
+        Ok(())
+    }
+
+    fn is_pinned(&self, window_id: u64) -> Result<bool> {
+        let address = format!("0x{:x}", window_id);
+        let windows = self.get_all_windows()?;
+        let window = windows
+            .iter()
+            .find(|w| w.get("address").and_then(|a| a.as_str()) == Some(address.as_str()))
+            .ok_or(WindowNotFound(window_id))?;
+        Ok(window.get("pinned").and_then(|p| p.as_bool()) == Some(true))
+    }
+
+    fn is_minimized(&self, window_id: u64) -> Result<bool> {
+        let address = format!("0x{:x}", window_id);
+        let windows = self.get_all_windows()?;
+        let window = windows
+            .iter()
+            .find(|w| w.get("address").and_then(|a| a.as_str()) == Some(address.as_str()))
+            .ok_or(WindowNotFound(window_id))?;
+        let workspace = window
+            .get("workspace")
+            .and_then(|w| w.get("name"))
+            .and_then(|n| n.as_str())
+            .unwrap_or_default();
+        Ok(Self::is_minimized_workspace(workspace, &self.minimize))
+    }
+
     fn minimize_window(&self, window_id: u64) -> Result<()> {
         let address = format!("0x{:x}", window_id);
-        Command::new("hyprctl")
-            .args([
-                "dispatch",
+        self.ipc
+            .dispatch(
                 "movetoworkspacesilent",
-                &format!("special,address:{}", address),
-            ])
-            .output()
+                &Self::minimize_target(&address, &self.minimize),
+            )
             .context("Failed to minimize window")?;
         Ok(())
     }
@@ -968,14 +2178,428 @@ impl WindowManager for HyprlandManager {
     fn restore_window(&self, window_id: u64) -> Result<()> {
         let address = format!("0x{:x}", window_id);
         // Move back to current workspace
-        Command::new("hyprctl")
-            .args([
-                "dispatch",
-                "movetoworkspace",
-                &format!("e+0,address:{}", address),
-            ])
-            .output()
+        self.ipc
+            .dispatch("movetoworkspace", &format!("e+0,address:{}", address))
             .context("Failed to restore window")?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn xwayland_tree() -> Value {
+        json!({
+            "type": "root",
+            "nodes": [{
+                "type": "output",
+                "name": "DP-1",
+                "nodes": [{
+                    "type": "workspace",
+                    "nodes": [{
+                        "type": "con",
+                        "id": 42,
+                        "name": "",
+                        "window_properties": {
+                            "class": "steam_app_8500",
+                            "title": "EVE - Alpha"
+                        }
+                    }]
+                }]
+            }]
+        })
+    }
+
+    #[test]
+    fn test_sway_title_falls_back_to_window_properties() {
+        let mut windows = Vec::new();
+        SwayManager::extract_windows(&xwayland_tree(), &mut windows, None);
+
+        assert_eq!(windows.len(), 1);
+        let (node, output) = &windows[0];
+        assert_eq!(output.as_deref(), Some("DP-1"));
+        assert_eq!(
+            SwayManager::get_window_title(node, SwayTitleField::Auto).as_deref(),
+            Some("EVE - Alpha")
+        );
+        assert_eq!(
+            SwayManager::get_window_title(node, SwayTitleField::Name),
+            None
+        );
+    }
+
+    #[test]
+    fn test_sway_title_prefers_name_when_present() {
+        let node = json!({
+            "type": "con",
+            "name": "EVE - Beta",
+            "window_properties": { "title": "EVE - Stale" }
+        });
+
+        assert_eq!(
+            SwayManager::get_window_title(&node, SwayTitleField::Auto).as_deref(),
+            Some("EVE - Beta")
+        );
+        assert_eq!(
+            SwayManager::get_window_title(&node, SwayTitleField::WindowProperties).as_deref(),
+            Some("EVE - Stale")
+        );
+    }
+
+    #[test]
+    fn test_marker_read_from_window_json() {
+        let marker = ("app_id".to_string(), "eve-main".to_string());
+        let native = json!({"app_id": "eve-main", "name": "Main"});
+        let xwayland = json!({
+            "app_id": null,
+            "window_properties": {"class": "steam_app_8500", "instance": "eve"}
+        });
+
+        assert_eq!(
+            marker_value(&native, Some(&marker)),
+            Some("eve-main".to_string())
+        );
+        assert_eq!(marker_value(&xwayland, Some(&marker)), None);
+        let instance = ("instance".to_string(), "eve".to_string());
+        assert_eq!(
+            marker_value(&xwayland, Some(&instance)),
+            Some("eve".to_string())
+        );
+        let class = ("class".to_string(), "steam_app_8500".to_string());
+        assert_eq!(
+            marker_value(&xwayland, Some(&class)),
+            Some("steam_app_8500".to_string())
+        );
+        assert_eq!(marker_value(&native, None), None);
+    }
+
+    #[test]
+    fn test_sway_scratchpad_commands() {
+        let strategy = SwayMinimize::Scratchpad;
+
+        assert_eq!(
+            SwayManager::minimize_command(42, &strategy),
+            "[con_id=42] move scratchpad"
+        );
+        assert_eq!(
+            SwayManager::restore_command(42, &strategy, "1"),
+            "[con_id=42] scratchpad show"
+        );
+    }
+
+    #[test]
+    fn test_hyprland_border_size_option() {
+        let option: Value =
+            serde_json::from_str(r#"{"option": "general:border_size", "int": 2, "set": true}"#)
+                .unwrap();
+        assert_eq!(HyprlandManager::parse_border_size(&option), 2);
+        assert_eq!(HyprlandManager::parse_border_size(&Value::Null), 0);
+    }
+
+    #[test]
+    fn test_sway_placement_commands() {
+        let placement = Placement {
+            window_id: 7,
+            title: "Main".to_string(),
+            monitor: Some("DP-2".to_string()),
+            x: -1920,
+            y: 0,
+            size: Some((1036, 1080)),
+        };
+
+        assert_eq!(
+            SwayManager::placement_commands(&placement),
+            [
+                "floating enable",
+                "move container to output DP-2",
+                "move absolute position -1920 0",
+                "resize set 1036 1080"
+            ]
+        );
+        let moved = Placement {
+            monitor: None,
+            size: None,
+            ..placement
+        };
+        assert_eq!(
+            SwayManager::placement_commands(&moved),
+            ["floating enable", "move absolute position -1920 0"]
+        );
+    }
+
+    #[test]
+    fn test_sway_workspace_commands() {
+        let strategy = SwayMinimize::Workspace("eve-hidden".to_string());
+
+        assert_eq!(
+            SwayManager::minimize_command(42, &strategy),
+            "[con_id=42] move container to workspace eve-hidden"
+        );
+        assert_eq!(
+            SwayManager::restore_command(42, &strategy, "3"),
+            "[con_id=42] move container to workspace 3"
+        );
+    }
+
+    #[test]
+    fn test_hyprland_minimized_workspaces() {
+        let special = HyprlandMinimize::default();
+        assert!(HyprlandManager::is_minimized_workspace(
+            "special:eve",
+            &special
+        ));
+        assert!(!HyprlandManager::is_minimized_workspace("2", &special));
+
+        let named = HyprlandMinimize::Workspace("eve-hidden".to_string());
+        assert!(HyprlandManager::is_minimized_workspace(
+            "eve-hidden",
+            &named
+        ));
+        assert!(!HyprlandManager::is_minimized_workspace("special", &named));
+    }
+
+    #[test]
+    fn test_hyprland_minimize_targets() {
+        let address = "0x55ade765da10";
+
+        assert_eq!(
+            HyprlandManager::minimize_target(address, &HyprlandMinimize::default()),
+            "special,address:0x55ade765da10"
+        );
+        assert_eq!(
+            HyprlandManager::minimize_target(
+                address,
+                &HyprlandMinimize::Special("eve".to_string())
+            ),
+            "special:eve,address:0x55ade765da10"
+        );
+        assert_eq!(
+            HyprlandManager::minimize_target(
+                address,
+                &HyprlandMinimize::Workspace("hidden".to_string())
+            ),
+            "name:hidden,address:0x55ade765da10"
+        );
+    }
+
+    #[test]
+    fn test_sway_outputs_focused_flag() {
+        let outputs = vec![
+            json!({
+                "name": "DP-1",
+                "focused": false,
+                "rect": {"x": 0, "y": 0, "width": 2560, "height": 1440}
+            }),
+            json!({
+                "name": "HDMI-A-1",
+                "focused": true,
+                "rect": {"x": 2560, "y": 0, "width": 1920, "height": 1080}
+            }),
+            // Disabled outputs have no "focused" key at all
+            json!({
+                "name": "DP-2",
+                "rect": {"x": 0, "y": 0, "width": 0, "height": 0}
+            }),
+        ];
+
+        let focused: Vec<(String, bool)> = SwayManager::parse_outputs(&outputs)
+            .into_iter()
+            .map(|m| (m.name, m.focused))
+            .collect();
+        assert_eq!(
+            focused,
+            vec![
+                ("DP-1".to_string(), false),
+                ("HDMI-A-1".to_string(), true),
+                ("DP-2".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hyprland_monitors_focused_flag() {
+        let monitors_json = vec![
+            json!({
+                "id": 0, "name": "DP-1", "x": -2560, "y": 0,
+                "width": 2560, "height": 1440, "focused": true
+            }),
+            json!({
+                "id": 1, "name": "DP-2", "x": 0, "y": 0,
+                "width": 1920, "height": 1080, "focused": false
+            }),
+        ];
+
+        let monitors = HyprlandManager::parse_monitors(&monitors_json);
+        assert_eq!(monitors.len(), 2);
+        assert!(monitors[0].focused);
+        assert_eq!(monitors[0].x, -2560);
+        assert!(!monitors[1].focused);
+    }
+
+    #[test]
+    fn test_reserved_space_comes_from_the_compositor() {
+        // Hyprland: waybar along the top of DP-1 only
+        let monitors = HyprlandManager::parse_monitors(&[
+            json!({"name": "DP-1", "x": 0, "y": 0, "width": 2560, "height": 1440,
+                   "reserved": [0, 30, 0, 0]}),
+            json!({"name": "DP-2", "x": 2560, "y": 0, "width": 1920, "height": 1080,
+                   "reserved": [0, 0, 0, 0]}),
+        ]);
+        assert_eq!(monitors[0].struts.top, 30);
+        assert!(monitors[0].panel_detected);
+        assert!(!monitors[1].panel_detected);
+
+        // Sway: swaybar at the bottom of HDMI-A-1, shown by workspace 2
+        let mut monitors = SwayManager::parse_outputs(&[
+            json!({"name": "DP-1", "rect": {"x": 0, "y": 0, "width": 2560, "height": 1440}}),
+            json!({"name": "HDMI-A-1", "rect": {"x": 2560, "y": 0, "width": 1920, "height": 1080}}),
+        ]);
+        let workspaces = [
+            json!({"name": "1", "output": "DP-1", "visible": true,
+                   "rect": {"x": 0, "y": 0, "width": 2560, "height": 1440}}),
+            json!({"name": "2", "output": "HDMI-A-1", "visible": true,
+                   "rect": {"x": 2560, "y": 0, "width": 1920, "height": 1056}}),
+            json!({"name": "3", "output": "HDMI-A-1", "visible": false,
+                   "rect": {"x": 2560, "y": 0, "width": 1920, "height": 1080}}),
+        ];
+        SwayManager::reserve_bars(&mut monitors, &workspaces);
+        assert_eq!(monitors[0].struts, Struts::default());
+        assert_eq!(monitors[1].struts.bottom, 24);
+        assert!(monitors[1].panel_detected);
+    }
+
+    #[test]
+    fn test_hyprland_cross_monitor_workspace() {
+        let monitors = vec![
+            json!({"id": 0, "name": "DP-1", "activeWorkspace": {"id": 1, "name": "1"}}),
+            json!({"id": 1, "name": "DP-2", "activeWorkspace": {"id": 4, "name": "4"}}),
+        ];
+        let clients = vec![
+            json!({"address": "0x10", "monitor": 0}),
+            json!({"address": "0x20", "monitor": 1}),
+        ];
+
+        let target = |address, monitor| {
+            HyprlandManager::workspace_on_monitor(&clients, &monitors, address, monitor)
+        };
+        assert_eq!(target("0x10", "DP-2"), Some(4));
+        // Already there, or nowhere to go
+        assert_eq!(target("0x20", "DP-2"), None);
+        assert_eq!(target("0x10", "HDMI-A-1"), None);
+    }
+
+    #[test]
+    fn test_hyprland_window_rules() {
+        let config = Config {
+            display_width: 2560,
+            display_height: 1440,
+            panel_height: 30,
+            eve_width: 1400,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            HyprlandManager::window_rules(&config),
+            vec![
+                "float,class:^(steam_app_8500)$",
+                "nofullscreenrequest,class:^(steam_app_8500)$",
+                "size 1400 1410,class:^(steam_app_8500)$",
+                "move 580 0,class:^(steam_app_8500)$",
+            ]
+        );
+        assert_eq!(
+            HyprlandManager::unset_rule(&config),
+            "unset,class:^(steam_app_8500)$"
+        );
+
+        let custom = Config {
+            eve_window_class: "eve.exe".to_string(),
+            ..config
+        };
+        assert_eq!(
+            HyprlandManager::unset_rule(&custom),
+            "unset,class:^(eve\\.exe)$"
+        );
+    }
+
+    #[test]
+    fn test_panel_discovery_parsing() {
+        let layers = json!({
+            "DP-1": {
+                "levels": {
+                    "0": [],
+                    "2": [{
+                        "address": "0x5612a0b0c0d0",
+                        "x": 0, "y": 0, "w": 2560, "h": 34,
+                        "namespace": "waybar"
+                    }]
+                }
+            }
+        });
+        let panels = HyprlandManager::parse_layers(&layers);
+        assert_eq!(panels.len(), 1);
+        assert_eq!(panels[0].class.as_deref(), Some("waybar"));
+        assert_eq!(panels[0].geometry, Some((0, 0, 2560, 34)));
+
+        let listing = "\
+0x02200003 -1 0    1400 2560 40   plasmashell.plasmashell  host Plasma
+0x04a00007  0 100  50   1280 720  steam_app_8500.steam_app_8500  host EVE - Main
+";
+        let windows = KWinManager::parse_geometry_listing(listing);
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].id, 0x02200003);
+        assert_eq!(windows[0].class.as_deref(), Some("plasmashell"));
+        assert_eq!(windows[0].geometry, Some((0, 1400, 2560, 40)));
+    }
+
+    #[test]
+    fn test_kwin_script_window_listing() {
+        let reply = json!([
+            {
+                "uuid": "{0b4c7a52-9d1e-4e8a-a7c2-5f3e2d1c0b9a}",
+                "caption": "EVE - Main",
+                "pid": 4242,
+                "class": "steam_app_8500",
+                "x": 0, "y": 0, "width": 1382.0, "height": 1440,
+                "output": "DP-1",
+                "minimized": false
+            },
+            { "uuid": "{no-geometry}", "caption": "broken" }
+        ]);
+
+        let windows = KWinDbusManager::parse_windows(&reply);
+        assert_eq!(windows.len(), 1);
+        let main = &windows[0];
+        assert_eq!(main.window.title, "EVE - Main");
+        assert_eq!(main.window.monitor.as_deref(), Some("DP-1"));
+        assert_eq!(main.window.geometry, Some((0, 0, 1382, 1440)));
+        assert_eq!(main.window.pid, Some(4242));
+        assert!(!main.minimized);
+        // Same internalId, same id across listings
+        assert_eq!(main.window.id, KWinDbusManager::window_id(&main.uuid));
+        assert_ne!(main.window.id, KWinDbusManager::window_id("{no-geometry}"));
+    }
+
+    #[test]
+    fn test_gnome_extension_replies() {
+        let list = MutterManager::parse_reply(
+            br#"{"type":"s","data":["[{\"id\":3120,\"title\":\"EVE - Main\",\"pid\":4242,\"class\":\"steam_app_8500\",\"x\":0,\"y\":0,\"width\":1382,\"height\":1440,\"monitor\":1,\"minimized\":true}]"]}"#,
+        )
+        .unwrap();
+        let list: Value = serde_json::from_str(list.as_str().unwrap()).unwrap();
+
+        let windows = MutterManager::parse_windows(&list);
+        assert_eq!(windows.len(), 1);
+        let (main, minimized) = &windows[0];
+        assert_eq!(main.id, 3120);
+        assert_eq!(main.title, "EVE - Main");
+        assert_eq!(main.monitor.as_deref(), Some("1"));
+        assert_eq!(main.geometry, Some((0, 0, 1382, 1440)));
+        assert!(minimized);
+
+        let activated = MutterManager::parse_reply(br#"{"type":"b","data":[false]}"#).unwrap();
+        assert_eq!(activated, Value::Bool(false));
+    }
+}
@@ -1,20 +1,385 @@
-use crate::config::Config;
+use crate::config::{
+    Backend, Config, FocusWorkaround, LayoutMode, MonitorRef, PanelEdge, WarpAnchor,
+};
+use crate::effects::EffectsLedger;
+use crate::events::WindowEvent;
+use crate::layout::{self, Placement, ZOp};
+use crate::matcher;
+use crate::report::ActionReport;
+#[cfg(target_os = "linux")]
+use crate::wayland_backends::{
+    HyprlandManager, KWinDbusManager, KWinManager, MutterManager, SwayManager,
+};
+#[cfg(target_os = "linux")]
+use crate::wlr_toplevel::WlrManager;
+#[cfg(target_os = "linux")]
+use crate::x11_manager::X11Manager;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+/// Space reserved along each edge of a monitor by docks and panels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Struts {
+    pub top: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub right: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Monitor {
     pub name: String,
     pub x: i32,
     pub y: i32,
     pub width: u32,
     pub height: u32,
+    pub struts: Struts,
+    /// Struts come from a detected panel window, so panel_height is not
+    /// reserved on top of them
+    pub panel_detected: bool,
+    /// Compositor-reported keyboard focus (Sway/Hyprland only)
+    pub focused: bool,
+    /// The output X11 calls primary (RandR and xrandr only)
+    pub primary: bool,
+}
+
+impl Monitor {
+    /// Area available for placing windows on this monitor as (x, y, width, height).
+    /// Subtracts the configured panel (per-monitor override or global panel_height,
+    /// on panel_edge) unless a panel was detected, any reserved struts, and the
+    /// outer margin.
+    pub fn usable_rect(&self, config: &Config) -> (i32, i32, u32, u32) {
+        let panel = if self.panel_detected {
+            0
+        } else {
+            config
+                .monitor_panel_heights
+                .get(&self.name)
+                .copied()
+                .unwrap_or(config.panel_height)
+        };
+
+        // Struts already include panels the compositor knows about, so take the
+        // larger of the two rather than reserving the panel twice
+        let mut top = self.struts.top;
+        let mut bottom = self.struts.bottom;
+        match config.panel_edge {
+            PanelEdge::Top => top = top.max(panel),
+            PanelEdge::Bottom => bottom = bottom.max(panel),
+        }
+        let left = self.struts.left;
+        let right = self.struts.right;
+        let margin = config.margin;
+
+        let x = self.x + (left + margin) as i32;
+        let y = self.y + (top + margin) as i32;
+        let width = self
+            .width
+            .saturating_sub(left + right)
+            .saturating_sub(margin * 2);
+        let height = self
+            .height
+            .saturating_sub(top + bottom)
+            .saturating_sub(margin * 2);
+
+        (x, y, width, height)
+    }
+
+    /// Reserve `struts` the compositor reported for this monitor. It knows
+    /// every panel and dock, so panel_height isn't reserved on top of them.
+    pub fn reserve(&mut self, struts: Struts) {
+        if struts != Struts::default() {
+            self.struts = struts;
+            self.panel_detected = true;
+        }
+    }
+}
+
+/// What the compositor's work area `work_area` (x, y, width, height), the
+/// space it leaves for windows once its panels and docks are reserved,
+/// takes off each edge of `mon`. A work area missing the monitor takes
+/// nothing.
+pub fn work_area_struts(mon: &Monitor, work_area: (i32, i32, u32, u32)) -> Struts {
+    let (mon_left, mon_top) = (mon.x as i64, mon.y as i64);
+    let (mon_right, mon_bottom) = (mon_left + mon.width as i64, mon_top + mon.height as i64);
+    let (x, y, width, height) = work_area;
+    let left = (x as i64).max(mon_left);
+    let top = (y as i64).max(mon_top);
+    let right = (x as i64 + width as i64).min(mon_right);
+    let bottom = (y as i64 + height as i64).min(mon_bottom);
+    if right <= left || bottom <= top {
+        return Struts::default();
+    }
+
+    Struts {
+        top: (top - mon_top) as u32,
+        bottom: (mon_bottom - bottom) as u32,
+        left: (left - mon_left) as u32,
+        right: (mon_right - right) as u32,
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Space a panel at `panel` (x, y, width, height) takes from the edge of
+/// `mon` it's docked to. Wide panels count against the top or bottom,
+/// tall ones against the left or right, whichever half they sit in.
+pub fn panel_struts(mon: &Monitor, panel: (i32, i32, u32, u32)) -> Struts {
+    let (mon_left, mon_top) = (mon.x as i64, mon.y as i64);
+    let (mon_right, mon_bottom) = (mon_left + mon.width as i64, mon_top + mon.height as i64);
+    let (x, y, width, height) = panel;
+    let left = (x as i64).max(mon_left);
+    let top = (y as i64).max(mon_top);
+    let right = (x as i64 + width as i64).min(mon_right);
+    let bottom = (y as i64 + height as i64).min(mon_bottom);
+
+    let mut struts = Struts::default();
+    if right <= left || bottom <= top {
+        return struts;
+    }
+
+    if right - left >= bottom - top {
+        if top + bottom < mon_top + mon_bottom {
+            struts.top = (bottom - mon_top) as u32;
+        } else {
+            struts.bottom = (mon_bottom - top) as u32;
+        }
+    } else if left + right < mon_left + mon_right {
+        struts.left = (right - mon_left) as u32;
+    } else {
+        struts.right = (mon_right - left) as u32;
+    }
+    struts
+}
+
+/// Reserve the space taken by panel windows whose class is in `classes`
+/// (case-insensitively). Monitors without one keep using panel_height.
+pub fn apply_detected_panels(monitors: &mut [Monitor], panels: &[EveWindow], classes: &[String]) {
+    let is_panel = |window: &&EveWindow| {
+        window
+            .class
+            .as_deref()
+            .is_some_and(|class| classes.iter().any(|c| c.eq_ignore_ascii_case(class)))
+    };
+
+    for mon in monitors.iter_mut() {
+        for geometry in panels.iter().filter(is_panel).filter_map(|w| w.geometry) {
+            let struts = panel_struts(mon, geometry);
+            if struts == Struts::default() {
+                continue;
+            }
+            mon.struts.top = mon.struts.top.max(struts.top);
+            mon.struts.bottom = mon.struts.bottom.max(struts.bottom);
+            mon.struts.left = mon.struts.left.max(struts.left);
+            mon.struts.right = mon.struts.right.max(struts.right);
+            mon.panel_detected = true;
+        }
+    }
+}
+
+/// The monitor containing a point. Uses wide arithmetic so monitors left of
+/// or above the primary (negative origins) are handled like any other.
+pub fn monitor_at(monitors: &[Monitor], x: i32, y: i32) -> Option<&Monitor> {
+    monitors.iter().find(|mon| {
+        let (x, y) = (x as i64, y as i64);
+        x >= mon.x as i64
+            && x < mon.x as i64 + mon.width as i64
+            && y >= mon.y as i64
+            && y < mon.y as i64 + mon.height as i64
+    })
+}
+
+/// Monitor holding a window, by name or else by its centre
+pub fn window_monitor<'a>(window: &EveWindow, monitors: &'a [Monitor]) -> Option<&'a Monitor> {
+    if let Some(name) = &window.monitor {
+        return monitors.iter().find(|m| &m.name == name);
+    }
+    let (x, y) = window_center(window.geometry?);
+    monitor_at(monitors, x, y)
+}
+
+/// Smallest (x, y, width, height) covering every monitor, or None without any
+pub fn bounding_box(monitors: &[Monitor]) -> Option<(i32, i32, u32, u32)> {
+    let left = monitors.iter().map(|m| m.x as i64).min()?;
+    let top = monitors.iter().map(|m| m.y as i64).min()?;
+    let right = monitors.iter().map(|m| m.x as i64 + m.width as i64).max()?;
+    let bottom = monitors
+        .iter()
+        .map(|m| m.y as i64 + m.height as i64)
+        .max()?;
+
+    Some((
+        left as i32,
+        top as i32,
+        (right - left) as u32,
+        (bottom - top) as u32,
+    ))
+}
+
+impl MonitorRef {
+    pub fn matches(&self, name: &str, monitors: &[Monitor]) -> bool {
+        match self {
+            MonitorRef::Name(wanted) => wanted == name,
+            MonitorRef::Index(index) => monitors.get(*index).is_some_and(|m| m.name == name),
+        }
+    }
+}
+
+/// Reorder detected monitors to follow `order`. Index references there are
+/// positions in the detected list; monitors `order` doesn't mention keep
+/// their detected order after the ones it does.
+pub fn order_monitors(monitors: Vec<Monitor>, order: &[MonitorRef]) -> Vec<Monitor> {
+    if order.is_empty() {
+        return monitors;
+    }
+
+    let rank = |name: &str| {
+        order
+            .iter()
+            .position(|r| r.matches(name, &monitors))
+            .unwrap_or(order.len())
+    };
+    let mut ranked: Vec<(usize, Monitor)> = monitors
+        .iter()
+        .map(|m| (rank(&m.name), m.clone()))
+        .collect();
+    // Stable, so unmentioned monitors stay in detected order
+    ranked.sort_by_key(|(rank, _)| *rank);
+    ranked.into_iter().map(|(_, m)| m).collect()
+}
+
+/// Drop windows sitting on a monitor that isn't in `allowed`. Windows whose
+/// monitor couldn't be resolved are kept, since we can't tell where they are.
+pub fn retain_on_monitors(
+    windows: &mut Vec<EveWindow>,
+    monitors: &[Monitor],
+    allowed: &[MonitorRef],
+) {
+    windows.retain(|window| match &window.monitor {
+        Some(name) => allowed.iter().any(|r| r.matches(name, monitors)),
+        None => true,
+    });
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct EveWindow {
     pub id: u64,
     pub title: String,
     pub monitor: Option<String>,
+    /// Current (x, y, width, height) in global coordinates, if the backend
+    /// could read it
+    pub geometry: Option<(i32, i32, u32, u32)>,
+    /// Owning process, where the backend reports it
+    pub pid: Option<u32>,
+    /// Window class (WM_CLASS / Hyprland class), where the backend reports it
+    pub class: Option<String>,
+}
+
+impl EveWindow {
+    /// Identity that survives the window being recreated. Hyprland hands a
+    /// reconnected client a new address, so use pid + class + character
+    /// there; otherwise the id is already stable for the window's lifetime.
+    pub fn stable_key(&self) -> String {
+        match (self.pid, &self.class) {
+            (Some(pid), Some(class)) => format!("{}:{}:{}", pid, class, self.title),
+            _ => format!("id:{}", self.id),
+        }
+    }
+}
+
+/// Returned by backends when asked to act on a window that no longer exists,
+/// so callers can refresh their window list and retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowNotFound(pub u64);
+
+impl std::fmt::Display for WindowNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Window {} no longer exists", self.0)
+    }
+}
+
+impl std::error::Error for WindowNotFound {}
+
+/// Whether compositor command output says the target window is gone
+pub fn is_missing_window_reply(reply: &str) -> bool {
+    let reply = reply.to_lowercase();
+    reply.contains("no matching node")
+        || reply.contains("no such window")
+        || reply.contains("window not found")
+}
+
+/// Run the configured focus workarounds for `window_id`, in order. Each step
+/// is best effort: a failure is ignored so activation still gets its turn.
+pub fn apply_focus_workarounds(
+    wm: &(impl WindowManager + ?Sized),
+    window_id: u64,
+    workarounds: &[FocusWorkaround],
+) {
+    for workaround in workarounds {
+        let _ = match workaround {
+            FocusWorkaround::Unminimize => wm.restore_window(window_id),
+            FocusWorkaround::RaiseFirst => wm.raise_window(window_id),
+            FocusWorkaround::WarpPointer => wm.warp_pointer(window_id),
+        };
+    }
+}
+
+/// The centre of a window's (x, y, width, height)
+pub fn window_center(geometry: (i32, i32, u32, u32)) -> (i32, i32) {
+    let (x, y, width, height) = geometry;
+    (x + (width / 2) as i32, y + (height / 2) as i32)
+}
+
+/// Where warping to a window puts the pointer: `anchor` within its
+/// (x, y, width, height), kept inside the window
+pub fn warp_target(geometry: (i32, i32, u32, u32), anchor: WarpAnchor) -> (i32, i32) {
+    let (x, y, width, height) = geometry;
+    let offset = |length: u32, fraction: f64| {
+        ((length as f64 * fraction) as u32).min(length.saturating_sub(1)) as i32
+    };
+    (x + offset(width, anchor.x), y + offset(height, anchor.y))
+}
+
+/// With no EVE clients discovered, fail with a specific error if that's
+/// because they're running inside a Wine virtual desktop, where the
+/// compositor only sees the desktop window
+pub fn check_wine_desktop(
+    wm: &(impl WindowManager + ?Sized),
+    windows: &[EveWindow],
+    config: &Config,
+) -> Result<()> {
+    if !windows.is_empty() || !config.detect_wine_desktop {
+        return Ok(());
+    }
+
+    let process_windows = wm.get_process_windows().unwrap_or_default();
+    if let Some(desktop) = process_windows
+        .iter()
+        .find(|w| matcher::is_wine_virtual_desktop(&w.title, w.class.as_deref()))
+    {
+        anyhow::bail!(
+            "No EVE windows found, but \"{}\" is a Wine virtual desktop. Clients inside it \
+             can't be managed one by one; turn off \"Emulate a virtual desktop\" in winecfg \
+             for the EVE prefix (set detect_wine_desktop = false to skip this check)",
+            desktop.title
+        );
+    }
+    Ok(())
+}
+
+/// Restore a minimized window and, with `focus`, activate it as well, so it
+/// ends up focused whether or not the backend's restore does that itself
+pub fn restore_and_focus(
+    wm: &(impl WindowManager + ?Sized),
+    window_id: u64,
+    focus: bool,
+) -> Result<()> {
+    wm.restore_window(window_id)?;
+    if focus {
+        wm.activate_window(window_id)?;
+    }
+    Ok(())
 }
 
 /// Trait for window management across different display servers and compositors
@@ -26,7 +391,161 @@ pub trait WindowManager: Send + Sync {
     fn activate_window(&self, window_id: u64) -> Result<()>;
 
     /// Stack all EVE windows at the same position (centered)
-    fn stack_windows(&self, windows: &[EveWindow], config: &Config) -> Result<()>;
+    fn stack_windows(&self, windows: &[EveWindow], config: &Config) -> Result<ActionReport> {
+        // The pip layout's big slot is always the active client
+        let following;
+        let config = if config.primary_follows_active || config.layout_mode() == LayoutMode::Pip {
+            following = Config {
+                primary_character: layout::resolve_primary(
+                    windows,
+                    self.get_active_window().ok(),
+                    config,
+                ),
+                ..config.clone()
+            };
+            &following
+        } else {
+            config
+        };
+        self.place_windows(windows, config)
+    }
+
+    /// Stack with `config.primary_character` as the primary, as is
+    fn place_windows(&self, windows: &[EveWindow], config: &Config) -> Result<ActionReport> {
+        let monitors = self.get_layout_monitors(config)?;
+
+        // A window's first error is the one reported; the rest still get placed
+        let mut errors: HashMap<u64, anyhow::Error> = HashMap::new();
+        let mut note = |window_id: u64, result: Result<()>| {
+            if let Err(e) = result {
+                errors.entry(window_id).or_insert(e);
+            }
+        };
+        let placements = layout::plan_stack(windows, &monitors, config);
+        for (placement, result) in placements.iter().zip(self.apply_placements(&placements)) {
+            note(placement.window_id, result);
+        }
+        for op in layout::plan_z_order(windows, config) {
+            match op {
+                ZOp::Raise(id) => note(id, self.raise_window(id)),
+                ZOp::Lower(id) => note(id, self.lower_window(id)),
+            }
+        }
+        if config.remove_decorations {
+            // Only stacked windows go borderless; `nicotine reset` undoes it
+            let mut ledger = EffectsLedger::load();
+            for window in windows {
+                note(window.id, ledger.undecorate(self, window.id));
+            }
+            ledger.save()?;
+        }
+        for window in windows.iter().filter(|w| config.pinned.contains(&w.title)) {
+            note(window.id, self.set_pinned(window.id, true));
+        }
+
+        // Windows left out of the plan (monitor_only) are already in place
+        let mut report = ActionReport::default();
+        for window in windows {
+            match errors.remove(&window.id) {
+                Some(e) => report.fail(&window.title, &e),
+                None => report.succeed(&window.title),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Trade the places of two clients, picked as layout::plan_swap does
+    fn swap_windows(&self, names: &[String], config: &Config) -> Result<()> {
+        let windows = self.get_eve_windows()?;
+        let active = self.get_active_window().unwrap_or(0);
+        for placement in layout::plan_swap(&windows, names, active, config)? {
+            self.apply_placement(&placement)?;
+        }
+        Ok(())
+    }
+
+    /// A character's client, or the active one without a name
+    fn character_window(&self, name: Option<&str>) -> Result<EveWindow> {
+        let windows = self.get_eve_windows()?;
+        let window = match name {
+            Some(name) => windows
+                .into_iter()
+                .find(|w| w.title == name)
+                .ok_or_else(|| anyhow::anyhow!("No EVE client for {}", name))?,
+            None => {
+                let active = self.get_active_window()?;
+                windows
+                    .into_iter()
+                    .find(|w| w.id == active)
+                    .ok_or_else(|| anyhow::anyhow!("The active window isn't an EVE client"))?
+            }
+        };
+        Ok(window)
+    }
+
+    /// Pin or unpin a character's client, or the active one without a
+    /// name. Returns the client's title.
+    fn pin_character(&self, name: Option<&str>, pinned: bool) -> Result<String> {
+        let window = self.character_window(name)?;
+        self.set_pinned(window.id, pinned)?;
+        Ok(window.title)
+    }
+
+    /// Move and resize a single window to its planned geometry
+    fn apply_placement(&self, placement: &Placement) -> Result<()>;
+
+    /// Apply several placements, returning one result per placement in
+    /// order. Backends that can send them all in one request override this.
+    fn apply_placements(&self, placements: &[Placement]) -> Vec<Result<()>> {
+        placements.iter().map(|p| self.apply_placement(p)).collect()
+    }
+
+    /// Every top-level window whose owning process is known, character or
+    /// not, with its raw title. Backends that can't report pids return none.
+    fn get_process_windows(&self) -> Result<Vec<EveWindow>> {
+        Ok(Vec::new())
+    }
+
+    /// Panels, docks and bars (windows or layer surfaces) with their class
+    /// and geometry, for detect_panels. Backends that can't list them return
+    /// none, leaving panel_height in effect.
+    fn get_panel_windows(&self) -> Result<Vec<EveWindow>> {
+        Ok(Vec::new())
+    }
+
+    /// Monitors to plan a stack against: detected panels reserved when
+    /// detect_panels is on, and the no_monitors policy applied
+    fn get_layout_monitors(&self, config: &Config) -> Result<Vec<Monitor>> {
+        let mut monitors = self.get_monitors()?;
+        if config.detect_panels {
+            let panels = self.get_panel_windows().unwrap_or_default();
+            apply_detected_panels(&mut monitors, &panels, &config.panel_classes);
+        }
+        layout::resolve_monitors(monitors, config)
+    }
+
+    /// The monitor the user is working on: the one the compositor reports as
+    /// focused, else the one holding the active window
+    fn get_active_monitor(&self) -> Result<Option<Monitor>> {
+        let monitors = self.get_monitors()?;
+        if let Some(focused) = monitors.iter().find(|m| m.focused) {
+            return Ok(Some(focused.clone()));
+        }
+
+        let active = self.get_active_window()?;
+        Ok(self
+            .get_eve_windows()?
+            .into_iter()
+            .find(|w| w.id == active)
+            .and_then(|w| window_monitor(&w, &monitors))
+            .cloned())
+    }
+
+    /// Read a window's current raw title (None if the backend can't tell)
+    fn get_title(&self, window_id: u64) -> Result<Option<String>> {
+        let _ = window_id;
+        Ok(None)
+    }
 
     /// Get the currently active window ID
     fn get_active_window(&self) -> Result<u64>;
@@ -47,11 +566,102 @@ pub trait WindowManager: Send + Sync {
     /// Restore a minimized window
     fn restore_window(&self, window_id: u64) -> Result<()>;
 
+    /// Whether a window is currently minimized (or hidden the backend's way)
+    fn is_minimized(&self, window_id: u64) -> Result<bool> {
+        // Default implementation: backend can't tell, assume it's showing
+        let _ = window_id;
+        Ok(false)
+    }
+
     /// Get all monitors/outputs with their geometry
     fn get_monitors(&self) -> Result<Vec<Monitor>> {
         // Default implementation: return empty vec (fallback to global config)
         Ok(Vec::new())
     }
+
+    /// Forget any monitors the backend has cached, so the next
+    /// get_monitors asks again, e.g. because one was just plugged in
+    fn invalidate_monitors(&self) {}
+
+    /// Raise a window above its siblings
+    fn raise_window(&self, window_id: u64) -> Result<()> {
+        // Default implementation: no-op (backend can't restack)
+        let _ = window_id;
+        Ok(())
+    }
+
+    /// Lower a window beneath its siblings
+    fn lower_window(&self, window_id: u64) -> Result<()> {
+        // Default implementation: no-op (backend can't restack)
+        let _ = window_id;
+        Ok(())
+    }
+
+    /// Move the pointer to warp_anchor in a window, its centre by default
+    fn warp_pointer(&self, window_id: u64) -> Result<()> {
+        // Default implementation: no-op (backend can't move the pointer)
+        let _ = window_id;
+        Ok(())
+    }
+
+    /// Stream of focus and window list changes, or None if the backend can
+    /// only be polled
+    fn subscribe_events(&self) -> Result<Option<Receiver<WindowEvent>>> {
+        Ok(None)
+    }
+
+    /// Stream that gets a message whenever monitors are added, removed or
+    /// reconfigured, or None if the backend's monitors can only be polled
+    fn subscribe_monitor_events(&self) -> Result<Option<Receiver<()>>> {
+        Ok(None)
+    }
+
+    /// Show or hide a window's title bar and border
+    fn set_decorations(&self, window_id: u64, decorated: bool) -> Result<()> {
+        // Default implementation: no-op (backend can't change decorations)
+        let _ = (window_id, decorated);
+        Ok(())
+    }
+
+    /// Get a window's explicit opacity (0.0-1.0), or None if it has none set
+    fn get_opacity(&self, window_id: u64) -> Result<Option<f64>> {
+        let _ = window_id;
+        Ok(None)
+    }
+
+    /// Set a window's opacity, or clear the property entirely with None
+    fn set_opacity(&self, window_id: u64, opacity: Option<f64>) -> Result<()> {
+        // Default implementation: no-op (backend has no opacity control)
+        let _ = (window_id, opacity);
+        Ok(())
+    }
+
+    /// Keep a window above every other one, or let it go again
+    fn set_pinned(&self, window_id: u64, pinned: bool) -> Result<()> {
+        let _ = (window_id, pinned);
+        anyhow::bail!("This backend can't pin windows")
+    }
+
+    /// Whether a window is kept above the others
+    fn is_pinned(&self, window_id: u64) -> Result<bool> {
+        // Default implementation: backend can't pin, so nothing is pinned
+        let _ = window_id;
+        Ok(false)
+    }
+
+    /// Flag a window as wanting attention, so it blinks in the taskbar
+    /// without taking focus. Cleared by the WM when the window is focused.
+    fn mark_urgent(&self, window_id: u64) -> Result<()> {
+        let _ = window_id;
+        anyhow::bail!("This backend can't mark windows urgent")
+    }
+
+    /// Ask a window to close, as its close button would, so the client
+    /// can shut down cleanly
+    fn close_window(&self, window_id: u64) -> Result<()> {
+        let _ = window_id;
+        anyhow::bail!("This backend can't close windows")
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -69,27 +679,42 @@ pub enum WaylandCompositor {
     Other,    // Other/unknown compositor
 }
 
-/// Detect which display server is running
-pub fn detect_display_server() -> DisplayServer {
-    if let Ok(session_type) = std::env::var("XDG_SESSION_TYPE") {
-        if session_type == "wayland" {
-            return DisplayServer::Wayland;
-        }
+/// An environment variable lookup, so detection can be tested without
+/// touching the real environment
+type Env<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn session_env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Which display server is running
+fn display_server_in(env: Env) -> DisplayServer {
+    match env("XDG_SESSION_TYPE").as_deref() {
+        Some("wayland") => return DisplayServer::Wayland,
+        Some("x11") => return DisplayServer::X11,
+        _ => {}
     }
 
-    // Fallback: check if WAYLAND_DISPLAY is set
-    if std::env::var("WAYLAND_DISPLAY").is_ok() {
+    // Not a login session (e.g. started from a script): go by the sockets
+    if ["WAYLAND_DISPLAY", "SWAYSOCK", "HYPRLAND_INSTANCE_SIGNATURE"]
+        .iter()
+        .any(|name| env(name).is_some())
+    {
         return DisplayServer::Wayland;
     }
 
-    // Default to X11
+    // Default to X11, which DISPLAY would point at
     DisplayServer::X11
 }
 
 /// Detect which Wayland compositor is running
 pub fn detect_wayland_compositor() -> WaylandCompositor {
+    compositor_in(&session_env)
+}
+
+fn compositor_in(env: Env) -> WaylandCompositor {
     // Check XDG_CURRENT_DESKTOP first
-    if let Ok(desktop) = std::env::var("XDG_CURRENT_DESKTOP") {
+    if let Some(desktop) = env("XDG_CURRENT_DESKTOP") {
         let desktop_lower = desktop.to_lowercase();
         if desktop_lower.contains("kde") {
             return WaylandCompositor::Kde;
@@ -106,13 +731,599 @@ pub fn detect_wayland_compositor() -> WaylandCompositor {
     }
 
     // Check for compositor-specific environment variables
-    if std::env::var("SWAYSOCK").is_ok() {
+    if env("SWAYSOCK").is_some() {
         return WaylandCompositor::Sway;
     }
 
-    if std::env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
+    if env("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
         return WaylandCompositor::Hyprland;
     }
 
+    if env("KDE_FULL_SESSION").is_some() {
+        return WaylandCompositor::Kde;
+    }
+
     WaylandCompositor::Other
 }
+
+/// The backend for this session
+pub fn detect_backend() -> Backend {
+    if cfg!(target_os = "windows") {
+        return Backend::Windows;
+    }
+    if cfg!(target_os = "macos") {
+        return Backend::Macos;
+    }
+    backend_in(&session_env)
+}
+
+fn backend_in(env: Env) -> Backend {
+    match display_server_in(env) {
+        DisplayServer::X11 => Backend::X11,
+        DisplayServer::Wayland => match compositor_in(env) {
+            WaylandCompositor::Kde => Backend::Kde,
+            WaylandCompositor::Sway => Backend::Sway,
+            WaylandCompositor::Hyprland => Backend::Hyprland,
+            WaylandCompositor::Gnome => Backend::Gnome,
+            WaylandCompositor::Other => Backend::Wlr,
+        },
+    }
+}
+
+/// Connect to the backend config.toml's `backend` names, or the one
+/// detected for this session when that's "auto"
+pub fn detect(config: &Config) -> Result<Arc<dyn WindowManager>> {
+    let backend = match config.backend {
+        Backend::Auto => {
+            let backend = detect_backend();
+            if backend == Backend::Windows {
+                tracing::info!("Detected Windows");
+            } else if backend == Backend::Macos {
+                tracing::info!("Detected macOS");
+            } else if backend == Backend::X11 {
+                tracing::info!("Detected X11 display server");
+            } else {
+                tracing::info!(
+                    "Detected Wayland display server with {:?} compositor",
+                    backend
+                );
+            }
+            backend
+        }
+        backend => backend,
+    };
+
+    match backend {
+        #[cfg(target_os = "linux")]
+        Backend::X11 | Backend::Auto => Ok(Arc::new(X11Manager::new(config)?)),
+        #[cfg(target_os = "linux")]
+        Backend::Kde => {
+            if config.kwin_scripting {
+                match KWinDbusManager::new(config) {
+                    Ok(manager) => {
+                        tracing::info!("Using KDE/KWin scripting backend");
+                        return Ok(Arc::new(manager));
+                    }
+                    Err(e) => tracing::warn!("{:#}, falling back to wmctrl", e),
+                }
+            }
+            tracing::info!("Using KDE/KWin backend");
+            Ok(Arc::new(KWinManager::new(config)?))
+        }
+        #[cfg(target_os = "linux")]
+        Backend::Sway => {
+            tracing::info!("Using Sway backend");
+            Ok(Arc::new(SwayManager::new(config)?))
+        }
+        #[cfg(target_os = "linux")]
+        Backend::Hyprland => {
+            tracing::info!("Using Hyprland backend");
+            Ok(Arc::new(HyprlandManager::new(config)?))
+        }
+        #[cfg(target_os = "linux")]
+        Backend::Gnome => {
+            tracing::info!("Using GNOME/Mutter backend");
+            Ok(Arc::new(MutterManager::new(config)?))
+        }
+        #[cfg(not(target_os = "linux"))]
+        Backend::X11
+        | Backend::Auto
+        | Backend::Kde
+        | Backend::Sway
+        | Backend::Hyprland
+        | Backend::Gnome
+        | Backend::Wlr => {
+            anyhow::bail!("The {:?} backend only exists in Linux builds", backend)
+        }
+        #[cfg(target_os = "windows")]
+        Backend::Windows => {
+            tracing::info!("Using Win32 backend");
+            Ok(Arc::new(crate::windows_manager::WindowsManager::new(
+                config,
+            )?))
+        }
+        #[cfg(not(target_os = "windows"))]
+        Backend::Windows => anyhow::bail!("The windows backend only exists in Windows builds"),
+        #[cfg(target_os = "macos")]
+        Backend::Macos => {
+            tracing::info!("Using macOS Accessibility backend");
+            Ok(Arc::new(crate::macos_manager::MacManager::new(config)?))
+        }
+        #[cfg(not(target_os = "macos"))]
+        Backend::Macos => anyhow::bail!("The macos backend only exists in macOS builds"),
+        #[cfg(target_os = "linux")]
+        Backend::Wlr => match WlrManager::new(config) {
+            Ok(manager) => {
+                tracing::info!("Using generic wlroots backend (focus and minimize only)");
+                Ok(Arc::new(manager))
+            }
+            Err(e) => anyhow::bail!(
+                "Unknown Wayland compositor ({:#}). \
+                 Supported: KDE Plasma, GNOME, Sway, Hyprland, \
+                 and compositors with wlr-foreign-toplevel-management",
+                e
+            ),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_window_manager::MockWindowManager;
+
+    fn monitor(name: &str, x: i32, y: i32, width: u32, height: u32) -> Monitor {
+        Monitor {
+            name: name.to_string(),
+            x,
+            y,
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_usable_rect_without_panel() {
+        let config = Config::default();
+        let mon = monitor("DP-1", 0, 0, 2560, 1440);
+
+        assert_eq!(mon.usable_rect(&config), (0, 0, 2560, 1440));
+    }
+
+    #[test]
+    fn test_usable_rect_bottom_panel() {
+        let config = Config {
+            panel_height: 40,
+            ..Config::default()
+        };
+        let mon = monitor("DP-1", 0, 0, 2560, 1440);
+
+        assert_eq!(mon.usable_rect(&config), (0, 0, 2560, 1400));
+    }
+
+    #[test]
+    fn test_usable_rect_top_panel_shifts_origin() {
+        let config = Config {
+            panel_height: 40,
+            panel_edge: PanelEdge::Top,
+            ..Config::default()
+        };
+        let mon = monitor("DP-1", 2560, 0, 1920, 1080);
+
+        assert_eq!(mon.usable_rect(&config), (2560, 40, 1920, 1040));
+    }
+
+    #[test]
+    fn test_usable_rect_per_monitor_panel_override() {
+        let mut config = Config {
+            panel_height: 40,
+            ..Config::default()
+        };
+        config
+            .monitor_panel_heights
+            .insert("HDMI-A-1".to_string(), 0);
+
+        let main = monitor("DP-1", 0, 0, 2560, 1440);
+        let side = monitor("HDMI-A-1", 2560, 0, 1920, 1080);
+
+        assert_eq!(main.usable_rect(&config), (0, 0, 2560, 1400));
+        assert_eq!(side.usable_rect(&config), (2560, 0, 1920, 1080));
+    }
+
+    #[test]
+    fn test_usable_rect_margin_on_all_edges() {
+        let config = Config {
+            panel_height: 30,
+            margin: 10,
+            ..Config::default()
+        };
+        let mon = monitor("DP-1", 0, 0, 1920, 1080);
+
+        assert_eq!(mon.usable_rect(&config), (10, 10, 1900, 1030));
+    }
+
+    #[test]
+    fn test_usable_rect_struts_and_panel_do_not_double_count() {
+        let config = Config {
+            panel_height: 30,
+            ..Config::default()
+        };
+        let mut mon = monitor("DP-1", 0, 0, 1920, 1080);
+        mon.struts = Struts {
+            top: 0,
+            bottom: 44,
+            left: 60,
+            right: 0,
+        };
+
+        // Bottom reserves max(44, 30); left dock shifts the origin
+        assert_eq!(mon.usable_rect(&config), (60, 0, 1860, 1036));
+    }
+
+    #[test]
+    fn test_work_area_replaces_panel_height() {
+        let config = Config {
+            panel_height: 30,
+            ..Config::default()
+        };
+        // A 32px bar at the top of the right-hand monitor only
+        let mut main = monitor("DP-1", 0, 0, 2560, 1440);
+        let mut side = monitor("HDMI-A-1", 2560, 0, 1920, 1080);
+        side.reserve(work_area_struts(&side, (2560, 32, 1920, 1048)));
+        main.reserve(work_area_struts(&main, (0, 0, 2560, 1440)));
+
+        assert_eq!(side.usable_rect(&config), (2560, 32, 1920, 1048));
+        // Nothing reserved here, so panel_height still applies
+        assert_eq!(main.usable_rect(&config), (0, 0, 2560, 1410));
+        assert_eq!(
+            work_area_struts(&main, (2560, 32, 1920, 1048)),
+            Struts::default()
+        );
+    }
+
+    fn window(id: u64, monitor: Option<&str>) -> EveWindow {
+        EveWindow {
+            id,
+            title: format!("Pilot{}", id),
+            monitor: monitor.map(|m| m.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_retain_on_monitors_omits_excluded_monitors() {
+        let monitors = vec![
+            monitor("DP-1", 0, 0, 2560, 1440),
+            monitor("HDMI-A-1", 2560, 0, 1920, 1080),
+            monitor("DP-2", 4480, 0, 1920, 1080),
+        ];
+        let mut windows = vec![
+            window(1, Some("DP-1")),
+            window(2, Some("HDMI-A-1")),
+            window(3, Some("DP-2")),
+            window(4, None),
+        ];
+
+        retain_on_monitors(
+            &mut windows,
+            &monitors,
+            &[MonitorRef::Name("DP-1".to_string()), MonitorRef::Index(2)],
+        );
+
+        let ids: Vec<u64> = windows.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn test_warp_target_is_the_anchor_point() {
+        assert_eq!(window_center((2560, 0, 1920, 1080)), (3520, 540));
+        assert_eq!(window_center((-1920, 200, 1001, 801)), (-1420, 600));
+
+        let top = WarpAnchor { x: 0.5, y: 0.1 };
+        assert_eq!(warp_target((2560, 0, 1920, 1080), top), (3520, 108));
+        assert_eq!(
+            warp_target((2560, 0, 1920, 1080), WarpAnchor::default()),
+            (3520, 540)
+        );
+        // The far edge is still inside the window
+        let corner = WarpAnchor { x: 1.0, y: 1.0 };
+        assert_eq!(warp_target((0, 0, 1920, 1080), corner), (1919, 1079));
+    }
+
+    #[test]
+    fn test_monitor_order_reorders_scrambled_list() {
+        // Detected right, left, middle
+        let detected = vec![
+            monitor("DP-2", 4480, 0, 1920, 1080),
+            monitor("DP-1", 0, 0, 2560, 1440),
+            monitor("HDMI-A-1", 2560, 0, 1920, 1080),
+        ];
+        let order = [MonitorRef::Name("DP-1".to_string()), MonitorRef::Index(2)];
+
+        let names: Vec<String> = order_monitors(detected.clone(), &order)
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+        assert_eq!(names, vec!["DP-1", "HDMI-A-1", "DP-2"]);
+
+        let unchanged: Vec<String> = order_monitors(detected, &[])
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+        assert_eq!(unchanged, vec!["DP-2", "DP-1", "HDMI-A-1"]);
+    }
+
+    #[test]
+    fn test_monitor_at_with_negative_origin() {
+        let monitors = vec![
+            monitor("DP-1", 0, 0, 2560, 1440),
+            monitor("HDMI-A-1", -1920, 200, 1920, 1080),
+        ];
+
+        assert_eq!(monitor_at(&monitors, -960, 700).unwrap().name, "HDMI-A-1");
+        assert_eq!(monitor_at(&monitors, 0, 0).unwrap().name, "DP-1");
+        assert!(monitor_at(&monitors, -1, 100).is_none());
+    }
+
+    #[test]
+    fn test_bounding_box_spans_negative_monitor() {
+        let monitors = vec![
+            monitor("DP-1", 0, 0, 2560, 1440),
+            monitor("HDMI-A-1", -1920, 200, 1920, 1080),
+        ];
+
+        assert_eq!(bounding_box(&monitors), Some((-1920, 0, 4480, 1440)));
+        assert_eq!(bounding_box(&[]), None);
+    }
+
+    #[test]
+    fn test_usable_rect_negative_origin_with_top_panel() {
+        let config = Config {
+            panel_height: 30,
+            panel_edge: PanelEdge::Top,
+            margin: 5,
+            ..Config::default()
+        };
+        let mon = monitor("HDMI-A-1", -1920, -1080, 1920, 1080);
+
+        assert_eq!(mon.usable_rect(&config), (-1915, -1045, 1910, 1040));
+    }
+
+    #[test]
+    fn test_persisted_key_reconciles_to_new_address() {
+        let before = EveWindow {
+            id: 0x55ade765da10,
+            title: "Main".to_string(),
+            pid: Some(4242),
+            class: Some("steam_app_8500".to_string()),
+            ..Default::default()
+        };
+        let persisted = before.stable_key();
+
+        // Client reconnected: same process and character, new address
+        let live = [
+            EveWindow {
+                id: 0x55ade7000001,
+                title: "Alt".to_string(),
+                pid: Some(5151),
+                class: Some("steam_app_8500".to_string()),
+                ..Default::default()
+            },
+            EveWindow {
+                id: 0x55ade7000002,
+                title: "Main".to_string(),
+                pid: Some(4242),
+                class: Some("steam_app_8500".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let matched: Vec<u64> = live
+            .iter()
+            .filter(|w| w.stable_key() == persisted)
+            .map(|w| w.id)
+            .collect();
+        assert_eq!(matched, vec![0x55ade7000002]);
+    }
+
+    #[test]
+    fn test_stable_key_falls_back_to_id() {
+        let window = EveWindow {
+            id: 7,
+            title: "Main".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(window.stable_key(), "id:7");
+    }
+
+    #[test]
+    fn test_missing_window_replies() {
+        assert!(is_missing_window_reply("Error: No matching node."));
+        assert!(is_missing_window_reply("No such window found"));
+        assert!(!is_missing_window_reply("ok"));
+    }
+
+    #[test]
+    fn test_detected_panel_drives_usable_rect() {
+        let config = Config {
+            panel_height: 50,
+            panel_edge: PanelEdge::Bottom,
+            ..Config::default()
+        };
+        let mut monitors = vec![
+            monitor("DP-1", 0, 0, 2560, 1440),
+            monitor("HDMI-A-1", 2560, 0, 1920, 1080),
+        ];
+        let panels = [
+            // waybar along the top of DP-1 only
+            EveWindow {
+                class: Some("Waybar".to_string()),
+                geometry: Some((0, 0, 2560, 34)),
+                ..Default::default()
+            },
+            // Not a panel class, however panel-shaped
+            EveWindow {
+                class: Some("firefox".to_string()),
+                geometry: Some((2560, 1000, 1920, 80)),
+                ..Default::default()
+            },
+        ];
+
+        apply_detected_panels(&mut monitors, &panels, &config.panel_classes);
+
+        // The real panel replaces the configured 50px at the bottom
+        assert_eq!(monitors[0].usable_rect(&config), (0, 34, 2560, 1406));
+        // No panel found on HDMI-A-1, so panel_height still applies
+        assert_eq!(monitors[1].usable_rect(&config), (2560, 0, 1920, 1030));
+    }
+
+    #[test]
+    fn test_panel_struts_pick_the_docked_edge() {
+        let mon = monitor("DP-1", -1920, 0, 1920, 1080);
+
+        let bottom = panel_struts(&mon, (-1920, 1040, 1920, 40));
+        assert_eq!(bottom.bottom, 40);
+        let left = panel_struts(&mon, (-1920, 0, 48, 1080));
+        assert_eq!(left.left, 48);
+        // On another monitor entirely
+        assert_eq!(panel_struts(&mon, (0, 0, 1920, 40)), Struts::default());
+    }
+
+    #[test]
+    fn test_usable_rect_saturates_on_tiny_monitor() {
+        let config = Config {
+            panel_height: 2000,
+            margin: 50,
+            ..Config::default()
+        };
+        let mon = monitor("DP-1", 0, 0, 80, 1080);
+
+        let (_, _, width, height) = mon.usable_rect(&config);
+        assert_eq!((width, height), (0, 0));
+    }
+
+    #[test]
+    fn test_partially_failing_stack_reports_each_window() {
+        let windows = vec![window(1, None), window(2, None), window(3, None)];
+        let wm = MockWindowManager::new(windows.clone()).with_failing(2);
+
+        let report = wm.stack_windows(&windows, &Config::default()).unwrap();
+
+        // The failure didn't stop the window after it being placed
+        assert_eq!(wm.calls(), ["place 1", "place 2", "place 3"]);
+        assert_eq!(report.succeeded, vec!["Pilot1", "Pilot3"]);
+        assert_eq!(
+            report.failed,
+            vec![("Pilot2".to_string(), "place 2 failed".to_string())]
+        );
+        assert!(report.skipped.is_empty());
+        assert_eq!(
+            report.summary("Stacked"),
+            "Stacked 2 of 3 windows (1 failed)"
+        );
+    }
+
+    #[test]
+    fn test_focus_workarounds_run_in_configured_order_before_activation() {
+        let wm = MockWindowManager::new(vec![window(7, None)]).with_focus_workarounds(vec![
+            FocusWorkaround::WarpPointer,
+            FocusWorkaround::Unminimize,
+            FocusWorkaround::RaiseFirst,
+        ]);
+
+        wm.activate_window(7).unwrap();
+        assert_eq!(wm.calls(), ["warp 7", "restore 7", "raise 7", "activate 7"]);
+    }
+
+    #[test]
+    fn test_no_focus_workarounds_only_activates() {
+        let wm = MockWindowManager::new(vec![window(7, None)]);
+
+        wm.activate_window(7).unwrap();
+        assert_eq!(wm.calls(), ["activate 7"]);
+    }
+
+    #[test]
+    fn test_failing_focus_workaround_does_not_block_activation() {
+        let wm = MockWindowManager::new(vec![window(7, None)])
+            .with_focus_workarounds(vec![
+                FocusWorkaround::Unminimize,
+                FocusWorkaround::RaiseFirst,
+            ])
+            .with_failing_call("restore", 7);
+
+        wm.activate_window(7).unwrap();
+        assert_eq!(wm.calls(), ["restore 7", "raise 7", "activate 7"]);
+    }
+
+    #[test]
+    fn test_focus_workarounds_parse_from_config() {
+        let config: Config = toml::from_str(
+            r#"
+            display_width = 1920
+            display_height = 1080
+            panel_height = 0
+            eve_width = 1036
+            eve_height = 1080
+            overlay_x = 10.0
+            overlay_y = 10.0
+            focus_workarounds = ["unminimize", "raise_first", "warp_pointer"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.focus_workarounds,
+            [
+                FocusWorkaround::Unminimize,
+                FocusWorkaround::RaiseFirst,
+                FocusWorkaround::WarpPointer,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_backend_detected_from_session_variables() {
+        let detect = |vars: &[(&str, &str)]| {
+            let env = |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            };
+            backend_in(&env)
+        };
+
+        assert_eq!(detect(&[("DISPLAY", ":0")]), Backend::X11);
+        assert_eq!(detect(&[]), Backend::X11);
+        assert_eq!(
+            detect(&[
+                ("XDG_SESSION_TYPE", "wayland"),
+                ("XDG_CURRENT_DESKTOP", "KDE")
+            ]),
+            Backend::Kde
+        );
+        assert_eq!(
+            detect(&[
+                ("WAYLAND_DISPLAY", "wayland-1"),
+                ("KDE_FULL_SESSION", "true")
+            ]),
+            Backend::Kde
+        );
+        // Sway's socket is enough even outside a login session
+        assert_eq!(detect(&[("SWAYSOCK", "/run/sway.sock")]), Backend::Sway);
+        assert_eq!(
+            detect(&[("HYPRLAND_INSTANCE_SIGNATURE", "abc"), ("DISPLAY", ":1")]),
+            Backend::Hyprland
+        );
+        assert_eq!(detect(&[("WAYLAND_DISPLAY", "wayland-0")]), Backend::Wlr);
+        // Plasma's X11 session sets KDE_FULL_SESSION too
+        assert_eq!(
+            detect(&[("XDG_SESSION_TYPE", "x11"), ("KDE_FULL_SESSION", "true")]),
+            Backend::X11
+        );
+
+        assert_eq!("sway".parse::<Backend>().unwrap(), Backend::Sway);
+        assert!("beos".parse::<Backend>().is_err());
+    }
+}
@@ -0,0 +1,312 @@
+//! Win32 backend: EnumWindows to find the clients, SetForegroundWindow to
+//! switch and SetWindowPos to place them, through the `windows` crate.
+
+use crate::config::{Config, MonitorRef, WindowMatch};
+use crate::layout::Placement;
+use crate::window_manager::{
+    monitor_at, order_monitors, retain_on_monitors, window_center, EveWindow, Monitor, Struts,
+    WindowManager, WindowNotFound,
+};
+use anyhow::Result;
+use windows::core::BOOL;
+use windows::Win32::Foundation::{HWND, LPARAM, RECT, WPARAM};
+use windows::Win32::Graphics::Gdi::{
+    EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFO, MONITORINFOEXW,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    EnumWindows, GetClassNameW, GetForegroundWindow, GetWindowRect, GetWindowTextW,
+    GetWindowThreadProcessId, IsIconic, IsWindow, IsWindowVisible, PostMessageW,
+    SetForegroundWindow, SetWindowPos, ShowWindow, HWND_BOTTOM, HWND_TOP, SET_WINDOW_POS_FLAGS,
+    SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE, SWP_NOZORDER, SW_MINIMIZE, SW_RESTORE, WM_CLOSE,
+};
+
+/// Window ids are the HWND's value
+fn hwnd(window_id: u64) -> HWND {
+    HWND(window_id as isize as *mut _)
+}
+
+unsafe extern "system" fn collect_window(hwnd: HWND, found: LPARAM) -> BOOL {
+    // SAFETY: `found` is the Vec passed to EnumWindows by top_level_windows,
+    // which outlives the call
+    let found = unsafe { &mut *(found.0 as *mut Vec<HWND>) };
+    found.push(hwnd);
+    true.into()
+}
+
+unsafe extern "system" fn collect_monitor(
+    monitor: HMONITOR,
+    _: HDC,
+    _: *mut RECT,
+    found: LPARAM,
+) -> BOOL {
+    // SAFETY: as in collect_window
+    let found = unsafe { &mut *(found.0 as *mut Vec<HMONITOR>) };
+    found.push(monitor);
+    true.into()
+}
+
+fn top_level_windows() -> Vec<HWND> {
+    let mut found: Vec<HWND> = Vec::new();
+    // SAFETY: the callback only pushes onto `found` during the call
+    let _ = unsafe { EnumWindows(Some(collect_window), LPARAM(&mut found as *mut _ as isize)) };
+    found
+}
+
+/// Read a UTF-16 string through GetWindowTextW or GetClassNameW
+fn read_wide(hwnd: HWND, get: unsafe fn(HWND, &mut [u16]) -> i32) -> String {
+    let mut buffer = [0u16; 512];
+    // SAFETY: the call writes at most the buffer's length
+    let len = unsafe { get(hwnd, &mut buffer) };
+    String::from_utf16_lossy(&buffer[..len.max(0) as usize])
+}
+
+fn window_rect(hwnd: HWND) -> Option<(i32, i32, u32, u32)> {
+    let mut rect = RECT::default();
+    // SAFETY: `rect` is a valid RECT to write to
+    unsafe { GetWindowRect(hwnd, &mut rect) }.ok()?;
+    Some((
+        rect.left,
+        rect.top,
+        (rect.right - rect.left).max(0) as u32,
+        (rect.bottom - rect.top).max(0) as u32,
+    ))
+}
+
+fn check_window(hwnd: HWND) -> Result<()> {
+    // SAFETY: IsWindow accepts any handle
+    if !unsafe { IsWindow(Some(hwnd)) }.as_bool() {
+        return Err(WindowNotFound(hwnd.0 as u64).into());
+    }
+    Ok(())
+}
+
+fn set_pos(
+    hwnd: HWND,
+    after: HWND,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    flags: SET_WINDOW_POS_FLAGS,
+) -> Result<()> {
+    check_window(hwnd)?;
+    // SAFETY: plain call on a window handle
+    unsafe { SetWindowPos(hwnd, Some(after), x, y, width, height, flags) }.map_err(|e| {
+        anyhow::anyhow!(
+            "SetWindowPos failed for window {:#x}: {}",
+            hwnd.0 as usize,
+            e
+        )
+    })
+}
+
+pub struct WindowsManager {
+    discover_monitors: Option<Vec<MonitorRef>>,
+    window_match: WindowMatch,
+    monitor_order: Vec<MonitorRef>,
+}
+
+impl WindowsManager {
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            discover_monitors: config.discover_monitors.clone(),
+            window_match: config.window_match.clone(),
+            monitor_order: config.monitor_order.clone(),
+        })
+    }
+
+    fn get_monitors_internal(&self) -> Vec<Monitor> {
+        let mut handles: Vec<HMONITOR> = Vec::new();
+        // SAFETY: the callback only pushes onto `handles` during the call
+        let _ = unsafe {
+            EnumDisplayMonitors(
+                None,
+                None,
+                Some(collect_monitor),
+                LPARAM(&mut handles as *mut _ as isize),
+            )
+        };
+
+        handles
+            .into_iter()
+            .filter_map(|handle| {
+                let mut info = MONITORINFOEXW::default();
+                info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+                // SAFETY: `info` is a MONITORINFOEXW with its size filled in
+                let info_ptr = &mut info as *mut MONITORINFOEXW as *mut MONITORINFO;
+                if !unsafe { GetMonitorInfoW(handle, info_ptr) }.as_bool() {
+                    return None;
+                }
+                let device = info.szDevice;
+                let len = device.iter().position(|&c| c == 0).unwrap_or(device.len());
+                let (m, w) = (info.monitorInfo.rcMonitor, info.monitorInfo.rcWork);
+                // The taskbar is whatever the work area leaves out
+                Some(Monitor {
+                    name: String::from_utf16_lossy(&device[..len]),
+                    x: m.left,
+                    y: m.top,
+                    width: (m.right - m.left) as u32,
+                    height: (m.bottom - m.top) as u32,
+                    struts: Struts {
+                        top: (w.top - m.top) as u32,
+                        bottom: (m.bottom - w.bottom) as u32,
+                        left: (w.left - m.left) as u32,
+                        right: (m.right - w.right) as u32,
+                    },
+                    panel_detected: w.top != m.top
+                        || w.bottom != m.bottom
+                        || w.left != m.left
+                        || w.right != m.right,
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+impl WindowManager for WindowsManager {
+    fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
+        let monitors = self.get_monitors().unwrap_or_default();
+        let mut eve_windows: Vec<EveWindow> = top_level_windows()
+            .into_iter()
+            // SAFETY: plain call on a window handle
+            .filter(|&hwnd| unsafe { IsWindowVisible(hwnd) }.as_bool())
+            .filter_map(|hwnd| {
+                let title = read_wide(hwnd, GetWindowTextW);
+                let class = read_wide(hwnd, GetClassNameW);
+                let character = self.window_match.client_name(&title, Some(&class))?;
+                let geometry = window_rect(hwnd);
+                let mut pid = 0;
+                // SAFETY: `pid` is a valid u32 to write to
+                unsafe { GetWindowThreadProcessId(hwnd, Some(&mut pid)) };
+                Some(EveWindow {
+                    id: hwnd.0 as u64,
+                    title: character.to_string(),
+                    monitor: geometry
+                        .and_then(|g| {
+                            let (x, y) = window_center(g);
+                            monitor_at(&monitors, x, y)
+                        })
+                        .map(|m| m.name.clone()),
+                    geometry,
+                    pid: (pid != 0).then_some(pid),
+                    class: Some(class),
+                })
+            })
+            .collect();
+
+        if let Some(allowed) = &self.discover_monitors {
+            retain_on_monitors(&mut eve_windows, &monitors, allowed);
+        }
+        Ok(eve_windows)
+    }
+
+    fn activate_window(&self, window_id: u64) -> Result<()> {
+        let hwnd = hwnd(window_id);
+        check_window(hwnd)?;
+        // SAFETY: plain calls on a window handle
+        unsafe {
+            if IsIconic(hwnd).as_bool() {
+                let _ = ShowWindow(hwnd, SW_RESTORE);
+            }
+            if !SetForegroundWindow(hwnd).as_bool() {
+                anyhow::bail!(
+                    "Windows refused to focus window {:#x} (focus stealing prevention)",
+                    window_id
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_placement(&self, placement: &Placement) -> Result<()> {
+        let (width, height, size_flag) = match placement.size {
+            Some((w, h)) => (w as i32, h as i32, SET_WINDOW_POS_FLAGS(0)),
+            None => (0, 0, SWP_NOSIZE),
+        };
+        set_pos(
+            hwnd(placement.window_id),
+            HWND_TOP,
+            placement.x,
+            placement.y,
+            width,
+            height,
+            SWP_NOZORDER | SWP_NOACTIVATE | size_flag,
+        )
+    }
+
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        Ok(order_monitors(
+            self.get_monitors_internal(),
+            &self.monitor_order,
+        ))
+    }
+
+    fn get_title(&self, window_id: u64) -> Result<Option<String>> {
+        let hwnd = hwnd(window_id);
+        check_window(hwnd)?;
+        let title = read_wide(hwnd, GetWindowTextW);
+        let class = read_wide(hwnd, GetClassNameW);
+        Ok(self
+            .window_match
+            .client_name(&title, Some(&class))
+            .map(String::from))
+    }
+
+    fn get_active_window(&self) -> Result<u64> {
+        // SAFETY: no arguments
+        match unsafe { GetForegroundWindow() } {
+            hwnd if hwnd.is_invalid() => anyhow::bail!("No active window found"),
+            hwnd => Ok(hwnd.0 as u64),
+        }
+    }
+
+    fn find_window_by_title(&self, title: &str) -> Result<Option<u64>> {
+        Ok(self
+            .get_eve_windows()?
+            .into_iter()
+            .find(|w| w.title == title)
+            .map(|w| w.id))
+    }
+
+    fn minimize_window(&self, window_id: u64) -> Result<()> {
+        let hwnd = hwnd(window_id);
+        check_window(hwnd)?;
+        // SAFETY: plain call on a window handle
+        let _ = unsafe { ShowWindow(hwnd, SW_MINIMIZE) };
+        Ok(())
+    }
+
+    fn restore_window(&self, window_id: u64) -> Result<()> {
+        let hwnd = hwnd(window_id);
+        check_window(hwnd)?;
+        // SAFETY: plain call on a window handle
+        let _ = unsafe { ShowWindow(hwnd, SW_RESTORE) };
+        Ok(())
+    }
+
+    fn close_window(&self, window_id: u64) -> Result<()> {
+        let hwnd = hwnd(window_id);
+        check_window(hwnd)?;
+        // SAFETY: plain call on a window handle; WM_CLOSE takes no arguments
+        unsafe { PostMessageW(Some(hwnd), WM_CLOSE, WPARAM(0), LPARAM(0)) }
+            .map_err(|e| anyhow::anyhow!("Failed to close window {:#x}: {}", window_id, e))
+    }
+
+    fn is_minimized(&self, window_id: u64) -> Result<bool> {
+        let hwnd = hwnd(window_id);
+        check_window(hwnd)?;
+        // SAFETY: plain call on a window handle
+        Ok(unsafe { IsIconic(hwnd) }.as_bool())
+    }
+
+    fn raise_window(&self, window_id: u64) -> Result<()> {
+        let flags = SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE;
+        set_pos(hwnd(window_id), HWND_TOP, 0, 0, 0, 0, flags)
+    }
+
+    fn lower_window(&self, window_id: u64) -> Result<()> {
+        let flags = SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE;
+        set_pos(hwnd(window_id), HWND_BOTTOM, 0, 0, 0, 0, flags)
+    }
+}
@@ -0,0 +1,418 @@
+use crate::config::{Config, MonitorRef, WindowMatch};
+use crate::layout::Placement;
+use crate::window_manager::{
+    order_monitors, retain_on_monitors, EveWindow, Monitor, WindowManager, WindowNotFound,
+};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use wayland_client::backend::ObjectId;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_output, wl_registry, wl_seat};
+use wayland_client::{event_created_child, Connection, Dispatch, Proxy, QueueHandle, WEnum};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+/// A toplevel as last announced by the compositor
+#[derive(Debug, Clone)]
+struct Toplevel {
+    handle: ZwlrForeignToplevelHandleV1,
+    title: String,
+    app_id: Option<String>,
+    outputs: Vec<ObjectId>,
+    minimized: bool,
+    activated: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Output {
+    name: Option<String>,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+/// What the event thread keeps up to date for the manager to read
+#[derive(Debug, Default)]
+struct Shared {
+    /// Toplevels that have sent their first done, by protocol id
+    toplevels: BTreeMap<u32, Toplevel>,
+    outputs: HashMap<ObjectId, Output>,
+}
+
+/// Dispatch state: toplevels still waiting for their first done, plus the
+/// published view
+struct State {
+    pending: HashMap<ObjectId, Toplevel>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// (minimized, activated) from a handle's state array of native-endian u32s
+fn parse_states(raw: &[u8]) -> (bool, bool) {
+    let states: Vec<u32> = raw
+        .chunks_exact(4)
+        .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+        .collect();
+    let has = |state: zwlr_foreign_toplevel_handle_v1::State| states.contains(&(state as u32));
+    (
+        has(zwlr_foreign_toplevel_handle_v1::State::Minimized),
+        has(zwlr_foreign_toplevel_handle_v1::State::Activated),
+    )
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_seat::WlSeat,
+        _: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, ()> for State {
+    fn event(
+        state: &mut Self,
+        output: &wl_output::WlOutput,
+        event: wl_output::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let mut shared = state.shared.lock().unwrap();
+        let entry = shared.outputs.entry(output.id()).or_default();
+        match event {
+            wl_output::Event::Name { name } => entry.name = Some(name),
+            wl_output::Event::Geometry { x, y, .. } => {
+                entry.x = x;
+                entry.y = y;
+            }
+            wl_output::Event::Mode {
+                flags: WEnum::Value(flags),
+                width,
+                height,
+                ..
+            } if flags.contains(wl_output::Mode::Current) => {
+                entry.width = width.max(0) as u32;
+                entry.height = height.max(0) as u32;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            state.pending.insert(
+                toplevel.id(),
+                Toplevel {
+                    handle: toplevel,
+                    title: String::new(),
+                    app_id: None,
+                    outputs: Vec::new(),
+                    minimized: false,
+                    activated: false,
+                },
+            );
+        }
+    }
+
+    event_created_child!(State, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use zwlr_foreign_toplevel_handle_v1::Event;
+
+        let protocol_id = handle.id().protocol_id();
+        if let Event::Closed = event {
+            state.pending.remove(&handle.id());
+            state.shared.lock().unwrap().toplevels.remove(&protocol_id);
+            handle.destroy();
+            return;
+        }
+
+        // Changes accumulate until done, then are published together
+        let mut shared = state.shared.lock().unwrap();
+        let toplevel = match state.pending.get_mut(&handle.id()) {
+            Some(toplevel) => toplevel,
+            None => match shared.toplevels.get(&protocol_id) {
+                Some(published) => state
+                    .pending
+                    .entry(handle.id())
+                    .or_insert_with(|| published.clone()),
+                None => return,
+            },
+        };
+        match event {
+            Event::Title { title } => toplevel.title = title,
+            Event::AppId { app_id } => toplevel.app_id = Some(app_id),
+            Event::OutputEnter { output } => toplevel.outputs.push(output.id()),
+            Event::OutputLeave { output } => toplevel.outputs.retain(|o| *o != output.id()),
+            Event::State { state: raw } => {
+                (toplevel.minimized, toplevel.activated) = parse_states(&raw);
+            }
+            Event::Done => {
+                if let Some(done) = state.pending.remove(&handle.id()) {
+                    shared.toplevels.insert(protocol_id, done);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// ============================================================================
+// Generic wlroots Backend (via wlr-foreign-toplevel-management)
+// ============================================================================
+
+/// Works on any compositor with wlr-foreign-toplevel-management (river,
+/// Wayfire, labwc, niri...). The protocol can list, focus and minimize
+/// windows but not move them, so stacking has to be done by the compositor.
+pub struct WlrManager {
+    conn: Connection,
+    seat: wl_seat::WlSeat,
+    shared: Arc<Mutex<Shared>>,
+    discover_monitors: Option<Vec<MonitorRef>>,
+    window_match: WindowMatch,
+    monitor_order: Vec<MonitorRef>,
+}
+
+impl WlrManager {
+    pub fn new(config: &Config) -> Result<Self> {
+        let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
+        let (globals, mut queue) =
+            registry_queue_init::<State>(&conn).context("Failed to read Wayland globals")?;
+        let qh = queue.handle();
+
+        globals
+            .bind::<ZwlrForeignToplevelManagerV1, _, _>(&qh, 1..=3, ())
+            .context("Compositor doesn't support wlr-foreign-toplevel-management")?;
+        let seat = globals
+            .bind::<wl_seat::WlSeat, _, _>(&qh, 1..=1, ())
+            .context("Compositor has no seat")?;
+        for global in globals.contents().clone_list() {
+            if global.interface == wl_output::WlOutput::interface().name {
+                let version = global.version.min(4);
+                globals
+                    .registry()
+                    .bind::<wl_output::WlOutput, _, _>(global.name, version, &qh, ());
+            }
+        }
+
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let mut state = State {
+            pending: HashMap::new(),
+            shared: Arc::clone(&shared),
+        };
+        // The first roundtrip announces toplevels and outputs, the second
+        // delivers their details
+        queue.roundtrip(&mut state)?;
+        queue.roundtrip(&mut state)?;
+
+        std::thread::spawn(move || while queue.blocking_dispatch(&mut state).is_ok() {});
+
+        Ok(Self {
+            conn,
+            seat,
+            shared,
+            discover_monitors: config.discover_monitors.clone(),
+            window_match: config.window_match.clone(),
+            monitor_order: config.monitor_order.clone(),
+        })
+    }
+
+    fn handle(&self, window_id: u64) -> Result<Toplevel> {
+        self.shared
+            .lock()
+            .unwrap()
+            .toplevels
+            .get(&(window_id as u32))
+            .cloned()
+            .ok_or_else(|| WindowNotFound(window_id).into())
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.conn
+            .flush()
+            .context("Failed to talk to the compositor")
+    }
+
+    fn windows(&self) -> Vec<EveWindow> {
+        let shared = self.shared.lock().unwrap();
+        shared
+            .toplevels
+            .iter()
+            .map(|(id, toplevel)| EveWindow {
+                id: *id as u64,
+                title: toplevel.title.clone(),
+                monitor: toplevel
+                    .outputs
+                    .first()
+                    .and_then(|o| shared.outputs.get(o))
+                    .and_then(|o| o.name.clone()),
+                class: toplevel.app_id.clone(),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+impl WindowManager for WlrManager {
+    fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
+        let mut eve_windows: Vec<EveWindow> = self
+            .windows()
+            .into_iter()
+            .filter_map(|window| {
+                let character = self
+                    .window_match
+                    .client_name(&window.title, window.class.as_deref())?
+                    .to_string();
+                Some(EveWindow {
+                    title: character,
+                    ..window
+                })
+            })
+            .collect();
+
+        if let Some(allowed) = &self.discover_monitors {
+            let monitors = self.get_monitors().unwrap_or_default();
+            retain_on_monitors(&mut eve_windows, &monitors, allowed);
+        }
+
+        Ok(eve_windows)
+    }
+
+    fn get_process_windows(&self) -> Result<Vec<EveWindow>> {
+        Ok(self.windows())
+    }
+
+    fn activate_window(&self, window_id: u64) -> Result<()> {
+        let toplevel = self.handle(window_id)?;
+        if toplevel.minimized {
+            toplevel.handle.unset_minimized();
+        }
+        toplevel.handle.activate(&self.seat);
+        self.flush()
+    }
+
+    fn apply_placement(&self, placement: &Placement) -> Result<()> {
+        anyhow::bail!(
+            "Can't move {}: wlr-foreign-toplevel-management has no way to position \
+             windows, use compositor rules instead",
+            placement.title
+        )
+    }
+
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        let monitors: Vec<Monitor> = self
+            .shared
+            .lock()
+            .unwrap()
+            .outputs
+            .values()
+            .filter_map(|o| {
+                Some(Monitor {
+                    name: o.name.clone()?,
+                    x: o.x,
+                    y: o.y,
+                    width: o.width,
+                    height: o.height,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        Ok(order_monitors(monitors, &self.monitor_order))
+    }
+
+    fn get_title(&self, window_id: u64) -> Result<Option<String>> {
+        Ok(self.handle(window_id).ok().map(|t| t.title))
+    }
+
+    fn get_active_window(&self) -> Result<u64> {
+        self.shared
+            .lock()
+            .unwrap()
+            .toplevels
+            .iter()
+            .find(|(_, t)| t.activated)
+            .map(|(id, _)| *id as u64)
+            .context("No active window found")
+    }
+
+    fn find_window_by_title(&self, title: &str) -> Result<Option<u64>> {
+        Ok(self
+            .windows()
+            .into_iter()
+            .find(|w| w.title == title)
+            .map(|w| w.id))
+    }
+
+    fn is_minimized(&self, window_id: u64) -> Result<bool> {
+        Ok(self.handle(window_id)?.minimized)
+    }
+
+    fn minimize_window(&self, window_id: u64) -> Result<()> {
+        self.handle(window_id)?.handle.set_minimized();
+        self.flush()
+    }
+
+    fn restore_window(&self, window_id: u64) -> Result<()> {
+        self.handle(window_id)?.handle.unset_minimized();
+        self.flush()
+    }
+
+    fn close_window(&self, window_id: u64) -> Result<()> {
+        self.handle(window_id)?.handle.close();
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toplevel_state_array() {
+        let raw: Vec<u8> = [2u32, 1].iter().flat_map(|s| s.to_ne_bytes()).collect();
+        assert_eq!(parse_states(&raw), (true, true));
+
+        let maximized: Vec<u8> = 0u32.to_ne_bytes().to_vec();
+        assert_eq!(parse_states(&maximized), (false, false));
+        assert_eq!(parse_states(&[]), (false, false));
+    }
+}
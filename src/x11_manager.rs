@@ -1,19 +1,98 @@
-use crate::config::Config;
-use crate::window_manager::{EveWindow, WindowManager};
+use crate::config::{Config, FocusWorkaround, MonitorRef, WarpAnchor, WindowMatch, X11MoveMethod};
+use crate::events::WindowEvent;
+use crate::layout::Placement;
+use crate::monitor_cache::MonitorCache;
+use crate::window_manager::{
+    apply_focus_workarounds, monitor_at, order_monitors, retain_on_monitors, warp_target,
+    work_area_struts, EveWindow, WindowManager, WindowNotFound,
+};
 use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::Arc;
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
 
 pub struct X11Manager {
     conn: Arc<RustConnection>,
     screen_num: usize,
     net_active_window_atom: Atom,
+    discover_monitors: Option<Vec<MonitorRef>>,
+    window_match: WindowMatch,
+    monitor_order: Vec<MonitorRef>,
+    monitor_cache: MonitorCache,
+    prefer_visible_name: bool,
+    match_property: Option<(String, String)>,
+    focus_workarounds: Vec<FocusWorkaround>,
+    warp_on_activate: bool,
+    warp_anchor: WarpAnchor,
+    move_method: X11MoveMethod,
+}
+
+/// A property's value as text: a string as it is (the first, for a list),
+/// numbers in decimal and comma separated
+fn decode_property(format: u8, value: &[u8]) -> Option<String> {
+    let numbers = |values: Vec<String>| Some(values.join(","));
+    match format {
+        8 => {
+            let first = value.split(|&b| b == 0).next()?;
+            Some(String::from_utf8_lossy(first).into_owned())
+        }
+        16 => numbers(
+            value
+                .chunks_exact(2)
+                .map(|c| u16::from_ne_bytes([c[0], c[1]]).to_string())
+                .collect(),
+        ),
+        32 => numbers(
+            value
+                .chunks_exact(4)
+                .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]).to_string())
+                .collect(),
+        ),
+        // Property not set on this window
+        _ => None,
+    }
+}
+
+/// X11 properties a window title can be read from (named after the atoms)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
+enum TitleSource {
+    NetWmVisibleName,
+    NetWmName,
+    WmName,
+}
+
+/// Order to try title properties in. _NET_WM_VISIBLE_NAME is what the WM
+/// actually shows (it may add a suffix to disambiguate duplicates), so it's
+/// only consulted when prefer_visible_name is set.
+fn title_sources(prefer_visible_name: bool) -> &'static [TitleSource] {
+    if prefer_visible_name {
+        &[
+            TitleSource::NetWmVisibleName,
+            TitleSource::NetWmName,
+            TitleSource::WmName,
+        ]
+    } else {
+        &[TitleSource::NetWmName, TitleSource::WmName]
+    }
+}
+
+/// Return the first non-empty title, reading sources lazily in order
+fn pick_title<F>(sources: &[TitleSource], mut read: F) -> Option<String>
+where
+    F: FnMut(TitleSource) -> Option<String>,
+{
+    sources
+        .iter()
+        .find_map(|&source| read(source).filter(|title| !title.is_empty()))
 }
 
 impl X11Manager {
-    pub fn new() -> Result<Self> {
+    pub fn new(config: &Config) -> Result<Self> {
         let (conn, screen_num) =
             RustConnection::connect(None).context("Failed to connect to X11 server")?;
 
@@ -29,10 +108,20 @@ impl X11Manager {
             conn,
             screen_num,
             net_active_window_atom,
+            discover_monitors: config.discover_monitors.clone(),
+            window_match: config.window_match.clone(),
+            monitor_order: config.monitor_order.clone(),
+            monitor_cache: MonitorCache::default(),
+            prefer_visible_name: config.prefer_visible_name,
+            match_property: config.match_property.clone(),
+            focus_workarounds: config.focus_workarounds.clone(),
+            warp_on_activate: config.warp_on_activate,
+            warp_anchor: config.warp_anchor,
+            move_method: config.x11_move_method,
         })
     }
 
-    pub fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
+    fn client_list(&self) -> Result<Vec<u32>> {
         let screen = &self.conn.setup().roots[self.screen_num];
         let root = screen.root;
 
@@ -53,24 +142,88 @@ impl X11Manager {
             .value32()
             .ok_or_else(|| anyhow::anyhow!("Failed to get window list"))?
             .collect();
+        Ok(windows)
+    }
+
+    fn get_window_pid(&self, window: u32) -> Option<u32> {
+        let pid_atom = self
+            .conn
+            .intern_atom(false, b"_NET_WM_PID")
+            .ok()?
+            .reply()
+            .ok()?
+            .atom;
+        self.conn
+            .get_property(false, window, pid_atom, AtomEnum::CARDINAL, 0, 1)
+            .ok()?
+            .reply()
+            .ok()?
+            .value32()?
+            .next()
+    }
+
+    /// The class half of WM_CLASS ("instance\0class\0")
+    fn get_window_class(&self, window: u32) -> Option<String> {
+        let reply = self
+            .conn
+            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)
+            .ok()?
+            .reply()
+            .ok()?;
+        let class = reply.value.split(|&b| b == 0).nth(1)?;
+        Some(String::from_utf8_lossy(class).into_owned())
+    }
 
+    /// `property`'s value on `window` as text, for match_property
+    fn get_property_text(&self, window: u32, property: Atom) -> Option<String> {
+        let reply = self
+            .conn
+            .get_property(false, window, property, AtomEnum::ANY, 0, 1024)
+            .ok()?
+            .reply()
+            .ok()?;
+        decode_property(reply.format, &reply.value)
+    }
+
+    pub fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
+        let windows = self.client_list()?;
         let mut eve_windows = Vec::new();
 
+        let marker_atom = match &self.match_property {
+            Some((name, _)) => Some(self.conn.intern_atom(false, name.as_bytes())?.reply()?.atom),
+            None => None,
+        };
+
         for &window in &windows {
             if let Ok(title) = self.get_window_title(window) {
-                // Filter for EVE windows (steam_app_8500) and exclude launcher
-                if title.starts_with("EVE - ") && !title.contains("Launcher") {
+                let marker = marker_atom.and_then(|atom| self.get_property_text(window, atom));
+                let class = self.get_window_class(window);
+                if let Some(character) = self.window_match.discovered_name(
+                    &title,
+                    class.as_deref(),
+                    marker.as_deref(),
+                    self.match_property.as_ref(),
+                ) {
                     // Determine which monitor this window is on based on its geometry
-                    let monitor = self.get_window_monitor(window);
+                    let geometry = self.get_window_geometry(window);
+                    let monitor = self.get_window_monitor(geometry);
                     eve_windows.push(EveWindow {
                         id: window as u64,
-                        title: title.trim_start_matches("EVE - ").to_string(),
+                        title: character.to_string(),
                         monitor,
+                        geometry,
+                        pid: self.get_window_pid(window),
+                        class,
                     });
                 }
             }
         }
 
+        if let Some(allowed) = &self.discover_monitors {
+            let monitors = self.get_monitors().unwrap_or_default();
+            retain_on_monitors(&mut eve_windows, &monitors, allowed);
+        }
+
         Ok(eve_windows)
     }
 
@@ -102,6 +255,11 @@ impl X11Manager {
         let root = screen.root;
         let window_id_u32 = window_id as u32;
 
+        // BadWindow here means the client has closed since we listed it
+        if self.conn.get_geometry(window_id_u32)?.reply().is_err() {
+            return Err(WindowNotFound(window_id).into());
+        }
+
         let current_active = self.get_active_window().unwrap_or(0) as u32;
 
         let event = ClientMessageEvent {
@@ -128,35 +286,46 @@ impl X11Manager {
     }
 
     fn get_window_title(&self, window: u32) -> Result<String> {
-        // Try _NET_WM_NAME first (UTF-8)
-        let net_wm_name = self.conn.intern_atom(false, b"_NET_WM_NAME")?.reply()?.atom;
-
         let utf8_string = self.conn.intern_atom(false, b"UTF8_STRING")?.reply()?.atom;
 
-        if let Ok(reply) = self
-            .conn
-            .get_property(false, window, net_wm_name, utf8_string, 0, 1024)?
-            .reply()
-        {
-            if !reply.value.is_empty() {
-                if let Ok(title) = String::from_utf8(reply.value.clone()) {
-                    return Ok(title);
-                }
-            }
-        }
+        let title = pick_title(title_sources(self.prefer_visible_name), |source| {
+            let (property, kind) = match source {
+                TitleSource::NetWmVisibleName => (
+                    self.conn
+                        .intern_atom(false, b"_NET_WM_VISIBLE_NAME")
+                        .ok()?
+                        .reply()
+                        .ok()?
+                        .atom,
+                    utf8_string,
+                ),
+                TitleSource::NetWmName => (
+                    self.conn
+                        .intern_atom(false, b"_NET_WM_NAME")
+                        .ok()?
+                        .reply()
+                        .ok()?
+                        .atom,
+                    utf8_string,
+                ),
+                TitleSource::WmName => (AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()),
+            };
 
-        // Fall back to WM_NAME
-        if let Ok(reply) = self
-            .conn
-            .get_property(false, window, AtomEnum::WM_NAME, AtomEnum::STRING, 0, 1024)?
-            .reply()
-        {
-            if !reply.value.is_empty() {
-                return Ok(String::from_utf8_lossy(&reply.value).to_string());
+            let reply = self
+                .conn
+                .get_property(false, window, property, kind, 0, 1024)
+                .ok()?
+                .reply()
+                .ok()?;
+
+            match source {
+                // WM_NAME is Latin-1 and may not be valid UTF-8
+                TitleSource::WmName => Some(String::from_utf8_lossy(&reply.value).to_string()),
+                _ => String::from_utf8(reply.value).ok(),
             }
-        }
+        });
 
-        Ok(String::new())
+        Ok(title.unwrap_or_default())
     }
 
     pub fn find_window_by_title(&self, title: &str) -> Result<Option<u64>> {
@@ -197,6 +366,52 @@ impl X11Manager {
         Ok(())
     }
 
+    /// Ask the window manager to move and resize a window, the way EWMH
+    /// pagers do. The placement is the outer frame, so the client gets what
+    /// is left after the frame the WM reports in _NET_FRAME_EXTENTS.
+    fn ewmh_move_resize(&self, placement: &Placement) -> Result<()> {
+        let root = self.conn.setup().roots[self.screen_num].root;
+        let window = placement.window_id as u32;
+        let net_moveresize_window = self
+            .conn
+            .intern_atom(false, b"_NET_MOVERESIZE_WINDOW")?
+            .reply()?
+            .atom;
+
+        let event = ClientMessageEvent {
+            response_type: CLIENT_MESSAGE_EVENT,
+            format: 32,
+            sequence: 0,
+            window,
+            type_: net_moveresize_window,
+            data: ClientMessageData::from(moveresize_data(placement, self.frame_extents(window)?)),
+        };
+        self.conn.send_event(
+            false,
+            root,
+            EventMask::SUBSTRUCTURE_NOTIFY | EventMask::SUBSTRUCTURE_REDIRECT,
+            event,
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// The frame around a window as [left, right, top, bottom], or none if
+    /// the WM doesn't say
+    fn frame_extents(&self, window: u32) -> Result<[u32; 4]> {
+        let net_frame_extents = self
+            .conn
+            .intern_atom(false, b"_NET_FRAME_EXTENTS")?
+            .reply()?
+            .atom;
+        let reply = self
+            .conn
+            .get_property(false, window, net_frame_extents, AtomEnum::CARDINAL, 0, 4)?
+            .reply()?;
+        let extents: Vec<u32> = reply.value32().into_iter().flatten().collect();
+        Ok(extents.try_into().unwrap_or_default())
+    }
+
     pub fn minimize_window(&self, window_id: u64) -> Result<()> {
         // Use WM_CHANGE_STATE with IconicState to minimize
         let wm_change_state = self
@@ -230,6 +445,101 @@ impl X11Manager {
         Ok(())
     }
 
+    /// WM_STATE is IconicState (3) while a window is minimized
+    pub fn is_minimized(&self, window_id: u64) -> Result<bool> {
+        let wm_state = self.conn.intern_atom(false, b"WM_STATE")?.reply()?.atom;
+        let reply = self
+            .conn
+            .get_property(false, window_id as u32, wm_state, wm_state, 0, 1)?
+            .reply()?;
+        Ok(reply.value32().and_then(|mut v| v.next()) == Some(3))
+    }
+
+    /// Add or remove _NET_WM_STATE_ABOVE
+    pub fn set_pinned(&self, window_id: u64, pinned: bool) -> Result<()> {
+        self.change_wm_state(window_id, b"_NET_WM_STATE_ABOVE", pinned)
+    }
+
+    /// Send WM_DELETE_WINDOW straight to the client, as the WM does when
+    /// its close button is clicked
+    pub fn close_window(&self, window_id: u64) -> Result<()> {
+        let wm_protocols = self.conn.intern_atom(false, b"WM_PROTOCOLS")?.reply()?.atom;
+        let wm_delete_window = self
+            .conn
+            .intern_atom(false, b"WM_DELETE_WINDOW")?
+            .reply()?
+            .atom;
+        let event = ClientMessageEvent {
+            response_type: CLIENT_MESSAGE_EVENT,
+            format: 32,
+            sequence: 0,
+            window: window_id as u32,
+            type_: wm_protocols,
+            data: ClientMessageData::from([wm_delete_window, x11rb::CURRENT_TIME, 0, 0, 0]),
+        };
+        self.conn
+            .send_event(false, window_id as u32, EventMask::NO_EVENT, event)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Add or remove one _NET_WM_STATE atom, asking the WM as a pager would
+    fn change_wm_state(&self, window_id: u64, state: &[u8], add: bool) -> Result<()> {
+        let root = self.conn.setup().roots[self.screen_num].root;
+        let net_wm_state = self
+            .conn
+            .intern_atom(false, b"_NET_WM_STATE")?
+            .reply()?
+            .atom;
+        let state = self.conn.intern_atom(false, state)?.reply()?.atom;
+
+        // _NET_WM_STATE_ADD is 1 and _NET_WM_STATE_REMOVE 0
+        let event = ClientMessageEvent {
+            response_type: CLIENT_MESSAGE_EVENT,
+            format: 32,
+            sequence: 0,
+            window: window_id as u32,
+            type_: net_wm_state,
+            data: ClientMessageData::from([u32::from(add), state, 0, 2, 0]),
+        };
+        self.conn.send_event(
+            false,
+            root,
+            EventMask::SUBSTRUCTURE_NOTIFY | EventMask::SUBSTRUCTURE_REDIRECT,
+            event,
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    pub fn is_pinned(&self, window_id: u64) -> Result<bool> {
+        let net_wm_state = self
+            .conn
+            .intern_atom(false, b"_NET_WM_STATE")?
+            .reply()?
+            .atom;
+        let above = self
+            .conn
+            .intern_atom(false, b"_NET_WM_STATE_ABOVE")?
+            .reply()?
+            .atom;
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                window_id as u32,
+                net_wm_state,
+                AtomEnum::ATOM,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+        let pinned = reply
+            .value32()
+            .is_some_and(|mut states| states.any(|s| s == above));
+        Ok(pinned)
+    }
+
     pub fn restore_window(&self, window_id: u64) -> Result<()> {
         // Map the window to restore it from minimized state
         self.conn.map_window(window_id as u32)?;
@@ -237,78 +547,279 @@ impl X11Manager {
         Ok(())
     }
 
-    /// Get monitor geometry using xrandr
-    pub fn get_monitors_internal(&self) -> Result<Vec<crate::window_manager::Monitor>> {
-        use std::process::Command;
+    pub fn warp_pointer(&self, window_id: u64) -> Result<()> {
+        let window = window_id as u32;
+        let geom = self
+            .conn
+            .get_geometry(window)?
+            .reply()
+            .map_err(|_| WindowNotFound(window_id))?;
+
+        // Relative to the window itself, so its origin is 0,0
+        let (x, y) = warp_target(
+            (0, 0, geom.width.into(), geom.height.into()),
+            self.warp_anchor,
+        );
+        self.conn
+            .warp_pointer(x11rb::NONE, window, 0, 0, 0, 0, x as i16, y as i16)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Watch the root window's _NET_ACTIVE_WINDOW and _NET_CLIENT_LIST, and
+    /// each client's title, on a connection of our own
+    pub fn subscribe_events(&self) -> Result<Receiver<WindowEvent>> {
+        let (conn, _) = RustConnection::connect(None).context("Failed to connect to X11 server")?;
+        let root = conn.setup().roots[self.screen_num].root;
+        let atoms = EventAtoms {
+            active_window: self.net_active_window_atom,
+            client_list: conn.intern_atom(false, b"_NET_CLIENT_LIST")?.reply()?.atom,
+            wm_name: conn.intern_atom(false, b"_NET_WM_NAME")?.reply()?.atom,
+        };
+
+        let listen = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+        conn.change_window_attributes(root, &listen)?;
+        conn.flush()?;
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = watch_events(&conn, root, atoms, &tx);
+        });
+        Ok(rx)
+    }
+
+    /// RandR screen and output changes, on a connection of our own
+    pub fn subscribe_monitor_events(&self) -> Result<Receiver<()>> {
+        use x11rb::protocol::randr::{ConnectionExt as _, NotifyMask};
+
+        let (conn, _) = RustConnection::connect(None).context("Failed to connect to X11 server")?;
+        let root = conn.setup().roots[self.screen_num].root;
+        conn.randr_query_version(1, 2)?
+            .reply()
+            .context("RandR is not available")?;
+        conn.randr_select_input(
+            root,
+            NotifyMask::SCREEN_CHANGE | NotifyMask::OUTPUT_CHANGE | NotifyMask::CRTC_CHANGE,
+        )?;
+        conn.flush()?;
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(event) = conn.wait_for_event() {
+                let changed = matches!(
+                    event,
+                    x11rb::protocol::Event::RandrScreenChangeNotify(_)
+                        | x11rb::protocol::Event::RandrNotify(_)
+                );
+                if changed && tx.send(()).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(rx)
+    }
+
+    fn restack_window(&self, window_id: u64, mode: StackMode) -> Result<()> {
+        let values = ConfigureWindowAux::new().stack_mode(mode);
+        self.conn.configure_window(window_id as u32, &values)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    pub fn get_opacity(&self, window_id: u64) -> Result<Option<f64>> {
+        let opacity_atom = self
+            .conn
+            .intern_atom(false, b"_NET_WM_WINDOW_OPACITY")?
+            .reply()?
+            .atom;
 
-        let output = Command::new("xrandr")
-            .arg("--query")
-            .output()
-            .context("Failed to execute xrandr")?;
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                window_id as u32,
+                opacity_atom,
+                AtomEnum::CARDINAL,
+                0,
+                1,
+            )?
+            .reply()?;
 
-        if !output.status.success() {
-            return Ok(Vec::new());
+        Ok(reply
+            .value32()
+            .and_then(|mut values| values.next())
+            .map(|raw| raw as f64 / u32::MAX as f64))
+    }
+
+    pub fn set_opacity(&self, window_id: u64, opacity: Option<f64>) -> Result<()> {
+        let opacity_atom = self
+            .conn
+            .intern_atom(false, b"_NET_WM_WINDOW_OPACITY")?
+            .reply()?
+            .atom;
+
+        match opacity {
+            Some(value) => {
+                let raw = (value.clamp(0.0, 1.0) * u32::MAX as f64) as u32;
+                self.conn.change_property32(
+                    PropMode::REPLACE,
+                    window_id as u32,
+                    opacity_atom,
+                    AtomEnum::CARDINAL,
+                    &[raw],
+                )?;
+            }
+            None => {
+                // Deleting the property lets the compositor fall back to its default
+                self.conn.delete_property(window_id as u32, opacity_atom)?;
+            }
         }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let mut monitors = Vec::new();
+        self.conn.flush()?;
+        Ok(())
+    }
 
-        // Parse xrandr output: "DP-1 connected primary 2560x1440+0+0 ..."
-        for line in stdout.lines() {
-            if line.contains(" connected") {
-                // Find geometry pattern: WIDTHxHEIGHT+X+Y
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                let name = parts.first().map(|s| s.to_string()).unwrap_or_default();
-
-                for part in &parts {
-                    // Match pattern like "2560x1440+0+0"
-                    if part.contains('x') && part.contains('+') {
-                        if let Some((res, pos)) = part.split_once('+') {
-                            if let Some((width_str, height_str)) = res.split_once('x') {
-                                let pos_parts: Vec<&str> = pos.split('+').collect();
-                                if pos_parts.len() >= 2 {
-                                    if let (Ok(width), Ok(height), Ok(x), Ok(y)) = (
-                                        width_str.parse::<u32>(),
-                                        height_str.parse::<u32>(),
-                                        pos_parts[0].parse::<i32>(),
-                                        pos_parts[1].parse::<i32>(),
-                                    ) {
-                                        monitors.push(crate::window_manager::Monitor {
-                                            name,
-                                            x,
-                                            y,
-                                            width,
-                                            height,
-                                        });
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                    }
+    pub fn get_monitors_internal(&self) -> Result<Vec<crate::window_manager::Monitor>> {
+        self.monitor_cache.get(|| {
+            let mut monitors = self.query_monitors()?;
+            if let Some(work_area) = self.work_area() {
+                for monitor in &mut monitors {
+                    monitor.reserve(work_area_struts(monitor, work_area));
                 }
             }
+            Ok(monitors)
+        })
+    }
+
+    /// The current desktop's _NET_WORKAREA: what the WM leaves for windows
+    /// after panels and docks. It's one rectangle for the whole screen, so
+    /// a panel along one monitor's edge also trims a neighbour sharing that
+    /// edge.
+    fn work_area(&self) -> Option<(i32, i32, u32, u32)> {
+        let root = self.conn.setup().roots[self.screen_num].root;
+        let atom = |name: &[u8]| Some(self.conn.intern_atom(false, name).ok()?.reply().ok()?.atom);
+        let cardinals = |property: Atom| -> Option<Vec<u32>> {
+            let reply = self
+                .conn
+                .get_property(false, root, property, AtomEnum::CARDINAL, 0, u32::MAX)
+                .ok()?
+                .reply()
+                .ok()?;
+            let values = reply.value32()?.collect();
+            Some(values)
+        };
+
+        let desktop = cardinals(atom(b"_NET_CURRENT_DESKTOP")?)
+            .and_then(|d| d.first().copied())
+            .unwrap_or(0) as usize;
+        let areas = cardinals(atom(b"_NET_WORKAREA")?)?;
+        let area = areas
+            .chunks_exact(4)
+            .nth(desktop)
+            .or_else(|| areas.chunks_exact(4).next())?;
+        Some((area[0] as i32, area[1] as i32, area[2], area[3]))
+    }
+
+    /// Monitors straight from RandR: the server's monitor list on RandR
+    /// 1.5, else the CRTC driving each connected output
+    fn query_monitors(&self) -> Result<Vec<crate::window_manager::Monitor>> {
+        use x11rb::protocol::randr::ConnectionExt as _;
+
+        let root = self.conn.setup().roots[self.screen_num].root;
+        let version = self
+            .conn
+            .randr_query_version(1, 5)?
+            .reply()
+            .context("RandR is not available")?;
+        if (version.major_version, version.minor_version) < (1, 5) {
+            return self.query_outputs(root);
         }
 
+        let reply = self.conn.randr_get_monitors(root, true)?.reply()?;
+        let mut monitors = Vec::new();
+        for info in reply.monitors {
+            let name = self.conn.get_atom_name(info.name)?.reply()?.name;
+            monitors.push(crate::window_manager::Monitor {
+                name: String::from_utf8_lossy(&name).into_owned(),
+                x: info.x as i32,
+                y: info.y as i32,
+                width: info.width as u32,
+                height: info.height as u32,
+                primary: info.primary,
+                ..Default::default()
+            });
+        }
         Ok(monitors)
     }
 
-    /// Determine which monitor a window is on based on its geometry
-    fn get_window_monitor(&self, window: u32) -> Option<String> {
+    /// RandR 1.2-1.4 has no monitor list: every connected output that's
+    /// lit, at its CRTC's position
+    fn query_outputs(&self, root: Window) -> Result<Vec<crate::window_manager::Monitor>> {
+        use x11rb::protocol::randr::{Connection, ConnectionExt as _};
+
+        let resources = self
+            .conn
+            .randr_get_screen_resources_current(root)?
+            .reply()?;
+        let primary = self.conn.randr_get_output_primary(root)?.reply()?.output;
+
+        let mut monitors = Vec::new();
+        for output in resources.outputs {
+            let info = self
+                .conn
+                .randr_get_output_info(output, resources.config_timestamp)?
+                .reply()?;
+            if info.connection != Connection::CONNECTED || info.crtc == x11rb::NONE {
+                continue;
+            }
+            let crtc = self
+                .conn
+                .randr_get_crtc_info(info.crtc, resources.config_timestamp)?
+                .reply()?;
+            if crtc.width == 0 || crtc.height == 0 {
+                continue;
+            }
+            monitors.push(crate::window_manager::Monitor {
+                name: String::from_utf8_lossy(&info.name).into_owned(),
+                x: crtc.x as i32,
+                y: crtc.y as i32,
+                width: crtc.width as u32,
+                height: crtc.height as u32,
+                primary: output == primary,
+                ..Default::default()
+            });
+        }
+        Ok(monitors)
+    }
+
+    /// Window position translated to root coordinates, plus its size
+    fn get_window_geometry(&self, window: u32) -> Option<(i32, i32, u32, u32)> {
+        let root = self.conn.setup().roots[self.screen_num].root;
         let geom = self.conn.get_geometry(window).ok()?.reply().ok()?;
-        let monitors = self.get_monitors_internal().ok()?;
+        let origin = self
+            .conn
+            .translate_coordinates(window, root, 0, 0)
+            .ok()?
+            .reply()
+            .ok()?;
+
+        Some((
+            origin.dst_x as i32,
+            origin.dst_y as i32,
+            geom.width as u32,
+            geom.height as u32,
+        ))
+    }
 
-        // Window center point
-        let win_center_x = geom.x as i32 + (geom.width as i32 / 2);
-        let win_center_y = geom.y as i32 + (geom.height as i32 / 2);
+    /// Determine which monitor a window is on based on its geometry
+    fn get_window_monitor(&self, geometry: Option<(i32, i32, u32, u32)>) -> Option<String> {
+        let monitors = self.get_monitors_internal().ok()?;
 
         // Find which monitor contains the window center
-        for mon in &monitors {
-            if win_center_x >= mon.x
-                && win_center_x < mon.x + mon.width as i32
-                && win_center_y >= mon.y
-                && win_center_y < mon.y + mon.height as i32
-            {
+        if let Some((x, y, width, height)) = geometry {
+            let center_x = x + (width / 2) as i32;
+            let center_y = y + (height / 2) as i32;
+            if let Some(mon) = monitor_at(&monitors, center_x, center_y) {
                 return Some(mon.name.clone());
             }
         }
@@ -318,76 +829,177 @@ impl X11Manager {
     }
 }
 
+/// NorthWestGravity: x and y place the top-left corner of the frame
+const GRAVITY_NORTH_WEST: u32 = 1;
+/// _NET_MOVERESIZE_WINDOW flags saying x and y, then width and height, are set
+const MOVERESIZE_POSITION: u32 = (1 << 8) | (1 << 9);
+const MOVERESIZE_SIZE: u32 = (1 << 10) | (1 << 11);
+/// Source indication 2: a pager acting for the user, which WMs honour where
+/// they'd second-guess an application moving itself
+const SOURCE_PAGER: u32 = 2 << 12;
+
+/// The data of a _NET_MOVERESIZE_WINDOW message placing a window's frame,
+/// given the frame's [left, right, top, bottom] extents
+fn moveresize_data(placement: &Placement, extents: [u32; 4]) -> [u32; 5] {
+    let mut flags = GRAVITY_NORTH_WEST | MOVERESIZE_POSITION | SOURCE_PAGER;
+    let (width, height) = match placement.inside_frame(extents).size {
+        Some(size) => {
+            flags |= MOVERESIZE_SIZE;
+            size
+        }
+        None => (0, 0),
+    };
+    // Coordinates are signed on the wire, so negative ones keep their bits
+    [flags, placement.x as u32, placement.y as u32, width, height]
+}
+
+#[derive(Clone, Copy)]
+struct EventAtoms {
+    active_window: Atom,
+    client_list: Atom,
+    wm_name: Atom,
+}
+
+fn read_windows(conn: &RustConnection, root: Window, atom: Atom) -> Result<Vec<u32>> {
+    let reply = conn
+        .get_property(false, root, atom, AtomEnum::WINDOW, 0, u32::MAX)?
+        .reply()?;
+    Ok(reply.value32().map(|v| v.collect()).unwrap_or_default())
+}
+
+/// Start watching titles of clients that are new since `clients`, and
+/// return how many there were before
+fn track_clients(
+    conn: &RustConnection,
+    root: Window,
+    atoms: EventAtoms,
+    clients: &mut HashSet<u32>,
+) -> Result<usize> {
+    let listen = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+    let current: HashSet<u32> = read_windows(conn, root, atoms.client_list)?
+        .into_iter()
+        .collect();
+    for &window in current.difference(clients) {
+        // The window may already be gone; nothing to watch then
+        let _ = conn.change_window_attributes(window, &listen);
+    }
+    conn.flush()?;
+
+    let before = clients.len();
+    *clients = current;
+    Ok(before)
+}
+
+/// Translate property changes into events until the receiver goes away
+fn watch_events(
+    conn: &RustConnection,
+    root: Window,
+    atoms: EventAtoms,
+    tx: &Sender<WindowEvent>,
+) -> Result<()> {
+    let mut clients = HashSet::new();
+    track_clients(conn, root, atoms, &mut clients)?;
+
+    loop {
+        let x11rb::protocol::Event::PropertyNotify(event) = conn.wait_for_event()? else {
+            continue;
+        };
+
+        let window_event = if event.window == root && event.atom == atoms.active_window {
+            let active = read_windows(conn, root, atoms.active_window)?;
+            WindowEvent::Focused(*active.first().unwrap_or(&0) as u64)
+        } else if event.window == root && event.atom == atoms.client_list {
+            let before = track_clients(conn, root, atoms, &mut clients)?;
+            if clients.len() >= before {
+                WindowEvent::Opened
+            } else {
+                WindowEvent::Closed
+            }
+        } else if event.window != root
+            && (event.atom == atoms.wm_name || event.atom == u32::from(AtomEnum::WM_NAME))
+        {
+            WindowEvent::TitleChanged
+        } else {
+            continue;
+        };
+
+        if tx.send(window_event).is_err() {
+            return Ok(());
+        }
+    }
+}
+
 impl WindowManager for X11Manager {
     fn get_eve_windows(&self) -> Result<Vec<EveWindow>> {
         self.get_eve_windows()
     }
 
     fn activate_window(&self, window_id: u64) -> Result<()> {
-        self.activate_window(window_id)
-    }
-
-    fn stack_windows(&self, windows: &[EveWindow], config: &Config) -> Result<()> {
-        let monitors = self.get_monitors()?;
-
-        for window in windows {
-            // Determine target monitor:
-            // - Primary character goes to primary_monitor
-            // - Others stay on their current monitor
-            let is_primary = config
-                .primary_character
-                .as_ref()
-                .map(|c| window.title == *c)
-                .unwrap_or(false);
-
-            let target_monitor = if is_primary {
-                // Primary character goes to primary_monitor
-                config
-                    .primary_monitor
-                    .as_ref()
-                    .and_then(|name| monitors.iter().find(|m| &m.name == name))
-                    .or_else(|| monitors.first())
-            } else {
-                // Others stay on current monitor
-                window
-                    .monitor
-                    .as_ref()
-                    .and_then(|name| monitors.iter().find(|m| &m.name == name))
-                    .or_else(|| monitors.first())
-            };
-
-            let (x, y, width, height) = if let Some(mon) = target_monitor {
-                if config.fullscreen_stack {
-                    // Fullscreen on monitor
-                    let height = mon.height.saturating_sub(config.panel_height);
-                    (mon.x, mon.y, mon.width, height)
-                } else {
-                    // Centered with eve_width
-                    let eve_w = config.eve_width.min(mon.width);
-                    let x = mon.x + ((mon.width - eve_w) / 2) as i32;
-                    let height = mon.height.saturating_sub(config.panel_height);
-                    (x, mon.y, eve_w, height)
-                }
-            } else {
-                // Fallback to global config
-                let x = ((config.display_width - config.eve_width) / 2) as i32;
-                let height = config.display_height - config.panel_height;
-                (x, 0, config.eve_width, height)
-            };
-
-            let values = ConfigureWindowAux::new()
-                .x(x)
-                .y(y)
-                .width(width)
-                .height(height);
+        apply_focus_workarounds(self, window_id, &self.focus_workarounds);
+        self.activate_window(window_id)?;
+        if self.warp_on_activate {
+            let _ = self.warp_pointer(window_id);
+        }
+        Ok(())
+    }
 
-            self.conn.configure_window(window.id as u32, &values)?;
+    fn apply_placement(&self, placement: &Placement) -> Result<()> {
+        if self.move_method == X11MoveMethod::Ewmh {
+            return self.ewmh_move_resize(placement);
+        }
+        // A reparenting WM puts the frame's corner at x, y (NorthWest
+        // gravity) but takes the size as the client's, so the frame is
+        // taken off it
+        let mut values = ConfigureWindowAux::new().x(placement.x).y(placement.y);
+        if placement.size.is_some() {
+            let extents = self.frame_extents(placement.window_id as u32)?;
+            if let Some((width, height)) = placement.inside_frame(extents).size {
+                values = values.width(width).height(height);
+            }
         }
 
+        self.conn
+            .configure_window(placement.window_id as u32, &values)?;
         self.conn.flush()?;
         Ok(())
     }
 
+    fn get_process_windows(&self) -> Result<Vec<EveWindow>> {
+        Ok(self
+            .client_list()?
+            .into_iter()
+            .filter_map(|window| {
+                let pid = self.get_window_pid(window)?;
+                let geometry = self.get_window_geometry(window);
+                Some(EveWindow {
+                    id: window as u64,
+                    title: self.get_window_title(window).unwrap_or_default(),
+                    monitor: self.get_window_monitor(geometry),
+                    geometry,
+                    pid: Some(pid),
+                    class: self.get_window_class(window),
+                })
+            })
+            .collect())
+    }
+
+    fn get_panel_windows(&self) -> Result<Vec<EveWindow>> {
+        Ok(self
+            .client_list()?
+            .into_iter()
+            .map(|window| EveWindow {
+                id: window as u64,
+                class: self.get_window_class(window),
+                geometry: self.get_window_geometry(window),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    fn get_title(&self, window_id: u64) -> Result<Option<String>> {
+        self.get_window_title(window_id as u32).map(Some)
+    }
+
     fn get_active_window(&self) -> Result<u64> {
         self.get_active_window()
     }
@@ -408,7 +1020,173 @@ impl WindowManager for X11Manager {
         self.restore_window(window_id)
     }
 
+    fn is_minimized(&self, window_id: u64) -> Result<bool> {
+        self.is_minimized(window_id)
+    }
+
     fn get_monitors(&self) -> Result<Vec<crate::window_manager::Monitor>> {
-        self.get_monitors_internal()
+        Ok(order_monitors(
+            self.get_monitors_internal()?,
+            &self.monitor_order,
+        ))
+    }
+
+    fn invalidate_monitors(&self) {
+        self.monitor_cache.invalidate();
+    }
+
+    fn raise_window(&self, window_id: u64) -> Result<()> {
+        self.restack_window(window_id, StackMode::ABOVE)
+    }
+
+    fn lower_window(&self, window_id: u64) -> Result<()> {
+        self.restack_window(window_id, StackMode::BELOW)
+    }
+
+    fn warp_pointer(&self, window_id: u64) -> Result<()> {
+        self.warp_pointer(window_id)
+    }
+
+    fn subscribe_events(&self) -> Result<Option<Receiver<WindowEvent>>> {
+        self.subscribe_events().map(Some)
+    }
+
+    fn subscribe_monitor_events(&self) -> Result<Option<Receiver<()>>> {
+        self.subscribe_monitor_events().map(Some)
+    }
+
+    fn get_opacity(&self, window_id: u64) -> Result<Option<f64>> {
+        self.get_opacity(window_id)
+    }
+
+    fn set_opacity(&self, window_id: u64, opacity: Option<f64>) -> Result<()> {
+        self.set_opacity(window_id, opacity)
+    }
+
+    fn set_pinned(&self, window_id: u64, pinned: bool) -> Result<()> {
+        self.set_pinned(window_id, pinned)
+    }
+
+    fn is_pinned(&self, window_id: u64) -> Result<bool> {
+        self.is_pinned(window_id)
+    }
+
+    fn mark_urgent(&self, window_id: u64) -> Result<()> {
+        // The WM drops the state again once the window has been focused
+        self.change_wm_state(window_id, b"_NET_WM_STATE_DEMANDS_ATTENTION", true)
+    }
+
+    fn close_window(&self, window_id: u64) -> Result<()> {
+        self.close_window(window_id)
+    }
+
+    fn set_decorations(&self, window_id: u64, decorated: bool) -> Result<()> {
+        let hints_atom = self
+            .conn
+            .intern_atom(false, b"_MOTIF_WM_HINTS")?
+            .reply()?
+            .atom;
+
+        // flags = MWM_HINTS_DECORATIONS, then functions, decorations,
+        // input_mode, status
+        let hints = [2, 0, decorated as u32, 0, 0];
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            window_id as u32,
+            hints_atom,
+            hints_atom,
+            &hints,
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moveresize_leaves_room_for_the_frame() {
+        let placement = Placement {
+            window_id: 1,
+            title: "Main".to_string(),
+            monitor: None,
+            x: -1920,
+            y: 0,
+            size: Some((1920, 1080)),
+        };
+
+        let data = moveresize_data(&placement, [2, 2, 30, 2]);
+        assert_eq!(data[0], 0x2f01);
+        assert_eq!(data[1] as i32, -1920);
+        assert_eq!(&data[2..], [0, 1916, 1048]);
+
+        // A move alone leaves the size flags off
+        let moved = Placement {
+            size: None,
+            ..placement
+        };
+        assert_eq!(moveresize_data(&moved, [0; 4])[0], 0x2301);
+    }
+
+    fn read_all_three(source: TitleSource) -> Option<String> {
+        Some(
+            match source {
+                TitleSource::NetWmVisibleName => "EVE - Pilot <2>",
+                TitleSource::NetWmName => "EVE - Pilot",
+                TitleSource::WmName => "EVE - Pilot (legacy)",
+            }
+            .to_string(),
+        )
+    }
+
+    #[test]
+    fn test_custom_property_drives_discovery() {
+        let marker = Some(("_NICOTINE_CLIENT".to_string(), "eve".to_string()));
+        let discover = |title, format, value: &[u8]| {
+            let property = decode_property(format, value);
+            WindowMatch::default()
+                .discovered_name(title, None, property.as_deref(), marker.as_ref())
+                .map(str::to_string)
+        };
+
+        // UTF8_STRING set by the launcher; the title isn't consulted
+        assert_eq!(discover("Main", 8, b"eve\0"), Some("Main".to_string()));
+        assert_eq!(discover("EVE - Alt", 8, b"eve"), Some("Alt".to_string()));
+        // Unset (format 0) or another value: not a client, whatever its title
+        assert_eq!(discover("EVE - Alt", 0, b""), None);
+        assert_eq!(discover("EVE - Alt", 8, b"eve-launcher"), None);
+
+        assert_eq!(
+            decode_property(32, &[7u32.to_ne_bytes(), 42u32.to_ne_bytes()].concat()),
+            Some("7,42".to_string())
+        );
+    }
+
+    #[test]
+    fn test_title_precedence_with_all_three_present() {
+        assert_eq!(
+            pick_title(title_sources(true), read_all_three).as_deref(),
+            Some("EVE - Pilot <2>")
+        );
+        assert_eq!(
+            pick_title(title_sources(false), read_all_three).as_deref(),
+            Some("EVE - Pilot")
+        );
+    }
+
+    #[test]
+    fn test_title_falls_through_empty_properties() {
+        let read = |source| match source {
+            TitleSource::NetWmVisibleName => Some(String::new()),
+            TitleSource::NetWmName => None,
+            TitleSource::WmName => Some("EVE - Pilot".to_string()),
+        };
+
+        assert_eq!(
+            pick_title(title_sources(true), read).as_deref(),
+            Some("EVE - Pilot")
+        );
     }
 }
@@ -0,0 +1,180 @@
+use crate::window_manager::Monitor;
+
+/// Monitors from `xrandr --query` output: every connected output with a
+/// WIDTHxHEIGHT+X+Y geometry. Outputs that are connected but off (no
+/// geometry) and anything unparsable are skipped rather than guessed at.
+pub fn parse_monitors(output: &str) -> Vec<Monitor> {
+    output.lines().filter_map(parse_output_line).collect()
+}
+
+/// One output line, e.g. "DP-1 connected primary 2560x1440+0+0 left (...) 597mm x 336mm"
+fn parse_output_line(line: &str) -> Option<Monitor> {
+    let mut parts = line.split_whitespace().peekable();
+    let name = parts.next()?;
+    if parts.next()? != "connected" {
+        return None;
+    }
+    let primary = parts.next_if_eq(&"primary").is_some();
+
+    // Geometry is the first token that parses; rotation and the mm sizes
+    // all fall through
+    let (width, height, x, y) = parts.find_map(parse_geometry)?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    Some(Monitor {
+        name: name.to_string(),
+        x,
+        y,
+        width,
+        height,
+        primary,
+        ..Default::default()
+    })
+}
+
+/// Parse "WIDTHxHEIGHT+X+Y". Offsets may be negative, written either as
+/// "-1920+0" or "+-1920+0".
+pub fn parse_geometry(token: &str) -> Option<(u32, u32, i32, i32)> {
+    let (width, rest) = token.split_once('x')?;
+    let width = width.parse::<u32>().ok()?;
+
+    let split = rest.find(['+', '-'])?;
+    let height = rest[..split].parse::<u32>().ok()?;
+
+    let (x, rest) = parse_offset(&rest[split..])?;
+    let (y, rest) = parse_offset(rest)?;
+    if !rest.is_empty() {
+        return None;
+    }
+
+    Some((width, height, x, y))
+}
+
+/// Take one signed offset off the front of `s`, returning it and the rest
+fn parse_offset(s: &str) -> Option<(i32, &str)> {
+    let unsigned = s.strip_prefix('+').unwrap_or(s);
+    if unsigned.len() == s.len() && !s.starts_with('-') {
+        return None;
+    }
+
+    let digits_start = usize::from(unsigned.starts_with('-'));
+    let end = unsigned[digits_start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(unsigned.len(), |i| i + digits_start);
+    if end == digits_start {
+        return None;
+    }
+
+    Some((unsigned[..end].parse::<i32>().ok()?, &unsigned[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const SAMPLE: &str = "\
+Screen 0: minimum 320 x 200, current 4480 x 1440, maximum 16384 x 16384
+DP-1 connected primary 2560x1440+0+0 (normal left inverted right x axis y axis) 597mm x 336mm
+   2560x1440     59.95*+ 143.97
+   1920x1080     60.00    59.94
+HDMI-A-1 connected 1080x1920+2560+0 left (normal left inverted right x axis y axis) 527mm x 296mm
+   1920x1080     60.00*+  50.00    59.94
+DP-2 disconnected (normal left inverted right x axis y axis)
+DP-3 connected (normal left inverted right x axis y axis)
+   1920x1080     59.96 +
+";
+
+    #[test]
+    fn test_parses_connected_outputs_only() {
+        let monitors = parse_monitors(SAMPLE);
+        let summary: Vec<(&str, i32, i32, u32, u32)> = monitors
+            .iter()
+            .map(|m| (m.name.as_str(), m.x, m.y, m.width, m.height))
+            .collect();
+
+        // DP-3 is connected but has no mode set, so it isn't a usable monitor
+        assert_eq!(
+            summary,
+            vec![
+                ("DP-1", 0, 0, 2560, 1440),
+                ("HDMI-A-1", 2560, 0, 1080, 1920),
+            ]
+        );
+        assert!(monitors[0].primary);
+        assert!(!monitors[1].primary);
+    }
+
+    #[test]
+    fn test_parse_geometry_handles_negative_offsets() {
+        assert_eq!(
+            parse_geometry("1920x1080-1920+0"),
+            Some((1920, 1080, -1920, 0))
+        );
+        assert_eq!(
+            parse_geometry("1920x1080+-1920+-200"),
+            Some((1920, 1080, -1920, -200))
+        );
+        assert_eq!(parse_geometry("1920x1080+0+0+0"), None);
+        assert_eq!(parse_geometry("1920x1080+0"), None);
+        assert_eq!(parse_geometry("597mm"), None);
+        assert_eq!(parse_geometry("x"), None);
+        assert_eq!(parse_geometry("60.00*+"), None);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_never_panics_on_arbitrary_input(input in "\\PC*") {
+            let _ = parse_monitors(&input);
+        }
+
+        #[test]
+        fn prop_never_panics_on_geometry_like_tokens(token in "[0-9x+\\-]{0,24}") {
+            let _ = parse_geometry(&token);
+        }
+
+        #[test]
+        fn prop_well_formed_lines_round_trip(
+            name in "[A-Za-z][A-Za-z0-9-]{0,12}",
+            width in 1u32..16384,
+            height in 1u32..16384,
+            x in -16384i32..16384,
+            y in -16384i32..16384,
+            primary in any::<bool>(),
+            rotation in prop::sample::select(vec!["", "left", "right", "inverted"]),
+            refresh in 24.0f64..360.0,
+        ) {
+            let line = format!(
+                "{} connected {}{}x{}{:+}{:+} {} (normal left inverted right x axis y axis) 597mm x 336mm\n   {}x{}     {:.2}*+",
+                name,
+                if primary { "primary " } else { "" },
+                width,
+                height,
+                x,
+                y,
+                rotation,
+                width,
+                height,
+                refresh,
+            );
+
+            let monitors = parse_monitors(&line);
+            prop_assert_eq!(monitors.len(), 1);
+            let m = &monitors[0];
+            prop_assert_eq!(&m.name, &name);
+            prop_assert_eq!((m.x, m.y, m.width, m.height), (x, y, width, height));
+        }
+
+        #[test]
+        fn prop_disconnected_outputs_are_ignored(
+            name in "[A-Za-z][A-Za-z0-9-]{0,12}",
+            width in 1u32..16384,
+            height in 1u32..16384,
+        ) {
+            let line = format!("{} disconnected {}x{}+0+0", name, width, height);
+            prop_assert!(parse_monitors(&line).is_empty());
+        }
+    }
+}